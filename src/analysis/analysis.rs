@@ -0,0 +1,106 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use crate::file_encoding_support::file_encoding_support::{FileEncodingFunctionDerivation, FileEncodingMethod};
+use crate::file_encoding_support::palette::Palette;
+use crate::file_encoding_support::pixel::{pixel_channel_samples, Pixel};
+
+/*
+    The detection side. Everything in here works on plain samples pulled out through the same Pixel types the
+    embedders use, so an attack sees exactly the values an embedder would have touched.
+ */
+
+/*
+    Samples of a pixel image split up by channel, each channel in row major order from the top left.
+    Indexed images have a single channel holding each pixel's luminance rank in the palette instead of the
+    raw index, the rank's low bit is what the palette embedding flips (see palette.rs)
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PixelChannels {
+    pub width: usize,
+    pub height: usize,
+    pub sample_bits: u32,
+    pub channels: Vec<Vec<u16>>,
+}
+
+impl PixelChannels {
+    pub fn from_pixel_map<P: Pixel + Default>(
+        pixel_map: &mut [u8],
+        width: u64,
+        height: u64,
+        padding: u64,
+        pixel_size_bytes: u64,
+    ) -> PixelChannels {
+        PixelChannels {
+            width: width as usize,
+            height: height as usize,
+            sample_bits: P::default().sample_bits(),
+            channels: pixel_channel_samples::<P>(pixel_map, width, height, padding, pixel_size_bytes),
+        }
+    }
+
+    pub fn from_palette_indices(indices: &[u8], palette: &Palette, width: usize, height: usize) -> PixelChannels {
+        let ranks = palette.ranks();
+        PixelChannels {
+            width,
+            height,
+            sample_bits: 8,
+            channels: vec![indices.iter().map(|index| ranks[*index as usize].unwrap_or(0) as u16).collect()],
+        }
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    /*
+        Pixels in the order the method walks them, a keyed shuffle that isn't a curve moves single samples around
+        instead of whole pixels so this only follows it pixel by pixel
+     */
+    pub fn visit_order(
+        &self,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Vec<usize> {
+        file_encoding_function_derivation
+            .grid_visit_order(encoding_method, self.width, self.height, 1)
+            .collect()
+    }
+}
+
+/*
+    What the length estimators (RS and sample pair analysis) come back with for one channel. embedding_rate is the
+    estimated fraction of the channel's samples carrying a message bit, LSB replacement only changes about half
+    of those so the fraction of samples actually changed is half of it.
+ */
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LengthEstimate {
+    pub channel: usize,
+    pub embedding_rate: f64,
+    pub message_bits: u64,
+}
+
+impl LengthEstimate {
+    pub fn new(channel: usize, embedding_rate: f64, samples: usize) -> LengthEstimate {
+        let embedding_rate = embedding_rate.clamp(0.0, 1.0);
+        LengthEstimate {
+            channel,
+            embedding_rate,
+            message_bits: (embedding_rate * samples as f64).round() as u64,
+        }
+    }
+}
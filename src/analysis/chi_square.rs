@@ -0,0 +1,115 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use crate::analysis::analysis::PixelChannels;
+use crate::mathematics_support::mathematics_support::regularized_gamma_q;
+
+/*
+    Westfeld and Pfitzmann's chi-square attack. LSB replacement turns 2k into 2k + 1 and back, so once a region
+    is full of message bits the two values of every pair (2k, 2k + 1) end up about equally common. The test compares
+    the count of each even value against the mean of its pair, a small statistic means the pairs have been evened
+    out and the probability of embedding is close to 1. An untouched image usually has lopsided pairs and a
+    probability near 0.
+ */
+
+/*
+    Pairs expected to show up fewer times than this are left out, the chi-square approximation doesn't hold
+    for them
+ */
+const MIN_EXPECTED_COUNT: f64 = 4.0;
+
+/*
+    Points on the curve when no window size is given
+ */
+pub const DEFAULT_CURVE_POINTS: usize = 100;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ChiSquare {
+    pub statistic: f64,
+    pub degrees_of_freedom: usize,
+    pub probability: f64, // Probability the samples carry embedded data
+}
+
+/*
+    One channel's result over the whole image, plus the probability for each window of window pixels along the
+    traversal order. A sequential embedding shows up as a curve near 1 that drops off where the message ends.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelChiSquare {
+    pub channel: usize,
+    pub overall: ChiSquare,
+    pub window: usize,
+    pub curve: Vec<f64>,
+}
+
+pub fn chi_square<I: IntoIterator<Item = u16>>(samples: I, sample_bits: u32) -> ChiSquare {
+    let mut histogram = vec![0u64; 1 << sample_bits.max(1)];
+    for sample in samples {
+        histogram[sample as usize] += 1;
+    }
+
+    let mut statistic = 0.0;
+    let mut categories = 0;
+    for pair in histogram.chunks(2) {
+        let expected = (pair[0] + pair[1]) as f64 / 2.0;
+        if expected < MIN_EXPECTED_COUNT {
+            continue;
+        }
+
+        statistic += (pair[0] as f64 - expected).powi(2) / expected;
+        categories += 1;
+    }
+
+    // A single pair can't say anything
+    if categories < 2 {
+        return ChiSquare { statistic, degrees_of_freedom: 0, probability: 0.0 };
+    }
+
+    let degrees_of_freedom = categories - 1;
+    ChiSquare {
+        statistic,
+        degrees_of_freedom,
+        probability: regularized_gamma_q(degrees_of_freedom as f64 / 2.0, statistic / 2.0),
+    }
+}
+
+/*
+    Runs the test on every channel, order is the pixel order to build the curve along (see PixelChannels::visit_order)
+    and window how many pixels go into each point of it, None spreads DEFAULT_CURVE_POINTS points over the image
+ */
+pub fn chi_square_attack(pixels: &PixelChannels, order: &[usize], window: Option<usize>) -> Vec<ChannelChiSquare> {
+    let window = window
+        .unwrap_or(order.len().div_ceil(DEFAULT_CURVE_POINTS))
+        .max(1);
+
+    pixels
+        .channels
+        .iter()
+        .enumerate()
+        .map(|(channel, samples)| ChannelChiSquare {
+            channel,
+            overall: chi_square(samples.iter().copied(), pixels.sample_bits),
+            window,
+            curve: order
+                .chunks(window)
+                .map(|pixels_in_window| {
+                    chi_square(pixels_in_window.iter().map(|pixel| samples[*pixel]), pixels.sample_bits).probability
+                })
+                .collect(),
+        })
+        .collect()
+}
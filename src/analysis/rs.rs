@@ -0,0 +1,135 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use crate::analysis::analysis::{LengthEstimate, PixelChannels};
+
+/*
+    Fridrich, Goljan and Du's RS analysis. Each row is cut into groups of four samples and a group is Regular if
+    flipping some of its samples makes it noisier (by the sum of neighbouring differences), Singular if it makes it
+    smoother. Flipping is LSB flipping (F1, 2k <-> 2k + 1) under the mask and shifted flipping (F-1, 2k <-> 2k - 1)
+    under the negated mask. In a clean image both masks give about the same R and S, LSB replacement drives
+    R and S together for F1 and apart for F-1. Measuring the same counts with every LSB flipped gives enough
+    points to solve for how much was embedded.
+ */
+
+const RS_MASK: [i32; 4] = [0, 1, 1, 0];
+
+/*
+    Fractions of Regular and Singular groups under the mask and the negated mask
+ */
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct RsCounts {
+    regular: f64,
+    singular: f64,
+    negative_regular: f64,
+    negative_singular: f64,
+}
+
+fn noise(group: &[i32]) -> i32 {
+    group.windows(2).map(|pair| (pair[1] - pair[0]).abs()).sum()
+}
+
+fn flip(sample: i32, mask: i32) -> i32 {
+    match mask {
+        1 => sample ^ 1,
+        -1 => ((sample + 1) ^ 1) - 1,
+        _ => sample,
+    }
+}
+
+fn rs_counts(samples: &[u16], width: usize, flip_all: bool) -> RsCounts {
+    let mut counts = RsCounts::default();
+    let mut groups = 0usize;
+
+    for row in samples.chunks(width.max(1)) {
+        for group in row.chunks_exact(RS_MASK.len()) {
+            let group: Vec<i32> = group.iter().map(|sample| if flip_all { (*sample ^ 1) as i32 } else { *sample as i32 }).collect();
+            let before = noise(&group);
+
+            let flipped: Vec<i32> = group.iter().zip(RS_MASK.iter()).map(|(sample, mask)| flip(*sample, *mask)).collect();
+            let negative: Vec<i32> = group.iter().zip(RS_MASK.iter()).map(|(sample, mask)| flip(*sample, -*mask)).collect();
+
+            match noise(&flipped).cmp(&before) {
+                std::cmp::Ordering::Greater => counts.regular += 1.0,
+                std::cmp::Ordering::Less => counts.singular += 1.0,
+                std::cmp::Ordering::Equal => {}
+            }
+            match noise(&negative).cmp(&before) {
+                std::cmp::Ordering::Greater => counts.negative_regular += 1.0,
+                std::cmp::Ordering::Less => counts.negative_singular += 1.0,
+                std::cmp::Ordering::Equal => {}
+            }
+            groups += 1;
+        }
+    }
+
+    if groups > 0 {
+        let groups = groups as f64;
+        counts.regular /= groups;
+        counts.singular /= groups;
+        counts.negative_regular /= groups;
+        counts.negative_singular /= groups;
+    }
+
+    counts
+}
+
+/*
+    Estimated fraction of the samples carrying a message bit, None when there isn't enough structure to go on
+    (too few groups, or a flat image where nothing is Regular or Singular)
+ */
+pub fn rs_embedding_rate(samples: &[u16], width: usize) -> Option<f64> {
+    let original = rs_counts(samples, width, false);
+    let flipped = rs_counts(samples, width, true);
+
+    let d0 = original.regular - original.singular;
+    let d1 = flipped.regular - flipped.singular;
+    let negative_d0 = original.negative_regular - original.negative_singular;
+    let negative_d1 = flipped.negative_regular - flipped.negative_singular;
+
+    /*
+        2(d1 + d0)x^2 + (d-0 - d-1 - d1 - 3d0)x + d0 - d-0 = 0, the root closer to 0 is the one we want
+     */
+    let a = 2.0 * (d1 + d0);
+    let b = negative_d0 - negative_d1 - d1 - 3.0 * d0;
+    let c = d0 - negative_d0;
+
+    let x = if a.abs() < f64::EPSILON {
+        if b.abs() < f64::EPSILON {
+            return None;
+        }
+        -c / b
+    } else {
+        let root = (b * b - 4.0 * a * c).max(0.0).sqrt();
+        let (first, second) = ((-b + root) / (2.0 * a), (-b - root) / (2.0 * a));
+        if first.abs() <= second.abs() { first } else { second }
+    };
+
+    let rate = x / (x - 0.5);
+    if rate.is_finite() { Some(rate) } else { None }
+}
+
+pub fn rs_estimate(pixels: &PixelChannels) -> Vec<LengthEstimate> {
+    pixels
+        .channels
+        .iter()
+        .enumerate()
+        .map(|(channel, samples)| {
+            LengthEstimate::new(channel, rs_embedding_rate(samples, pixels.width).unwrap_or(0.0), samples.len())
+        })
+        .collect()
+}
@@ -0,0 +1,81 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use crate::analysis::analysis::{LengthEstimate, PixelChannels};
+
+/*
+    Dumitrescu, Wu and Wang's sample pair analysis. Horizontal neighbours (u, v) are sorted by how LSB replacement
+    can move them: X holds the pairs where the even sample is the larger one (v even and u < v, or v odd and u > v),
+    Y the pairs the other way round and K the pairs sharing everything but the LSB (u / 2 == v / 2). In a clean
+    image X and Y are about the same size, embedding moves pairs between them at a rate set by the message length
+    and solving for that rate gives a quadratic in it.
+ */
+
+/*
+    Estimated fraction of the samples carrying a message bit, None when no pair is close enough for it to work
+ */
+pub fn sample_pair_embedding_rate(samples: &[u16], width: usize) -> Option<f64> {
+    let mut x: f64 = 0.0;
+    let mut y: f64 = 0.0;
+    let mut k: f64 = 0.0;
+    let mut pairs: f64 = 0.0;
+
+    for row in samples.chunks(width.max(1)) {
+        for pair in row.windows(2) {
+            let (u, v) = (pair[0], pair[1]);
+            let v_even = v % 2 == 0;
+
+            if (v_even && u < v) || (!v_even && u > v) {
+                x += 1.0;
+            }
+            if (v_even && u > v) || (!v_even && u < v) {
+                y += 1.0;
+            }
+            if u / 2 == v / 2 {
+                k += 1.0;
+            }
+            pairs += 1.0;
+        }
+    }
+
+    if k == 0.0 {
+        return None;
+    }
+
+    /*
+        2k q^2 + 2(2x - pairs) q + (y - x) = 0 where q is the fraction of samples that were changed, the smaller root.
+        Half the embedded bits already matched the LSB they replaced so the embedding rate is twice that.
+     */
+    let a = 2.0 * k;
+    let b = 2.0 * (2.0 * x - pairs);
+    let c = y - x;
+    let root = (b * b - 4.0 * a * c).max(0.0).sqrt();
+
+    let changed = ((-b + root) / (2.0 * a)).min((-b - root) / (2.0 * a));
+    if changed.is_finite() { Some(2.0 * changed) } else { None }
+}
+
+pub fn sample_pair_estimate(pixels: &PixelChannels) -> Vec<LengthEstimate> {
+    pixels
+        .channels
+        .iter()
+        .enumerate()
+        .map(|(channel, samples)| {
+            LengthEstimate::new(channel, sample_pair_embedding_rate(samples, pixels.width).unwrap_or(0.0), samples.len())
+        })
+        .collect()
+}
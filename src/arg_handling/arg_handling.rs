@@ -20,123 +20,306 @@ pub mod arg_handling {
     const ERROR : i32 = 1;
     const SUCCESS : i32 = 0;
 
-    use std::fs::File;
+    /*
+        Passing this to --in, --out or --payload-file means stdin / stdout instead of a file
+     */
+    pub const STDIO : &str = "-";
+
+    const USAGE : &str = "Usage: maya <embed|extract|capacity|info|analyze> --in <file|-> [options]
+  embed     --in carrier --out stego (--message 'text' | --payload-file file|-)
+  extract   --in stego [--out file|-]        writes to stdout unless --out is given
+  capacity  --in carrier                     raw, usable and recommended message size for the chosen encoding and method
+  info      --in carrier                     file type and the raw capacity of every encoding that works on it
+  analyze   --in file                        looks for payloads with every encoding and method
+  analyze   --in file --chi-square [--window pixels]   chi-square attack per channel along --method's order
+  analyze   --in file --estimate-length          RS and sample pair estimates of the LSB message length per channel
+Options:
+  --encoding Lsb|LsbMatching|PixelValueDifferencing|Hamming|JSteg|F5 (Lsb by default)
+                            extract with the encoding used to embed, Lsb can't read LsbMatching back or the other way round
+  --method LeftRight|RightLeft|TopBottom|SinWave|CosWave|PolynomialFunc|FractalFunc|Hilbert|Morton|SpiralIn|SpiralOut|BlockRaster (LeftRight by default)
+  --key passphrase          shuffles where the message goes, the same passphrase is needed to extract it
+  --passphrase passphrase   encrypts the message before embedding
+  --compression none|deflate|text   how the message is packed before embedding, deflate by default, text suits short messages
+  --hamming-k 1-7                   message bits per Hamming group when embedding, bigger changes fewer pixels but holds less, fitted to the message by default
+  --palette-reorder                 indexed images get their palette rewritten in luminance order when embedding, any index LSB reader sees the message then
+  --palette-tolerance 0-255          indexed images only swap palette colors at most this far apart, the same value is needed to extract
+  --rle-output recompress|uncompressed   how an RLE compressed BMP is written after embedding, recompress by default, uncompressed is bigger but plain BI_RGB
+  --pvd-ranges 8,8,16,32,64,128     PixelValueDifferencing range widths, powers of two adding up to 256, the same ones are needed to extract
+  --help, --version";
+
     use std::process::exit;
-    use crate::file_encoding_support;
-    use crate::file_encoding_support::file_encoding_support::{FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod, FileEncodingSupport, ImageSupport, Operation};
-    use crate::filetype_support::bmp::BmpImageParser;
-    use crate::filetype_support::filetype_support::FileType::Bmp;
+    use veritasobscura::compression::compression::CompressionCodec;
+    use veritasobscura::file_encoding_support::file_encoding_support::{FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod, Operation};
+    use veritasobscura::file_encoding_support::pixel::{PvdRangeTable, HAMMING_MAX_K};
+    use veritasobscura::file_encoding_support::palette::PaletteEmbedding;
+    use veritasobscura::filetype_support::bmp::RleOutput;
+
+    /*
+        What the command line asked for, none of the files are touched until the arguments all check out
+     */
+    pub struct ImageSupport {
+        pub(crate) input: String,
+        pub(crate) output: Option<String>,
+        pub(crate) payload_file: Option<String>, // Message comes from here instead of data when set
+        pub(crate) encoding: FileEncoding,
+        pub(crate) encoding_method: FileEncodingMethod,
+        pub(crate) file_encoding_function_derivation: FileEncodingFunctionDerivation,
+        pub(crate) operation: Operation,
+        pub(crate) data : Vec<u8>,
+        pub(crate) passphrase: Option<String>, // Encrypts the message before embedding when set, see encryption.rs
+        pub(crate) compression: CompressionCodec, // Packs the message before encrypting and embedding, see compression.rs
+        pub(crate) chi_square: bool, // analyze runs the chi-square attack instead of looking for payloads
+        pub(crate) window: Option<usize>, // Pixels per point on the chi-square curve
+        pub(crate) estimate_length: bool, // analyze runs RS and sample pair analysis instead of looking for payloads
+        pub(crate) hamming_k: Option<u32>, // Group size for Hamming, fitted to the message when None
+        pub(crate) palette_tolerance: Option<u8>, // Furthest apart two palette colors can be and still swap
+        pub(crate) palette_embedding: PaletteEmbedding, // Embedding rewrites the palette when ReorderPalette
+        pub(crate) rle_output: RleOutput, // What embedding does with an RLE compressed BMP's pixel map
+        pub(crate) pvd_ranges: Option<Vec<u32>>, // Range widths for PixelValueDifferencing, Wu and Tsai's when None
+    }
 
-    pub fn parse_arguments(args: Vec<String>) -> ImageSupport {
-        if args.len() == 2 && args[1] == "--help" {
-            println!("Usage: maya encoding(Lsb,PixelValueDifferencing,Hamming) encoding-method(LeftRight, TopBottom, SinWave,CosWave, PolyFunc, FractalFunc) operation(embed/extract) <optional>'Message to be hidden'</optional> filename.ext(either the file to extract or the filename to embed into)");
-            println!("This is a stegonagraphy tool for embedding and extracting secret messages within images.");
-            println!("Options: --help, --version");
-            exit(SUCCESS);
-        }
+    fn fail(message: &str) -> ! {
+        eprintln!("{message}");
+        eprintln!("Try --help for help.");
+        exit(ERROR);
+    }
 
-        if (args.len() == 2 && args[1] == "--version") {
-            println!("Maya version {}", env!("CARGO_PKG_VERSION"));
-            exit(SUCCESS);
-        }
-        if (args.len() < 5 ) {
-            println!("Too few arguments!");
-            println!("Usage: maya encoding(Lsb,PixelValueDifferencing,Hamming) encoding-method(LeftRight, TopBottom, SinWave,CosWave, PolyFunc, FractalFunc) operation(embed/extract) <optional>'Message to be hidden'</optional> filename.ext(either the file to extract or the filename to embed into)");
-            println!("Try --help for help.");
-            exit(ERROR);
+    pub fn parse_encoding(name: &str) -> Option<FileEncoding> {
+        match name {
+            "Lsb" => Some(FileEncoding::Lsb),
+            "LsbMatching" => Some(FileEncoding::LsbMatching),
+            "PixelValueDifferencing" => Some(FileEncoding::PixelValueDifferencing),
+            "Hamming" => Some(FileEncoding::HammingMatrix),
+            "JSteg" => Some(FileEncoding::JSteg),
+            "F5" => Some(FileEncoding::F5),
+            _ => None,
         }
+    }
 
-        if (args.len() > 6 ) {
-            println!("Too many arguments!");
-            println!("Usage: maya encoding(Lsb,PixelValueDifferencing,Hamming) encoding-method(LeftRight, TopBottom, SinWave,CosWave, PolyFunc, FractalFunc) operation(embed/extract) <optional>'Message to be hidden'</optional> filename.ext(either the file to extract or the filename to embed into)");
-            println!("Try --help for help.");
-            exit(ERROR);
+    pub fn parse_encoding_method(name: &str) -> Option<FileEncodingMethod> {
+        match name {
+            "LeftRight" => Some(FileEncodingMethod::LeftToRight),
+            "TopBottom" => Some(FileEncodingMethod::TopToBottom),
+            "RightLeft" => Some(FileEncodingMethod::RightToLeft),
+            "CosWave" => Some(FileEncodingMethod::CosWave),
+            "SinWave" => Some(FileEncodingMethod::SinWave),
+            "FractalFunc" => Some(FileEncodingMethod::FractalFunction),
+            "PolynomialFunc" => Some(FileEncodingMethod::PolynomialFunction),
+            "Hilbert" => Some(FileEncodingMethod::HilbertCurve),
+            "Morton" => Some(FileEncodingMethod::MortonOrder),
+            "SpiralIn" => Some(FileEncodingMethod::SpiralInward),
+            "SpiralOut" => Some(FileEncodingMethod::SpiralOutward),
+            "BlockRaster" => Some(FileEncodingMethod::BlockRaster),
+            _ => None,
         }
+    }
 
-
-        if { args[3] == "embed" &&  args.len() != 6 } {
-            println!("You must specific a message with the embed option!");
-            println!("Usage: maya encoding(Lsb,PixelValueDifferencing,Hamming) encoding-method(LeftRight, TopBottom, SinWave,CosWave, PolyFunc, FractalFunc) operation(embed/extract) <optional>'Message to be hidden'</optional> filename.ext(either the file to extract or the filename to embed into)");
-            println!("Try --help for help.");
+    pub fn parse_arguments(args: Vec<String>) -> ImageSupport {
+        if args.len() < 2 {
+            eprintln!("{USAGE}");
             exit(ERROR);
         }
 
-        let encoding = match args[1].as_str() {
-            "Lsb" => {FileEncoding::Lsb},
-            "PixelValueDifferencing" => {FileEncoding::PixelValueDifferencing},
-            "Hamming" => {FileEncoding::HammingMatrix},
-            _ => {
-                println!("Invalid encoding found! : {}", args[1].as_str());
-                exit(1);
-            }
-
-        };
-
-        let encoding_method = match args[2].as_str() {
-            "LeftRight" => {FileEncodingMethod::LeftToRight},
-            "TopBottom" => {FileEncodingMethod::TopToBottom},
-            "RightLeft" => {FileEncodingMethod::RightToLeft},
-            "CosWave" => {FileEncodingMethod::CosWave},
-            "SinWave" => {FileEncodingMethod::SinWave},
-            "FractalFunc" => {FileEncodingMethod::FractalFunction},
-            "PolynomialFunc" => {FileEncodingMethod::PolynomialFunction},
-            _ => {
-                println!("Invalid encoding method found! : {}", args[2].as_str());
-                exit(1);
-            }
+        if args[1] == "--help" || args[1] == "-h" {
+            println!("{USAGE}");
+            println!("This is a stegonagraphy tool for embedding and extracting secret messages within images.");
+            exit(SUCCESS);
+        }
 
-        };
+        if args[1] == "--version" {
+            println!("Maya version {}", env!("CARGO_PKG_VERSION"));
+            exit(SUCCESS);
+        }
 
-        let operation = match args[3].as_str() {
+        let operation = match args[1].as_str() {
             "embed" => Operation::Embed,
             "extract" => Operation::Extract,
-            _ => {
-                println!("Invalid operation found! : {}", args[3]);
-                exit(1);
-            }
+            "capacity" => Operation::Capacity,
+            "info" => Operation::Info,
+            "analyze" => Operation::Analyze,
+            _ => fail(&format!("Invalid subcommand found! : {}", args[1])),
         };
 
-        let mut message = Vec::new();
-        if(operation == Operation::Embed) {
-            message = args[4].as_bytes().to_vec();
-        }
+        let mut image_support = ImageSupport {
+            input: String::new(),
+            output: None,
+            payload_file: None,
+            encoding: FileEncoding::Lsb,
+            encoding_method: FileEncodingMethod::LeftToRight,
+            file_encoding_function_derivation: FileEncodingFunctionDerivation::MethodBased,
+            operation,
+            data: Vec::new(),
+            passphrase: None,
+            compression: CompressionCodec::Deflate,
+            chi_square: false,
+            window: None,
+            estimate_length: false,
+            hamming_k: None,
+            palette_tolerance: None,
+            palette_embedding: PaletteEmbedding::LuminanceOrder,
+            rle_output: RleOutput::Recompress,
+            pvd_ranges: None,
+        };
+        let mut message = None;
+        let mut rle_output_given = false;
 
         /*
-            position is different depending on if the operation is embed (embed has the message extra)
+            Everything after the subcommand is a --flag value pair in any order, bar the switches that take no value
          */
-        let file_no = match operation {
-            Operation::Embed   => 5,
-            Operation::Extract => 4
-        };
+        let mut remaining = args[2..].iter();
+        while let Some(flag) = remaining.next() {
+            if flag == "--help" || flag == "-h" {
+                println!("{USAGE}");
+                exit(SUCCESS);
+            }
 
+            if flag == "--chi-square" {
+                image_support.chi_square = true;
+                continue;
+            }
 
-        let file_ext = args[file_no].as_str().split_at(".".parse().unwrap()).1;
+            if flag == "--estimate-length" {
+                image_support.estimate_length = true;
+                continue;
+            }
 
-        if(file_ext == "bmp"){
-            let image_parser : ImageSupport = ImageSupport{
-                encoding_support: Box::new(BmpImageParser::new( args[file_no].as_str())),
-                image_file : File::open(args[file_no].as_str()).unwrap(),
-                encoding :encoding,
-                encoding_method : encoding_method,
-                file_encoding_function_derivation: FileEncodingFunctionDerivation::KeyBased, //This isnt implemented yet
-                operation : operation,
-                data : message
+            if flag == "--palette-reorder" {
+                image_support.palette_embedding = PaletteEmbedding::ReorderPalette;
+                continue;
+            }
+
+            let value = match remaining.next() {
+                Some(value) => value.clone(),
+                None => fail(&format!("{flag} needs a value after it!")),
             };
 
+            match flag.as_str() {
+                "--in" => image_support.input = value,
+                "--out" => image_support.output = Some(value),
+                "--message" => message = Some(value),
+                "--payload-file" => image_support.payload_file = Some(value),
+                "--encoding" => {
+                    image_support.encoding = match parse_encoding(&value) {
+                        Some(encoding) => encoding,
+                        None => fail(&format!("Invalid encoding found! : {value}")),
+                    };
+                }
+                "--method" => {
+                    image_support.encoding_method = match parse_encoding_method(&value) {
+                        Some(encoding_method) => encoding_method,
+                        None => fail(&format!("Invalid encoding method found! : {value}")),
+                    };
+                }
+                "--key" => image_support.file_encoding_function_derivation = FileEncodingFunctionDerivation::from_passphrase(&value),
+                "--passphrase" => image_support.passphrase = Some(value),
+                "--window" => {
+                    image_support.window = match value.parse::<usize>() {
+                        Ok(window) if window > 0 => Some(window),
+                        _ => fail(&format!("Invalid window size found! : {value}")),
+                    };
+                }
+                "--compression" => {
+                    image_support.compression = match value.as_str() {
+                        "none" => CompressionCodec::None,
+                        "deflate" => CompressionCodec::Deflate,
+                        "text" => CompressionCodec::ShortText,
+                        _ => fail(&format!("Invalid compression codec found! : {value}")),
+                    };
+                }
+                "--hamming-k" => {
+                    image_support.hamming_k = match value.parse::<u32>() {
+                        Ok(k) if (1..=HAMMING_MAX_K).contains(&k) => Some(k),
+                        _ => fail(&format!("Invalid Hamming k found, it goes from 1 to {HAMMING_MAX_K}! : {value}")),
+                    };
+                }
+                "--palette-tolerance" => {
+                    image_support.palette_tolerance = match value.parse::<u8>() {
+                        Ok(tolerance) => Some(tolerance),
+                        _ => fail(&format!("Invalid palette tolerance found, it goes from 0 to 255! : {value}")),
+                    };
+                }
+                "--rle-output" => {
+                    image_support.rle_output = match value.as_str() {
+                        "recompress" => RleOutput::Recompress,
+                        "uncompressed" => RleOutput::Uncompressed,
+                        _ => fail(&format!("Invalid RLE output found! : {value}")),
+                    };
+                    rle_output_given = true;
+                }
+                "--pvd-ranges" => {
+                    let widths: Option<Vec<u32>> = value.split(',').map(|width| width.trim().parse::<u32>().ok()).collect();
+                    image_support.pvd_ranges = match widths {
+                        Some(widths) if PvdRangeTable::new(widths.clone(), u8::MAX as u16).is_some() => Some(widths),
+                        _ => fail(&format!("Invalid PVD ranges found, they have to be powers of two adding up to 256! : {value}")),
+                    };
+                }
+                _ => fail(&format!("Unknown option found! : {flag}")),
+            }
+        }
+
+        if image_support.input.is_empty() {
+            fail("--in is required, pass - to read the file from stdin");
+        }
 
+        if operation != Operation::Analyze && (image_support.chi_square || image_support.window.is_some()) {
+            fail("--chi-square and --window only make sense with analyze");
+        }
 
-             return image_parser;
+        if operation != Operation::Analyze && image_support.estimate_length {
+            fail("--estimate-length only makes sense with analyze");
         }
 
+        if image_support.chi_square && image_support.estimate_length {
+            fail("pick one of --chi-square and --estimate-length");
+        }
 
+        if image_support.window.is_some() && !image_support.chi_square {
+            fail("--window needs --chi-square");
+        }
 
+        if image_support.pvd_ranges.is_some() && image_support.encoding != FileEncoding::PixelValueDifferencing && operation != Operation::Info {
+            fail("--pvd-ranges needs --encoding PixelValueDifferencing");
+        }
 
+        if rle_output_given && operation != Operation::Embed {
+            fail("--rle-output only makes sense with embed");
+        }
 
-        println!("Invalid file ext found! : {}", args[file_no]);
-        exit(ERROR);
+        if image_support.palette_embedding == PaletteEmbedding::ReorderPalette && operation != Operation::Embed {
+            fail("--palette-reorder only makes sense with embed, extract reads either palette");
+        }
 
+        if image_support.hamming_k.is_some() && (operation != Operation::Embed || image_support.encoding != FileEncoding::HammingMatrix) {
+            fail("--hamming-k only makes sense with embed and --encoding Hamming, extract reads k from the image");
+        }
 
+        match operation {
+            Operation::Embed => {
+                if image_support.output.is_none() {
+                    fail("embed needs --out, pass - to write the new file to stdout");
+                }
+
+                match (message, &image_support.payload_file) {
+                    (Some(message), None) => image_support.data = message.into_bytes(),
+                    (None, Some(payload_file)) => {
+                        if payload_file == STDIO && image_support.input == STDIO {
+                            fail("--in and --payload-file can't both come from stdin");
+                        }
+                    }
+                    _ => fail("embed needs exactly one of --message or --payload-file"),
+                }
+            }
+            _ => {
+                if message.is_some() || image_support.payload_file.is_some() {
+                    fail("--message and --payload-file only make sense with embed");
+                }
 
+                if operation != Operation::Extract && image_support.output.is_some() {
+                    fail("--out only makes sense with embed and extract");
+                }
+            }
+        }
 
+        image_support
     }
-}
\ No newline at end of file
+}
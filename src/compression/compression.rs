@@ -14,4 +14,386 @@
  * You should have received a copy of the GNU General Public License
  * along with this program; if not, write to the Free Software
  * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
- */
\ No newline at end of file
+ */
+use crate::file_encoding_support::payload::PAYLOAD_FLAG_COMPRESSION_MASK;
+use crate::mathematics_support::huffman::HuffmanTable;
+use crate::mathematics_support::mathematics_support::adler32;
+use fdeflate::BoundedDecompressionError;
+use std::fmt;
+use std::sync::OnceLock;
+
+/*
+    Messages are packed before they are encrypted and embedded, carrier capacity is the scarce resource so every
+    byte saved here is a bit or more the image doesn't have to give up. The codec used ends up in the payload header
+    flags so the extractor knows how to undo it.
+
+    Deflate is the right choice for anything of size, but its zlib wrapper and checksum cost more than a one line
+    message saves. ShortText is a fixed Huffman code over typical English text instead, nothing about the code
+    is stored so it wins on exactly the messages deflate loses on.
+ */
+
+/*
+    Anything claiming to unpack to more than this is treated as hostile rather than allocated
+ */
+pub const MAX_DECOMPRESSED_SIZE: usize = 1 << 28;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionCodec {
+    None,
+    Deflate,
+    ShortText,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionError {
+    UnknownCodec(u8),
+    Corrupt,
+    TooLarge,
+}
+
+impl fmt::Display for CompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressionError::UnknownCodec(id) => write!(f, "unknown compression codec id {id}"),
+            CompressionError::Corrupt => write!(f, "compressed message is corrupt"),
+            CompressionError::TooLarge => write!(
+                f,
+                "compressed message unpacks to more than {MAX_DECOMPRESSED_SIZE} bytes"
+            ),
+        }
+    }
+}
+
+impl CompressionCodec {
+    pub fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<CompressionCodec> {
+        match id {
+            0 => Some(CompressionCodec::None),
+            1 => Some(CompressionCodec::Deflate),
+            2 => Some(CompressionCodec::ShortText),
+            _ => None,
+        }
+    }
+
+    pub fn from_flags(flags: u8) -> Result<CompressionCodec, CompressionError> {
+        let id = flags & PAYLOAD_FLAG_COMPRESSION_MASK;
+        CompressionCodec::from_id(id).ok_or(CompressionError::UnknownCodec(id))
+    }
+}
+
+/*
+    Returns the codec that was actually used alongside the packed bytes, when packing would not make the message
+    any smaller it is left alone and CompressionCodec::None comes back
+ */
+pub fn compress(data: &[u8], codec: CompressionCodec) -> (CompressionCodec, Vec<u8>) {
+    let packed = match codec {
+        CompressionCodec::None => return (CompressionCodec::None, data.to_vec()),
+        CompressionCodec::Deflate => deflate_compress(data),
+        CompressionCodec::ShortText => short_text_compress(data),
+    };
+
+    if packed.len() >= data.len() {
+        return (CompressionCodec::None, data.to_vec());
+    }
+
+    (codec, packed)
+}
+
+pub fn decompress(data: &[u8], codec: CompressionCodec) -> Result<Vec<u8>, CompressionError> {
+    match codec {
+        CompressionCodec::None => Ok(data.to_vec()),
+        CompressionCodec::Deflate => {
+            match fdeflate::decompress_to_vec_bounded(data, MAX_DECOMPRESSED_SIZE) {
+                Ok(unpacked) => Ok(unpacked),
+                Err(BoundedDecompressionError::OutputTooLarge { .. }) => Err(CompressionError::TooLarge),
+                Err(BoundedDecompressionError::DecompressionError { .. }) => Err(CompressionError::Corrupt),
+            }
+        }
+        CompressionCodec::ShortText => short_text_decompress(data),
+    }
+}
+
+/*
+    fdeflate's own compressor only codes runs of zero bytes, it is built for filtered PNG rows and makes text bigger.
+    Messages get a small LZ77 pass written out with the fixed deflate codes (RFC 1951 section 3.2.6) instead, the
+    result is an ordinary zlib stream and fdeflate unpacks it like any other
+ */
+const DEFLATE_WINDOW: usize = 32768;
+const DEFLATE_MIN_MATCH: usize = 3;
+const DEFLATE_MAX_MATCH: usize = 258;
+const DEFLATE_MAX_CHAIN: usize = 128;
+const DEFLATE_HASH_BITS: u32 = 15;
+
+const DEFLATE_LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const DEFLATE_LENGTH_EXTRA_BITS: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DEFLATE_DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+    6145, 8193, 12289, 16385, 24577,
+];
+const DEFLATE_DISTANCE_EXTRA_BITS: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+/*
+    Deflate packs bits least significant first, Huffman codes are the exception and go in most significant bit first
+ */
+#[derive(Default)]
+struct DeflateBitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    bits: u32,
+}
+
+impl DeflateBitWriter {
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.buffer |= (value as u64) << self.bits;
+        self.bits += count;
+
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn write_code(&mut self, code: u32, length: u32) {
+        self.write_bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn write_literal_length(&mut self, symbol: u16) {
+        let (code, length) = match symbol {
+            0..=143 => (0x30 + symbol, 8),
+            144..=255 => (0x190 + symbol - 144, 9),
+            256..=279 => (symbol - 256, 7),
+            _ => (0xC0 + symbol - 280, 8),
+        };
+        self.write_code(code as u32, length);
+    }
+
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let index = DEFLATE_LENGTH_BASES.partition_point(|base| *base as usize <= length) - 1;
+        self.write_literal_length(257 + index as u16);
+        self.write_bits((length - DEFLATE_LENGTH_BASES[index] as usize) as u32, DEFLATE_LENGTH_EXTRA_BITS[index]);
+
+        let index = DEFLATE_DISTANCE_BASES.partition_point(|base| *base as usize <= distance) - 1;
+        self.write_code(index as u32, 5);
+        self.write_bits((distance - DEFLATE_DISTANCE_BASES[index] as usize) as u32, DEFLATE_DISTANCE_EXTRA_BITS[index]);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/*
+    Greedy matching over hash chains of every 3 byte prefix, all in one final block with the fixed codes. Messages
+    are short enough that a dynamic table would mostly cost more than it saves
+ */
+fn deflate_compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = DeflateBitWriter::default();
+    writer.bytes.extend_from_slice(&[0x78, 0x01]); // zlib header, 32K window and no preset dictionary
+    writer.write_bits(1, 1); // BFINAL
+    writer.write_bits(1, 2); // BTYPE fixed codes
+
+    let hash = |position: usize| {
+        let prefix = (data[position] as u32) << 16 | (data[position + 1] as u32) << 8 | data[position + 2] as u32;
+        (prefix.wrapping_mul(2654435761) >> (32 - DEFLATE_HASH_BITS)) as usize
+    };
+    let mut head = vec![usize::MAX; 1 << DEFLATE_HASH_BITS];
+    let mut previous = vec![usize::MAX; data.len()];
+
+    let mut position = 0;
+    while position < data.len() {
+        let max_length = DEFLATE_MAX_MATCH.min(data.len() - position);
+        let mut best_length = 0;
+        let mut best_distance = 0;
+
+        if max_length >= DEFLATE_MIN_MATCH {
+            let mut candidate = head[hash(position)];
+            let mut chain = 0;
+
+            while candidate != usize::MAX && position - candidate <= DEFLATE_WINDOW && chain < DEFLATE_MAX_CHAIN {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[position..position + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+
+                if length > best_length {
+                    best_length = length;
+                    best_distance = position - candidate;
+                    if length == max_length {
+                        break;
+                    }
+                }
+
+                candidate = previous[candidate];
+                chain += 1;
+            }
+        }
+
+        let step = if best_length >= DEFLATE_MIN_MATCH {
+            writer.write_match(best_length, best_distance);
+            best_length
+        } else {
+            writer.write_literal_length(data[position] as u16);
+            1
+        };
+
+        let hashable = (position + step).min(data.len().saturating_sub(DEFLATE_MIN_MATCH - 1));
+        for (inserted, link) in previous.iter_mut().enumerate().take(hashable).skip(position) {
+            let bucket = hash(inserted);
+            *link = head[bucket];
+            head[bucket] = inserted;
+        }
+        position += step;
+    }
+
+    writer.write_literal_length(256); // End of block
+    let mut packed = writer.finish();
+    packed.extend_from_slice(&adler32(data).to_be_bytes());
+    packed
+}
+
+/*
+    Rough letter frequencies of English prose, parts per ten thousand of all characters. Capitals get a sixteenth of
+    their lower case weight and every byte not listed keeps a count of 1 so anything can still be coded, just not well
+ */
+const SHORT_TEXT_LETTER_WEIGHTS: [(u8, u32); 26] = [
+    (b'e', 1016), (b't', 728), (b'a', 656), (b'o', 600), (b'i', 560), (b'n', 536), (b's', 504), (b'h', 488),
+    (b'r', 480), (b'd', 344), (b'l', 320), (b'c', 224), (b'u', 224), (b'm', 192), (b'w', 192), (b'f', 176),
+    (b'g', 160), (b'y', 160), (b'p', 152), (b'b', 120), (b'v', 80), (b'k', 64), (b'j', 12), (b'x', 12),
+    (b'q', 8), (b'z', 6),
+];
+
+const SHORT_TEXT_OTHER_WEIGHTS: [(u8, u32); 14] = [
+    (b' ', 1800), (b'.', 90), (b',', 80), (b'\n', 40), (b'\'', 30), (b'"', 20), (b'-', 16), (b'?', 10),
+    (b'!', 10), (b':', 8), (b';', 4), (b'(', 4), (b')', 4), (b'/', 4),
+];
+
+fn short_text_table() -> &'static HuffmanTable {
+    static TABLE: OnceLock<HuffmanTable> = OnceLock::new();
+
+    TABLE.get_or_init(|| {
+        let mut frequencies = [1u32; 256];
+
+        for (letter, weight) in SHORT_TEXT_LETTER_WEIGHTS {
+            frequencies[letter as usize] = weight;
+            frequencies[letter.to_ascii_uppercase() as usize] = weight / 16 + 1;
+        }
+        for (byte, weight) in SHORT_TEXT_OTHER_WEIGHTS {
+            frequencies[byte as usize] = weight;
+        }
+        for digit in b'0'..=b'9' {
+            frequencies[digit as usize] = 20;
+        }
+
+        HuffmanTable::optimal(&frequencies)
+    })
+}
+
+/*
+    Layout is the unpacked length as an LEB128 varint followed by the codes packed most significant bit first, the
+    last byte is padded out with zeros
+ */
+fn short_text_compress(data: &[u8]) -> Vec<u8> {
+    let table = short_text_table();
+    let mut packed = Vec::with_capacity(data.len() / 2 + 10);
+
+    let mut length = data.len() as u64;
+    loop {
+        let byte = (length & 0x7F) as u8;
+        length >>= 7;
+        if length == 0 {
+            packed.push(byte);
+            break;
+        }
+        packed.push(byte | 0x80);
+    }
+
+    let mut buffer: u32 = 0;
+    let mut bits: u32 = 0;
+
+    for symbol in data {
+        let (code, length) = table.code(*symbol).expect("every byte has a short text code");
+        buffer = (buffer << length) | code as u32;
+        bits += length as u32;
+
+        while bits >= 8 {
+            bits -= 8;
+            packed.push((buffer >> bits) as u8);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+
+    if bits > 0 {
+        packed.push((buffer << (8 - bits)) as u8);
+    }
+
+    packed
+}
+
+fn short_text_decompress(data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    let table = short_text_table();
+
+    let mut length: u64 = 0;
+    let mut position = 0;
+    loop {
+        let byte = *data.get(position).ok_or(CompressionError::Corrupt)?;
+        if position >= 9 {
+            return Err(CompressionError::Corrupt);
+        }
+        length |= ((byte & 0x7F) as u64) << (7 * position);
+        position += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    if length > MAX_DECOMPRESSED_SIZE as u64 {
+        return Err(CompressionError::TooLarge);
+    }
+
+    // Every code is at least a bit long, a length the remaining bytes can't back up is corruption
+    let mut remaining_bits = (data.len() - position) as u64 * 8;
+    if length > remaining_bits {
+        return Err(CompressionError::Corrupt);
+    }
+
+    let mut unpacked = Vec::with_capacity(length as usize);
+    let mut next_bit = || {
+        if remaining_bits == 0 {
+            return None;
+        }
+        let index = data.len() as u64 * 8 - remaining_bits;
+        remaining_bits -= 1;
+        Some(((data[index as usize / 8] >> (7 - index % 8)) & 1) as i32)
+    };
+
+    while (unpacked.len() as u64) < length {
+        let mut code: i32 = 0;
+        let mut symbol = None;
+
+        for code_length in 1..=16 {
+            code = (code << 1) | next_bit().ok_or(CompressionError::Corrupt)?;
+            symbol = table.lookup(code, code_length);
+            if symbol.is_some() {
+                break;
+            }
+        }
+
+        unpacked.push(symbol.ok_or(CompressionError::Corrupt)?);
+    }
+
+    Ok(unpacked)
+}
@@ -0,0 +1,107 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use crate::compression::compression::CompressionError;
+use crate::file_encoding_support::encryption::EncryptionError;
+use crate::file_encoding_support::file_encoding_support::FileEncoding;
+use crate::file_encoding_support::payload::PayloadError;
+use crate::filetype_support::filetype_support::FileType;
+use std::fmt;
+use std::io;
+
+/*
+    Everything that can go wrong between a carrier file and a message. Nothing in the library prints or exits,
+    errors come back up to whoever called and the CLI decides what to tell the user.
+ */
+#[derive(Debug)]
+pub enum MayaError {
+    UnsupportedFormat(String), // Not a file we know, or a variant of one we can't handle yet
+    UnsupportedEncoding { encoding: FileEncoding, file_type: FileType }, // e.g. JSteg on a BMP
+    CapacityExceeded { needed_bits: u64, capacity_bits: u64 },
+    CorruptHeader(String), // The carrier's own structure is broken
+    NotParsed,             // Embedding or extracting before parse_file / parse_bytes
+    InvalidParameter(String), // An EncodingParameters setting the encoding can't work with
+    Payload(PayloadError), // Nothing embedded, or not with this encoding, method and key
+    WrongKey,              // The payload is encrypted and the passphrase doesn't open it
+    PassphraseRequired,
+    Encryption(EncryptionError),
+    Compression(CompressionError),
+    Io(io::Error),
+}
+
+impl fmt::Display for MayaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MayaError::UnsupportedFormat(what) => write!(f, "unsupported format: {what}"),
+            MayaError::UnsupportedEncoding { encoding, file_type } => {
+                write!(f, "{encoding:?} can't be used on {file_type:?} files")
+            }
+            MayaError::CapacityExceeded {
+                needed_bits,
+                capacity_bits,
+            } => write!(
+                f,
+                "not enough space in the image to embed {needed_bits} bits, only have {capacity_bits} bits available"
+            ),
+            MayaError::CorruptHeader(what) => write!(f, "corrupt file: {what}"),
+            MayaError::NotParsed => write!(f, "file has not been parsed yet"),
+            MayaError::InvalidParameter(what) => write!(f, "invalid parameter: {what}"),
+            MayaError::Payload(e) => write!(f, "{e}"),
+            MayaError::WrongKey => write!(f, "wrong passphrase or no encrypted payload"),
+            MayaError::PassphraseRequired => write!(f, "payload is encrypted, a passphrase is needed to decrypt it"),
+            MayaError::Encryption(e) => write!(f, "{e}"),
+            MayaError::Compression(e) => write!(f, "{e}"),
+            MayaError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for MayaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MayaError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MayaError {
+    fn from(e: io::Error) -> MayaError {
+        MayaError::Io(e)
+    }
+}
+
+impl From<PayloadError> for MayaError {
+    fn from(e: PayloadError) -> MayaError {
+        MayaError::Payload(e)
+    }
+}
+
+impl From<EncryptionError> for MayaError {
+    fn from(e: EncryptionError) -> MayaError {
+        match e {
+            EncryptionError::WrongKeyOrNoPayload => MayaError::WrongKey,
+            e => MayaError::Encryption(e),
+        }
+    }
+}
+
+impl From<CompressionError> for MayaError {
+    fn from(e: CompressionError) -> MayaError {
+        MayaError::Compression(e)
+    }
+}
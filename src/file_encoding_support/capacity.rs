@@ -0,0 +1,101 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use crate::file_encoding_support::encryption::ENCRYPTION_OVERHEAD;
+use crate::file_encoding_support::file_encoding_support::{FileEncoding, FileEncodingMethod};
+use crate::file_encoding_support::payload::PAYLOAD_HEADER_SIZE;
+use crate::filetype_support::filetype_support::FileType;
+use std::fmt;
+
+/*
+    How much of the raw capacity can be used before the usual steganalysis gets a reliable signal, as a fraction
+    numerator / denominator. Plain LSB and JSteg replace bits outright, chi-square and RS / SPA start picking them
+    up at a few percent of the carrier. LSB matching doesn't leave the pairs of values those attacks count on,
+    Hamming and F5 change far fewer samples per message bit and PVD hides in the edges, so they can go further.
+    These are rules of thumb, not guarantees.
+ */
+pub fn recommended_fraction(encoding: FileEncoding) -> (u64, u64) {
+    match encoding {
+        FileEncoding::Lsb => (1, 20),
+        FileEncoding::JSteg => (1, 20),
+        FileEncoding::PixelValueDifferencing => (1, 10),
+        FileEncoding::LsbMatching => (1, 10),
+        FileEncoding::HammingMatrix => (1, 8),
+        FileEncoding::F5 => (1, 4),
+    }
+}
+
+/*
+    Everything worth knowing about how big a message can go into one carrier with one encoding and method. The
+    traversal methods all visit every slot so the method doesn't change the numbers, it is kept so the report says
+    what it was worked out for
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CapacityReport {
+    pub file_type: FileType,
+    pub encoding: FileEncoding,
+    pub encoding_method: FileEncodingMethod,
+    pub raw_bits: u64,          // Every bit the encoding can hide, the payload header included
+    pub overhead_bytes: u64,    // Payload header plus the encryption envelope if there's a passphrase
+    pub usable_bytes: u64,      // Largest message that fits, before compression
+    pub recommended_bytes: u64, // Largest message that stays within recommended_fraction of the carrier
+}
+
+impl CapacityReport {
+    pub fn new(
+        file_type: FileType,
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        raw_bits: u64,
+        encrypted: bool,
+    ) -> CapacityReport {
+        let overhead_bytes = match encrypted {
+            true => (PAYLOAD_HEADER_SIZE + ENCRYPTION_OVERHEAD) as u64,
+            false => PAYLOAD_HEADER_SIZE as u64,
+        };
+        let (numerator, denominator) = recommended_fraction(encoding);
+
+        CapacityReport {
+            file_type,
+            encoding,
+            encoding_method,
+            raw_bits,
+            overhead_bytes,
+            usable_bytes: (raw_bits / 8).saturating_sub(overhead_bytes),
+            recommended_bytes: (raw_bits * numerator / denominator / 8).saturating_sub(overhead_bytes),
+        }
+    }
+}
+
+impl fmt::Display for CapacityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:?} with {:?} / {:?}", self.file_type, self.encoding, self.encoding_method)?;
+        writeln!(f, "  raw capacity: {} bits", self.raw_bits)?;
+        writeln!(f, "  usable: {} bytes ({} bytes of overhead)", self.usable_bytes, self.overhead_bytes)?;
+        write!(f, "  recommended: {} bytes or less to stay hard to detect", self.recommended_bytes)?;
+
+        /*
+            Lsb writes every plane a 16 bit sample offers, LsbMatching only the lowest one, and the header records
+            which was used, so the two don't read each other's payloads back even though both are LSB
+         */
+        if self.encoding == FileEncoding::LsbMatching {
+            write!(f, "\n  extract with LsbMatching as well, the header records it and Lsb won't read the message back")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use crate::error::error::MayaError;
+use crate::file_encoding_support::file_encoding_support::{FileEncodingFunctionDerivation, FileEncodingMethod};
+use crate::file_encoding_support::pixel::increment_bit_and_byte_counters;
+use crate::mathematics_support::mathematics_support::{hamming_choose_k, hamming_syndrome};
+
+/*
+    Embedding in quantized JPEG DCT coefficients. The file format side hands us the AC coefficients of every
+    block as one flat run, DC coefficients are left alone since changing a block's average is far too visible.
+ */
+
+/*
+    F5 writes the matrix encoding parameter k ahead of the payload, one bit per usable coefficient
+ */
+pub const F5_K_BITS: u32 = 4;
+pub const F5_MAX_K: u32 = 7;
+
+/*
+    JSteg skips 0 and 1 entirely, flipping the LSB of anything else keeps it inside {-2,-1}, {2,3}, {-4,-3} ... so a
+    coefficient can never turn into a 0 or 1 and the extractor sees exactly the same set of usable coefficients
+ */
+fn jsteg_usable(coefficient: i16) -> bool {
+    coefficient != 0 && coefficient != 1
+}
+
+pub fn jsteg_capacity_bits(coefficients: &[i16]) -> u64 {
+    coefficients.iter().filter(|coefficient| jsteg_usable(**coefficient)).count() as u64
+}
+
+pub fn embed_jsteg_data(
+    data: &[u8],
+    coefficients: &mut [i16],
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Result<(), MayaError> {
+    let mut bits_to_embed = data.len() * 8;
+    let mut current_byte: u32 = 0;
+    let mut current_bit: u32 = 0;
+
+    let capacity = jsteg_capacity_bits(coefficients);
+
+    if bits_to_embed as u64 > capacity {
+        return Err(MayaError::CapacityExceeded {
+            needed_bits: bits_to_embed as u64,
+            capacity_bits: capacity,
+        });
+    }
+
+    for position in file_encoding_function_derivation.visit_order(encoding_method, coefficients.len()) {
+        if bits_to_embed == 0 {
+            break;
+        }
+
+        if !jsteg_usable(coefficients[position]) {
+            continue;
+        }
+
+        let bit = ((data[current_byte as usize] >> current_bit) & 1) as i16;
+        coefficients[position] = (coefficients[position] & !1) | bit;
+
+        increment_bit_and_byte_counters(&mut current_bit, &mut current_byte);
+        bits_to_embed -= 1;
+    }
+
+    Ok(())
+}
+
+pub fn extract_jsteg_data(
+    coefficients: &[i16],
+    embedded_bits: u64,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Vec<u8> {
+    let mut bytes: u32 = 0;
+    let mut bits: u32 = 0;
+
+    //The plus one is just in case we have a sub byte number of bits , we would lose those few bits or write into an invalid offset
+    let mut extracted_data: Vec<u8> = vec![0u8; (embedded_bits as usize / 8) + 1];
+
+    for position in file_encoding_function_derivation.visit_order(encoding_method, coefficients.len()) {
+        if (bits + bytes * 8) as u64 == embedded_bits {
+            break;
+        }
+
+        if !jsteg_usable(coefficients[position]) {
+            continue;
+        }
+
+        if coefficients[position] & 1 == 1 {
+            extracted_data[bytes as usize] |= 1 << bits;
+        }
+
+        increment_bit_and_byte_counters(&mut bits, &mut bytes);
+    }
+
+    extracted_data
+}
+
+/*
+    F5 reads a bit out of the sign adjusted LSB of every non zero coefficient and only ever moves a coefficient
+    towards zero. When that turns it into a 0 (shrinkage) the extractor will skip it so the same bits have to be
+    embedded again further on.
+ */
+fn f5_bit(coefficient: i16) -> u32 {
+    if coefficient > 0 {
+        (coefficient & 1) as u32
+    } else {
+        1 - (coefficient & 1) as u32
+    }
+}
+
+fn f5_decrement(coefficient: &mut i16) {
+    if *coefficient > 0 {
+        *coefficient -= 1;
+    } else {
+        *coefficient += 1;
+    }
+}
+
+/*
+    Every change to a coefficient of magnitude 1 wastes it, so only the larger ones are counted as sure capacity.
+    This is what embedding checks against, extraction can't know how many coefficients shrank and has to use
+    f5_max_bits instead.
+ */
+pub fn f5_capacity_bits(coefficients: &[i16]) -> u64 {
+    let usable = coefficients.iter().filter(|coefficient| coefficient.abs() > 1).count() as u64;
+    usable.saturating_sub(F5_K_BITS as u64)
+}
+
+pub fn f5_max_bits(coefficients: &[i16]) -> u64 {
+    let usable = coefficients.iter().filter(|coefficient| **coefficient != 0).count() as u64;
+    usable.saturating_sub(F5_K_BITS as u64)
+}
+
+/*
+    Walks the non zero coefficients in visit order
+ */
+struct F5Cursor {
+    order: Vec<usize>,
+    next: usize,
+}
+
+impl F5Cursor {
+    fn new(len: usize, encoding_method: FileEncodingMethod, file_encoding_function_derivation: FileEncodingFunctionDerivation) -> F5Cursor {
+        F5Cursor {
+            order: file_encoding_function_derivation.visit_order(encoding_method, len).collect(),
+            next: 0,
+        }
+    }
+
+    /*
+        The next count non zero coefficients from the current position, without moving past them
+     */
+    fn peek_group(&self, coefficients: &[i16], count: usize) -> Option<(Vec<usize>, usize)> {
+        let mut group = Vec::with_capacity(count);
+        let mut next = self.next;
+
+        while group.len() < count {
+            let position = *self.order.get(next)?;
+            if coefficients[position] != 0 {
+                group.push(position);
+            }
+            next += 1;
+        }
+
+        Some((group, next))
+    }
+}
+
+fn f5_hash(coefficients: &[i16], group: &[usize]) -> u32 {
+    hamming_syndrome(group.iter().map(|position| f5_bit(coefficients[*position]) == 1))
+}
+
+/*
+    (1, 2^k - 1, k) matrix encoding: k message bits go into each group of n = 2^k - 1 coefficients by changing at
+    most one of them, the bits are read back as the XOR of the (1 based) indices of the coefficients whose bit is set
+ */
+fn f5_embed_group(
+    coefficients: &mut [i16],
+    cursor: &mut F5Cursor,
+    message: u32,
+    k: u32,
+) -> Result<(), ()> {
+    let n = (1usize << k) - 1;
+
+    loop {
+        let (group, next) = cursor.peek_group(coefficients, n).ok_or(())?;
+        let change = f5_hash(coefficients, &group) ^ message;
+
+        if change == 0 {
+            cursor.next = next;
+            return Ok(());
+        }
+
+        let position = group[change as usize - 1];
+        f5_decrement(&mut coefficients[position]);
+
+        if coefficients[position] != 0 {
+            cursor.next = next;
+            return Ok(());
+        }
+        // Shrinkage, the group now starts over without the coefficient that became 0
+    }
+}
+
+fn f5_extract_group(coefficients: &[i16], cursor: &mut F5Cursor, k: u32) -> Option<u32> {
+    let (group, next) = cursor.peek_group(coefficients, (1usize << k) - 1)?;
+    cursor.next = next;
+    Some(f5_hash(coefficients, &group))
+}
+
+pub fn embed_f5_data(
+    data: &[u8],
+    coefficients: &mut [i16],
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Result<(), MayaError> {
+    let bits_to_embed = data.len() as u64 * 8;
+    let capacity = f5_capacity_bits(coefficients);
+
+    if bits_to_embed > capacity {
+        return Err(MayaError::CapacityExceeded {
+            needed_bits: bits_to_embed,
+            capacity_bits: capacity,
+        });
+    }
+
+    let k = hamming_choose_k(bits_to_embed, capacity, F5_MAX_K);
+    let mut cursor = F5Cursor::new(coefficients.len(), encoding_method, file_encoding_function_derivation);
+
+    let mut embedded = (0..F5_K_BITS).all(|bit| f5_embed_group(coefficients, &mut cursor, (k >> bit) & 1, 1).is_ok());
+
+    let mut current_byte: u32 = 0;
+    let mut current_bit: u32 = 0;
+    let mut remaining = bits_to_embed;
+
+    while embedded && remaining > 0 {
+        let mut message = 0;
+        for bit in 0..k.min(remaining as u32) {
+            message |= (((data[current_byte as usize] >> current_bit) & 1) as u32) << bit;
+            increment_bit_and_byte_counters(&mut current_bit, &mut current_byte);
+        }
+        remaining = remaining.saturating_sub(k as u64);
+
+        embedded = f5_embed_group(coefficients, &mut cursor, message, k).is_ok();
+    }
+
+    /*
+        Too many coefficients shrank to 0 along the way, whatever got in before the last group is all that fits
+     */
+    if !embedded {
+        return Err(MayaError::CapacityExceeded {
+            needed_bits: bits_to_embed,
+            capacity_bits: (bits_to_embed - remaining).saturating_sub(k as u64),
+        });
+    }
+
+    Ok(())
+}
+
+pub fn extract_f5_data(
+    coefficients: &[i16],
+    embedded_bits: u64,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Vec<u8> {
+    let mut bytes: u32 = 0;
+    let mut bits: u32 = 0;
+
+    //The plus one is just in case we have a sub byte number of bits , we would lose those few bits or write into an invalid offset
+    let mut extracted_data: Vec<u8> = vec![0u8; (embedded_bits as usize / 8) + 1];
+
+    let mut cursor = F5Cursor::new(coefficients.len(), encoding_method, file_encoding_function_derivation);
+    let mut k = 0;
+    for bit in 0..F5_K_BITS {
+        match f5_extract_group(coefficients, &mut cursor, 1) {
+            Some(value) => k |= value << bit,
+            None => return extracted_data,
+        }
+    }
+
+    if k == 0 || k > F5_MAX_K {
+        return extracted_data;
+    }
+
+    while ((bits + bytes * 8) as u64) < embedded_bits {
+        let message = match f5_extract_group(coefficients, &mut cursor, k) {
+            Some(message) => message,
+            None => break,
+        };
+
+        for bit in 0..k {
+            if (bits + bytes * 8) as u64 == embedded_bits {
+                break;
+            }
+            if (message >> bit) & 1 == 1 {
+                extracted_data[bytes as usize] |= 1 << bits;
+            }
+            increment_bit_and_byte_counters(&mut bits, &mut bytes);
+        }
+    }
+
+    extracted_data
+}
@@ -0,0 +1,186 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::fmt;
+
+/*
+    Optional encryption of the message before it is handed to the embedders. The passphrase is stretched with
+    Argon2id using a salt picked fresh for every image, the result keys ChaCha20-Poly1305. Everything needed to
+    decrypt apart from the passphrase travels with the ciphertext:
+
+        0..4    magic "MAYE"
+        4       version
+        5..9    Argon2 memory in KiB (u32, little endian)
+        9..13   Argon2 passes (u32, little endian)
+        13      Argon2 lanes
+        14..30  salt
+        30..42  nonce
+        42..    ciphertext followed by the 16 byte tag
+
+    Bytes 0..42 are authenticated as associated data so none of them can be changed without decryption failing.
+ */
+pub const ENCRYPTION_MAGIC: [u8; 4] = *b"MAYE";
+pub const ENCRYPTION_VERSION: u8 = 1;
+pub const ENCRYPTION_SALT_SIZE: usize = 16;
+pub const ENCRYPTION_NONCE_SIZE: usize = 12;
+pub const ENCRYPTION_TAG_SIZE: usize = 16;
+pub const ENCRYPTION_HEADER_SIZE: usize = 14 + ENCRYPTION_SALT_SIZE + ENCRYPTION_NONCE_SIZE;
+
+/*
+    Bytes encryption adds on top of the message, worth knowing when checking a message against capacity
+ */
+pub const ENCRYPTION_OVERHEAD: usize = ENCRYPTION_HEADER_SIZE + ENCRYPTION_TAG_SIZE;
+
+/*
+    Anything asking for more memory than this is refused instead of letting a crafted image exhaust the machine
+ */
+const MAX_KDF_MEMORY_KIB: u32 = 1 << 20;
+
+/*
+    Same again for the pass count, a crafted header asking for billions of passes would hang extraction just as well
+ */
+const MAX_KDF_ITERATIONS: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionError {
+    NotEncrypted,
+    UnsupportedVersion(u8),
+    UnsupportedKdfParams,
+    WrongKeyOrNoPayload,
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptionError::NotEncrypted => write!(f, "payload is not encrypted"),
+            EncryptionError::UnsupportedVersion(version) => {
+                write!(f, "unsupported encryption version {version}")
+            }
+            EncryptionError::UnsupportedKdfParams => write!(f, "unsupported key derivation parameters"),
+            EncryptionError::WrongKeyOrNoPayload => write!(f, "wrong key or no payload"),
+        }
+    }
+}
+
+/*
+    Argon2id cost, the defaults are the OWASP recommended minimum of 19 MiB and 2 passes on one lane
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u8,
+}
+
+impl Default for KdfParams {
+    fn default() -> KdfParams {
+        KdfParams {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    fn derive_key(&self, passphrase: &str, salt: &[u8]) -> Result<Key, EncryptionError> {
+        if self.memory_kib > MAX_KDF_MEMORY_KIB || self.iterations > MAX_KDF_ITERATIONS {
+            return Err(EncryptionError::UnsupportedKdfParams);
+        }
+
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism as u32, Some(32))
+            .map_err(|_| EncryptionError::UnsupportedKdfParams)?;
+
+        let mut key = Key::default();
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|_| EncryptionError::UnsupportedKdfParams)?;
+
+        Ok(key)
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.len() >= ENCRYPTION_OVERHEAD && data[0..4] == ENCRYPTION_MAGIC
+}
+
+pub fn encrypt_payload(data: &[u8], passphrase: &str) -> Result<Vec<u8>, EncryptionError> {
+    encrypt_payload_with_params(data, passphrase, KdfParams::default())
+}
+
+/*
+    Fails only when kdf_params are out of range for Argon2 or ask for more memory than decrypt_payload would accept
+ */
+pub fn encrypt_payload_with_params(data: &[u8], passphrase: &str, kdf_params: KdfParams) -> Result<Vec<u8>, EncryptionError> {
+    let mut header = Vec::with_capacity(ENCRYPTION_HEADER_SIZE);
+    header.extend_from_slice(&ENCRYPTION_MAGIC);
+    header.push(ENCRYPTION_VERSION);
+    header.extend_from_slice(&kdf_params.memory_kib.to_le_bytes());
+    header.extend_from_slice(&kdf_params.iterations.to_le_bytes());
+    header.push(kdf_params.parallelism);
+
+    let mut salt = [0u8; ENCRYPTION_SALT_SIZE];
+    OsRng.fill_bytes(&mut salt);
+    header.extend_from_slice(&salt);
+
+    let mut nonce = [0u8; ENCRYPTION_NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+    header.extend_from_slice(&nonce);
+
+    let key = kdf_params.derive_key(passphrase, &salt)?;
+
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: data, aad: &header })
+        .expect("ChaCha20-Poly1305 only fails on messages far bigger than any image");
+
+    header.extend_from_slice(&ciphertext);
+    Ok(header)
+}
+
+pub fn decrypt_payload(data: &[u8], passphrase: &str) -> Result<Vec<u8>, EncryptionError> {
+    if !is_encrypted(data) {
+        return Err(EncryptionError::NotEncrypted);
+    }
+
+    if data[4] != ENCRYPTION_VERSION {
+        return Err(EncryptionError::UnsupportedVersion(data[4]));
+    }
+
+    let kdf_params = KdfParams {
+        memory_kib: u32::from_le_bytes([data[5], data[6], data[7], data[8]]),
+        iterations: u32::from_le_bytes([data[9], data[10], data[11], data[12]]),
+        parallelism: data[13],
+    };
+
+    let salt = &data[14..14 + ENCRYPTION_SALT_SIZE];
+    let nonce = &data[14 + ENCRYPTION_SALT_SIZE..ENCRYPTION_HEADER_SIZE];
+    let key = kdf_params.derive_key(passphrase, salt)?;
+
+    ChaCha20Poly1305::new(&key)
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: &data[ENCRYPTION_HEADER_SIZE..],
+                aad: &data[..ENCRYPTION_HEADER_SIZE],
+            },
+        )
+        .map_err(|_| EncryptionError::WrongKeyOrNoPayload)
+}
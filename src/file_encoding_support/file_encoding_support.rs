@@ -16,164 +16,273 @@ along with this program; if not, write to the Free Software
 Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
  */
 
-use std::fs::File;
-
-pub struct ImageSupport {
-    pub(crate) image_file: File,
-    pub(crate) encoding: FileEncoding,
-    pub(crate) encoding_method: FileEncodingMethod,
-    pub(crate) file_encoding_function_derivation: FileEncodingFunctionDerivation,
-    pub(crate) operation: Operation,
-    pub(crate) data : Vec<u8>,
-    pub(crate) encoding_support: Box<dyn FileEncodingSupport>,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum WaveFunction {
-    Horizontal,
-    Vertical,
-    DiagonalRight,
-    DiagonalLeft,
-    ZigZagHorizontal,
-    ZigZagVertical,
-    Sinusoidal,
-}
+use crate::analysis::analysis::PixelChannels;
+use crate::error::error::MayaError;
+use crate::filetype_support::bmp::RleOutput;
+use crate::filetype_support::filetype_support::FileType;
+use crate::file_encoding_support::key::TraversalKey;
+use crate::file_encoding_support::palette::PaletteEmbedding;
+use crate::file_encoding_support::traversal::TraversalParameters;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Operation {
     Embed,
-    Extract
+    Extract,
+    Capacity, // How much fits, nothing is written
+    Info,
+    Analyze, // Looks for payloads without knowing how they were embedded
 }
+/*
+    The discriminants are written into the embedded payload header (see payload.rs) so they must never be
+    reordered or reused, only appended to
+ */
+#[repr(u8)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum FileEncoding {
-    Lsb,
-    PixelValueDifferencing,
-    HammingMatrix,
+    Lsb = 0,
+    PixelValueDifferencing = 1,
+    HammingMatrix = 2,
+    JSteg = 3, // JPEG only, works on the quantized DCT coefficients
+    F5 = 4,    // JPEG only, works on the quantized DCT coefficients
+    LsbMatching = 5, // +1 or -1 instead of overwriting the LSB, lowest plane only so it has to be extracted as LsbMatching
 }
 
+#[repr(u8)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum FileEncodingMethod {
-    LeftToRight,
-    RightToLeft,
-    TopToBottom,
-    SinWave,
-    CosWave,
-    PolynomialFunction,
-    FractalFunction,
+    LeftToRight = 0,
+    RightToLeft = 1,
+    TopToBottom = 2,
+    SinWave = 3,
+    CosWave = 4,
+    PolynomialFunction = 5,
+    FractalFunction = 6,
+    HilbertCurve = 7,
+    MortonOrder = 8,
+    SpiralInward = 9,
+    SpiralOutward = 10,
+    BlockRaster = 11,
 }
 
+/*
+    Where the order embedding positions are visited in comes from, the encoding method on its own or the method
+    shuffled by a key derived from a passphrase
+ */
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum FileEncodingFunctionDerivation {
-    KeyBased,
+    MethodBased,
+    KeyBased(TraversalKey),
+}
+
+/*
+    Settings the individual encodings can be tuned with. They aren't in the payload header, whoever extracts has
+    to pass the same ones the embedder used. None everywhere gets the defaults.
+ */
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EncodingParameters {
+    pub hamming_k: Option<u32>, // Message bits per group of 2^k - 1 cover bits, picked from the payload size when None
+    pub pvd_ranges: Option<Vec<u32>>, // PVD range widths for 8 bit samples, scaled up for 16 bit ones, see PvdRangeTable
+    pub palette_tolerance: Option<u8>, // Indexed images skip palette neighbours further apart than this, see Palette
+    pub palette_embedding: PaletteEmbedding, // Whether embedding rewrites the palette itself, extraction reads either
 }
 
 pub trait FileEncodingSupport {
+    /*
+        Only remembers where the file is, nothing is read until parse_file
+     */
     fn new(filename : &str) -> Self where Self: Sized;
 
-    fn parse_file(&mut self);
+    fn parse_file(&mut self) -> Result<(), MayaError>;
+
+    /*
+        Same as parse_file for a file that is already in memory
+     */
+    fn parse_bytes(&mut self, file_data: Vec<u8>) -> Result<(), MayaError>;
+
+    fn from_bytes(file_data: Vec<u8>) -> Result<Self, MayaError> where Self: Sized {
+        let mut parser = Self::new("");
+        parser.parse_bytes(file_data)?;
+        Ok(parser)
+    }
+
+    fn file_type(&self) -> FileType;
 
-    fn embed_data(&mut self, data: &mut Vec<u8>, encoding: FileEncoding, encoding_method: FileEncodingMethod, file_encoding_function_derivation: FileEncodingFunctionDerivation);
+    /*
+        Used by every embed, extract and capacity call after it, formats that don't use any of them ignore it
+     */
+    fn set_encoding_parameters(&mut self, _parameters: EncodingParameters) {}
 
-    fn retrieve_data(&mut self,data: Vec<u8>,encoding: FileEncoding, encoding_method: FileEncodingMethod, file_encoding_function_derivation: FileEncodingFunctionDerivation);
+    /*
+        What to_bytes does with an RLE compressed pixel map after embedding, only BMP has those
+     */
+    fn set_rle_output(&mut self, _rle_output: RleOutput) {}
 
-    fn write_file(&mut self, new_file_location: &str);
-    
+    /*
+        Compressed and uncompressed size in bytes when to_bytes writes an RLE pixel map out uncompressed, so the
+        caller can say the file got bigger. None for everything else
+     */
+    fn rle_expansion(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /*
+        Raw bits the encoding can hide in this file, the payload header comes out of this too
+     */
+    fn carrier_capacity_bits(&mut self, encoding: FileEncoding) -> Result<u64, MayaError>;
+
+    /*
+        flags end up in the payload header next to the message, see payload.rs for what the bits mean
+     */
+    fn embed_data_with_flags(&mut self, data: &mut Vec<u8>, encoding: FileEncoding, encoding_method: FileEncodingMethod, file_encoding_function_derivation: FileEncodingFunctionDerivation, flags: u8) -> Result<(), MayaError>;
+
+    fn retrieve_data_with_flags(&mut self, encoding: FileEncoding, encoding_method: FileEncodingMethod, file_encoding_function_derivation: FileEncodingFunctionDerivation) -> Result<(Vec<u8>, u8), MayaError>;
+
+    fn embed_data(&mut self, data: &mut Vec<u8>, encoding: FileEncoding, encoding_method: FileEncodingMethod, file_encoding_function_derivation: FileEncodingFunctionDerivation) -> Result<(), MayaError> {
+        self.embed_data_with_flags(data, encoding, encoding_method, file_encoding_function_derivation, 0)
+    }
+
+    fn retrieve_data(&mut self, encoding: FileEncoding, encoding_method: FileEncodingMethod, file_encoding_function_derivation: FileEncodingFunctionDerivation) -> Result<Vec<u8>, MayaError> {
+        Ok(self.retrieve_data_with_flags(encoding, encoding_method, file_encoding_function_derivation)?.0)
+    }
+
+    /*
+        Every sample of the image split up by channel for the steganalysis in analysis/, formats without pixels
+        to look at (JPEG coefficients) say so instead
+     */
+    fn pixel_channels(&mut self) -> Result<PixelChannels, MayaError> {
+        Err(MayaError::UnsupportedFormat(format!("{:?} has no pixel samples to analyze", self.file_type())))
+    }
+
+    /*
+        The whole file as it should be written back out, with whatever was embedded in it
+     */
+    fn to_bytes(&mut self) -> Result<Vec<u8>, MayaError>;
+
+    fn write_file(&mut self, new_file_location: &str) -> Result<(), MayaError> {
+        let output = self.to_bytes()?;
+        std::fs::write(new_file_location, output)?;
+        Ok(())
+    }
 }
 
 
-/*
-    The idea at the time of writing this is the other parameters like the FileEncoding as defined above, will be stored in the specific object and can be referenced internally
- */
-pub trait FileEncodingAlgorithms{
-    fn left_to_right(&self);
-    fn right_to_left(&self);
-    fn top_to_bottom(&self);
-    fn wave(&self, wave_type: WaveFunction, amplitude: f32, phase: f32, frequency: f32);
+impl FileEncoding {
+    pub fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<FileEncoding> {
+        match id {
+            0 => Some(FileEncoding::Lsb),
+            1 => Some(FileEncoding::PixelValueDifferencing),
+            2 => Some(FileEncoding::HammingMatrix),
+            3 => Some(FileEncoding::JSteg),
+            4 => Some(FileEncoding::F5),
+            5 => Some(FileEncoding::LsbMatching),
+            _ => None,
+        }
+    }
+
+    /*
+        JSteg and F5 only make sense on JPEG coefficients, everything else works on pixels (or palette indices)
+     */
+    pub fn works_on_coefficients(&self) -> bool {
+        matches!(self, FileEncoding::JSteg | FileEncoding::F5)
+    }
 }
 
+impl FileEncodingMethod {
+    pub fn id(&self) -> u8 {
+        *self as u8
+    }
 
+    pub fn from_id(id: u8) -> Option<FileEncodingMethod> {
+        match id {
+            0 => Some(FileEncodingMethod::LeftToRight),
+            1 => Some(FileEncodingMethod::RightToLeft),
+            2 => Some(FileEncodingMethod::TopToBottom),
+            3 => Some(FileEncodingMethod::SinWave),
+            4 => Some(FileEncodingMethod::CosWave),
+            5 => Some(FileEncodingMethod::PolynomialFunction),
+            6 => Some(FileEncodingMethod::FractalFunction),
+            7 => Some(FileEncodingMethod::HilbertCurve),
+            8 => Some(FileEncodingMethod::MortonOrder),
+            9 => Some(FileEncodingMethod::SpiralInward),
+            10 => Some(FileEncodingMethod::SpiralOutward),
+            11 => Some(FileEncodingMethod::BlockRaster),
+            _ => None,
+        }
+    }
 
-impl WaveFunction {
-    pub(crate) fn traverse(&self, rows: usize, cols: usize) -> Vec<(usize, usize)> {
-        let mut positions = Vec::new();
+    /*
+        Order to visit a flat run of len embedding slots in (palette indices, JPEG coefficients), pixel maps
+        have their own row aware traversal in pixel.rs
+     */
+    pub fn visit_order(self, len: usize) -> Box<dyn Iterator<Item = usize>> {
+        match self {
+            FileEncodingMethod::LeftToRight => Box::new(0..len),
+            FileEncodingMethod::RightToLeft => Box::new((0..len).rev()),
+            method => method.flat_grid_visit_order(&TraversalParameters::default(), len),
+        }
+    }
+}
 
+impl FileEncodingFunctionDerivation {
+    pub fn from_passphrase(passphrase: &str) -> FileEncodingFunctionDerivation {
+        FileEncodingFunctionDerivation::KeyBased(TraversalKey::from_passphrase(passphrase))
+    }
+
+    /*
+        Which way LSB matching steps each sample it has to change, the key decides if there is one and the payload
+        does otherwise. Extraction never needs these so nothing has to be agreed on
+     */
+    pub fn lsb_matching_signs(self, payload: &[u8]) -> impl FnMut() -> bool + use<> {
         match self {
-            // Horizontal wave: Traverse each row from left to right
-            WaveFunction::Horizontal => {
-                for row in 0..rows {
-                    for col in 0..cols {
-                        positions.push((row, col));
-                    }
-                }
-            }
-            // Vertical wave: Traverse each column from top to bottom
-            WaveFunction::Vertical => {
-                for col in 0..cols {
-                    for row in 0..rows {
-                        positions.push((row, col));
-                    }
-                }
-            }
-            // DiagonalRight wave: Traverse diagonally from top-left to bottom-right
-            WaveFunction::DiagonalRight => {
-                let mut row = 0;
-                let mut col = 0;
-                while row < rows && col < cols {
-                    positions.push((row, col));
-                    row += 1;
-                    col += 1;
-                }
-            }
-            // DiagonalLeft wave: Traverse diagonally from top-right to bottom-left
-            WaveFunction::DiagonalLeft => {
-                let mut row = 0;
-                let mut col = cols - 1;
-                while row < rows && col >= 0 {
-                    positions.push((row, col));
-                    row += 1;
-                    col = col.saturating_sub(1);
-                }
-            }
-            // ZigZagHorizontal: Traverse rows in a zigzag pattern (left-right, then right-left)
-            WaveFunction::ZigZagHorizontal => {
-                for row in 0..rows {
-                    if row % 2 == 0 {
-                        for col in 0..cols {
-                            positions.push((row, col));
-                        }
-                    } else {
-                        for col in (0..cols).rev() {
-                            positions.push((row, col));
-                        }
-                    }
-                }
-            }
-            // ZigZagVertical: Traverse columns in a zigzag pattern (top-bottom, then bottom-top)
-            WaveFunction::ZigZagVertical => {
-                for col in 0..cols {
-                    if col % 2 == 0 {
-                        for row in 0..rows {
-                            positions.push((row, col));
-                        }
-                    } else {
-                        for row in (0..rows).rev() {
-                            positions.push((row, col));
-                        }
-                    }
-                }
+            FileEncodingFunctionDerivation::KeyBased(key) => key.lsb_matching_signs(),
+            FileEncodingFunctionDerivation::MethodBased => TraversalKey::from_payload(payload).lsb_matching_signs(),
+        }
+    }
+
+    /*
+        The method's order as is, or a keyed permutation with the method picking which one. Curve methods are the
+        exception, under a key they keep their shape and the key picks the curve's parameters instead
+     */
+    pub fn visit_order(self, encoding_method: FileEncodingMethod, len: usize) -> Box<dyn Iterator<Item = usize>> {
+        match self {
+            FileEncodingFunctionDerivation::MethodBased => encoding_method.visit_order(len),
+            FileEncodingFunctionDerivation::KeyBased(key) if encoding_method.is_curve() => {
+                encoding_method.flat_grid_visit_order(&key.traversal_parameters(encoding_method.id() as u64), len)
             }
-            // Sinusoidal wave: Simulate a sine wave pattern over rows and columns
-            WaveFunction::Sinusoidal => {
-                // A basic sine-wave-like pattern, with amplitude and frequency scaling
-                for row in 0..rows {
-                    let sine_wave_offset = ((row as f32 / rows as f32) * 2.0 * std::f32::consts::PI).sin();
-                    // Translate sine value to column index (oscillation over columns)
-                    let col_offset = ((sine_wave_offset + 1.0) * (cols as f32 / 2.0)) as usize;
-                    positions.push((row, col_offset));
-                }
+            FileEncodingFunctionDerivation::KeyBased(key) => {
+                Box::new(key.permutation(encoding_method.id() as u64, len))
             }
         }
+    }
 
-        positions
+    /*
+        Same as visit_order for slots laid out row major over a cols by rows grid with per_cell consecutive slots
+        to each cell (channels and bit planes of a pixel, channels of a PVD pair). The method's order moves a cell at
+        a time and carries every slot of the cell along with it, only a keyed shuffle splits cells up
+     */
+    pub fn grid_visit_order(
+        self,
+        encoding_method: FileEncodingMethod,
+        cols: usize,
+        rows: usize,
+        per_cell: usize,
+    ) -> Box<dyn Iterator<Item = usize>> {
+        let parameters = match self {
+            FileEncodingFunctionDerivation::KeyBased(key) if !encoding_method.is_curve() => {
+                return Box::new(key.permutation(encoding_method.id() as u64, cols * rows * per_cell))
+            }
+            FileEncodingFunctionDerivation::KeyBased(key) => key.traversal_parameters(encoding_method.id() as u64),
+            FileEncodingFunctionDerivation::MethodBased => TraversalParameters::default(),
+        };
+
+        Box::new(
+            encoding_method
+                .grid_visit_order(&parameters, cols, rows)
+                .flat_map(move |cell| cell * per_cell..(cell + 1) * per_cell),
+        )
     }
-}
\ No newline at end of file
+}
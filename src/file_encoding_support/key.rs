@@ -0,0 +1,195 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use crate::file_encoding_support::traversal::TraversalParameters;
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+
+/*
+    Keyed traversal. A passphrase is hashed down to a 256 bit key which seeds a ChaCha20 stream, that stream drives
+    a Fisher-Yates shuffle of every embedding position (pixel, channel and bit plane, PVD pair, palette index or
+    JPEG coefficient). The payload header goes through the same shuffle so without the key an extractor can't find
+    where anything starts, let alone how much there is.
+ */
+
+/*
+    Mixed into the hash so the traversal key can never collide with anything else derived from the same passphrase
+ */
+const TRAVERSAL_KEY_CONTEXT: &[u8] = b"maya keyed traversal v1";
+
+/*
+    Permutations use the encoding method id as their stream, curve parameters take the streams with the top bit set
+ */
+const TRAVERSAL_PARAMETER_STREAMS: u64 = 1 << 63;
+
+/*
+    LSB matching's +1 / -1 choices get a stream of their own, clear of both of the above
+ */
+const LSB_MATCHING_STREAM: u64 = 1 << 62;
+
+/*
+    Seeds LSB matching when there is no passphrase, see TraversalKey::from_payload
+ */
+const LSB_MATCHING_CONTEXT: &[u8] = b"maya lsb matching v1";
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct TraversalKey([u8; 32]);
+
+/*
+    Keep the key out of debug output and panic messages
+ */
+impl fmt::Debug for TraversalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TraversalKey(..)")
+    }
+}
+
+impl TraversalKey {
+    pub fn from_passphrase(passphrase: &str) -> TraversalKey {
+        let mut hasher = Sha256::new();
+        hasher.update(TRAVERSAL_KEY_CONTEXT);
+        hasher.update(passphrase.as_bytes());
+        TraversalKey(hasher.finalize().into())
+    }
+
+    pub fn from_bytes(key: [u8; 32]) -> TraversalKey {
+        TraversalKey(key)
+    }
+
+    /*
+        A key made from the payload itself, for when LSB matching needs its coin flips and no passphrase was given.
+        The same message into the same carrier comes out the same every time
+     */
+    pub fn from_payload(payload: &[u8]) -> TraversalKey {
+        let mut hasher = Sha256::new();
+        hasher.update(LSB_MATCHING_CONTEXT);
+        hasher.update(payload);
+        TraversalKey(hasher.finalize().into())
+    }
+
+    /*
+        Endless fair coin flips, true means step the sample up and false step it down
+     */
+    pub fn lsb_matching_signs(self) -> impl FnMut() -> bool {
+        let mut rng = ChaCha20Rng::from_seed(self.0);
+        rng.set_stream(LSB_MATCHING_STREAM);
+
+        let mut flips = 0u64;
+        let mut left = 0u32;
+        move || {
+            if left == 0 {
+                flips = rng.next_u64();
+                left = u64::BITS;
+            }
+            left -= 1;
+            let flip = flips & 1 == 1;
+            flips >>= 1;
+            flip
+        }
+    }
+
+    /*
+        A permutation of 0..len, stream picks one of 2^64 independent permutations for the same key and is
+        what lets the encoding method still change the order under a key
+     */
+    pub fn permutation(&self, stream: u64, len: usize) -> KeyedPermutation {
+        let mut rng = ChaCha20Rng::from_seed(self.0);
+        rng.set_stream(stream);
+
+        KeyedPermutation {
+            rng,
+            len,
+            next: 0,
+            displaced: HashMap::new(),
+        }
+    }
+
+    /*
+        Curve parameters for the curve traversals, drawn from their own streams so they have nothing in common with
+        any permutation. The ranges keep every curve visibly a curve, anything goes inside them
+     */
+    pub fn traversal_parameters(&self, stream: u64) -> TraversalParameters {
+        let mut rng = ChaCha20Rng::from_seed(self.0);
+        rng.set_stream(TRAVERSAL_PARAMETER_STREAMS | stream);
+
+        let mut unit = || (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        let amplitude = 0.05 + unit() * 0.45;
+        let phase = unit() * std::f64::consts::TAU;
+        let frequency = 0.5 + unit() * 7.5;
+
+        TraversalParameters {
+            amplitude,
+            phase,
+            frequency,
+            coefficients: [rng.next_u64(), rng.next_u64(), rng.next_u64(), rng.next_u64()],
+            depth: 2 + uniform_below(&mut rng, 5) as u32,
+        }
+    }
+}
+
+/*
+    Fisher-Yates run front to back one step at a time. Only the slots that have been swapped out of place are
+    kept, so reading a header out of a multi megapixel image costs a few hundred steps and not a full shuffle.
+ */
+pub struct KeyedPermutation {
+    rng: ChaCha20Rng,
+    len: usize,
+    next: usize,
+    displaced: HashMap<usize, usize>,
+}
+
+impl Iterator for KeyedPermutation {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.next == self.len {
+            return None;
+        }
+
+        let swap = self.next + uniform_below(&mut self.rng, (self.len - self.next) as u64) as usize;
+        let current = self.displaced.remove(&self.next).unwrap_or(self.next);
+        let chosen = if swap == self.next {
+            current
+        } else {
+            self.displaced.insert(swap, current).unwrap_or(swap)
+        };
+
+        self.next += 1;
+        Some(chosen)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len - self.next, Some(self.len - self.next))
+    }
+}
+
+/*
+    Uniform in 0..bound, anything in the biased tail at the top of the u64 range is thrown away and drawn again
+ */
+fn uniform_below(rng: &mut ChaCha20Rng, bound: u64) -> u64 {
+    let zone = u64::MAX - (u64::MAX % bound);
+
+    loop {
+        let value = rng.next_u64();
+        if value < zone {
+            return value % bound;
+        }
+    }
+}
@@ -17,4 +17,11 @@
  */
 
 pub mod file_encoding_support;
-pub mod pixel;
\ No newline at end of file
+pub mod pixel;
+pub mod payload;
+pub mod palette;
+pub mod coefficient;
+pub mod key;
+pub mod traversal;
+pub mod encryption;
+pub mod capacity;
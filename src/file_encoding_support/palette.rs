@@ -0,0 +1,248 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use crate::error::error::MayaError;
+use crate::file_encoding_support::file_encoding_support::{FileEncodingFunctionDerivation, FileEncodingMethod};
+use crate::file_encoding_support::pixel::increment_bit_and_byte_counters;
+
+/*
+    Indexed images can't have their color bytes touched directly, flipping the low bit of an index can jump to a
+    completely different color. Instead the palette is put into luminance order and each index's position in that
+    order (its rank) carries the bit, rank ^ 1 is always the closest neighbouring color. This is the EzStego approach.
+
+    Closest in luminance can still be a long way off in a small or colorful palette (a 1 bit image only has black and
+    white), so a palette can carry a tolerance and any pair of neighbours further apart than that is left alone.
+ */
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PaletteEmbedding {
+    /*
+        Leave the palette as is, the luminance order only exists while embedding / extracting
+     */
+    #[default]
+    LuminanceOrder,
+    /*
+        Rewrite the palette itself in luminance order and remap every index, after that the rank is the index
+        so any plain index LSB extractor can read it back
+     */
+    ReorderPalette,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PaletteEntry {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl PaletteEntry {
+    /*
+        ITU-R BT.601 luma scaled by 1000 so we can stay in integers
+     */
+    pub fn luminance(&self) -> u32 {
+        299 * self.red as u32 + 587 * self.green as u32 + 114 * self.blue as u32
+    }
+
+    /*
+        Largest change any one channel (alpha included) goes through when swapping this entry for the other
+     */
+    pub fn distance(&self, other: &PaletteEntry) -> u8 {
+        [
+            self.red.abs_diff(other.red),
+            self.green.abs_diff(other.green),
+            self.blue.abs_diff(other.blue),
+            self.alpha.abs_diff(other.alpha),
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Palette {
+    pub entries: Vec<PaletteEntry>,
+    pub tolerance: Option<u8>, // Furthest apart (see PaletteEntry::distance) two neighbours can be and still swap, None for no limit
+}
+
+impl Palette {
+    /*
+        Palette indices in luminance order. Entries are grouped by alpha first so that a bit flip never swaps a
+        transparent color for an opaque one. The sort is stable so equal entries keep their relative order and the
+        extractor always ends up with the same ranks as the embedder.
+     */
+    pub fn luminance_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        order.sort_by_key(|&index| (self.entries[index].alpha, self.entries[index].luminance()));
+        order
+    }
+
+    /*
+        rank_of_index[index] gives the rank of that palette entry, any index past the end of the palette maps to None
+     */
+    pub fn ranks(&self) -> [Option<usize>; 256] {
+        let mut rank_of_index = [None; 256];
+        for (rank, index) in self.luminance_order().into_iter().enumerate() {
+            if index < 256 {
+                rank_of_index[index] = Some(rank);
+            }
+        }
+        rank_of_index
+    }
+
+    /*
+        Sort the entries into luminance order, the returned table maps every old index to its new index
+     */
+    pub fn sort_by_luminance(&mut self) -> [u8; 256] {
+        let order = self.luminance_order();
+        let mut remap = [0u8; 256];
+
+        for (new_index, old_index) in order.iter().enumerate() {
+            remap[*old_index] = new_index as u8;
+        }
+
+        self.entries = order.iter().map(|index| self.entries[*index]).collect();
+        remap
+    }
+
+    /*
+        Rank of every index that can carry a bit, which means rank ^ 1 is inside the palette (with an odd number of
+        entries the brightest one is left on its own) and the two colors are within the tolerance. Pairs are
+        symmetric so swapping an index for its partner never changes whether it can carry a bit.
+     */
+    fn carrier_ranks(&self) -> [Option<usize>; 256] {
+        let order = self.luminance_order();
+        let mut carriers = [None; 256];
+
+        for (rank, index) in order.iter().enumerate() {
+            let partner = match order.get(rank ^ 1) {
+                Some(partner) => *partner,
+                None => continue,
+            };
+
+            let within_tolerance = self
+                .tolerance
+                .is_none_or(|tolerance| self.entries[*index].distance(&self.entries[partner]) <= tolerance);
+
+            if *index < 256 && within_tolerance {
+                carriers[*index] = Some(rank);
+            }
+        }
+
+        carriers
+    }
+}
+
+/*
+    Pixels whose palette entry has no usable partner (or which point outside the palette) can't carry anything and
+    are skipped by both the embedder and the extractor. Embedding never moves a pixel in or out of that set.
+ */
+pub fn palette_capacity_bits(indices: &[u8], palette: &Palette) -> u64 {
+    let carriers = palette.carrier_ranks();
+    indices
+        .iter()
+        .filter(|index| carriers[**index as usize].is_some())
+        .count() as u64
+}
+
+/*
+    indices is one per pixel, row by row over a width by height image, so the curve methods follow the picture
+ */
+pub fn embed_palette_data(
+    data: &[u8],
+    indices: &mut [u8],
+    width: usize,
+    height: usize,
+    palette: &Palette,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Result<(), MayaError> {
+    let carriers = palette.carrier_ranks();
+    let order = palette.luminance_order();
+
+    let mut bits_to_embed = data.len() * 8;
+    let mut current_byte: u32 = 0;
+    let mut current_bit: u32 = 0;
+
+    let capacity = palette_capacity_bits(indices, palette);
+
+    if bits_to_embed as u64 > capacity {
+        return Err(MayaError::CapacityExceeded {
+            needed_bits: bits_to_embed as u64,
+            capacity_bits: capacity,
+        });
+    }
+
+    for position in file_encoding_function_derivation.grid_visit_order(encoding_method, width, height, 1) {
+        if bits_to_embed == 0 {
+            break;
+        }
+
+        let rank = match carriers[indices[position] as usize] {
+            Some(rank) => rank,
+            None => continue,
+        };
+
+        let bit = (data[current_byte as usize] >> current_bit) & 1;
+
+        if (rank & 1) as u8 != bit {
+            indices[position] = order[rank ^ 1] as u8;
+        }
+
+        increment_bit_and_byte_counters(&mut current_bit, &mut current_byte);
+        bits_to_embed -= 1;
+    }
+
+    Ok(())
+}
+
+pub fn extract_palette_data(
+    indices: &[u8],
+    width: usize,
+    height: usize,
+    palette: &Palette,
+    embedded_bits: u64,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Vec<u8> {
+    let carriers = palette.carrier_ranks();
+
+    let mut bytes: u32 = 0;
+    let mut bits: u32 = 0;
+
+    //The plus one is just in case we have a sub byte number of bits , we would lose those few bits or write into an invalid offset
+    let mut extracted_data: Vec<u8> = vec![0u8; (embedded_bits as usize / 8) + 1];
+
+    for position in file_encoding_function_derivation.grid_visit_order(encoding_method, width, height, 1) {
+        if (bits + bytes * 8) as u64 == embedded_bits {
+            break;
+        }
+
+        let rank = match carriers[indices[position] as usize] {
+            Some(rank) => rank,
+            None => continue,
+        };
+
+        if rank & 1 == 1 {
+            extracted_data[bytes as usize] |= 1 << bits;
+        }
+
+        increment_bit_and_byte_counters(&mut bits, &mut bytes);
+    }
+
+    extracted_data
+}
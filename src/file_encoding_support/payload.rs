@@ -0,0 +1,214 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use crate::file_encoding_support::file_encoding_support::{FileEncoding, FileEncodingMethod};
+use crate::mathematics_support::mathematics_support::crc32_update;
+use std::fmt;
+
+/*
+    Every embedded message is prefixed with this header, and the header itself is embedded with the same
+    encoding and method as the message. That way nothing about the payload lives in the carrier's own file
+    headers and the extractor can find out everything it needs from the pixels alone, no matter the format.
+
+    Layout (all multi byte fields are little endian):
+
+        0..4    magic "MAYA"
+        4       version
+        5       FileEncoding id
+        6       FileEncodingMethod id
+        7       flags, bits 0..2 hold the CompressionCodec id the message was packed with, bits 2..5 the k a
+                HammingMatrix payload was embedded with (0 for every other encoding), the rest are reserved
+        8..16   payload length in bytes (u64)
+        16..20  crc32 over bytes 0..16 followed by the payload
+ */
+pub const PAYLOAD_MAGIC: [u8; 4] = *b"MAYA";
+pub const PAYLOAD_VERSION: u8 = 1;
+pub const PAYLOAD_HEADER_SIZE: usize = 20;
+pub const PAYLOAD_HEADER_BITS: u64 = (PAYLOAD_HEADER_SIZE * 8) as u64;
+pub const PAYLOAD_FLAG_COMPRESSION_MASK: u8 = 0b0000_0011;
+pub const PAYLOAD_FLAG_HAMMING_K_MASK: u8 = 0b0001_1100;
+pub const PAYLOAD_FLAG_HAMMING_K_SHIFT: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnknownEncoding(u8),
+    UnknownEncodingMethod(u8),
+    EncodingMismatch,
+    LengthExceedsCapacity { length: u64, capacity_bits: u64 },
+    CrcMismatch { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayloadError::BadMagic => write!(f, "no payload header found"),
+            PayloadError::UnsupportedVersion(version) => write!(f, "unsupported payload version {version}"),
+            PayloadError::UnknownEncoding(id) => write!(f, "unknown encoding id {id}"),
+            PayloadError::UnknownEncodingMethod(id) => write!(f, "unknown encoding method id {id}"),
+            PayloadError::EncodingMismatch => write!(f, "payload was embedded with a different encoding or encoding method"),
+            PayloadError::LengthExceedsCapacity { length, capacity_bits } => {
+                write!(f, "payload claims {length} bytes but the carrier only holds {capacity_bits} bits")
+            }
+            PayloadError::CrcMismatch { expected, actual } => {
+                write!(f, "payload crc mismatch, expected {expected:#010x} got {actual:#010x}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadHeader {
+    pub version: u8,
+    pub encoding: FileEncoding,
+    pub encoding_method: FileEncodingMethod,
+    pub flags: u8,
+    pub length: u64,
+    pub crc: u32,
+}
+
+impl PayloadHeader {
+    pub fn new(data: &[u8], encoding: FileEncoding, encoding_method: FileEncodingMethod, flags: u8) -> PayloadHeader {
+        let mut header = PayloadHeader {
+            version: PAYLOAD_VERSION,
+            encoding,
+            encoding_method,
+            flags,
+            length: data.len() as u64,
+            crc: 0,
+        };
+        header.crc = header.compute_crc(data);
+        header
+    }
+
+    pub fn to_bytes(self) -> [u8; PAYLOAD_HEADER_SIZE] {
+        let mut bytes = [0u8; PAYLOAD_HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&PAYLOAD_MAGIC);
+        bytes[4] = self.version;
+        bytes[5] = self.encoding.id();
+        bytes[6] = self.encoding_method.id();
+        bytes[7] = self.flags;
+        bytes[8..16].copy_from_slice(&self.length.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.crc.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<PayloadHeader, PayloadError> {
+        if bytes.len() < PAYLOAD_HEADER_SIZE || bytes[0..4] != PAYLOAD_MAGIC {
+            return Err(PayloadError::BadMagic);
+        }
+
+        if bytes[4] != PAYLOAD_VERSION {
+            return Err(PayloadError::UnsupportedVersion(bytes[4]));
+        }
+
+        let encoding = FileEncoding::from_id(bytes[5]).ok_or(PayloadError::UnknownEncoding(bytes[5]))?;
+        let encoding_method = FileEncodingMethod::from_id(bytes[6]).ok_or(PayloadError::UnknownEncodingMethod(bytes[6]))?;
+
+        let mut length = [0u8; 8];
+        length.copy_from_slice(&bytes[8..16]);
+        let mut crc = [0u8; 4];
+        crc.copy_from_slice(&bytes[16..20]);
+
+        Ok(PayloadHeader {
+            version: bytes[4],
+            encoding,
+            encoding_method,
+            flags: bytes[7],
+            length: u64::from_le_bytes(length),
+            crc: u32::from_le_bytes(crc),
+        })
+    }
+
+    /*
+        Number of bits the header and payload take up together in the carrier
+     */
+    pub fn total_bits(&self) -> u64 {
+        PAYLOAD_HEADER_BITS.saturating_add(self.length.saturating_mul(8))
+    }
+
+    pub fn verify(&self, data: &[u8]) -> Result<(), PayloadError> {
+        let actual = self.compute_crc(data);
+        if actual != self.crc {
+            return Err(PayloadError::CrcMismatch { expected: self.crc, actual });
+        }
+        Ok(())
+    }
+
+    fn compute_crc(&self, data: &[u8]) -> u32 {
+        let header_bytes = self.to_bytes();
+        let crc = crc32_update(0xFFFF_FFFF, &header_bytes[0..16]);
+        crc32_update(crc, data) ^ 0xFFFF_FFFF
+    }
+}
+
+/*
+    The Hamming k bits of the flags byte, the only place k is kept. The header itself always goes in at k = 1 so
+    these can be read before the rest of the message
+ */
+pub fn hamming_k_flags(k: u32) -> u8 {
+    ((k as u8) << PAYLOAD_FLAG_HAMMING_K_SHIFT) & PAYLOAD_FLAG_HAMMING_K_MASK
+}
+
+pub fn hamming_k_from_flags(flags: u8) -> u32 {
+    ((flags & PAYLOAD_FLAG_HAMMING_K_MASK) >> PAYLOAD_FLAG_HAMMING_K_SHIFT) as u32
+}
+
+/*
+    Prefix the message with its header, the result is what actually gets handed to the embedding functions
+ */
+pub fn build_payload(data: &[u8], encoding: FileEncoding, encoding_method: FileEncodingMethod, flags: u8) -> Vec<u8> {
+    let header = PayloadHeader::new(data, encoding, encoding_method, flags);
+    let mut payload = Vec::with_capacity(PAYLOAD_HEADER_SIZE + data.len());
+    payload.extend_from_slice(&header.to_bytes());
+    payload.extend_from_slice(data);
+    payload
+}
+
+/*
+    Format independent extraction. extract_bits is handed a number of bits and must return at least that many
+    bits worth of bytes read from the start of the carrier with the given encoding and method, the header is read
+    first so we know how much more to pull out. The header flags come back alongside the message. Whatever error
+    extract_bits fails with is handed straight back, payload problems are converted into it.
+ */
+pub fn read_payload<F, E>(encoding: FileEncoding, encoding_method: FileEncodingMethod, capacity_bits: u64, mut extract_bits: F) -> Result<(Vec<u8>, u8), E>
+where
+    F: FnMut(u64) -> Result<Vec<u8>, E>,
+    E: From<PayloadError>,
+{
+    if capacity_bits < PAYLOAD_HEADER_BITS {
+        return Err(PayloadError::BadMagic.into());
+    }
+
+    let header = PayloadHeader::from_bytes(&extract_bits(PAYLOAD_HEADER_BITS)?)?;
+
+    if header.encoding != encoding || header.encoding_method != encoding_method {
+        return Err(PayloadError::EncodingMismatch.into());
+    }
+
+    if header.total_bits() > capacity_bits {
+        return Err(PayloadError::LengthExceedsCapacity { length: header.length, capacity_bits }.into());
+    }
+
+    let extracted = extract_bits(header.total_bits())?;
+    let data = extracted[PAYLOAD_HEADER_SIZE..PAYLOAD_HEADER_SIZE + header.length as usize].to_vec();
+
+    header.verify(&data)?;
+
+    Ok((data, header.flags))
+}
@@ -15,47 +15,82 @@
  * along with this program; if not, write to the Free Software
  * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
  */
-use crate::file_encoding_support::file_encoding_support::WaveFunction;
-use std::ops::{AddAssign, BitOrAssign, BitXor, BitXorAssign, Sub, SubAssign};
+use crate::error::error::MayaError;
+use crate::file_encoding_support::file_encoding_support::{
+    EncodingParameters, FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod,
+};
+use crate::file_encoding_support::payload::{
+    hamming_k_flags, hamming_k_from_flags, PayloadHeader, PAYLOAD_FLAG_HAMMING_K_MASK, PAYLOAD_HEADER_BITS, PAYLOAD_HEADER_SIZE,
+};
+use crate::mathematics_support::mathematics_support::{hamming_choose_k, hamming_syndrome};
+use std::ops::SubAssign;
+
+/*
+    Widest a sample can be, anything narrower is handed to us either as whole bytes (8 bit) or unpacked to one
+    sample per byte (1, 2 and 4 bit) so a pixel is always a whole number of bytes
+ */
+pub const MAX_SAMPLE_BITS: u32 = 16;
+
+/*
+    A 16 bit sample changes by at most 15 / 65535 when its low 4 bits are rewritten, that is still under a single
+    step once the image is shown at 8 bits per channel so we take 4 bits per sample instead of 1
+ */
+pub const WIDE_SAMPLE_LSB_BITS: u32 = 4;
 
 pub trait Pixel {
-    fn red(&self) -> u8;
-    fn green(&self) -> u8;
-    fn blue(&self) -> u8;
+    /*
+       1 for grayscale, 2 for grayscale + alpha, 3 for RGB and 4 for RGBA
+    */
+    fn channel_count(&self) -> usize;
 
     /*
-       This should just return 255 if this particular pixel does not support alpha
+       Width of every sample in bits, one of 1, 2, 4, 8 or 16
     */
-    fn alpha(&self) -> u8;
+    fn sample_bits(&self) -> u32 {
+        8
+    }
 
     /*
-       These will be for embedding just the first , second etc color value irrespective of which order the colors are
+       How many low order bits of each sample LSB embedding writes into
     */
-    fn first(&self) -> u8;
-    fn second(&self) -> u8;
-    fn third(&self) -> u8;
+    fn lsb_bits(&self) -> u32 {
+        if self.sample_bits() == MAX_SAMPLE_BITS {
+            WIDE_SAMPLE_LSB_BITS
+        } else {
+            1
+        }
+    }
 
-    fn fourth(&self) -> u8; // This one should just return 255 if not in use
+    fn max_sample(&self) -> u16 {
+        ((1u32 << self.sample_bits()) - 1) as u16
+    }
 
-    fn set_red(&mut self, value: u8);
-    fn set_green(&mut self, value: u8);
-    fn set_blue(&mut self, value: u8);
+    /*
+       These are for embedding into the first, second etc sample irrespective of which order the colors are in,
+       indexing past channel_count is a bug
+    */
+    fn channel(&self, index: usize) -> u16;
+    fn set_channel(&mut self, index: usize, value: u16);
 
-    fn set_first(&mut self, value: u8);
-    fn set_second(&mut self, value: u8);
-    fn set_third(&mut self, value: u8);
+    /*
+       Grayscale pixels should return the gray sample for all three colors
+    */
+    fn red(&self) -> u16;
+    fn green(&self) -> u16;
+    fn blue(&self) -> u16;
 
-    // This should do nothing or exit / panic on invocation from a 3 byte pixel
-    fn set_fourth(&mut self, value: u8);
     /*
-       This should do nothing on a 3 byte pixel impl
+       This should just return max_sample if this particular pixel does not support alpha
     */
-    fn set_alpha(&mut self, value: u8);
+    fn alpha(&self) -> u16;
 
+    /*
+       Size of the pixel in bytes as it sits in the pixel map
+    */
     fn pixel_size(&self) -> usize;
 }
 
-pub fn transform_pixels<P, F>(pixel_map: &mut Vec<P>, transform_function: F)
+pub fn transform_pixels<P, F>(pixel_map: &mut [P], transform_function: F)
 where
     P: Pixel,
     F: Fn(&mut P),
@@ -66,7 +101,7 @@ where
 }
 
 pub fn transform_pixel_quadrants<P, F>(
-    pixel_map: &mut Vec<P>,
+    pixel_map: &mut [P],
     transform_function: F,
     coordinates: (u64, u64),
     quadrant_size: u64,
@@ -74,7 +109,7 @@ pub fn transform_pixel_quadrants<P, F>(
     P: Pixel + Sized,
     F: Fn(&mut [P]),
 {
-    let start_index = (coordinates.0 as usize * coordinates.1 as usize) as usize;
+    let start_index = coordinates.0 as usize * coordinates.1 as usize;
     let end_index = start_index + quadrant_size as usize;
 
     let quadrant_slice = &mut pixel_map[start_index..end_index];
@@ -90,668 +125,1229 @@ pub fn increment_bit_and_byte_counters(bit: &mut u32, byte: &mut u32) {
     }
 }
 
-fn embed_pixel_lsb<P: Pixel>(
-    pixel: &mut P,
-    current_bit: &mut u32,
-    current_byte: &mut u32,
-    data: &Vec<u8>,
-    bits_to_embed: &mut usize,
-) {
-    let mut bit: u8 = data[*current_byte as usize] & (1 << *current_bit);
+/*
+    Where the pixels are in a pixel map. Rows sit stride bytes apart since BMP pads every row out to a multiple of 4
+    bytes, padding is that many bytes on the end of a row and not a number of pixels. A cell is a single pixel, or
+    for PVD a pair of horizontally adjacent ones with any odd pixel at the end of a row left alone
+ */
+#[derive(Debug, Clone, Copy)]
+struct PixelGrid {
+    cols: usize,
+    rows: usize,
+    stride: usize,
+    cell_bytes: usize,
+}
 
-    if bit == 0 {
-        pixel.set_first(pixel.first() & !1);
-    } else {
-        pixel.set_first(pixel.first() | 1);
+impl PixelGrid {
+    fn new(width: u64, length: u64, padding: u64, pixel_size_bytes: u64) -> PixelGrid {
+        PixelGrid {
+            cols: width as usize,
+            rows: length as usize,
+            stride: (width * pixel_size_bytes + padding) as usize,
+            cell_bytes: pixel_size_bytes as usize,
+        }
     }
 
-    increment_bit_and_byte_counters(current_bit, current_byte);
-    bits_to_embed.sub_assign(1);
-
-    if *bits_to_embed == 0 {
-        return;
+    fn pairs(self) -> PixelGrid {
+        PixelGrid {
+            cols: self.cols / 2,
+            cell_bytes: self.cell_bytes * 2,
+            ..self
+        }
     }
 
-    bit = data[*current_byte as usize] & (1 << *current_bit);
-
-    if bit == 0 {
-        pixel.set_second(pixel.second() & !1);
-    } else {
-        pixel.set_second(pixel.second() | 1);
+    fn cells(&self) -> usize {
+        self.cols * self.rows
     }
 
-    increment_bit_and_byte_counters(current_bit, current_byte);
-    bits_to_embed.sub_assign(1);
-
-    if *bits_to_embed == 0 {
-        return;
+    /*
+        Byte offset of a cell given as row * cols + col, the way the traversal orders hand them out
+     */
+    fn offset(&self, cell: usize) -> usize {
+        (cell / self.cols) * self.stride + (cell % self.cols) * self.cell_bytes
     }
+}
 
-    bit = data[*current_byte as usize] & (1 << *current_bit);
-
-    if bit == 0 {
-        pixel.set_third(pixel.third() & !1);
-    } else {
-        pixel.set_third(pixel.third() | 1);
-    }
-    increment_bit_and_byte_counters(current_bit, current_byte);
-    bits_to_embed.sub_assign(1);
+/*
+    Number of set bits across every sample of the pixel, the color encodings carry one bit in its parity
+ */
+fn pixel_ones<P: Pixel>(pixel: &P) -> u32 {
+    (0..pixel.channel_count())
+        .map(|channel| pixel.channel(channel).count_ones())
+        .sum()
+}
 
-    if *bits_to_embed == 0 {
-        return;
-    }
+fn embed_pixel_color<P: Pixel>(
+    pixel: &mut P,
+    current_bit: &mut u32,
+    current_byte: &mut u32,
+    data: &[u8],
+    bits_to_embed: &mut usize,
+) {
+    let bit = data[*current_byte as usize] & (1 << *current_bit);
 
-    if pixel.pixel_size() == 4 {
-        bit = data[*current_byte as usize] & (1 << *current_bit);
-        if bit == 0 {
-            pixel.set_fourth(pixel.fourth() & !1);
-        } else {
-            pixel.set_fourth(pixel.fourth() | 1);
+    /*
+        An even number of ones reads back as a 1, if the parity is wrong flip the lowest zero bit we can find
+        (lowest bit plane first, then channel order) and if every bit is already set clear the lowest one instead
+     */
+    if (bit != 0) != (pixel_ones(pixel).is_multiple_of(2)) {
+        let mut changed = false;
+
+        'planes: for plane in 0..pixel.sample_bits() {
+            for channel in 0..pixel.channel_count() {
+                let sample = pixel.channel(channel);
+                if sample & (1 << plane) == 0 {
+                    pixel.set_channel(channel, sample | (1 << plane));
+                    changed = true;
+                    break 'planes;
+                }
+            }
         }
 
-        increment_bit_and_byte_counters(current_bit, current_byte);
-        bits_to_embed.sub_assign(1);
+        if !changed {
+            pixel.set_channel(0, pixel.channel(0) & !1);
+        }
     }
+
+    increment_bit_and_byte_counters(current_bit, current_byte);
+    bits_to_embed.sub_assign(1);
 }
 
-fn extract_pixel_lsb<P: Pixel>(
+fn extract_pixel_color<P: Pixel>(
     pixel: &P,
     bits: &mut u32,
     bytes: &mut u32,
-    extracted_data: &mut Vec<u8>,
-    embedded_bits: usize,
+    extracted_data: &mut [u8],
+    _embedded_bits: usize,
 ) {
-    let mut current_bit = pixel.first() & 1;
-
-    if current_bit == 0 {
-        extracted_data[*bytes as usize] &= !(1 << *bits);
-    } else {
+    if pixel_ones(pixel).is_multiple_of(2) {
         extracted_data[*bytes as usize] |= 1 << *bits;
+    } else {
+        extracted_data[*bytes as usize] &= !(1 << *bits);
     }
 
     increment_bit_and_byte_counters(bits, bytes);
+}
 
-    if *bits + (*bytes * 8) == embedded_bits as u32 {
-        return;
-    }
+/*
+    Color parity embedding, one bit in the parity of each pixel's set bits visited in the method's order
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn embed_color_data_in_order<P: Pixel>(
+    data: &[u8],
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Result<(), MayaError> {
+    let grid = PixelGrid::new(width, length, padding, pixel_size_bytes);
 
-    current_bit = pixel.second() & 1;
+    let mut bits_to_embed = data.len() * 8;
 
-    if current_bit == 0 {
-        extracted_data[*bytes as usize] &= !(1 << *bits);
-    } else {
-        extracted_data[*bytes as usize] |= 1 << *bits;
+    let mut current_byte: u32 = 0;
+    let mut current_bit: u32 = 0;
+
+    if bits_to_embed > grid.cells() {
+        return Err(MayaError::CapacityExceeded {
+            needed_bits: bits_to_embed as u64,
+            capacity_bits: grid.cells() as u64,
+        });
     }
 
-    increment_bit_and_byte_counters(bits, bytes);
+    for cell in file_encoding_function_derivation.grid_visit_order(encoding_method, grid.cols, grid.rows, 1) {
+        if bits_to_embed == 0 {
+            break;
+        }
 
-    if *bits + (*bytes * 8) == embedded_bits as u32 {
-        return;
+        let pixel = pixel_at::<P>(pixel_map, grid.offset(cell), pixel_size_bytes);
+        embed_pixel_color(pixel, &mut current_bit, &mut current_byte, data, &mut bits_to_embed);
     }
 
-    current_bit = pixel.third() & 1;
+    Ok(())
+}
 
-    if current_bit == 0 {
-        extracted_data[*bytes as usize] &= !(1 << *bits);
-    } else {
-        extracted_data[*bytes as usize] |= 1 << *bits;
-    }
+#[allow(clippy::too_many_arguments)]
+pub fn extract_color_data_in_order<P: Pixel>(
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+    embedded_bits: u64,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Vec<u8> {
+    let grid = PixelGrid::new(width, length, padding, pixel_size_bytes);
 
-    increment_bit_and_byte_counters(&mut *bits, &mut *bytes);
+    let mut bytes: u32 = 0;
+    let mut bits: u32 = 0;
+
+    //The plus one is just in case we have a sub byte number of bits , we would lose those few bits or write into an invalid offset
+    let mut extracted_data: Vec<u8> = vec![0u8; (embedded_bits as usize / 8) + 1];
 
-    if *bits + (*bytes * 8) == embedded_bits as u32 {
-        return;
+    for cell in file_encoding_function_derivation.grid_visit_order(encoding_method, grid.cols, grid.rows, 1) {
+        if (bits + bytes * 8) as u64 == embedded_bits {
+            break;
+        }
+
+        let pixel = pixel_at::<P>(pixel_map, grid.offset(cell), pixel_size_bytes);
+        extract_pixel_color(pixel, &mut bits, &mut bytes, &mut extracted_data, embedded_bits as usize);
     }
 
-    if pixel.pixel_size() == 4 {
-        current_bit = pixel.fourth() & 1;
+    extracted_data
+}
 
-        if current_bit == 0 {
-            extracted_data[*bytes as usize] &= !(1 << *bits);
-        } else {
-            extracted_data[*bytes as usize] |= 1 << *bits;
-        }
+pub fn embed_color_data_left_right<P: Pixel>(
+    data: &[u8],
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+) -> Result<(), MayaError> {
+    embed_color_data_in_order::<P>(
+        data,
+        pixel_map,
+        width,
+        length,
+        padding,
+        pixel_size_bytes,
+        FileEncodingMethod::LeftToRight,
+        FileEncodingFunctionDerivation::MethodBased,
+    )
+}
 
-        increment_bit_and_byte_counters(bits, bytes);
+pub fn extract_color_data_left_right<P: Pixel>(
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+    embedded_bits: u64,
+) -> Vec<u8> {
+    extract_color_data_in_order::<P>(
+        pixel_map,
+        width,
+        length,
+        padding,
+        pixel_size_bytes,
+        embedded_bits,
+        FileEncodingMethod::LeftToRight,
+        FileEncodingFunctionDerivation::MethodBased,
+    )
+}
 
-        if *bits + (*bytes * 8) == embedded_bits as u32 {
-            return;
-        }
-    }
+pub fn embed_color_data_right_left<P: Pixel>(
+    data: &[u8],
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+) -> Result<(), MayaError> {
+    embed_color_data_in_order::<P>(
+        data,
+        pixel_map,
+        width,
+        length,
+        padding,
+        pixel_size_bytes,
+        FileEncodingMethod::RightToLeft,
+        FileEncodingFunctionDerivation::MethodBased,
+    )
 }
 
-fn embed_pixel_color<P: Pixel>(
-    pixel: &mut P,
-    current_bit: &mut u32,
-    current_byte: &mut u32,
-    data: &Vec<u8>,
-    bits_to_embed: &mut usize,
-) {
-    let bit = data[*current_byte as usize] & (1 << *current_bit);
-    let mut ones = 0;
-
-    let first = pixel.first();
-    let second = pixel.second();
-    let third = pixel.third();
-    let fourth = match pixel.pixel_size() {
-        3 => 0,
-        4 => pixel.fourth(),
-        _ => unreachable!(),
-    };
+pub fn extract_color_data_right_left<P: Pixel>(
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+    embedded_bits: u64,
+) -> Vec<u8> {
+    extract_color_data_in_order::<P>(
+        pixel_map,
+        width,
+        length,
+        padding,
+        pixel_size_bytes,
+        embedded_bits,
+        FileEncodingMethod::RightToLeft,
+        FileEncodingFunctionDerivation::MethodBased,
+    )
+}
 
-    let mut changed =false;
+pub fn embed_lsb_data_left_right<P: Pixel + Default>(
+    data: &[u8],
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+) -> Result<(), MayaError> {
+    embed_lsb_data_in_order::<P>(
+        data,
+        pixel_map,
+        width,
+        length,
+        padding,
+        pixel_size_bytes,
+        FileEncodingMethod::LeftToRight,
+        FileEncodingFunctionDerivation::MethodBased,
+    )
+}
 
-    for i in 0..8 {
-        if (first & (1 << i)) == 0 {
-            ones += 1;
-        }
-        if (second & (1 << i)) == 0 {
-            ones += 1;
-        }
-        if (third & (1 << i)) == 0 {
-            ones += 1;
+pub fn extract_lsb_data_left_right<P: Pixel + Default>(
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+    embedded_bits: u64,
+) -> Vec<u8> {
+    extract_lsb_data_in_order::<P>(
+        pixel_map,
+        width,
+        length,
+        padding,
+        pixel_size_bytes,
+        embedded_bits,
+        FileEncodingMethod::LeftToRight,
+        FileEncodingFunctionDerivation::MethodBased,
+    )
+}
+
+pub fn embed_lsb_data_right_left<P: Pixel + Default>(
+    data: &[u8],
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+) -> Result<(), MayaError> {
+    embed_lsb_data_in_order::<P>(
+        data,
+        pixel_map,
+        width,
+        length,
+        padding,
+        pixel_size_bytes,
+        FileEncodingMethod::RightToLeft,
+        FileEncodingFunctionDerivation::MethodBased,
+    )
+}
+
+pub fn extract_lsb_data_right_left<P: Pixel + Default>(
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+    embedded_bits: u64,
+) -> Vec<u8> {
+    extract_lsb_data_in_order::<P>(
+        pixel_map,
+        width,
+        length,
+        padding,
+        pixel_size_bytes,
+        embedded_bits,
+        FileEncodingMethod::RightToLeft,
+        FileEncodingFunctionDerivation::MethodBased,
+    )
+}
+
+/*
+    Wu-Tsai pixel value differencing. Every pair of horizontally adjacent pixels gives one pair of samples per
+    channel, the difference between the two samples picks a range out of the range table and the difference is
+    replaced by one inside the same range that encodes log2(range width) bits. Edges and texture have large
+    differences and land in the wide ranges so busy regions carry more than smooth ones.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PvdRangeTable {
+    widths: Vec<u32>, // Width of each range starting from a difference of 0, all powers of two
+}
+
+impl PvdRangeTable {
+    /*
+        The widths have to be powers of two and cover every difference from 0 up to max_sample exactly
+     */
+    pub fn new(widths: Vec<u32>, max_sample: u16) -> Option<PvdRangeTable> {
+        if widths.iter().any(|width| !width.is_power_of_two()) {
+            return None;
         }
 
-        if (pixel.pixel_size() ==4 && (fourth & (1 << i)) == 0) {
-            ones += 1;
+        if widths.iter().map(|width| *width as u64).sum::<u64>() != max_sample as u64 + 1 {
+            return None;
         }
-    }
 
-    if ((bit == 0 && (ones % 2 == 0)) || (bit != 0 && (ones % 2 != 0))) {
-        for i in 0..8 {
-            if (first & (1 << i)) == 0 {
-                pixel.set_first(first | (1 << i));
-                changed = true;
-                break;
-            }
+        Some(PvdRangeTable { widths })
+    }
 
-            if (second & (1 << i)) == 0 {
-                pixel.set_second(second | (1 << i));
-                changed = true;
-                break;
-            }
+    /*
+        The table from the original paper, 8 8 16 32 64 128, scaled up for 16 bit samples. Samples narrower than 8
+        bits don't have the room for it so they get ranges of 2, one bit per pair.
+     */
+    pub fn wu_tsai(sample_bits: u32) -> PvdRangeTable {
+        let widths = if sample_bits >= 8 {
+            [8u32, 8, 16, 32, 64, 128].iter().map(|width| width << (sample_bits - 8)).collect()
+        } else {
+            vec![2; 1 << (sample_bits - 1)]
+        };
 
-            if (third & (1 << i)) == 0 {
-                pixel.set_third(third | (1 << i));
-                changed = true;
-                break;
-            }
+        PvdRangeTable { widths }
+    }
 
-            if (pixel.pixel_size() == 4 && (fourth & (1 << i)) == 0) {
-                pixel.set_fourth(fourth | (1 << i));
-                changed = true;
-                break;
-            }
+    /*
+        A table given as widths for 8 bit samples, scaled up for wider samples the same way wu_tsai is. Narrower
+        samples still only get ranges of 2 but the widths have to make a valid 8 bit table either way.
+     */
+    pub fn scaled(widths: &[u32], sample_bits: u32) -> Option<PvdRangeTable> {
+        let table = PvdRangeTable::new(widths.to_vec(), u8::MAX as u16)?;
+
+        match sample_bits {
+            8 => Some(table),
+            bits if bits > 8 => PvdRangeTable::new(
+                widths.iter().map(|width| width << (bits - 8)).collect(),
+                ((1u32 << bits) - 1) as u16,
+            ),
+            bits => Some(PvdRangeTable::wu_tsai(bits)),
         }
+    }
 
-        if(!changed){
-            for i in 0..8 {
-                if (first & (1 << i)) != 0 {
-                    pixel.set_first(first & !(1 << i));
-                    break;
-                }
-                if (second & (1 << i)) != 0 {
-                    pixel.set_second(second & !(1 << i));
-                    break;
-                }
-                if (third & (1 << i)) != 0 {
-                    pixel.set_third(third & !(1 << i));
-                    break;
-                }
-
-                if (pixel.pixel_size() == 4 && (fourth & (1 << i)) != 0) {
-                    pixel.set_fourth(fourth & !(1 << i));
-                    break;
-                }
+    /*
+        Lower bound and number of bits for the range a difference falls in
+     */
+    fn range(&self, difference: u32) -> (u32, u32) {
+        let mut lower = 0;
+        for width in self.widths.iter() {
+            if difference < lower + width {
+                return (lower, width.trailing_zeros());
             }
+            lower += width;
         }
-    }
 
+        unreachable!("difference {difference} past the end of the range table")
+    }
+}
 
-    increment_bit_and_byte_counters(current_bit, current_byte);
-    bits_to_embed.sub_assign(1);
+/*
+    Pair up samples through the integer average of the two and their difference, changing only the difference
+    keeps the average where it was and lets the extractor redo the fall off check on exactly the same pair
+ */
+fn pvd_split(average: i32, difference: i32) -> (i32, i32) {
+    let first = average - difference.div_euclid(2);
+    (first, first + difference)
 }
 
-fn extract_pixel_color<P: Pixel>(
-    pixel: &P,
-    bits: &mut u32,
-    bytes: &mut u32,
-    extracted_data: &mut Vec<u8>,
-    embedded_bits: usize,
-) {
-    let mut ones = 0;
-
-    let first = pixel.first();
-    let second = pixel.second();
-    let third = pixel.third();
-    let fourth = match pixel.pixel_size() {
-        3 => 0,
-        4 => pixel.fourth(),
-        _ => unreachable!(),
-    };
+/*
+    Lower bound, bit count and whether the difference is negative for a pair that can carry data. A pair is left
+    alone when moving its difference to the top of its range would push a sample out of bounds (falling off),
+    since the range and the sign never change the same check gives the same answer after embedding.
+ */
+fn pvd_usable(first: u16, second: u16, max_sample: u16, range_table: &PvdRangeTable) -> Option<(u32, u32, bool)> {
+    let difference = second as i32 - first as i32;
+    let average = (first as i32 + second as i32).div_euclid(2);
+    let (lower, bits) = range_table.range(difference.unsigned_abs());
+    let upper = (lower + (1 << bits) - 1) as i32;
 
-    for i in 0..8 {
-        if (first & (1 << i)) != 0 {
-            ones += 1;
-        }
-        if (second & (1 << i)) != 0 {
-            ones += 1;
-        }
-        if (third & (1 << i)) != 0 {
-            ones += 1;
-        }
-        if (pixel.pixel_size() == 4 && (fourth & (1 << i)) != 0) {
-            ones += 1;
-        }
-    }
-    let mut current_bit = false;
+    let (extreme_first, extreme_second) = pvd_split(average, if difference < 0 { -upper } else { upper });
+    let in_bounds = |sample: i32| (0..=max_sample as i32).contains(&sample);
 
-    if (ones % 2 == 0) {
-        current_bit = true;
+    if bits == 0 || !in_bounds(extreme_first) || !in_bounds(extreme_second) {
+        return None;
     }
 
-    if current_bit {
-        extracted_data[*bytes as usize] |= 1 << *bits;
-    } else {
-        extracted_data[*bytes as usize] &= !(1 << *bits);
-    }
+    Some((lower, bits, difference < 0))
+}
 
-    increment_bit_and_byte_counters(bits, bytes);
+fn pixel_pair<P: Pixel>(pixel_map: &mut [u8], offset: usize, pixel_size_bytes: u64) -> (&mut P, &mut P) {
+    assert!(offset + 2 * pixel_size_bytes as usize <= pixel_map.len());
 
+    let first = unsafe { pixel_map.as_mut_ptr().add(offset) } as *mut P;
+    let second = unsafe { pixel_map.as_mut_ptr().add(offset + pixel_size_bytes as usize) } as *mut P;
 
+    unsafe { (&mut *first, &mut *second) }
 }
 
-pub fn embed_color_data_left_right<P: Pixel>(
-    data: &Vec<u8>,
+pub fn pvd_capacity_bits<P: Pixel + Default>(
     pixel_map: &mut [u8],
     width: u64,
     length: u64,
     padding: u64,
     pixel_size_bytes: u64,
-) {
-    let total_length = (width + padding) * length;
+    range_table: &PvdRangeTable,
+) -> u64 {
+    let grid = PixelGrid::new(width, length, padding, pixel_size_bytes).pairs();
+    let channel_count = P::default().channel_count();
+
+    (0..grid.cells() * channel_count)
+        .filter_map(|position| {
+            let (cell, channel) = (position / channel_count, position % channel_count);
+            let (first, second) = pixel_pair::<P>(pixel_map, grid.offset(cell), pixel_size_bytes);
+            pvd_usable(first.channel(channel), second.channel(channel), first.max_sample(), range_table)
+        })
+        .map(|(_, bits, _)| bits as u64)
+        .sum()
+}
 
+#[allow(clippy::too_many_arguments)]
+pub fn embed_pvd_data<P: Pixel + Default>(
+    data: &[u8],
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+    range_table: &PvdRangeTable,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Result<(), MayaError> {
     let mut bits_to_embed = data.len() * 8;
 
     let mut current_byte: u32 = 0;
     let mut current_bit: u32 = 0;
 
-    if bits_to_embed > (width * length) as usize {
-        panic!(
-            "Not enough space in the image to embed {bits_to_embed} bits! Only have {} bits available!",
-            width * length * pixel_size_bytes
-        )
-    }
-
-    for row in 0..length as usize {
-        let start = (width + padding) * pixel_size_bytes * row as u64;
-        let end = start + (width * pixel_size_bytes);
-        let row_start_ptr = unsafe { pixel_map.as_mut_ptr().add(start as usize) };
-        let row_pixels_ptr = row_start_ptr as *mut P;
-        let row_pixels: &mut [P] =
-            unsafe { std::slice::from_raw_parts_mut(row_pixels_ptr, width as usize) };
-
-        for pixel in row_pixels.iter_mut() {
-            embed_pixel_color(
-                pixel,
-                &mut current_bit,
-                &mut current_byte,
-                &data,
-                &mut bits_to_embed,
-            );
+    let capacity = pvd_capacity_bits::<P>(pixel_map, width, length, padding, pixel_size_bytes, range_table);
+
+    if bits_to_embed as u64 > capacity {
+        return Err(MayaError::CapacityExceeded {
+            needed_bits: bits_to_embed as u64,
+            capacity_bits: capacity,
+        });
+    }
+
+    let grid = PixelGrid::new(width, length, padding, pixel_size_bytes).pairs();
+    let channel_count = P::default().channel_count();
+
+    for position in file_encoding_function_derivation.grid_visit_order(encoding_method, grid.cols, grid.rows, channel_count) {
+        if bits_to_embed == 0 {
+            break;
+        }
+
+        let (cell, channel) = (position / channel_count, position % channel_count);
+        let (first, second) = pixel_pair::<P>(pixel_map, grid.offset(cell), pixel_size_bytes);
+        let (first_sample, second_sample) = (first.channel(channel), second.channel(channel));
+
+        let (lower, bits, negative) = match pvd_usable(first_sample, second_sample, first.max_sample(), range_table) {
+            Some(range) => range,
+            None => continue,
+        };
 
+        // Whatever is left over at the end of the data is padded out with zeros
+        let mut value: u32 = 0;
+        for bit in 0..bits {
             if bits_to_embed == 0 {
-                return;
+                break;
             }
+            value |= (((data[current_byte as usize] >> current_bit) & 1) as u32) << bit;
+            increment_bit_and_byte_counters(&mut current_bit, &mut current_byte);
+            bits_to_embed -= 1;
         }
+
+        let difference = (lower + value) as i32;
+        let average = (first_sample as i32 + second_sample as i32).div_euclid(2);
+        let (new_first, new_second) = pvd_split(average, if negative { -difference } else { difference });
+
+        first.set_channel(channel, new_first as u16);
+        second.set_channel(channel, new_second as u16);
     }
+
+    Ok(())
 }
 
-pub fn extract_color_data_left_right<P: Pixel>(
+#[allow(clippy::too_many_arguments)]
+pub fn extract_pvd_data<P: Pixel + Default>(
     pixel_map: &mut [u8],
     width: u64,
     length: u64,
     padding: u64,
     pixel_size_bytes: u64,
     embedded_bits: u64,
+    range_table: &PvdRangeTable,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
 ) -> Vec<u8> {
-    let total_length = (width + padding) * length;
-
     let mut bytes: u32 = 0;
     let mut bits: u32 = 0;
 
     //The plus one is just in case we have a sub byte number of bits , we would lose those few bits or write into an invalid offset
-    let mut extracted_data: Vec<u8> = vec![0u8; ((embedded_bits as usize / 8) + 1) as usize];
-
-    for row in 0..length as usize {
-        let start = (width + padding) * pixel_size_bytes * row as u64;
-        let end = start + (width * pixel_size_bytes);
-        let row_start_ptr = unsafe { pixel_map.as_mut_ptr().add(start as usize) };
-        let row_pixels_ptr = row_start_ptr as *mut P;
-        let row_pixels: &[P] =
-            unsafe { std::slice::from_raw_parts(row_pixels_ptr, width as usize) };
-
-        let mut current_bit: u8 = 0;
-
-        for pixel in row_pixels.iter() {
-            extract_pixel_color(
-                pixel,
-                &mut bits,
-                &mut bytes,
-                &mut extracted_data,
-                embedded_bits as usize,
-            );
-
-            if bits + (bytes * 8) == embedded_bits as u32 {
+    let mut extracted_data: Vec<u8> = vec![0u8; (embedded_bits as usize / 8) + 1];
+
+    let grid = PixelGrid::new(width, length, padding, pixel_size_bytes).pairs();
+    let channel_count = P::default().channel_count();
+
+    for position in file_encoding_function_derivation.grid_visit_order(encoding_method, grid.cols, grid.rows, channel_count) {
+        if (bits + bytes * 8) as u64 == embedded_bits {
+            break;
+        }
+
+        let (cell, channel) = (position / channel_count, position % channel_count);
+        let (first, second) = pixel_pair::<P>(pixel_map, grid.offset(cell), pixel_size_bytes);
+        let (first_sample, second_sample) = (first.channel(channel), second.channel(channel));
+
+        let (lower, range_bits, _) = match pvd_usable(first_sample, second_sample, first.max_sample(), range_table) {
+            Some(range) => range,
+            None => continue,
+        };
+
+        let value = (second_sample as i32 - first_sample as i32).unsigned_abs() - lower;
+        for bit in 0..range_bits {
+            if (bits + bytes * 8) as u64 == embedded_bits {
                 break;
             }
+            if (value >> bit) & 1 == 1 {
+                extracted_data[bytes as usize] |= 1 << bits;
+            }
+            increment_bit_and_byte_counters(&mut bits, &mut bytes);
         }
+    }
+
+    extracted_data
+}
+
+fn pixel_at<P: Pixel>(pixel_map: &mut [u8], offset: usize, pixel_size_bytes: u64) -> &mut P {
+    assert!(offset + pixel_size_bytes as usize <= pixel_map.len());
+    unsafe { &mut *(pixel_map.as_mut_ptr().add(offset) as *mut P) }
+}
+
+/*
+    Hamming matrix embedding. The cover is the lowest bit of every sample, k message bits go into each group of
+    2^k - 1 cover bits by flipping at most one of them. The payload header always goes in at k = 1, which comes
+    down to plain LSB, so the extractor can read the header flags first and find the k the rest went in with.
+    Only bit 0 is used even for 16 bit samples, the point is to change as little as possible.
+ */
+pub const HAMMING_MAX_K: u32 = 7;
+
+fn check_hamming_k(k: u32) -> Result<u32, MayaError> {
+    match (1..=HAMMING_MAX_K).contains(&k) {
+        true => Ok(k),
+        false => Err(MayaError::InvalidParameter(format!("Hamming matrix embedding needs k between 1 and {HAMMING_MAX_K}, got {k}"))),
+    }
+}
+
+/*
+    The header flags a HammingMatrix payload goes out with, k is fitted to the message when it isn't given. The
+    flags are the only place k is kept, embed_hamming_data and extract_hamming_data both read it from there
+ */
+pub fn hamming_payload_flags(flags: u8, k: Option<u32>, message_bytes: usize, cover_bits: u64) -> Result<u8, MayaError> {
+    let k = k.unwrap_or_else(|| {
+        hamming_choose_k(message_bytes as u64 * 8, cover_bits.saturating_sub(PAYLOAD_HEADER_BITS), HAMMING_MAX_K)
+    });
 
-        if bits + (bytes * 8) == embedded_bits as u32 {
+    Ok((flags & !PAYLOAD_FLAG_HAMMING_K_MASK) | hamming_k_flags(check_hamming_k(k)?))
+}
+
+/*
+    Matrix embed data into a run of cover bits, k message bits a group
+ */
+pub fn hamming_embed_bits(data: &[u8], cover: &mut [bool], k: u32) -> Result<(), MayaError> {
+    let k = check_hamming_k(k)?;
+    let bits_to_embed = data.len() as u64 * 8;
+    let n = (1usize << k) - 1;
+    let capacity = (cover.len() as u64 / n as u64) * k as u64;
+
+    if bits_to_embed > capacity {
+        return Err(MayaError::CapacityExceeded {
+            needed_bits: bits_to_embed,
+            capacity_bits: capacity,
+        });
+    }
+
+    let mut current_byte: u32 = 0;
+    let mut current_bit: u32 = 0;
+    let mut remaining = bits_to_embed;
+
+    for group in cover.chunks_mut(n) {
+        if remaining == 0 {
             break;
         }
+
+        // Whatever is left over at the end of the data is padded out with zeros
+        let mut message = 0;
+        for bit in 0..k.min(remaining as u32) {
+            message |= (((data[current_byte as usize] >> current_bit) & 1) as u32) << bit;
+            increment_bit_and_byte_counters(&mut current_bit, &mut current_byte);
+        }
+        remaining = remaining.saturating_sub(k as u64);
+
+        let change = hamming_syndrome(group.iter().copied()) ^ message;
+        if change != 0 {
+            group[change as usize - 1] = !group[change as usize - 1];
+        }
+    }
+
+    Ok(())
+}
+
+pub fn hamming_extract_bits(cover: &[bool], embedded_bits: u64, k: u32) -> Vec<u8> {
+    let mut bytes: u32 = 0;
+    let mut bits: u32 = 0;
+
+    //The plus one is just in case we have a sub byte number of bits , we would lose those few bits or write into an invalid offset
+    let mut extracted_data: Vec<u8> = vec![0u8; (embedded_bits as usize / 8) + 1];
+
+    if k == 0 || k > HAMMING_MAX_K {
+        return extracted_data;
+    }
+
+    for group in cover.chunks_exact((1 << k) - 1) {
+        let message = hamming_syndrome(group.iter().copied());
+
+        for bit in 0..k {
+            if (bits + bytes * 8) as u64 == embedded_bits {
+                return extracted_data;
+            }
+            if (message >> bit) & 1 == 1 {
+                extracted_data[bytes as usize] |= 1 << bits;
+            }
+            increment_bit_and_byte_counters(&mut bits, &mut bytes);
+        }
     }
 
     extracted_data
 }
 
-pub fn embed_color_data_right_left<P: Pixel>(
-    data: &Vec<u8>,
+/*
+    data is the whole payload, header included, the k its flags hold is what the message after the header uses
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn embed_hamming_data<P: Pixel + Default>(
+    data: &[u8],
     pixel_map: &mut [u8],
     width: u64,
     length: u64,
     padding: u64,
     pixel_size_bytes: u64,
-) {
-    let total_length = (width + padding) * length;
-
-    let mut bits_to_embed = data.len() * 8;
-
-    let mut current_byte: u32 = 0;
-    let mut current_bit: u32 = 0;
-
-    if bits_to_embed > (width * length) as usize {
-        panic!(
-            "Not enough space in the image to embed {bits_to_embed} bits! Only have {} bits available!",
-            width * length * pixel_size_bytes
-        )
-    }
-
-    for row in (0..length).rev() {
-        let start = (width + padding) * pixel_size_bytes * row as u64;
-        let end = start + (width * pixel_size_bytes);
-        let row_start_ptr = unsafe { pixel_map.as_mut_ptr().add(start as usize) };
-        let row_pixels_ptr = row_start_ptr as *mut P;
-        let row_pixels: &mut [P] =
-            unsafe { std::slice::from_raw_parts_mut(row_pixels_ptr, width as usize) };
-
-        for pixel in row_pixels.iter_mut().rev() {
-            embed_pixel_color(
-                pixel,
-                &mut current_bit,
-                &mut current_byte,
-                &data,
-                &mut bits_to_embed,
-            );
-
-            if bits_to_embed == 0 {
-                return;
-            }
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Result<(), MayaError> {
+    let grid = PixelGrid::new(width, length, padding, pixel_size_bytes);
+    let channel_count = P::default().channel_count();
+
+    let header = PayloadHeader::from_bytes(data)?;
+    let k = check_hamming_k(hamming_k_from_flags(header.flags))?;
+    let (header_bytes, message) = data.split_at(PAYLOAD_HEADER_SIZE);
+
+    // Only as much of the visit order as the header and the groups will use, a keyed order is generated lazily
+    let groups = (message.len() * 8).div_ceil(k as usize);
+    let needed = PAYLOAD_HEADER_BITS as usize + groups * ((1usize << k) - 1);
+
+    let order: Vec<usize> = file_encoding_function_derivation
+        .grid_visit_order(encoding_method, grid.cols, grid.rows, channel_count)
+        .take(needed)
+        .collect();
+
+    let mut cover: Vec<bool> = order
+        .iter()
+        .map(|index| {
+            let (cell, channel) = (index / channel_count, index % channel_count);
+            pixel_at::<P>(pixel_map, grid.offset(cell), pixel_size_bytes).channel(channel) & 1 == 1
+        })
+        .collect();
+
+    let (header_cover, message_cover) = cover.split_at_mut((PAYLOAD_HEADER_BITS as usize).min(order.len()));
+    hamming_embed_bits(header_bytes, header_cover, 1)?;
+    hamming_embed_bits(message, message_cover, k)?;
+
+    for (index, bit) in order.iter().zip(cover.iter()) {
+        let (cell, channel) = (index / channel_count, index % channel_count);
+        let pixel = pixel_at::<P>(pixel_map, grid.offset(cell), pixel_size_bytes);
+        let sample = pixel.channel(channel);
+
+        if (sample & 1 == 1) != *bit {
+            pixel.set_channel(channel, sample ^ 1);
         }
     }
+
+    Ok(())
 }
 
-pub fn extract_color_data_right_left<P: Pixel>(
+#[allow(clippy::too_many_arguments)]
+pub fn extract_hamming_data<P: Pixel + Default>(
     pixel_map: &mut [u8],
     width: u64,
     length: u64,
     padding: u64,
     pixel_size_bytes: u64,
     embedded_bits: u64,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
 ) -> Vec<u8> {
-    let total_length = (width + padding) * length;
+    let grid = PixelGrid::new(width, length, padding, pixel_size_bytes);
+    let channel_count = P::default().channel_count();
+    let mut order = file_encoding_function_derivation.grid_visit_order(encoding_method, grid.cols, grid.rows, channel_count);
+    let mut read_cover = |count: usize| -> Vec<bool> {
+        order
+            .by_ref()
+            .take(count)
+            .map(|index| {
+                let (cell, channel) = (index / channel_count, index % channel_count);
+                pixel_at::<P>(pixel_map, grid.offset(cell), pixel_size_bytes).channel(channel) & 1 == 1
+            })
+            .collect()
+    };
 
-    let mut bytes: u32 = 0;
-    let mut bits: u32 = 0;
+    // The header at k = 1 first, then only the groups holding the rest of embedded_bits at the k its flags give
+    let header_bits = embedded_bits.min(PAYLOAD_HEADER_BITS);
+    let mut extracted = hamming_extract_bits(&read_cover(header_bits as usize), header_bits, 1);
 
-    //The plus one is just in case we have a sub byte number of bits , we would lose those few bits or write into an invalid offset
-    let mut extracted_data: Vec<u8> = vec![0u8; ((embedded_bits as usize / 8) + 1) as usize];
-
-    for row in (0..length).rev() {
-        let start = (width + padding) * pixel_size_bytes * row as u64;
-        let end = start + (width * pixel_size_bytes);
-        let row_start_ptr = unsafe { pixel_map.as_mut_ptr().add(start as usize) };
-        let row_pixels_ptr = row_start_ptr as *mut P;
-        let row_pixels: &[P] =
-            unsafe { std::slice::from_raw_parts(row_pixels_ptr, width as usize) };
-
-        let mut current_bit: u8 = 0;
-
-        for pixel in row_pixels.iter().rev() {
-            extract_pixel_color(
-                pixel,
-                &mut bits,
-                &mut bytes,
-                &mut extracted_data,
-                embedded_bits as usize,
-            );
-
-            if bits + (bytes * 8) == embedded_bits as u32 {
-                break;
-            }
-        }
+    if embedded_bits <= PAYLOAD_HEADER_BITS {
+        return extracted;
+    }
 
-        if bits + (bytes * 8) == embedded_bits as u32 {
-            break;
-        }
+    let message_bits = embedded_bits - PAYLOAD_HEADER_BITS;
+    let k = PayloadHeader::from_bytes(&extracted).map_or(0, |header| hamming_k_from_flags(header.flags));
+    let groups = match k {
+        1..=HAMMING_MAX_K => message_bits.div_ceil(k as u64) as usize * ((1 << k) - 1),
+        _ => 0,
+    };
+
+    extracted.truncate(PAYLOAD_HEADER_SIZE);
+    extracted.extend(hamming_extract_bits(&read_cover(groups), message_bits, k));
+    extracted
+}
+
+/*
+    LSB embedding through the method's visit order, every bit plane of every sample is its own position so a keyed
+    order scatters the payload across pixels, channels and planes. Every other order moves a whole pixel at a time
+    and fills all of its planes, channel by channel, before stepping on to the next pixel
+ */
+fn lsb_positions_per_pixel<P: Pixel + Default>(encoding: FileEncoding) -> usize {
+    P::default().channel_count() * lsb_planes::<P>(encoding)
+}
+
+/*
+    LSB matching only ever uses the lowest plane, a +1 or -1 can carry into the planes above and wipe out whatever
+    was already written there
+ */
+fn lsb_planes<P: Pixel + Default>(encoding: FileEncoding) -> usize {
+    match encoding {
+        FileEncoding::LsbMatching => 1,
+        _ => P::default().lsb_bits() as usize,
     }
+}
 
-    extracted_data
+/*
+    Step a sample whose LSB is wrong one up or one down, whichever the coin says unless it would leave the sample's
+    range. Either way the LSB flips, the difference from replacement is that the values don't just swap in pairs
+ */
+fn lsb_match(sample: u16, max_sample: u16, up: bool) -> u16 {
+    if sample == 0 || (up && sample < max_sample) {
+        sample + 1
+    } else {
+        sample - 1
+    }
 }
-pub fn embed_lsb_data_left_right<P: Pixel>(
-    data: &Vec<u8>,
+
+#[allow(clippy::too_many_arguments)]
+pub fn embed_lsb_data_in_order<P: Pixel + Default>(
+    data: &[u8],
     pixel_map: &mut [u8],
     width: u64,
     length: u64,
     padding: u64,
     pixel_size_bytes: u64,
-) {
-    let total_length = (width + padding) * length;
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Result<(), MayaError> {
+    embed_lsb_positions::<P>(
+        data,
+        pixel_map,
+        width,
+        length,
+        padding,
+        pixel_size_bytes,
+        FileEncoding::Lsb,
+        encoding_method,
+        file_encoding_function_derivation,
+    )
+}
 
+#[allow(clippy::too_many_arguments)]
+pub fn embed_lsb_matching_data_in_order<P: Pixel + Default>(
+    data: &[u8],
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Result<(), MayaError> {
+    embed_lsb_positions::<P>(
+        data,
+        pixel_map,
+        width,
+        length,
+        padding,
+        pixel_size_bytes,
+        FileEncoding::LsbMatching,
+        encoding_method,
+        file_encoding_function_derivation,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn embed_lsb_positions<P: Pixel + Default>(
+    data: &[u8],
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+    encoding: FileEncoding,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Result<(), MayaError> {
     let mut bits_to_embed = data.len() * 8;
 
     let mut current_byte: u32 = 0;
     let mut current_bit: u32 = 0;
 
-    if bits_to_embed > (width * length * pixel_size_bytes) as usize {
-        panic!(
-            "Not enough space in the image to embed {bits_to_embed} bits! Only have {} bits available!",
-            width * length * pixel_size_bytes
-        )
-    }
-
-    for row in 0..length as usize {
-        let start = (width + padding) * pixel_size_bytes * row as u64;
-        let end = start + (width * pixel_size_bytes);
-        let row_start_ptr = unsafe { pixel_map.as_mut_ptr().add(start as usize) };
-        let row_pixels_ptr = row_start_ptr as *mut P;
-        let row_pixels: &mut [P] =
-            unsafe { std::slice::from_raw_parts_mut(row_pixels_ptr, width as usize) };
-
-        for pixel in row_pixels.iter_mut() {
-            embed_pixel_lsb(
-                pixel,
-                &mut current_bit,
-                &mut current_byte,
-                &data,
-                &mut bits_to_embed,
-            );
+    let capacity = capacity_bits::<P>(width, length, encoding);
 
-            if bits_to_embed == 0 {
-                return;
+    if bits_to_embed as u64 > capacity {
+        return Err(MayaError::CapacityExceeded {
+            needed_bits: bits_to_embed as u64,
+            capacity_bits: capacity,
+        });
+    }
+
+    let grid = PixelGrid::new(width, length, padding, pixel_size_bytes);
+    let per_pixel = lsb_positions_per_pixel::<P>(encoding);
+    let planes = lsb_planes::<P>(encoding);
+    let mut signs = file_encoding_function_derivation.lsb_matching_signs(data);
+
+    for position in file_encoding_function_derivation.grid_visit_order(encoding_method, grid.cols, grid.rows, per_pixel) {
+        if bits_to_embed == 0 {
+            break;
+        }
+
+        let (cell, within) = (position / per_pixel, position % per_pixel);
+        let (channel, plane) = (within / planes, within % planes);
+        let pixel = pixel_at::<P>(pixel_map, grid.offset(cell), pixel_size_bytes);
+        let bit = ((data[current_byte as usize] >> current_bit) & 1) as u16;
+        let sample = pixel.channel(channel);
+
+        if encoding == FileEncoding::LsbMatching {
+            if sample & 1 != bit {
+                pixel.set_channel(channel, lsb_match(sample, pixel.max_sample(), signs()));
             }
+        } else {
+            pixel.set_channel(channel, (sample & !(1 << plane)) | (bit << plane));
         }
+
+        increment_bit_and_byte_counters(&mut current_bit, &mut current_byte);
+        bits_to_embed -= 1;
     }
+
+    Ok(())
 }
 
-pub fn extract_lsb_data_left_right<P: Pixel>(
+#[allow(clippy::too_many_arguments)]
+pub fn extract_lsb_data_in_order<P: Pixel + Default>(
     pixel_map: &mut [u8],
     width: u64,
     length: u64,
     padding: u64,
     pixel_size_bytes: u64,
     embedded_bits: u64,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
 ) -> Vec<u8> {
-    let total_length = (width + padding) * length;
+    extract_lsb_positions::<P>(
+        pixel_map,
+        width,
+        length,
+        padding,
+        pixel_size_bytes,
+        embedded_bits,
+        FileEncoding::Lsb,
+        encoding_method,
+        file_encoding_function_derivation,
+    )
+}
 
+/*
+    Reading is plain LSB extraction, only over the lowest plane of every sample
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn extract_lsb_matching_data_in_order<P: Pixel + Default>(
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+    embedded_bits: u64,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Vec<u8> {
+    extract_lsb_positions::<P>(
+        pixel_map,
+        width,
+        length,
+        padding,
+        pixel_size_bytes,
+        embedded_bits,
+        FileEncoding::LsbMatching,
+        encoding_method,
+        file_encoding_function_derivation,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn extract_lsb_positions<P: Pixel + Default>(
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+    embedded_bits: u64,
+    encoding: FileEncoding,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Vec<u8> {
     let mut bytes: u32 = 0;
     let mut bits: u32 = 0;
 
     //The plus one is just in case we have a sub byte number of bits , we would lose those few bits or write into an invalid offset
-    let mut extracted_data: Vec<u8> = vec![0u8; ((embedded_bits as usize / 8) + 1) as usize];
-
-    for row in 0..length as usize {
-        let start = (width + padding) * pixel_size_bytes * row as u64;
-        let end = start + (width * pixel_size_bytes);
-        let row_start_ptr = unsafe { pixel_map.as_mut_ptr().add(start as usize) };
-        let row_pixels_ptr = row_start_ptr as *mut P;
-        let row_pixels: &[P] =
-            unsafe { std::slice::from_raw_parts(row_pixels_ptr, width as usize) };
-
-        let mut current_bit: u8 = 0;
-
-        for pixel in row_pixels.iter() {
-            extract_pixel_lsb(
-                pixel,
-                &mut bits,
-                &mut bytes,
-                &mut extracted_data,
-                embedded_bits as usize,
-            );
-
-            if bits + (bytes * 8) == embedded_bits as u32 {
-                break;
-            }
-        }
+    let mut extracted_data: Vec<u8> = vec![0u8; (embedded_bits as usize / 8) + 1];
 
-        if bits + (bytes * 8) == embedded_bits as u32 {
+    let grid = PixelGrid::new(width, length, padding, pixel_size_bytes);
+    let per_pixel = lsb_positions_per_pixel::<P>(encoding);
+    let planes = lsb_planes::<P>(encoding);
+
+    for position in file_encoding_function_derivation.grid_visit_order(encoding_method, grid.cols, grid.rows, per_pixel) {
+        if (bits + bytes * 8) as u64 == embedded_bits {
             break;
         }
+
+        let (cell, within) = (position / per_pixel, position % per_pixel);
+        let (channel, plane) = (within / planes, within % planes);
+        if (pixel_at::<P>(pixel_map, grid.offset(cell), pixel_size_bytes).channel(channel) >> plane) & 1 == 1 {
+            extracted_data[bytes as usize] |= 1 << bits;
+        }
+
+        increment_bit_and_byte_counters(&mut bits, &mut bytes);
     }
 
     extracted_data
 }
 
-pub fn embed_lsb_data_right_left<P: Pixel>(
-    data: &Vec<u8>,
+/*
+    Expand rows of packed samples into one byte per sample. Samples under 8 bits are packed most significant bits
+    first as both PNG and BMP do, row_bytes can be larger than the packed row to skip over any row padding.
+ */
+pub fn unpack_samples(
+    packed: &[u8],
+    rows: usize,
+    row_bytes: usize,
+    samples_per_row: usize,
+    bits_per_sample: usize,
+) -> Vec<u8> {
+    let mut samples = Vec::with_capacity(rows * samples_per_row);
+    let mask = ((1u16 << bits_per_sample) - 1) as u8;
+
+    for row in 0..rows {
+        let line = &packed[row * row_bytes..(row + 1) * row_bytes];
+        for sample in 0..samples_per_row {
+            let bit = sample * bits_per_sample;
+            let shift = 8 - bits_per_sample - (bit % 8);
+            samples.push((line[bit / 8] >> shift) & mask);
+        }
+    }
+
+    samples
+}
+
+/*
+    Reverse of unpack_samples, only the bits belonging to samples are touched so row padding is left as it was
+ */
+pub fn pack_samples(
+    samples: &[u8],
+    packed: &mut [u8],
+    rows: usize,
+    row_bytes: usize,
+    samples_per_row: usize,
+    bits_per_sample: usize,
+) {
+    let mask = ((1u16 << bits_per_sample) - 1) as u8;
+
+    for row in 0..rows {
+        let line = &mut packed[row * row_bytes..(row + 1) * row_bytes];
+        for sample in 0..samples_per_row {
+            let bit = sample * bits_per_sample;
+            let shift = 8 - bits_per_sample - (bit % 8);
+            line[bit / 8] &= !(mask << shift);
+            line[bit / 8] |= (samples[row * samples_per_row + sample] & mask) << shift;
+        }
+    }
+}
+
+/*
+    Number of bits the given encoding can hide in a pixel map, this does not account for the payload header
+ */
+pub fn capacity_bits<P: Pixel + Default>(width: u64, length: u64, encoding: FileEncoding) -> u64 {
+    let pixel = P::default();
+    match encoding {
+        FileEncoding::Lsb => width * length * pixel.channel_count() as u64 * pixel.lsb_bits() as u64,
+        FileEncoding::LsbMatching => width * length * pixel.channel_count() as u64,
+        // With k = 1 every cover bit carries one bit, anything bigger trades capacity for fewer changes
+        FileEncoding::HammingMatrix => width * length * pixel.channel_count() as u64,
+        // PVD depends on what is in the pixels, see pixel_map_capacity_bits, and JSteg and F5 don't work on pixels
+        _ => 0,
+    }
+}
+
+/*
+    Every channel of every pixel in row major order, one vector per channel. This is what the steganalysis in
+    analysis/ works on so it sees samples exactly the way the embedders do
+ */
+pub fn pixel_channel_samples<P: Pixel + Default>(
     pixel_map: &mut [u8],
     width: u64,
     length: u64,
     padding: u64,
     pixel_size_bytes: u64,
-) {
-    let total_length = (width + padding) * length;
-
-    let mut bits_to_embed = data.len() * 8;
+) -> Vec<Vec<u16>> {
+    let grid = PixelGrid::new(width, length, padding, pixel_size_bytes);
+    let mut channels = vec![Vec::with_capacity(grid.cells()); P::default().channel_count()];
+
+    for cell in 0..grid.cells() {
+        let pixel = pixel_at::<P>(pixel_map, grid.offset(cell), pixel_size_bytes);
+        for (channel, samples) in channels.iter_mut().enumerate() {
+            samples.push(pixel.channel(channel));
+        }
+    }
 
-    let mut current_byte: u32 = 0;
-    let mut current_bit: u32 = 0;
+    channels
+}
 
-    if bits_to_embed > (width * length * pixel_size_bytes) as usize {
-        panic!(
-            "Not enough space in the image to embed {bits_to_embed} bits! Only have {} bits available!",
-            width * length * pixel_size_bytes
-        )
-    }
-
-    for row in (0..length).rev() {
-        let start = (width + padding) * pixel_size_bytes * row as u64;
-        let end = start + (width * pixel_size_bytes);
-        let row_start_ptr = unsafe { pixel_map.as_mut_ptr().add(start as usize) };
-        let row_pixels_ptr = row_start_ptr as *mut P;
-        let row_pixels: &mut [P] =
-            unsafe { std::slice::from_raw_parts_mut(row_pixels_ptr, width as usize) };
-
-        for pixel in row_pixels.iter_mut().rev() {
-            embed_pixel_lsb(
-                pixel,
-                &mut current_bit,
-                &mut current_byte,
-                &data,
-                &mut bits_to_embed,
-            );
+/*
+    The range table asked for in the parameters, or Wu and Tsai's own
+ */
+fn pvd_range_table<P: Pixel + Default>(parameters: &EncodingParameters) -> Result<PvdRangeTable, MayaError> {
+    let sample_bits = P::default().sample_bits();
+
+    match &parameters.pvd_ranges {
+        Some(widths) => PvdRangeTable::scaled(widths, sample_bits).ok_or_else(|| {
+            MayaError::InvalidParameter(format!("PVD ranges {widths:?} aren't powers of two adding up to 256"))
+        }),
+        None => Ok(PvdRangeTable::wu_tsai(sample_bits)),
+    }
+}
 
-            if bits_to_embed == 0 {
-                return;
-            }
+/*
+    Same as capacity_bits for encodings whose capacity depends on what is in the image and not just its size
+ */
+pub fn pixel_map_capacity_bits<P: Pixel + Default>(
+    pixel_map: &mut [u8],
+    width: u64,
+    length: u64,
+    padding: u64,
+    pixel_size_bytes: u64,
+    encoding: FileEncoding,
+    parameters: &EncodingParameters,
+) -> Result<u64, MayaError> {
+    match encoding {
+        FileEncoding::PixelValueDifferencing => {
+            let range_table = pvd_range_table::<P>(parameters)?;
+            Ok(pvd_capacity_bits::<P>(pixel_map, width, length, padding, pixel_size_bytes, &range_table))
         }
+        _ => Ok(capacity_bits::<P>(width, length, encoding)),
     }
 }
 
-pub fn extract_lsb_data_right_left<P: Pixel>(
+/*
+    Pick the embedding function for the encoding and method, the file format parsers only need to pick the pixel type
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn embed_data_with_method<P: Pixel + Default>(
+    data: &[u8],
     pixel_map: &mut [u8],
     width: u64,
     length: u64,
     padding: u64,
     pixel_size_bytes: u64,
-    embedded_bits: u64,
-) -> Vec<u8> {
-    let total_length = (width + padding) * length;
-
-    let mut bytes: u32 = 0;
-    let mut bits: u32 = 0;
-
-    //The plus one is just in case we have a sub byte number of bits , we would lose those few bits or write into an invalid offset
-    let mut extracted_data: Vec<u8> = vec![0u8; ((embedded_bits as usize / 8) + 1) as usize];
-
-    for row in (0..length).rev() {
-        let start = (width + padding) * pixel_size_bytes * row as u64;
-        let end = start + (width * pixel_size_bytes);
-        let row_start_ptr = unsafe { pixel_map.as_mut_ptr().add(start as usize) };
-        let row_pixels_ptr = row_start_ptr as *mut P;
-        let row_pixels: &[P] =
-            unsafe { std::slice::from_raw_parts(row_pixels_ptr, width as usize) };
-
-        for pixel in row_pixels.iter().rev() {
-            extract_pixel_lsb(
-                pixel,
-                &mut bits,
-                &mut bytes,
-                &mut extracted_data,
-                embedded_bits as usize,
-            );
-            if bits + (bytes * 8) == embedded_bits as u32 {
-                break;
-            }
+    encoding: FileEncoding,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    parameters: &EncodingParameters,
+) -> Result<(), MayaError> {
+    match (encoding, encoding_method) {
+        (FileEncoding::Lsb, _) => {
+            embed_lsb_data_in_order::<P>(data, pixel_map, width, length, padding, pixel_size_bytes, encoding_method, file_encoding_function_derivation)
         }
-
-        if bits + (bytes * 8) == embedded_bits as u32 {
-            break;
+        (FileEncoding::LsbMatching, _) => embed_lsb_matching_data_in_order::<P>(
+            data,
+            pixel_map,
+            width,
+            length,
+            padding,
+            pixel_size_bytes,
+            encoding_method,
+            file_encoding_function_derivation,
+        ),
+        (FileEncoding::HammingMatrix, _) => {
+            embed_hamming_data::<P>(data, pixel_map, width, length, padding, pixel_size_bytes, encoding_method, file_encoding_function_derivation)
+        }
+        (FileEncoding::PixelValueDifferencing, _) => {
+            let range_table = pvd_range_table::<P>(parameters)?;
+            embed_pvd_data::<P>(data, pixel_map, width, length, padding, pixel_size_bytes, &range_table, encoding_method, file_encoding_function_derivation)
         }
+        (encoding, _) => Err(MayaError::UnsupportedFormat(format!("{encoding:?} works on JPEG coefficients, not pixels"))),
     }
-
-    extracted_data
 }
 
-pub fn embed_lsb_wave_function_left_right<P: Pixel>(
+#[allow(clippy::too_many_arguments)]
+pub fn extract_data_with_method<P: Pixel + Default>(
     pixel_map: &mut [u8],
     width: u64,
     length: u64,
     padding: u64,
     pixel_size_bytes: u64,
     embedded_bits: u64,
-    wave_function: WaveFunction,
-) {
-    let points = WaveFunction::traverse(&wave_function, width as usize, length as usize);
-
-    for point in points {
-        let x: u64 = point.0 as u64;
-        let y: u64 = point.1 as u64;
+    encoding: FileEncoding,
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    parameters: &EncodingParameters,
+) -> Result<Vec<u8>, MayaError> {
+    let extracted = match (encoding, encoding_method) {
+        (FileEncoding::Lsb, _) => extract_lsb_data_in_order::<P>(
+            pixel_map,
+            width,
+            length,
+            padding,
+            pixel_size_bytes,
+            embedded_bits,
+            encoding_method,
+            file_encoding_function_derivation,
+        ),
+        (FileEncoding::LsbMatching, _) => extract_lsb_matching_data_in_order::<P>(
+            pixel_map,
+            width,
+            length,
+            padding,
+            pixel_size_bytes,
+            embedded_bits,
+            encoding_method,
+            file_encoding_function_derivation,
+        ),
+        (FileEncoding::HammingMatrix, _) => {
+            extract_hamming_data::<P>(pixel_map, width, length, padding, pixel_size_bytes, embedded_bits, encoding_method, file_encoding_function_derivation)
+        }
+        (FileEncoding::PixelValueDifferencing, _) => {
+            let range_table = pvd_range_table::<P>(parameters)?;
+            extract_pvd_data::<P>(pixel_map, width, length, padding, pixel_size_bytes, embedded_bits, &range_table, encoding_method, file_encoding_function_derivation)
+        }
+        (encoding, _) => {
+            return Err(MayaError::UnsupportedFormat(format!("{encoding:?} works on JPEG coefficients, not pixels")))
+        }
+    };
 
-        let offset = unsafe {
-            pixel_map
-                .as_mut_ptr()
-                .add((((width + padding) * x) + (y * length)) as usize)
-        };
-    }
+    Ok(extracted)
 }
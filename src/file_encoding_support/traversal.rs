@@ -0,0 +1,369 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use crate::file_encoding_support::file_encoding_support::FileEncodingMethod;
+use crate::mathematics_support::mathematics_support::{cos_scaled, sin_scaled, sqrt_scaled};
+use std::f64::consts::TAU;
+use std::rc::Rc;
+
+/*
+    Shapes to walk a rows by cols grid in. Every one of them visits every cell exactly once, so whatever order is
+    picked the capacity is the whole grid.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaveFunction {
+    Horizontal,       // Row by row, left to right
+    Vertical,         // Column by column, top to bottom
+    DiagonalRight,    // Diagonals running down and to the right, from the bottom left corner to the top right one
+    DiagonalLeft,     // Diagonals running down and to the left, from the top left corner to the bottom right one
+    ZigZagHorizontal, // Rows alternating left to right and right to left
+    ZigZagVertical,   // Columns alternating top to bottom and bottom to top
+    Sinusoidal,       // Bands following a sine wave, see below
+    Hilbert,          // Generalized Hilbert curve, neighbouring cells stay close together on any size of grid
+    Morton,           // Z-order, row and column bits interleaved
+    SpiralInward,     // Clockwise around the edge from the top left corner, winding in to the centre
+    SpiralOutward,    // SpiralInward run backwards, from the centre out to the top left corner
+    BlockRaster,      // 8x8 blocks left to right and top to bottom, each one row by row like a JPEG block
+}
+
+/*
+    Side of the blocks BlockRaster walks, the same blocks JPEG compresses in
+ */
+pub const BLOCK_SIZE: usize = 8;
+
+impl WaveFunction {
+    /*
+        Every (row, col) of the grid once, lazily so nothing the size of the image is ever built up front
+     */
+    pub fn traverse(self, rows: usize, cols: usize) -> Box<dyn Iterator<Item = (usize, usize)>> {
+        if rows == 0 || cols == 0 {
+            return Box::new(std::iter::empty());
+        }
+
+        match self {
+            WaveFunction::Horizontal => Box::new((0..rows).flat_map(move |row| (0..cols).map(move |col| (row, col)))),
+            WaveFunction::Vertical => Box::new((0..cols).flat_map(move |col| (0..rows).map(move |row| (row, col)))),
+            WaveFunction::DiagonalRight => Box::new((0..rows + cols - 1).flat_map(move |diagonal| {
+                let start_row = (rows - 1).saturating_sub(diagonal);
+                let start_col = diagonal.saturating_sub(rows - 1);
+                let steps = (rows - start_row).min(cols - start_col);
+                (0..steps).map(move |step| (start_row + step, start_col + step))
+            })),
+            WaveFunction::DiagonalLeft => Box::new((0..rows + cols - 1).flat_map(move |diagonal| {
+                // Every cell on a diagonal has row + col == diagonal, start from its top right end
+                let first_row = diagonal.saturating_sub(cols - 1);
+                let last_row = diagonal.min(rows - 1);
+                (first_row..=last_row).map(move |row| (row, diagonal - row))
+            })),
+            WaveFunction::ZigZagHorizontal => Box::new((0..rows).flat_map(move |row| {
+                (0..cols).map(move |col| (row, if row % 2 == 0 { col } else { cols - 1 - col }))
+            })),
+            WaveFunction::ZigZagVertical => Box::new((0..cols).flat_map(move |col| {
+                (0..rows).map(move |row| (if col % 2 == 0 { row } else { rows - 1 - row }, col))
+            })),
+            WaveFunction::Sinusoidal => Box::new(
+                FileEncodingMethod::SinWave
+                    .grid_visit_order(&TraversalParameters::default(), cols, rows)
+                    .map(move |cell| (cell / cols, cell % cols)),
+            ),
+            WaveFunction::Hilbert => Box::new(HilbertCells::new(rows, cols)),
+            WaveFunction::Morton => Box::new(morton_cells(rows, cols)),
+            WaveFunction::SpiralInward => Box::new(spiral_cells(rows, cols)),
+            WaveFunction::SpiralOutward => Box::new(spiral_cells(rows, cols).rev()),
+            WaveFunction::BlockRaster => {
+                let block_cols = cols.div_ceil(BLOCK_SIZE);
+                Box::new((0..rows.div_ceil(BLOCK_SIZE) * block_cols).flat_map(move |block| {
+                    let (top, left) = ((block / block_cols) * BLOCK_SIZE, (block % block_cols) * BLOCK_SIZE);
+                    let (bottom, right) = ((top + BLOCK_SIZE).min(rows), (left + BLOCK_SIZE).min(cols));
+                    (top..bottom).flat_map(move |row| (left..right).map(move |col| (row, col)))
+                }))
+            }
+        }
+    }
+
+    /*
+        Same order as traverse as row major cell indices, row * cols + col
+     */
+    pub fn cells(self, rows: usize, cols: usize) -> Box<dyn Iterator<Item = usize>> {
+        Box::new(self.traverse(rows, cols).map(move |(row, col)| row * cols + col))
+    }
+}
+
+/*
+    Generalized Hilbert curve ("gilbert", Jakub Cervený). A rectangle is split in two along its long side when it is
+    much wider than tall, otherwise into three like the classic curve, and the pieces are walked so each one starts
+    next to where the last one ended. Splits are rounded so every piece has an even side wherever that can be done,
+    odd sizes can leave a single diagonal step here and there but every cell is still visited exactly once.
+
+    Each region is the corner it starts in plus a major axis a and a minor axis b, the recursion is kept on an
+    explicit stack so cells come out one at a time.
+ */
+struct HilbertCells {
+    regions: Vec<[i64; 6]>,                  // x, y, ax, ay, bx, by, popped from the end
+    line: Option<(i64, i64, i64, i64, i64)>, // x, y, dx, dy and cells left on a region one cell thick
+}
+
+impl HilbertCells {
+    fn new(rows: usize, cols: usize) -> HilbertCells {
+        let (width, height) = (cols as i64, rows as i64);
+        let region = if width >= height { [0, 0, width, 0, 0, height] } else { [0, 0, 0, height, width, 0] };
+
+        HilbertCells {
+            regions: vec![region],
+            line: None,
+        }
+    }
+
+    fn split(&mut self, [x, y, ax, ay, bx, by]: [i64; 6]) {
+        let (w, h) = ((ax + ay).abs(), (bx + by).abs());
+        let (dax, day, dbx, dby) = (ax.signum(), ay.signum(), bx.signum(), by.signum());
+
+        if h == 1 {
+            self.line = Some((x, y, dax, day, w));
+            return;
+        }
+        if w == 1 {
+            self.line = Some((x, y, dbx, dby, h));
+            return;
+        }
+
+        let (mut ax2, mut ay2) = (ax.div_euclid(2), ay.div_euclid(2));
+        let (mut bx2, mut by2) = (bx.div_euclid(2), by.div_euclid(2));
+
+        // Pushed last to first so the first piece comes off the stack next
+        if 2 * w > 3 * h {
+            if (ax2 + ay2).abs() % 2 == 1 && w > 2 {
+                (ax2, ay2) = (ax2 + dax, ay2 + day);
+            }
+            self.regions.push([x + ax2, y + ay2, ax - ax2, ay - ay2, bx, by]);
+            self.regions.push([x, y, ax2, ay2, bx, by]);
+        } else {
+            if (bx2 + by2).abs() % 2 == 1 && h > 2 {
+                (bx2, by2) = (bx2 + dbx, by2 + dby);
+            }
+            self.regions.push([
+                x + (ax - dax) + (bx2 - dbx),
+                y + (ay - day) + (by2 - dby),
+                -bx2,
+                -by2,
+                -(ax - ax2),
+                -(ay - ay2),
+            ]);
+            self.regions.push([x + bx2, y + by2, ax, ay, bx - bx2, by - by2]);
+            self.regions.push([x, y, bx2, by2, ax2, ay2]);
+        }
+    }
+}
+
+impl Iterator for HilbertCells {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        loop {
+            if let Some((x, y, dx, dy, left)) = self.line {
+                self.line = if left > 1 { Some((x + dx, y + dy, dx, dy, left - 1)) } else { None };
+                return Some((y as usize, x as usize));
+            }
+
+            let region = self.regions.pop()?;
+            self.split(region);
+        }
+    }
+}
+
+/*
+    Z-order over the smallest power of two rectangle holding the grid, bits alternate column then row for as long as
+    both sides still need them and the longer side takes whatever is left. That keeps the rectangle under 4 times
+    the grid however stretched it is, the cells past the edge are skipped
+ */
+fn morton_cells(rows: usize, cols: usize) -> impl Iterator<Item = (usize, usize)> {
+    let bits = |side: usize| usize::BITS - (side - 1).leading_zeros();
+    let (col_bits, row_bits) = (bits(cols), bits(rows));
+
+    (0..1usize << (col_bits + row_bits))
+        .map(move |index| {
+            let (mut row, mut col) = (0, 0);
+            let (mut row_bit, mut col_bit) = (0, 0);
+
+            for bit in 0..col_bits + row_bits {
+                let value = (index >> bit) & 1;
+                let to_col = col_bit < col_bits && (row_bit == row_bits || col_bit <= row_bit);
+                if to_col {
+                    col |= value << col_bit;
+                    col_bit += 1;
+                } else {
+                    row |= value << row_bit;
+                    row_bit += 1;
+                }
+            }
+
+            (row, col)
+        })
+        .filter(move |(row, col)| *row < rows && *col < cols)
+}
+
+/*
+    Clockwise rings from the outside in, a ring is its top row, right column, bottom row backwards and left column
+    upwards with the bottom and left left out once the ring is a single row or column thick
+ */
+fn spiral_cells(rows: usize, cols: usize) -> impl DoubleEndedIterator<Item = (usize, usize)> {
+    (0..rows.min(cols).div_ceil(2)).flat_map(move |ring| {
+        let (top, left, bottom, right) = (ring, ring, rows - 1 - ring, cols - 1 - ring);
+        let bottom_start = if bottom > top { left } else { right };
+        let left_end = if left < right { bottom } else { top + 1 };
+
+        (left..=right)
+            .map(move |col| (top, col))
+            .chain((top + 1..=bottom).map(move |row| (row, right)))
+            .chain((bottom_start..right).rev().map(move |col| (bottom, col)))
+            .chain((top + 1..left_end).rev().map(move |row| (row, left)))
+    })
+}
+
+/*
+    Curve traversals. The grid is cut into bands that all follow the same curve, band k visits column c at row
+    (k + offset(c)) mod rows. For any one column that is just a rotation of the rows, so every cell is visited by
+    exactly one band no matter what the curve looks like, and the bands are walked top to bottom. The payload then
+    fills the image along the curve instead of in straight rows.
+
+    SinWave and CosWave offset by a single wave, PolynomialFunction by a cubic in the column taken mod the rows and
+    FractalFunction by a Weierstrass sum, the same wave at doubling frequency and halving amplitude depth times over.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraversalParameters {
+    pub amplitude: f64,         // Peak offset of the wave as a fraction of the rows
+    pub phase: f64,             // Radians
+    pub frequency: f64,         // Full periods across the width
+    pub coefficients: [u64; 4], // c0 + c1 x + c2 x^2 + c3 x^3 for PolynomialFunction
+    pub depth: u32,             // Octaves summed for FractalFunction
+}
+
+/*
+    What MethodBased traversal uses, under a key every one of these is drawn from the key instead (see key.rs)
+ */
+impl Default for TraversalParameters {
+    fn default() -> TraversalParameters {
+        TraversalParameters {
+            amplitude: 0.25,
+            phase: 0.0,
+            frequency: 2.0,
+            coefficients: [0, 1, 3, 2],
+            depth: 4,
+        }
+    }
+}
+
+pub const MAX_FRACTAL_DEPTH: u32 = 16;
+
+impl FileEncodingMethod {
+    /*
+        Methods that walk a curve and so take TraversalParameters, the rest have a single fixed order
+     */
+    pub fn is_curve(&self) -> bool {
+        matches!(
+            self,
+            FileEncodingMethod::SinWave
+                | FileEncodingMethod::CosWave
+                | FileEncodingMethod::PolynomialFunction
+                | FileEncodingMethod::FractalFunction
+        )
+    }
+
+    /*
+        Row offset of every column for a curve method, reduced mod rows
+     */
+    fn curve_offsets(&self, parameters: &TraversalParameters, cols: usize, rows: usize) -> Vec<usize> {
+        let scale = parameters.amplitude * rows as f64;
+        let angle = |col: usize, frequency: f64| TAU * frequency * col as f64 / cols as f64 + parameters.phase;
+
+        (0..cols)
+            .map(|col| {
+                let offset = match self {
+                    FileEncodingMethod::SinWave => sin_scaled(angle(col, parameters.frequency), scale),
+                    FileEncodingMethod::CosWave => cos_scaled(angle(col, parameters.frequency), scale),
+                    FileEncodingMethod::PolynomialFunction => {
+                        // Horner's rule mod rows, nothing can overflow and the result is exact
+                        let x = (col % rows) as u128;
+                        let offset = parameters
+                            .coefficients
+                            .iter()
+                            .rev()
+                            .fold(0u128, |sum, coefficient| (sum * x + *coefficient as u128) % rows as u128);
+                        offset as i64
+                    }
+                    FileEncodingMethod::FractalFunction => (0..parameters.depth.min(MAX_FRACTAL_DEPTH))
+                        .map(|octave| {
+                            let octave_scale = (1u64 << octave) as f64;
+                            sin_scaled(angle(col, parameters.frequency * octave_scale), scale / octave_scale)
+                        })
+                        .sum(),
+                    _ => unreachable!("{self:?} is not a curve"),
+                };
+
+                offset.rem_euclid(rows as i64) as usize
+            })
+            .collect()
+    }
+
+    /*
+        Order to visit the cells of a cols by rows grid in, as row * cols + col. RightToLeft is LeftToRight run
+        backwards, from the last pixel of the last row
+     */
+    pub fn grid_visit_order(
+        self,
+        parameters: &TraversalParameters,
+        cols: usize,
+        rows: usize,
+    ) -> Box<dyn Iterator<Item = usize>> {
+        if cols == 0 || rows == 0 {
+            return Box::new(std::iter::empty());
+        }
+
+        match self {
+            FileEncodingMethod::LeftToRight => WaveFunction::Horizontal.cells(rows, cols),
+            FileEncodingMethod::RightToLeft => Box::new((0..rows * cols).rev()),
+            FileEncodingMethod::TopToBottom => WaveFunction::Vertical.cells(rows, cols),
+            FileEncodingMethod::HilbertCurve => WaveFunction::Hilbert.cells(rows, cols),
+            FileEncodingMethod::MortonOrder => WaveFunction::Morton.cells(rows, cols),
+            FileEncodingMethod::SpiralInward => WaveFunction::SpiralInward.cells(rows, cols),
+            FileEncodingMethod::SpiralOutward => WaveFunction::SpiralOutward.cells(rows, cols),
+            FileEncodingMethod::BlockRaster => WaveFunction::BlockRaster.cells(rows, cols),
+            method => {
+                let offsets: Rc<[usize]> = method.curve_offsets(parameters, cols, rows).into();
+                Box::new((0..rows).flat_map(move |band| {
+                    let offsets = Rc::clone(&offsets);
+                    (0..cols).map(move |col| ((band + offsets[col]) % rows) * cols + col)
+                }))
+            }
+        }
+    }
+
+    /*
+        Curves need two dimensions, a flat run of slots with no shape of its own (palette indices, JPEG
+        coefficients) is laid out on the smallest square that holds it and the cells past the end are skipped
+     */
+    pub fn flat_grid_visit_order(self, parameters: &TraversalParameters, len: usize) -> Box<dyn Iterator<Item = usize>> {
+        let mut cols = sqrt_scaled(len as u64, 1.0) as usize;
+        if cols * cols < len {
+            cols += 1;
+        }
+        let rows = if cols == 0 { 0 } else { len.div_ceil(cols) };
+
+        Box::new(self.grid_visit_order(parameters, cols, rows).filter(move |cell| *cell < len))
+    }
+}
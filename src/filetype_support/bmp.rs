@@ -16,15 +16,45 @@
  * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
  */
 use crate::file_encoding_support::file_encoding_support::{
-    FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod, FileEncodingSupport,
+    EncodingParameters, FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod, FileEncodingSupport,
 };
-use crate::file_encoding_support::pixel::{embed_lsb_data_left_right, extract_lsb_data_left_right, Pixel};
-use std::fs::File;
-use std::io::{Read, Write};
+use crate::file_encoding_support::palette::{
+    embed_palette_data, extract_palette_data, palette_capacity_bits, Palette, PaletteEmbedding,
+    PaletteEntry,
+};
+use crate::file_encoding_support::payload::{build_payload, read_payload};
+use crate::file_encoding_support::pixel::{
+    embed_data_with_method, extract_data_with_method, hamming_payload_flags, pack_samples, pixel_map_capacity_bits,
+    unpack_samples, Pixel,
+};
+use crate::analysis::analysis::PixelChannels;
+use crate::error::error::MayaError;
+use crate::filetype_support::filetype_support::FileType;
 use std::mem;
-use std::process::exit;
 
 const BMP_MAGIC: u16 = 0x4D42;
+
+/*
+    bi_compression values. RLE gets decoded into a plain pixel map up front (see decode_rle), the embedded JPEG / PNG
+    streams are turned away and everything else is raw pixels
+ */
+pub const BI_RGB: u32 = 0;
+pub const BI_RLE8: u32 = 1;
+pub const BI_RLE4: u32 = 2;
+pub const BI_BITFIELDS: u32 = 3;
+pub const BI_ALPHABITFIELDS: u32 = 6;
+
+/*
+    DIB header sizes we know. Everything from BITMAPINFOHEADER up shares the same first 40 bytes, the later
+    versions only add masks, colour space and ICC profile fields on the end and those are left as they are
+ */
+pub const BITMAPCOREHEADER_SIZE: u32 = 12; // OS/2 1.x, 16 bit width and height and 3 byte color table entries
+pub const BITMAPINFOHEADER_SIZE: u32 = 40;
+pub const BITMAPV2INFOHEADER_SIZE: u32 = 52;
+pub const BITMAPV3INFOHEADER_SIZE: u32 = 56;
+pub const BITMAPV4HEADER_SIZE: u32 = 108;
+pub const BITMAPV5HEADER_SIZE: u32 = 124;
+
 #[repr(C, packed)]
 #[derive(Debug, Clone, Copy)]
 pub struct BitmapFileHeader {
@@ -38,12 +68,12 @@ pub struct BitmapFileHeader {
 #[repr(C, packed)]
 #[derive(Debug, Clone, Copy)]
 pub struct BitmapDIBHeader {
-    pub bi_size: u32,             // Size of this header (40 bytes, for BITMAPINFOHEADER)
+    pub bi_size: u32,             // Size of this header (40 bytes for BITMAPINFOHEADER, up to 124 for BITMAPV5HEADER)
     pub bi_width: i32,            // Width of bitmap in pixels
     pub bi_height: i32, // Height of bitmap in pixels. If positive, bottom-up. If negative, top-down.
     pub bi_planes: u16, // Number of color planes (must be 1 for all bitmaps)
     pub bi_bit_count: u16, // Bits per pixel (e.g., 24 for RGB, 1 for monochrome)
-    pub bi_compression: u32, // Compression type (e.g., 0 = none, 1 = BI_RLE8, 2 = BI_RLE4)
+    pub bi_compression: u32, // Compression type (e.g., 0 = none, 1 = BI_RLE8, 2 = BI_RLE4, 3 = BI_BITFIELDS)
     pub bi_size_image: u32, // Image size in bytes (may be 0 for uncompressed images)
     pub bi_x_pels_per_meter: i32, // Horizontal resolution of the image (pixels per meter)
     pub bi_y_pels_per_meter: i32, // Vertical resolution of the image (pixels per meter)
@@ -53,7 +83,7 @@ pub struct BitmapDIBHeader {
 /*
    Presence is mandatory when bits per pixel is <= 8
 
-   We likely won't need this
+   Indexed images are embedded in the index domain (see palette.rs) so this gets read into a Palette
 
    The size of color table entries is 3 bytes if BITMAPCOREHEADER is
    substituted for BITMAPV5HEADER
@@ -86,10 +116,21 @@ pub struct RgbaPixel {
     pub alpha: u8, // Can be actual alpha or just padding (usually 0 or 255)
 }
 
+/*
+    What to_bytes does with an RLE image after embedding. The runs are different afterwards so the file size changes
+    either way, uncompressed output is just easier on whatever reads it next
+ */
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RleOutput {
+    Recompress,
+    Uncompressed,
+}
+
 pub struct BmpBitmap {
     pub num_embedded_bits : Option<usize>,
     pub width: u32,
     pub height: u32,
+    pub top_down: bool, // Negative bi_height, first row in the file is the top of the image instead of the bottom
     pub pixel_map_start: u64, // File offset where pixel map begins, will be indexed via file_data
 }
 
@@ -99,150 +140,819 @@ pub struct BmpImageParser {
     pub pixel_size: u8,
     pub padding_size: u8,
     pub pixel_map: BmpBitmap,
-    pub image_file: File,
-    pub file_data: Box<Vec<u8>>,
+    pub palette: Option<Palette>, // Color table of an indexed image
+    pub bitfields: Option<BitfieldMasks>, // Channel layout of 16 bit and BI_BITFIELDS images
+    pub encoding_parameters: EncodingParameters,
+    pub rle_output: RleOutput,
+    pub rle_pixels: Option<Vec<u8>>, // Decoded RLE4 / RLE8 pixel map, laid out like a BI_RGB one would be
+    pub rle_stream_size: usize, // Bytes of the original compressed stream in file_data
+    pub filename: String,
+    pub file_data: Vec<u8>,
     ready: bool,
 }
 
 // For RGB pixel type
 impl Pixel for RgbPixel {
-    fn red(&self) -> u8 {
-        self.red
+    fn channel_count(&self) -> usize {
+        3
+    }
+
+    fn channel(&self, index: usize) -> u16 {
+        match index {
+            0 => self.blue as u16,
+            1 => self.green as u16,
+            2 => self.red as u16,
+            _ => unreachable!("bmp.rs RgbPixel channel {index} requested from a 3 byte pixel! This is a bug!"),
+        }
     }
-    fn green(&self) -> u8 {
-        self.green
+
+    fn set_channel(&mut self, index: usize, value: u16) {
+        match index {
+            0 => self.blue = value as u8,
+            1 => self.green = value as u8,
+            2 => self.red = value as u8,
+            _ => unreachable!("bmp.rs RgbPixel set_channel {index} called on a 3 byte pixel! This is a bug!"),
+        }
     }
-    fn blue(&self) -> u8 {
-        self.blue
+
+    fn red(&self) -> u16 {
+        self.red as u16
     }
-    fn alpha(&self) -> u8 {
+    fn green(&self) -> u16 {
+        self.green as u16
+    }
+    fn blue(&self) -> u16 {
+        self.blue as u16
+    }
+    fn alpha(&self) -> u16 {
         255
     } // No alpha in RGB, so always 255
 
-    fn first(&self) -> u8 {
-        self.blue
+    fn pixel_size(&self) -> usize {
+        3
     }
+}
 
-    fn second(&self) -> u8 {
-        self.green
+// For RGBA pixel type
+impl Pixel for RgbaPixel {
+    fn channel_count(&self) -> usize {
+        4
     }
-    fn third(&self) -> u8 {
-        self.red
+
+    fn channel(&self, index: usize) -> u16 {
+        match index {
+            0 => self.blue as u16,
+            1 => self.green as u16,
+            2 => self.red as u16,
+            3 => self.alpha as u16,
+            _ => unreachable!("bmp.rs RgbaPixel channel {index} requested from a 4 byte pixel! This is a bug!"),
+        }
     }
-    fn fourth(&self) -> u8 {
-        255
+
+    fn set_channel(&mut self, index: usize, value: u16) {
+        match index {
+            0 => self.blue = value as u8,
+            1 => self.green = value as u8,
+            2 => self.red = value as u8,
+            3 => self.alpha = value as u8,
+            _ => unreachable!("bmp.rs RgbaPixel set_channel {index} called on a 4 byte pixel! This is a bug!"),
+        }
+    }
+
+    fn red(&self) -> u16 {
+        self.red as u16
     }
-    fn set_red(&mut self, value: u8) {
-        self.red = value
+    fn green(&self) -> u16 {
+        self.green as u16
+    }
+    fn blue(&self) -> u16 {
+        self.blue as u16
+    }
+    fn alpha(&self) -> u16 {
+        self.alpha as u16
     }
 
-    fn set_green(&mut self, value: u8) {
-        self.green = value
+    fn pixel_size(&self) -> usize {
+        4
     }
+}
+
+/*
+    Where each channel sits in a 16 or 32 bit pixel, from the masks after the header for BI_BITFIELDS or the
+    555 default for plain 16 bit images
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitfieldMasks {
+    pub red: u32,
+    pub green: u32,
+    pub blue: u32,
+    pub alpha: u32, // 0 when there is no alpha channel
+}
+
+impl BitfieldMasks {
+    pub const RGB555: BitfieldMasks = BitfieldMasks {
+        red: 0x7C00,
+        green: 0x03E0,
+        blue: 0x001F,
+        alpha: 0,
+    };
+
+    /*
+        Every sample is cut down to the narrowest color channel, the wider ones (green in 565) are embedded through
+        their top bits so all samples are really the same width and PVD can't push one past its mask
+     */
+    pub fn sample_bits(&self) -> u32 {
+        [self.red, self.green, self.blue]
+            .iter()
+            .map(|mask| mask.count_ones())
+            .min()
+            .unwrap_or(0)
+    }
+
+    /*
+        Masks of the channels that get embedded into in the blue, green, red, alpha order RgbaPixel uses. An alpha
+        narrower than the colors (the 1 bit of 1555) is left alone, a flipped bit there would be anything but subtle
+     */
+    pub fn channels(&self) -> Vec<u32> {
+        let mut channels = vec![self.blue, self.green, self.red];
+        if self.alpha != 0 && self.alpha.count_ones() >= self.sample_bits() {
+            channels.push(self.alpha);
+        }
+        channels
+    }
+
+    fn validate(&self, bit_count: u16) -> Result<(), MayaError> {
+        let masks = [self.red, self.green, self.blue, self.alpha];
+        let contiguous = |mask: u32| mask == 0 || ((mask >> mask.trailing_zeros()) + 1).is_power_of_two();
+        let overlapping = masks.iter().enumerate().any(|(i, a)| masks[i + 1..].iter().any(|b| a & b != 0));
+        let in_pixel = masks.iter().all(|mask| bit_count >= 32 || mask >> bit_count == 0);
+
+        if self.red == 0 || self.green == 0 || self.blue == 0 || !masks.iter().all(|mask| contiguous(*mask)) || overlapping || !in_pixel {
+            return Err(MayaError::CorruptHeader(format!("BMP bitfield masks {self:08x?} don't describe a pixel")));
+        }
+
+        if !matches!(self.sample_bits(), 4 | 5 | 8) {
+            return Err(MayaError::UnsupportedFormat(format!(
+                "BMP bitfields with {} bit channels, only 4, 5 and 8 bit channels are supported",
+                self.sample_bits()
+            )));
+        }
+
+        Ok(())
+    }
+}
 
-    fn set_blue(&mut self, value: u8) {
-        self.blue = value
+/*
+    One unpacked BI_BITFIELDS pixel, a byte per embedded channel (see BitfieldMasks::channels) holding the top
+    BITS bits of that channel
+ */
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BitfieldPixel<const CHANNELS: usize, const BITS: u32> {
+    pub samples: [u8; CHANNELS],
+}
+
+impl<const CHANNELS: usize, const BITS: u32> Default for BitfieldPixel<CHANNELS, BITS> {
+    fn default() -> Self {
+        BitfieldPixel { samples: [0; CHANNELS] }
     }
+}
 
-    fn set_first(&mut self, value: u8) {
-        self.blue = value
+impl<const CHANNELS: usize, const BITS: u32> Pixel for BitfieldPixel<CHANNELS, BITS> {
+    fn channel_count(&self) -> usize {
+        CHANNELS
     }
 
-    fn set_second(&mut self, value: u8) {
-        self.green = value
+    fn sample_bits(&self) -> u32 {
+        BITS
     }
 
-    fn set_third(&mut self, value: u8) {
-        self.red = value
+    fn channel(&self, index: usize) -> u16 {
+        self.samples[index] as u16
     }
 
-    fn set_fourth(&mut self, value: u8) {
-        println!("bmp.rs RgbPixel set_fourth called on a 3 byte pixel! This is a bug! Exiting");
-        exit(1)
+    fn set_channel(&mut self, index: usize, value: u16) {
+        self.samples[index] = value as u8 & self.max_sample() as u8;
     }
 
-    fn set_alpha(&mut self, value: u8) {
-        println!("bmp.rs RgbPixel set_alpha called on a 3 byte pixel! This is a bug! Exiting");
-        exit(1)
+    fn red(&self) -> u16 {
+        self.samples[2] as u16
+    }
+    fn green(&self) -> u16 {
+        self.samples[1] as u16
+    }
+    fn blue(&self) -> u16 {
+        self.samples[0] as u16
+    }
+    fn alpha(&self) -> u16 {
+        if CHANNELS == 4 { self.samples[3] as u16 } else { self.max_sample() }
     }
 
     fn pixel_size(&self) -> usize {
-        3
+        CHANNELS
     }
 }
 
-// For RGBA pixel type
-impl Pixel for RgbaPixel {
-    fn red(&self) -> u8 {
-        self.red
+/*
+    Largest pixel map we'll decode an RLE stream into, a few delta escapes can claim an enormous image
+ */
+const MAX_RLE_PIXEL_MAP_SIZE: usize = 1 << 30;
+
+/*
+    RLE8 / RLE4 streams are pairs of bytes. A non zero first byte is a run of that many pixels using the second
+    byte (RLE4 alternates between its two nibbles), a zero first byte is an escape:
+        0 end of line, 1 end of bitmap, 2 delta (the next 2 bytes move right and up),
+        3-255 that many literal pixels follow, padded out to a 16 bit boundary
+    Pixels skipped by a delta or an early end of line are left as index 0. Returns one index per pixel in file
+    row order along with how many bytes of the stream were used.
+ */
+pub fn decode_rle(stream: &[u8], width: usize, height: usize, bits_per_pixel: usize) -> Result<(Vec<u8>, usize), MayaError> {
+    let mut indices = vec![0u8; width * height];
+    let mut x = 0;
+    let mut y = 0;
+    let mut position = 0;
+
+    let truncated = || MayaError::CorruptHeader("BMP RLE stream ends in the middle of a command".to_string());
+
+    let mut put = |x: usize, y: usize, index: u8| {
+        if x < width && y < height {
+            indices[y * width + x] = index;
+        }
+    };
+
+    while position + 1 < stream.len() {
+        let (count, value) = (stream[position] as usize, stream[position + 1]);
+        position += 2;
+
+        match (count, value) {
+            (0, 0) => {
+                x = 0;
+                y += 1;
+            }
+            (0, 1) => break,
+            (0, 2) => {
+                let delta = stream.get(position..position + 2).ok_or_else(truncated)?;
+                x += delta[0] as usize;
+                y += delta[1] as usize;
+                position += 2;
+            }
+            (0, literal) => {
+                let literal = literal as usize;
+                let bytes = (literal * bits_per_pixel).div_ceil(8);
+                let pixels = stream.get(position..position + bytes).ok_or_else(truncated)?;
+                for pixel in 0..literal {
+                    put(x + pixel, y, rle_pixel(pixels[pixel * bits_per_pixel / 8], pixel, bits_per_pixel));
+                }
+                x += literal;
+                position += bytes + bytes % 2;
+            }
+            (count, value) => {
+                for pixel in 0..count {
+                    put(x + pixel, y, rle_pixel(value, pixel, bits_per_pixel));
+                }
+                x += count;
+            }
+        }
+
+        if y >= height {
+            break;
+        }
     }
-    fn green(&self) -> u8 {
-        self.green
+
+    Ok((indices, position.min(stream.len())))
+}
+
+/*
+    Pixel number n of an RLE byte, RLE4 packs two pixels into it high nibble first
+ */
+fn rle_pixel(byte: u8, pixel: usize, bits_per_pixel: usize) -> u8 {
+    match (bits_per_pixel, pixel % 2) {
+        (4, 0) => byte >> 4,
+        (4, _) => byte & 0x0F,
+        _ => byte,
     }
-    fn blue(&self) -> u8 {
-        self.blue
+}
+
+/*
+    Reverse of decode_rle. Runs of 3 or more of the same index get a run, anything else is gathered up into a literal
+    block (or single pixel runs when there are only 1 or 2 of them since literals need at least 3).
+ */
+pub fn encode_rle(indices: &[u8], width: usize, height: usize, bits_per_pixel: usize) -> Vec<u8> {
+    let mut stream = Vec::new();
+    let pack = |index: u8| if bits_per_pixel == 4 { (index << 4) | index } else { index };
+
+    for (row_number, row) in indices.chunks(width.max(1)).take(height).enumerate() {
+        let run_at = |start: usize| row[start..].iter().take(255).take_while(|index| **index == row[start]).count();
+        let mut x = 0;
+
+        while x < row.len() {
+            let run = run_at(x);
+            if run >= 3 {
+                stream.extend_from_slice(&[run as u8, pack(row[x])]);
+                x += run;
+                continue;
+            }
+
+            let mut end = x;
+            while end < row.len() && end - x < 255 && run_at(end) < 3 {
+                end += 1;
+            }
+
+            if end - x < 3 {
+                for index in &row[x..end] {
+                    stream.extend_from_slice(&[1, pack(*index)]);
+                }
+            } else {
+                stream.extend_from_slice(&[0, (end - x) as u8]);
+                let literal = &row[x..end];
+                let bytes: Vec<u8> = if bits_per_pixel == 4 {
+                    literal.chunks(2).map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0)).collect()
+                } else {
+                    literal.to_vec()
+                };
+                stream.extend_from_slice(&bytes);
+                if bytes.len() % 2 == 1 {
+                    stream.push(0);
+                }
+            }
+            x = end;
+        }
+
+        if row_number + 1 < height {
+            stream.extend_from_slice(&[0, 0]);
+        }
     }
-    fn alpha(&self) -> u8 {
-        self.alpha
+
+    stream.extend_from_slice(&[0, 1]);
+    stream
+}
+
+impl BmpImageParser {
+    /*
+        The color table sits straight after the DIB header, bi_clr_used of 0 means the full 2^bi_bit_count entries
+     */
+    fn color_table_offset(&self) -> usize {
+        14 + self.bmp_dib_header.bi_size as usize
     }
 
-    fn first(&self) -> u8 {
-        self.blue
+    /*
+        RGBQUAD entries, or RGBTRIPLE ones under the OS/2 core header
+     */
+    fn color_table_entry_size(&self) -> usize {
+        if self.bmp_dib_header.bi_size == BITMAPCOREHEADER_SIZE { 3 } else { 4 }
     }
 
-    fn second(&self) -> u8 {
-        self.green
+    fn color_table_entries(&self) -> usize {
+        match self.bmp_dib_header.bi_clr_used {
+            0 => 1 << self.bmp_dib_header.bi_bit_count,
+            used => used as usize,
+        }
     }
-    fn third(&self) -> u8 {
-        self.red
+
+    /*
+        The masks follow the first 40 bytes of the header whether they're part of it (V2 and up) or tacked on after a
+        BITMAPINFOHEADER. Alpha is there for BI_ALPHABITFIELDS and from V3 on, a V3+ BI_BITFIELDS image with a 0 alpha
+        mask has no alpha
+     */
+    fn read_bitfield_masks(&self) -> Result<BitfieldMasks, MayaError> {
+        let with_alpha = self.bmp_dib_header.bi_compression == BI_ALPHABITFIELDS || self.bmp_dib_header.bi_size >= BITMAPV3INFOHEADER_SIZE;
+        let count = if with_alpha { 4 } else { 3 };
+        let offset = 14 + BITMAPINFOHEADER_SIZE as usize;
+
+        if offset + count * 4 > self.file_data.len() || offset + count * 4 > self.bmp_header.bf_off_bits as usize {
+            return Err(MayaError::CorruptHeader("BMP bitfield masks run past the pixel map".to_string()));
+        }
+
+        let mask = |index: usize| {
+            let at = offset + index * 4;
+            u32::from_le_bytes([self.file_data[at], self.file_data[at + 1], self.file_data[at + 2], self.file_data[at + 3]])
+        };
+
+        Ok(BitfieldMasks {
+            red: mask(0),
+            green: mask(1),
+            blue: mask(2),
+            alpha: if with_alpha { mask(3) } else { 0 },
+        })
     }
-    fn fourth(&self) -> u8 {
-        self.alpha
+
+    fn read_color_table(&self) -> Result<Palette, MayaError> {
+        let offset = self.color_table_offset();
+        let entries = self.color_table_entries().min(256);
+        let entry_size = self.color_table_entry_size();
+
+        if offset + entries * entry_size > self.bmp_header.bf_off_bits as usize || offset + entries * entry_size > self.file_data.len() {
+            return Err(MayaError::CorruptHeader("BMP color table runs past the pixel map".to_string()));
+        }
+
+        Ok(Palette {
+            entries: self.file_data[offset..offset + entries * entry_size]
+                .chunks(entry_size)
+                .map(|entry| {
+                    let color = BitmapColorTable {
+                        blue: entry[0],
+                        green: entry[1],
+                        red: entry[2],
+                        reserved: 0,
+                    };
+                    PaletteEntry {
+                        red: color.red,
+                        green: color.green,
+                        blue: color.blue,
+                        alpha: 255,
+                    }
+                })
+                .collect(),
+            tolerance: None,
+        })
     }
-    fn set_red(&mut self, value: u8) {
-        self.red = value
+
+    /*
+        Bytes of actual pixels in a row, 1 and 4 bit images pack several pixels into a byte
+     */
+    fn row_bytes(&self) -> usize {
+        (self.pixel_map.width as usize * self.bmp_dib_header.bi_bit_count as usize).div_ceil(8)
     }
 
-    fn set_green(&mut self, value: u8) {
-        self.green = value
+    fn row_stride(&self) -> usize {
+        self.row_bytes() + self.padding_size as usize
     }
 
-    fn set_blue(&mut self, value: u8) {
-        self.blue = value
+    /*
+        Expand the RLE stream into rle_pixels, the stream runs from bf_off_bits for bi_size_image bytes (or to the
+        end of the file when that's 0 or nonsense)
+     */
+    fn decode_rle_pixels(&mut self) -> Result<(), MayaError> {
+        if self.pixel_map.top_down {
+            return Err(MayaError::CorruptHeader("RLE compressed BMPs can't be top down".to_string()));
+        }
+
+        let width = self.pixel_map.width as usize;
+        let height = self.pixel_map.height as usize;
+        let bits = self.bmp_dib_header.bi_bit_count as usize;
+        let stride = self.row_stride();
+
+        let pixel_map_size = stride.checked_mul(height).filter(|size| *size <= MAX_RLE_PIXEL_MAP_SIZE).ok_or_else(|| {
+            MayaError::UnsupportedFormat(format!("RLE compressed BMP of {width}x{height} is too large to decode"))
+        })?;
+
+        let start = self.pixel_map.pixel_map_start as usize;
+        if start > self.file_data.len() {
+            return Err(MayaError::CorruptHeader("BMP RLE stream starts past the end of the file".to_string()));
+        }
+
+        let stream_end = match self.bmp_dib_header.bi_size_image as usize {
+            0 => self.file_data.len(),
+            size => start.saturating_add(size).min(self.file_data.len()),
+        };
+
+        let (indices, used) = decode_rle(&self.file_data[start..stream_end], width, height, bits)?;
+        let mut pixels = vec![0u8; pixel_map_size];
+        pack_samples(&indices, &mut pixels, height, stride, width, bits);
+
+        self.rle_stream_size = if self.bmp_dib_header.bi_size_image == 0 { used } else { stream_end - start };
+        self.rle_pixels = Some(pixels);
+        Ok(())
     }
 
-    fn set_first(&mut self, value: u8) {
-        self.blue = value
+    /*
+        Put the file back together around a fresh pixel map stream, either re-encoded or left uncompressed
+        depending on rle_output. The header sizes are fixed up and so is a V5 ICC profile that sits after the
+        pixels since its offset moves with the stream.
+     */
+    fn rle_bytes(&self) -> Vec<u8> {
+        let pixels = match &self.rle_pixels {
+            Some(pixels) => pixels,
+            None => return self.file_data.to_vec(),
+        };
+
+        let width = self.pixel_map.width as usize;
+        let height = self.pixel_map.height as usize;
+        let bits = self.bmp_dib_header.bi_bit_count as usize;
+
+        let stream = match self.rle_output {
+            RleOutput::Recompress => encode_rle(&unpack_samples(pixels, height, self.row_stride(), width, bits), width, height, bits),
+            RleOutput::Uncompressed => pixels.clone(),
+        };
+
+        let start = self.pixel_map.pixel_map_start as usize;
+        let stream_end = start + self.rle_stream_size;
+        let mut bytes = Vec::with_capacity(self.file_data.len() + stream.len());
+        bytes.extend_from_slice(&self.file_data[..start]);
+        bytes.extend_from_slice(&stream);
+        bytes.extend_from_slice(&self.file_data[stream_end..]);
+
+        let set_field = |bytes: &mut Vec<u8>, offset: usize, value: u32| bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        let file_size = bytes.len() as u32;
+        set_field(&mut bytes, 2, file_size);
+        set_field(&mut bytes, 34, stream.len() as u32);
+        if self.rle_output == RleOutput::Uncompressed {
+            set_field(&mut bytes, 30, BI_RGB);
+        }
+
+        if self.bmp_dib_header.bi_size == BITMAPV5HEADER_SIZE {
+            let profile_offset = u32::from_le_bytes([bytes[126], bytes[127], bytes[128], bytes[129]]) as usize;
+            if profile_offset != 0 && profile_offset + 14 >= stream_end {
+                set_field(&mut bytes, 126, (profile_offset + stream.len() - self.rle_stream_size) as u32);
+            }
+        }
+
+        bytes
     }
 
-    fn set_second(&mut self, value: u8) {
-        self.green = value
+    /*
+        The uncompressed pixel map, straight out of file_data unless the image was RLE compressed
+     */
+    fn pixel_data(&self) -> &[u8] {
+        match &self.rle_pixels {
+            Some(pixels) => pixels,
+            None => &self.file_data[self.pixel_map.pixel_map_start as usize..],
+        }
     }
 
-    fn set_third(&mut self, value: u8) {
-        self.red = value
+    fn pixel_data_mut(&mut self) -> &mut [u8] {
+        match &mut self.rle_pixels {
+            Some(pixels) => pixels,
+            None => &mut self.file_data[self.pixel_map.pixel_map_start as usize..],
+        }
     }
 
-    fn set_fourth(&mut self, value: u8) {
-        self.alpha = value
+    /*
+        Offset into pixel_data of a row counted from the top of the image, bottom up files store the last row first.
+        Everything that walks the pixel map goes through here so a payload lands in the same visual spots
+        no matter which way round the rows were written
+     */
+    fn row_offset(&self, row: usize) -> usize {
+        let stored_row = if self.pixel_map.top_down {
+            row
+        } else {
+            self.pixel_map.height as usize - 1 - row
+        };
+        stored_row * self.row_stride()
     }
 
-    fn set_alpha(&mut self, value: u8) {
-        self.alpha = value
+    /*
+        Copy of the pixel bytes in visual order with the row padding dropped
+     */
+    fn visual_rows(&self) -> Vec<u8> {
+        let row_size = self.row_bytes();
+        let mut rows = Vec::with_capacity(row_size * self.pixel_map.height as usize);
+        for row in 0..self.pixel_map.height as usize {
+            let offset = self.row_offset(row);
+            rows.extend_from_slice(&self.pixel_data()[offset..offset + row_size]);
+        }
+        rows
     }
 
-    fn pixel_size(&self) -> usize {
-        4
+    fn store_visual_rows(&mut self, rows: &[u8]) {
+        let row_size = self.row_bytes();
+        for (row, line) in rows.chunks(row_size.max(1)).enumerate().take(self.pixel_map.height as usize) {
+            let offset = self.row_offset(row);
+            self.pixel_data_mut()[offset..offset + row_size].copy_from_slice(line);
+        }
     }
-}
 
-/*
-   We will just add support for 24 bit and 32 bit pixel sizes, will likely only encounter 24 bit pixels
-*/
+    /*
+        The color table with the palette tolerance applied, this is what embedding and extraction actually work against
+     */
+    fn embedding_palette(&self) -> Option<Palette> {
+        self.palette.as_ref().map(|palette| Palette {
+            entries: palette.entries.clone(),
+            tolerance: self.encoding_parameters.palette_tolerance,
+        })
+    }
+
+    /*
+        One index per pixel in visual order, whatever the bit depth
+     */
+    fn palette_indices(&self) -> Vec<u8> {
+        let width = self.pixel_map.width as usize;
+        let bits = self.bmp_dib_header.bi_bit_count as usize;
+        let mut indices = Vec::with_capacity(width * self.pixel_map.height as usize);
+        for row in 0..self.pixel_map.height as usize {
+            indices.extend(unpack_samples(&self.pixel_data()[self.row_offset(row)..], 1, self.row_stride(), width, bits));
+        }
+        indices
+    }
+
+    fn store_palette_indices(&mut self, indices: &[u8]) {
+        let stride = self.row_stride();
+        let width = self.pixel_map.width as usize;
+        let bits = self.bmp_dib_header.bi_bit_count as usize;
+        for (row, line) in indices.chunks(width.max(1)).enumerate().take(self.pixel_map.height as usize) {
+            let offset = self.row_offset(row);
+            pack_samples(line, &mut self.pixel_data_mut()[offset..], 1, stride, width, bits);
+        }
+    }
+
+    /*
+        Pull every pixel of a bitfield image apart into a BitfieldPixel, rows go top to bottom with no padding
+     */
+    fn bitfield_samples(&self, masks: &BitfieldMasks) -> Vec<u8> {
+        let bits = masks.sample_bits();
+        let channels = masks.channels();
+        let pixel_size = self.pixel_size as usize;
+        let mut samples = Vec::with_capacity(self.pixel_map.width as usize * self.pixel_map.height as usize * channels.len());
+
+        for row in 0..self.pixel_map.height as usize {
+            let line = &self.pixel_data()[self.row_offset(row)..];
+            for pixel in line[..self.pixel_map.width as usize * pixel_size].chunks(pixel_size) {
+                let raw = pixel.iter().rev().fold(0u32, |raw, byte| (raw << 8) | *byte as u32);
+                for mask in channels.iter() {
+                    let shift = mask.trailing_zeros() + mask.count_ones() - bits;
+                    samples.push(((raw >> shift) & ((1 << bits) - 1)) as u8);
+                }
+            }
+        }
+
+        samples
+    }
 
-pub enum BmpPixelType {
-    Rgb,
-    Rgba,
+    /*
+        Reverse of bitfield_samples, the low bits of wide channels and anything outside the masks stay as they were
+     */
+    fn store_bitfield_samples(&mut self, masks: &BitfieldMasks, samples: &[u8]) {
+        let bits = masks.sample_bits();
+        let channels = masks.channels();
+        let pixel_size = self.pixel_size as usize;
+        let width = self.pixel_map.width as usize;
+        let mut samples = samples.iter();
+
+        for row in 0..self.pixel_map.height as usize {
+            let offset = self.row_offset(row);
+            let line = &mut self.pixel_data_mut()[offset..offset + width * pixel_size];
+            for pixel in line.chunks_mut(pixel_size) {
+                let mut raw = pixel.iter().rev().fold(0u32, |raw, byte| (raw << 8) | *byte as u32);
+                for mask in channels.iter() {
+                    let shift = mask.trailing_zeros() + mask.count_ones() - bits;
+                    raw = (raw & !(((1 << bits) - 1) << shift)) | ((*samples.next().unwrap() as u32) << shift);
+                }
+                pixel.copy_from_slice(&raw.to_le_bytes()[..pixel_size]);
+            }
+        }
+    }
+
+    fn embed_bitfield_pixels<P: Pixel + Default>(
+        &mut self,
+        masks: BitfieldMasks,
+        data: &[u8],
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Result<(), MayaError> {
+        let width = self.pixel_map.width as u64;
+        let height = self.pixel_map.height as u64;
+        let pixel_size = P::default().pixel_size() as u64;
+
+        let mut samples = self.bitfield_samples(&masks);
+        embed_data_with_method::<P>(data, &mut samples, width, height, 0, pixel_size, encoding, encoding_method, file_encoding_function_derivation, &self.encoding_parameters)?;
+        self.store_bitfield_samples(&masks, &samples);
+        Ok(())
+    }
+
+    fn extract_bitfield_pixels<P: Pixel + Default>(
+        &self,
+        masks: BitfieldMasks,
+        embedded_bits: u64,
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Result<Vec<u8>, MayaError> {
+        let width = self.pixel_map.width as u64;
+        let height = self.pixel_map.height as u64;
+        let pixel_size = P::default().pixel_size() as u64;
+
+        let mut samples = self.bitfield_samples(&masks);
+        extract_data_with_method::<P>(&mut samples, width, height, 0, pixel_size, embedded_bits, encoding, encoding_method, file_encoding_function_derivation, &self.encoding_parameters)
+    }
+
+    fn bitfield_channels<P: Pixel + Default>(&self, masks: BitfieldMasks) -> PixelChannels {
+        let width = self.pixel_map.width as u64;
+        let height = self.pixel_map.height as u64;
+        let pixel_size = P::default().pixel_size() as u64;
+
+        let mut samples = self.bitfield_samples(&masks);
+        PixelChannels::from_pixel_map::<P>(&mut samples, width, height, 0, pixel_size)
+    }
+
+    fn bitfield_capacity_bits<P: Pixel + Default>(&self, masks: BitfieldMasks, encoding: FileEncoding) -> Result<u64, MayaError> {
+        let width = self.pixel_map.width as u64;
+        let height = self.pixel_map.height as u64;
+        let pixel_size = P::default().pixel_size() as u64;
+
+        let mut samples = self.bitfield_samples(&masks);
+        pixel_map_capacity_bits::<P>(&mut samples, width, height, 0, pixel_size, encoding, &self.encoding_parameters)
+    }
+
+    /*
+        Sort the color table into luminance order in the file and remap every pixel to match
+     */
+    fn reorder_palette(&mut self) {
+        let offset = self.color_table_offset();
+        let entry_size = self.color_table_entry_size();
+        let palette = match self.palette.as_mut() {
+            Some(palette) => palette,
+            None => return,
+        };
+
+        let remap = palette.sort_by_luminance();
+
+        for (index, entry) in palette.entries.iter().enumerate() {
+            let color = BitmapColorTable {
+                blue: entry.blue,
+                green: entry.green,
+                red: entry.red,
+                reserved: 0,
+            };
+            self.file_data[offset + index * entry_size..offset + (index + 1) * entry_size]
+                .copy_from_slice(&[color.blue, color.green, color.red, color.reserved][..entry_size]);
+        }
+
+        let indices: Vec<u8> = self
+            .palette_indices()
+            .iter()
+            .map(|index| remap[*index as usize])
+            .collect();
+        self.store_palette_indices(&indices);
+    }
+
+    fn embed_bits(
+        &mut self,
+        data: &[u8],
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Result<(), MayaError> {
+        if self.palette.is_some() {
+            if self.encoding_parameters.palette_embedding == PaletteEmbedding::ReorderPalette {
+                self.reorder_palette();
+            }
+
+            let mut indices = self.palette_indices();
+            if let Some(palette) = &self.embedding_palette() {
+                embed_palette_data(data, &mut indices, self.pixel_map.width as usize, self.pixel_map.height as usize, palette, encoding_method, file_encoding_function_derivation)?;
+            }
+            self.store_palette_indices(&indices);
+            return Ok(());
+        }
+
+        if let Some(masks) = self.bitfields {
+            return match (masks.channels().len(), masks.sample_bits()) {
+                (3, 4) => self.embed_bitfield_pixels::<BitfieldPixel<3, 4>>(masks, data, encoding, encoding_method, file_encoding_function_derivation),
+                (3, 5) => self.embed_bitfield_pixels::<BitfieldPixel<3, 5>>(masks, data, encoding, encoding_method, file_encoding_function_derivation),
+                (3, _) => self.embed_bitfield_pixels::<BitfieldPixel<3, 8>>(masks, data, encoding, encoding_method, file_encoding_function_derivation),
+                (_, 4) => self.embed_bitfield_pixels::<BitfieldPixel<4, 4>>(masks, data, encoding, encoding_method, file_encoding_function_derivation),
+                (_, 5) => self.embed_bitfield_pixels::<BitfieldPixel<4, 5>>(masks, data, encoding, encoding_method, file_encoding_function_derivation),
+                _ => self.embed_bitfield_pixels::<BitfieldPixel<4, 8>>(masks, data, encoding, encoding_method, file_encoding_function_derivation),
+            };
+        }
+
+        let width = self.pixel_map.width as u64;
+        let height = self.pixel_map.height as u64;
+        let mut pixels = self.visual_rows();
+
+        if self.pixel_size == 3 {
+            embed_data_with_method::<RgbPixel>(data, &mut pixels, width, height, 0, 3, encoding, encoding_method, file_encoding_function_derivation, &self.encoding_parameters)?;
+        } else {
+            embed_data_with_method::<RgbaPixel>(data, &mut pixels, width, height, 0, 4, encoding, encoding_method, file_encoding_function_derivation, &self.encoding_parameters)?;
+        }
+
+        self.store_visual_rows(&pixels);
+        Ok(())
+    }
+
+    fn extract_bits(
+        &mut self,
+        embedded_bits: u64,
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Result<Vec<u8>, MayaError> {
+        if let Some(palette) = &self.embedding_palette() {
+            return Ok(extract_palette_data(&self.palette_indices(), self.pixel_map.width as usize, self.pixel_map.height as usize, palette, embedded_bits, encoding_method, file_encoding_function_derivation));
+        }
+
+        if let Some(masks) = self.bitfields {
+            return match (masks.channels().len(), masks.sample_bits()) {
+                (3, 4) => self.extract_bitfield_pixels::<BitfieldPixel<3, 4>>(masks, embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+                (3, 5) => self.extract_bitfield_pixels::<BitfieldPixel<3, 5>>(masks, embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+                (3, _) => self.extract_bitfield_pixels::<BitfieldPixel<3, 8>>(masks, embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+                (_, 4) => self.extract_bitfield_pixels::<BitfieldPixel<4, 4>>(masks, embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+                (_, 5) => self.extract_bitfield_pixels::<BitfieldPixel<4, 5>>(masks, embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+                _ => self.extract_bitfield_pixels::<BitfieldPixel<4, 8>>(masks, embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+            };
+        }
+
+        let width = self.pixel_map.width as u64;
+        let height = self.pixel_map.height as u64;
+        let mut pixels = self.visual_rows();
+
+        if self.pixel_size == 3 {
+            extract_data_with_method::<RgbPixel>(&mut pixels, width, height, 0, 3, embedded_bits, encoding, encoding_method, file_encoding_function_derivation, &self.encoding_parameters)
+        } else {
+            extract_data_with_method::<RgbaPixel>(&mut pixels, width, height, 0, 4, embedded_bits, encoding, encoding_method, file_encoding_function_derivation, &self.encoding_parameters)
+        }
+    }
 }
 
 impl FileEncodingSupport for BmpImageParser {
@@ -274,48 +984,57 @@ impl FileEncodingSupport for BmpImageParser {
                 num_embedded_bits: None,
                 width: 0,
                 height: 0,
+                top_down: false,
                 pixel_map_start: 0,
             },
-            image_file: match File::open(filename) {
-                Ok(file) => file,
-                Err(e) => {
-                    println!("bmp.rs: new : failed to open file {}: {}", filename, e);
-                    exit(1);
-                }
-            },
-            file_data: Box::new(vec![]),
+            filename: filename.to_string(),
+            palette: None,
+            bitfields: None,
+            encoding_parameters: EncodingParameters::default(),
+            rle_output: RleOutput::Recompress,
+            rle_pixels: None,
+            rle_stream_size: 0,
+            file_data: Vec::new(),
             ready: false,
         }
     }
 
-    fn parse_file(&mut self) {
-        let header_size = std::mem::size_of::<BitmapFileHeader>();
-        let dib_header_size = std::mem::size_of::<BitmapDIBHeader>();
+    fn file_type(&self) -> FileType {
+        FileType::Bmp
+    }
+
+    fn set_encoding_parameters(&mut self, parameters: EncodingParameters) {
+        self.encoding_parameters = parameters;
+    }
 
-        let file: &mut File = &mut self.image_file;
+    fn set_rle_output(&mut self, rle_output: RleOutput) {
+        self.rle_output = rle_output;
+    }
 
-        let file_size: u32 = match file.metadata() {
-            Ok(metadata) => metadata.len() as u32,
-            Err(_) => {
-                println!("bmp.rs : parse_file: Error reading file metadata! Exiting...",);
-                exit(1);
-            }
-        };
+    fn rle_expansion(&self) -> Option<(usize, usize)> {
+        match (&self.rle_pixels, self.rle_output) {
+            (Some(pixels), RleOutput::Uncompressed) => Some((self.rle_stream_size, pixels.len())),
+            _ => None,
+        }
+    }
 
-        self.file_data = Box::new(Vec::<u8>::with_capacity(file_size as usize));
+    fn parse_file(&mut self) -> Result<(), MayaError> {
+        let file_data = std::fs::read(&self.filename)?;
+        self.parse_bytes(file_data)
+    }
 
-        match file.read_to_end(&mut self.file_data) {
-            Ok(_) => (),
-            Err(e) => {
-                println!(
-                    "bmp.rs: parse_file: Error reading file to end with err {}",
-                    e
-                );
-                exit(1);
-            }
+    fn parse_bytes(&mut self, file_data: Vec<u8>) -> Result<(), MayaError> {
+        let header_size = mem::size_of::<BitmapFileHeader>();
+        let dib_header_size = mem::size_of::<BitmapDIBHeader>();
+
+        if file_data.len() < header_size + 4 {
+            return Err(MayaError::CorruptHeader(format!(
+                "BMP file of {} bytes is too short to hold its headers",
+                file_data.len()
+            )));
         }
 
-        let header_size = std::mem::size_of::<BitmapFileHeader>();
+        self.file_data = file_data;
 
         unsafe {
             let header_pointer: *mut BitmapFileHeader = &mut self.bmp_header;
@@ -325,124 +1044,238 @@ impl FileEncodingSupport for BmpImageParser {
                 header_size,
             );
         }
-        let dib_header_size = mem::size_of::<BitmapDIBHeader>();
 
-        unsafe {
-            let dib_header_pointer: *mut BitmapDIBHeader = &mut self.bmp_dib_header;
-            std::ptr::copy(
-                &mut self.file_data[14] as *mut u8,
-                dib_header_pointer as *mut u8,
-                dib_header_size,
-            );
+        if { self.bmp_header.bf_type } != BMP_MAGIC {
+            return Err(MayaError::UnsupportedFormat("no BM signature, not a BMP file".to_string()));
         }
 
-        self.pixel_map.width = self.bmp_dib_header.bi_width as u32;
-        self.pixel_map.height = self.bmp_dib_header.bi_height as u32;
+        let bi_size = u32::from_le_bytes([self.file_data[14], self.file_data[15], self.file_data[16], self.file_data[17]]);
+        if !matches!(
+            bi_size,
+            BITMAPCOREHEADER_SIZE | BITMAPINFOHEADER_SIZE | BITMAPV2INFOHEADER_SIZE | BITMAPV3INFOHEADER_SIZE | BITMAPV4HEADER_SIZE | BITMAPV5HEADER_SIZE
+        ) {
+            return Err(MayaError::UnsupportedFormat(format!("BMP DIB header of {bi_size} bytes")));
+        }
+
+        if self.file_data.len() < header_size + bi_size as usize {
+            return Err(MayaError::CorruptHeader(format!(
+                "BMP file of {} bytes is too short to hold its headers",
+                self.file_data.len()
+            )));
+        }
 
-        if self.bmp_dib_header.bi_size != 40 {
+        if bi_size == BITMAPCOREHEADER_SIZE {
             /*
-               Bit extreme, we'll keep it for now and see.
-               If V5 headers are common I'll remove this
-            */
-            println!("bmp.rs: parse_file: File Size Mismatch, exiting ...");
-            exit(1);
+                Unsigned 16 bit width and height and no compression, spread it out into the info header fields
+             */
+            let field = |offset: usize| u16::from_le_bytes([self.file_data[offset], self.file_data[offset + 1]]);
+            self.bmp_dib_header = BitmapDIBHeader {
+                bi_size,
+                bi_width: field(18) as i32,
+                bi_height: field(20) as i32,
+                bi_planes: field(22),
+                bi_bit_count: field(24),
+                bi_compression: BI_RGB,
+                bi_size_image: 0,
+                bi_x_pels_per_meter: 0,
+                bi_y_pels_per_meter: 0,
+                bi_clr_used: 0,
+                bi_clr_important: 0,
+            };
+        } else {
+            unsafe {
+                let dib_header_pointer: *mut BitmapDIBHeader = &mut self.bmp_dib_header;
+                std::ptr::copy(
+                    &mut self.file_data[14] as *mut u8,
+                    dib_header_pointer as *mut u8,
+                    dib_header_size,
+                );
+            }
         }
 
-        self.padding_size = ((self.pixel_map.width as u64 * self.pixel_size as u64) % 4) as u8;
+        if self.bmp_dib_header.bi_width < 0 {
+            return Err(MayaError::CorruptHeader(format!("BMP width of {}", { self.bmp_dib_header.bi_width })));
+        }
+
+        /*
+            A negative height means the rows are stored top down, the core header can only be bottom up
+         */
+        self.pixel_map.width = self.bmp_dib_header.bi_width as u32;
+        self.pixel_map.height = self.bmp_dib_header.bi_height.unsigned_abs();
+        self.pixel_map.top_down = self.bmp_dib_header.bi_height < 0;
+
+        if !matches!(self.bmp_dib_header.bi_bit_count, 1 | 4 | 8 | 16 | 24 | 32) {
+            return Err(MayaError::UnsupportedFormat(format!(
+                "{} bit BMP, only 1, 4, 8, 16, 24 and 32 bit images are supported",
+                { self.bmp_dib_header.bi_bit_count }
+            )));
+        }
+
+        self.bitfields = match (self.bmp_dib_header.bi_compression, self.bmp_dib_header.bi_bit_count) {
+            (BI_RGB, 16) => Some(BitfieldMasks::RGB555),
+            (BI_RGB, _) => None,
+            (BI_BITFIELDS | BI_ALPHABITFIELDS, 16 | 32) => Some(self.read_bitfield_masks()?),
+            (BI_RLE8, 8) | (BI_RLE4, 4) => None,
+            (compression, bit_count) => {
+                return Err(MayaError::UnsupportedFormat(format!(
+                    "BMP compression {compression} on a {bit_count} bit image"
+                )));
+            }
+        };
+
+        if let Some(masks) = self.bitfields {
+            masks.validate(self.bmp_dib_header.bi_bit_count)?;
+        }
+
+        // 0 for 1 and 4 bit images, those only ever get looked at through palette_indices
         self.pixel_size = (self.bmp_dib_header.bi_bit_count / 8) as u8;
 
-        self.padding_size = ((self.pixel_map.width * self.pixel_size as u32) % 4) as u8;
+        // Rows are padded out to a multiple of 4 bytes
+        self.padding_size = ((4 - self.row_bytes() % 4) % 4) as u8;
 
         self.pixel_map.pixel_map_start = self.bmp_header.bf_off_bits as u64;
+        self.rle_pixels = None;
+
+        if matches!(self.bmp_dib_header.bi_compression, BI_RLE8 | BI_RLE4) {
+            self.decode_rle_pixels()?;
+        }
+
+        let pixel_map_end = (self.row_stride() as u64)
+            .checked_mul(self.pixel_map.height as u64)
+            .and_then(|size| size.checked_add(self.pixel_map.pixel_map_start));
+        if self.rle_pixels.is_none() && pixel_map_end.is_none_or(|end| end > self.file_data.len() as u64) {
+            return Err(MayaError::CorruptHeader(format!(
+                "BMP pixel map of {}x{} runs past the end of the file",
+                self.pixel_map.width, self.pixel_map.height
+            )));
+        }
+
+        if self.bmp_dib_header.bi_bit_count <= 8 {
+            self.palette = Some(self.read_color_table()?);
+        }
 
         self.ready = true;
+        Ok(())
     }
 
-    fn embed_data(
+    fn carrier_capacity_bits(&mut self, encoding: FileEncoding) -> Result<u64, MayaError> {
+        if !self.ready {
+            return Err(MayaError::NotParsed);
+        }
+
+        // Indexed images only have the palette embedding, see palette.rs
+        if encoding.works_on_coefficients() || (self.palette.is_some() && encoding != FileEncoding::Lsb) {
+            return Err(MayaError::UnsupportedEncoding { encoding, file_type: FileType::Bmp });
+        }
+
+        if let Some(palette) = &self.embedding_palette() {
+            return Ok(palette_capacity_bits(&self.palette_indices(), palette));
+        }
+
+        if let Some(masks) = self.bitfields {
+            return match (masks.channels().len(), masks.sample_bits()) {
+                (3, 4) => self.bitfield_capacity_bits::<BitfieldPixel<3, 4>>(masks, encoding),
+                (3, 5) => self.bitfield_capacity_bits::<BitfieldPixel<3, 5>>(masks, encoding),
+                (3, _) => self.bitfield_capacity_bits::<BitfieldPixel<3, 8>>(masks, encoding),
+                (_, 4) => self.bitfield_capacity_bits::<BitfieldPixel<4, 4>>(masks, encoding),
+                (_, 5) => self.bitfield_capacity_bits::<BitfieldPixel<4, 5>>(masks, encoding),
+                _ => self.bitfield_capacity_bits::<BitfieldPixel<4, 8>>(masks, encoding),
+            };
+        }
+
+        let width = self.pixel_map.width as u64;
+        let height = self.pixel_map.height as u64;
+        let mut pixels = self.visual_rows();
+
+        if self.pixel_size == 3 {
+            pixel_map_capacity_bits::<RgbPixel>(&mut pixels, width, height, 0, 3, encoding, &self.encoding_parameters)
+        } else {
+            pixel_map_capacity_bits::<RgbaPixel>(&mut pixels, width, height, 0, 4, encoding, &self.encoding_parameters)
+        }
+    }
+
+    fn embed_data_with_flags(
         &mut self,
         data: &mut Vec<u8>,
         encoding: FileEncoding,
         encoding_method: FileEncodingMethod,
         file_encoding_function_derivation: FileEncodingFunctionDerivation,
-    ) {
-        if !self.ready {
-            println!("bmp.rs: embed_data called with File Not Ready");
-            exit(1);
-        }
-        match encoding_method {
-            FileEncodingMethod::LeftToRight => match encoding {
-                FileEncoding::Lsb => {
-                    if(self.pixel_size == 3){
-                        embed_lsb_data_left_right::<RgbPixel>(&data, &mut self.file_data[self.pixel_map.pixel_map_start as usize..], self.pixel_map.width as u64, self.pixel_map.height as u64, self.padding_size as u64, 3)
-                    }else {
-                        embed_lsb_data_left_right::<RgbaPixel>(&data, &mut self.file_data[self.pixel_map.pixel_map_start as usize..], self.pixel_map.width as u64, self.pixel_map.height as u64, self.padding_size as u64, 4)
-                    }
-                }
-
-                _ => todo!(),
-            },
+        flags: u8,
+    ) -> Result<(), MayaError> {
+        let capacity_bits = self.carrier_capacity_bits(encoding)?;
+        let flags = match encoding {
+            FileEncoding::HammingMatrix => hamming_payload_flags(flags, self.encoding_parameters.hamming_k, data.len(), capacity_bits)?,
+            _ => flags,
+        };
+        let payload = build_payload(data, encoding, encoding_method, flags);
 
-            _ => todo!(),
+        if payload.len() as u64 * 8 > capacity_bits {
+            return Err(MayaError::CapacityExceeded {
+                needed_bits: payload.len() as u64 * 8,
+                capacity_bits,
+            });
         }
 
-        self.bmp_header.bf_reserved1 = data.len() as u16;
+        self.embed_bits(&payload, encoding, encoding_method, file_encoding_function_derivation)
     }
 
-    fn retrieve_data(
+    fn retrieve_data_with_flags(
         &mut self,
-        data: Vec<u8>,
         encoding: FileEncoding,
         encoding_method: FileEncodingMethod,
         file_encoding_function_derivation: FileEncodingFunctionDerivation,
-    ) {
+    ) -> Result<(Vec<u8>, u8), MayaError> {
+        let capacity_bits = self.carrier_capacity_bits(encoding)?;
+
+        let (data, flags) = read_payload(encoding, encoding_method, capacity_bits, |bits| {
+            self.extract_bits(bits, encoding, encoding_method, file_encoding_function_derivation)
+        })?;
+
+        self.pixel_map.num_embedded_bits = Some(data.len() * 8);
+        Ok((data, flags))
+    }
+
+    fn pixel_channels(&mut self) -> Result<PixelChannels, MayaError> {
         if !self.ready {
-            println!("bmp.rs: retrieve_data called with File Not Ready");
-            exit(1);
+            return Err(MayaError::NotParsed);
         }
 
-        self.pixel_map.num_embedded_bits = Some(self.bmp_header.bf_reserved1 as usize * 8);
-        match encoding_method {
-            FileEncodingMethod::LeftToRight => match encoding {
-                FileEncoding::Lsb => {
-                    if(self.pixel_size == 3){
-                        let data_vec : Vec<u8> = extract_lsb_data_left_right::<RgbPixel>(&mut self.file_data[self.pixel_map.pixel_map_start as usize..], self.pixel_map.width as u64, self.pixel_map.height as u64, self.padding_size as u64, 3, self.pixel_map.num_embedded_bits.unwrap() as u64);
-                    }else {
-                        let data_vec : Vec<u8> = extract_lsb_data_left_right::<RgbaPixel>( &mut self.file_data[self.pixel_map.pixel_map_start as usize..], self.pixel_map.width as u64, self.pixel_map.height as u64, self.padding_size as u64, 4,self.pixel_map.num_embedded_bits.unwrap() as u64);
+        let width = self.pixel_map.width as u64;
+        let height = self.pixel_map.height as u64;
 
-                    }
-                }
+        if let Some(palette) = &self.palette {
+            return Ok(PixelChannels::from_palette_indices(&self.palette_indices(), palette, width as usize, height as usize));
+        }
 
-                _ => todo!(),
-            },
+        if let Some(masks) = self.bitfields {
+            return Ok(match (masks.channels().len(), masks.sample_bits()) {
+                (3, 4) => self.bitfield_channels::<BitfieldPixel<3, 4>>(masks),
+                (3, 5) => self.bitfield_channels::<BitfieldPixel<3, 5>>(masks),
+                (3, _) => self.bitfield_channels::<BitfieldPixel<3, 8>>(masks),
+                (_, 4) => self.bitfield_channels::<BitfieldPixel<4, 4>>(masks),
+                (_, 5) => self.bitfield_channels::<BitfieldPixel<4, 5>>(masks),
+                _ => self.bitfield_channels::<BitfieldPixel<4, 8>>(masks),
+            });
+        }
+
+        let mut pixels = self.visual_rows();
 
-            _ => todo!(),
+        if self.pixel_size == 3 {
+            Ok(PixelChannels::from_pixel_map::<RgbPixel>(&mut pixels, width, height, 0, 3))
+        } else {
+            Ok(PixelChannels::from_pixel_map::<RgbaPixel>(&mut pixels, width, height, 0, 4))
         }
     }
 
-    fn write_file(&mut self, file_location: &str) {
+    fn to_bytes(&mut self) -> Result<Vec<u8>, MayaError> {
         if !self.ready {
-            println!("bmp.rs: write_file called with File Not Ready");
-            exit(1);
+            return Err(MayaError::NotParsed);
         }
 
-        match File::create(file_location.to_string()) {
-            Ok(_) => {}
-            Err(e) => {
-                println!(
-                    "bmp.rs: write_file Error opening file {} {}",
-                    file_location, e
-                );
-                exit(1);
-            }
+        if self.rle_pixels.is_some() {
+            return Ok(self.rle_bytes());
         }
-        let mut file = File::create(file_location.to_string()).expect("bmp.rs: write_file:  Error opening file");
 
-        match file.write_all(self.file_data.as_slice()) {
-            Ok(_) => {}
-            Err(e) => {
-                println!("bmp.rs: write_file called with File Not Ready {e}");
-                exit(1);
-            }
-        }
+        Ok(self.file_data.to_vec())
     }
 }
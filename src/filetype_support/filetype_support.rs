@@ -26,5 +26,20 @@ pub enum FileType{
     Mp4
 }
 
-
-
+impl FileType {
+    /*
+        Goes by the magic bytes at the start of the file, the extension can't be trusted for buffers that came
+        off the network
+     */
+    pub fn detect(data: &[u8]) -> Option<FileType> {
+        if data.starts_with(b"BM") {
+            Some(FileType::Bmp)
+        } else if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+            Some(FileType::Png)
+        } else if data.starts_with(&[0xFF, 0xD8]) {
+            Some(FileType::Jpeg)
+        } else {
+            None
+        }
+    }
+}
@@ -15,4 +15,1094 @@
  * along with this program; if not, write to the Free Software
  * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
  */
+use crate::file_encoding_support::file_encoding_support::{
+    FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod, FileEncodingSupport,
+};
+use crate::file_encoding_support::coefficient::{
+    embed_f5_data, embed_jsteg_data, extract_f5_data, extract_jsteg_data, f5_capacity_bits, f5_max_bits,
+    jsteg_capacity_bits,
+};
+use crate::file_encoding_support::payload::{build_payload, read_payload};
+use crate::error::error::MayaError;
+use crate::filetype_support::filetype_support::FileType;
+use crate::mathematics_support::huffman::HuffmanTable;
+use std::io;
 
+/*
+    JPEG carriers are never decoded to pixels. Every block is only Huffman decoded as far as its quantized DCT
+    coefficients, embedding happens on those and the exact same entropy coding is applied on the way back out so
+    an untouched file is written back byte for byte and a touched one loses nothing beyond the coefficients we
+    changed. Only sequential Huffman coded frames (SOF0 / SOF1 at 8 bits) are supported.
+ */
+
+// Second byte of every marker, the first is always 0xFF
+pub const TEM: u8 = 0x01;
+pub const SOF0: u8 = 0xC0;
+pub const SOF1: u8 = 0xC1;
+pub const SOF2: u8 = 0xC2;
+pub const DHT: u8 = 0xC4;
+pub const JPG: u8 = 0xC8;
+pub const DAC: u8 = 0xCC;
+pub const SOF15: u8 = 0xCF;
+pub const RST0: u8 = 0xD0;
+pub const RST7: u8 = 0xD7;
+pub const SOI: u8 = 0xD8;
+pub const EOI: u8 = 0xD9;
+pub const SOS: u8 = 0xDA;
+pub const DQT: u8 = 0xDB;
+pub const DNL: u8 = 0xDC;
+pub const DRI: u8 = 0xDD;
+pub const APP0: u8 = 0xE0;
+pub const APP15: u8 = 0xEF;
+pub const COM: u8 = 0xFE;
+
+/*
+    ZIGZAG[k] is the natural (row major) position of the k'th coefficient in coding order
+ */
+pub const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14,
+    21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59, 52, 45, 38, 31, 39, 46, 53, 60,
+    61, 54, 47, 55, 62, 63,
+];
+
+/*
+    Quantized coefficients of one 8x8 block in zigzag (coding) order, index 0 is the DC coefficient
+ */
+pub type CoefficientBlock = [i16; 64];
+
+#[derive(Debug, Clone, Copy)]
+pub struct QuantizationTable {
+    pub precision: u8, // 0 for 8 bit entries, 1 for 16 bit
+    pub values: [u16; 64], // Zigzag order, same as the coefficients they divide
+}
+
+impl HuffmanTable {
+    fn decode(&self, reader: &mut BitReader) -> io::Result<u8> {
+        let mut code: i32 = 0;
+
+        for length in 1..=16 {
+            code = (code << 1) | reader.read_bit() as i32;
+            if let Some(symbol) = self.lookup(code, length) {
+                return Ok(symbol);
+            }
+        }
+
+        Err(invalid_data("Invalid Huffman code"))
+    }
+
+    fn encode(&self, writer: &mut BitWriter, symbol: u8) -> io::Result<()> {
+        let (code, length) = self
+            .code(symbol)
+            .ok_or_else(|| invalid_data("Huffman table has no code for symbol"))?;
+        writer.write_bits(code as u32, length as u32);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JpegComponent {
+    pub id: u8,
+    pub h_sampling: u8,
+    pub v_sampling: u8,
+    pub quant_table: u8,
+    /*
+        Blocks are stored padded out to whole MCUs, a scan with only this component in it may code fewer of them
+     */
+    pub blocks_wide: usize,
+    pub blocks_high: usize,
+    pub coefficients: Vec<CoefficientBlock>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JpegFrame {
+    pub marker: u8,
+    pub precision: u8,
+    pub height: u16,
+    pub width: u16,
+    pub components: Vec<JpegComponent>,
+    pub max_h_sampling: u8,
+    pub max_v_sampling: u8,
+}
+
+impl JpegFrame {
+    fn mcus_wide(&self) -> usize {
+        (self.width as usize).div_ceil(8 * self.max_h_sampling as usize)
+    }
+
+    fn mcus_high(&self) -> usize {
+        (self.height as usize).div_ceil(8 * self.max_v_sampling as usize)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanComponent {
+    pub component: usize, // Index into JpegFrame::components
+    pub dc_table: HuffmanTable,
+    pub ac_table: HuffmanTable,
+}
+
+#[derive(Debug, Clone)]
+pub struct JpegScan {
+    pub header: Vec<u8>, // The SOS segment body as read, written back untouched
+    pub components: Vec<ScanComponent>,
+    pub restart_interval: u16, // DRI in effect when the scan started, 0 for none
+}
+
+/*
+    Everything between SOI and EOI in file order. Scans are kept out of line since their entropy coded data is
+    regenerated from the coefficients when writing
+ */
+#[derive(Debug, Clone)]
+pub enum JpegSegment {
+    Marker { marker: u8, data: Vec<u8> },
+    Scan(usize),
+}
+
+/*
+    Most 8x8 coefficient blocks a frame can have across all of its components, at 128 bytes a block that's 1 GiB
+ */
+const MAX_COEFFICIENT_BLOCKS: usize = 1 << 23;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/*
+    Reads the entropy coded data of a scan. 0xFF bytes are stuffed with a 0x00 which is skipped, any other byte
+    after a 0xFF is a marker which ends the data. Running into a marker early just yields zero bits like libjpeg does.
+ */
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    bits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8], position: usize) -> BitReader<'a> {
+        BitReader {
+            data,
+            position,
+            buffer: 0,
+            bits: 0,
+        }
+    }
+
+    fn at_marker(&self) -> bool {
+        self.position + 1 < self.data.len() && self.data[self.position] == 0xFF && self.data[self.position + 1] != 0
+    }
+
+    fn read_bit(&mut self) -> u32 {
+        if self.bits == 0 {
+            self.buffer = if self.position >= self.data.len() || self.at_marker() {
+                0
+            } else if self.data[self.position] == 0xFF {
+                self.position += 2;
+                0xFF
+            } else {
+                self.position += 1;
+                self.data[self.position - 1] as u32
+            };
+            self.bits = 8;
+        }
+
+        self.bits -= 1;
+        (self.buffer >> self.bits) & 1
+    }
+
+    fn receive(&mut self, count: u32) -> i32 {
+        let mut value: i32 = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.read_bit() as i32;
+        }
+        value
+    }
+
+    /*
+        Reads an additional bits field of the given size category and sign extends it, section F.2.2.1
+     */
+    fn receive_extend(&mut self, size: u32) -> i32 {
+        if size == 0 {
+            return 0;
+        }
+        let value = self.receive(size);
+        if value < 1 << (size - 1) {
+            value - (1 << size) + 1
+        } else {
+            value
+        }
+    }
+
+    fn read_restart(&mut self, expected: u8) -> io::Result<()> {
+        self.bits = 0;
+
+        if self.position + 1 >= self.data.len()
+            || self.data[self.position] != 0xFF
+            || self.data[self.position + 1] != RST0 + expected
+        {
+            return Err(invalid_data("Missing restart marker"));
+        }
+
+        self.position += 2;
+        Ok(())
+    }
+}
+
+struct BitWriter {
+    output: Vec<u8>,
+    accumulator: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            output: Vec::new(),
+            accumulator: 0,
+            bits: 0,
+        }
+    }
+
+    fn write_bits(&mut self, value: u32, count: u32) {
+        for bit in (0..count).rev() {
+            self.accumulator = (self.accumulator << 1) | ((value >> bit) & 1);
+            self.bits += 1;
+
+            if self.bits == 8 {
+                self.output.push(self.accumulator as u8);
+                if self.accumulator == 0xFF {
+                    self.output.push(0);
+                }
+                self.accumulator = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    /*
+        The last byte before a marker is padded out with one bits
+     */
+    fn flush(&mut self) {
+        while self.bits != 0 {
+            self.write_bits(1, 1);
+        }
+    }
+}
+
+/*
+    Number of bits needed for the magnitude of a value, the SSSS category of section F.1.2.1
+ */
+fn magnitude_category(value: i32) -> u32 {
+    32 - value.unsigned_abs().leading_zeros()
+}
+
+/*
+    Additional bits that go with a value of the given category, negative values are sent as value - 1
+ */
+fn magnitude_bits(value: i32, category: u32) -> u32 {
+    if value < 0 {
+        (value - 1) as u32 & ((1 << category) - 1)
+    } else {
+        value as u32
+    }
+}
+
+fn decode_block(
+    reader: &mut BitReader,
+    block: &mut CoefficientBlock,
+    predictor: &mut i32,
+    dc_table: &HuffmanTable,
+    ac_table: &HuffmanTable,
+) -> io::Result<()> {
+    let category = dc_table.decode(reader)? as u32;
+    if category > 11 {
+        return Err(invalid_data("DC difference out of range"));
+    }
+    *predictor += reader.receive_extend(category);
+    block[0] = *predictor as i16;
+
+    let mut k = 1;
+    while k < 64 {
+        let symbol = ac_table.decode(reader)?;
+        let run = (symbol >> 4) as usize;
+        let size = (symbol & 15) as u32;
+
+        if size == 0 {
+            if run != 15 {
+                break; // End of block, everything left is zero
+            }
+            k += 16;
+            continue;
+        }
+
+        k += run;
+        if k > 63 {
+            return Err(invalid_data("AC coefficient index out of range"));
+        }
+        block[k] = reader.receive_extend(size) as i16;
+        k += 1;
+    }
+
+    Ok(())
+}
+
+fn encode_block(
+    writer: &mut BitWriter,
+    block: &CoefficientBlock,
+    predictor: &mut i32,
+    dc_table: &HuffmanTable,
+    ac_table: &HuffmanTable,
+) -> io::Result<()> {
+    let difference = block[0] as i32 - *predictor;
+    *predictor = block[0] as i32;
+
+    let category = magnitude_category(difference);
+    dc_table.encode(writer, category as u8)?;
+    writer.write_bits(magnitude_bits(difference, category), category);
+
+    let mut run = 0;
+    for coefficient in block.iter().skip(1) {
+        let value = *coefficient as i32;
+        if value == 0 {
+            run += 1;
+            continue;
+        }
+
+        while run > 15 {
+            ac_table.encode(writer, 0xF0)?;
+            run -= 16;
+        }
+
+        let category = magnitude_category(value);
+        ac_table.encode(writer, ((run << 4) | category) as u8)?;
+        writer.write_bits(magnitude_bits(value, category), category);
+        run = 0;
+    }
+
+    if run > 0 {
+        ac_table.encode(writer, 0x00)?;
+    }
+
+    Ok(())
+}
+
+/*
+    Tally the symbols encode_block would send for a block, used to build new Huffman tables
+ */
+fn count_block_symbols(
+    block: &CoefficientBlock,
+    predictor: &mut i32,
+    dc_frequencies: &mut [u32; 256],
+    ac_frequencies: &mut [u32; 256],
+) {
+    let difference = block[0] as i32 - *predictor;
+    *predictor = block[0] as i32;
+    dc_frequencies[magnitude_category(difference) as usize] += 1;
+
+    let mut run = 0;
+    for coefficient in block.iter().skip(1) {
+        let value = *coefficient as i32;
+        if value == 0 {
+            run += 1;
+            continue;
+        }
+
+        while run > 15 {
+            ac_frequencies[0xF0] += 1;
+            run -= 16;
+        }
+
+        ac_frequencies[((run << 4) | magnitude_category(value)) as usize] += 1;
+        run = 0;
+    }
+
+    if run > 0 {
+        ac_frequencies[0x00] += 1;
+    }
+}
+
+pub struct JpegImageParser {
+    pub frame: JpegFrame,
+    pub quantization_tables: [Option<QuantizationTable>; 4],
+    pub segments: Vec<JpegSegment>,
+    pub scans: Vec<JpegScan>,
+    pub trailer: Vec<u8>, // Anything after EOI, some cameras put a second image or padding there
+    pub filename: String,
+    pub file_data: Vec<u8>,
+    ready: bool,
+}
+
+impl JpegImageParser {
+    /*
+        The blocks making up each MCU of a scan in coding order as (scan component, block index) pairs. A scan of
+        several components interleaves h x v blocks of each one per MCU, a scan of one component is just its blocks
+        left to right top to bottom cropped to the component's own size.
+     */
+    fn scan_units(&self, scan: &JpegScan) -> Vec<Vec<(usize, usize)>> {
+        let frame = &self.frame;
+        let mut units = Vec::new();
+
+        if scan.components.len() == 1 {
+            let component = &frame.components[scan.components[0].component];
+            let width = (frame.width as usize * component.h_sampling as usize).div_ceil(frame.max_h_sampling as usize);
+            let height = (frame.height as usize * component.v_sampling as usize).div_ceil(frame.max_v_sampling as usize);
+
+            for block_y in 0..height.div_ceil(8) {
+                for block_x in 0..width.div_ceil(8) {
+                    units.push(vec![(0, block_y * component.blocks_wide + block_x)]);
+                }
+            }
+            return units;
+        }
+
+        for mcu_y in 0..frame.mcus_high() {
+            for mcu_x in 0..frame.mcus_wide() {
+                let mut unit = Vec::new();
+                for (index, scan_component) in scan.components.iter().enumerate() {
+                    let component = &frame.components[scan_component.component];
+                    let h = component.h_sampling as usize;
+                    let v = component.v_sampling as usize;
+
+                    for block_v in 0..v {
+                        for block_h in 0..h {
+                            let block_y = mcu_y * v + block_v;
+                            let block_x = mcu_x * h + block_h;
+                            unit.push((index, block_y * component.blocks_wide + block_x));
+                        }
+                    }
+                }
+                units.push(unit);
+            }
+        }
+
+        units
+    }
+
+    /*
+        Decodes the scan starting at position into the frame's coefficient blocks, returns where the scan ended
+     */
+    fn decode_scan(&mut self, scan_index: usize, position: usize) -> io::Result<usize> {
+        let scan = &self.scans[scan_index];
+        let units = self.scan_units(scan);
+        let mut reader = BitReader::new(&self.file_data, position);
+        let mut predictors = vec![0i32; scan.components.len()];
+        let mut next_restart: u8 = 0;
+
+        for (mcu, unit) in units.iter().enumerate() {
+            if scan.restart_interval != 0 && mcu != 0 && mcu % scan.restart_interval as usize == 0 {
+                reader.read_restart(next_restart)?;
+                next_restart = (next_restart + 1) % 8;
+                predictors.iter_mut().for_each(|predictor| *predictor = 0);
+            }
+
+            for (scan_component, block) in unit {
+                let tables = &scan.components[*scan_component];
+                let component = &mut self.frame.components[tables.component];
+                decode_block(
+                    &mut reader,
+                    &mut component.coefficients[*block],
+                    &mut predictors[*scan_component],
+                    &tables.dc_table,
+                    &tables.ac_table,
+                )?;
+            }
+        }
+
+        /*
+            Skip anything left before the next marker, that is the padding of the last byte and any fill bytes
+         */
+        let mut end = reader.position;
+        while end + 1 < self.file_data.len()
+            && !(self.file_data[end] == 0xFF && self.file_data[end + 1] != 0 && !(RST0..=RST7).contains(&self.file_data[end + 1]))
+        {
+            end += 1;
+        }
+
+        Ok(end)
+    }
+
+    fn encode_scan(&self, scan: &JpegScan) -> io::Result<Vec<u8>> {
+        let mut writer = BitWriter::new();
+        let mut predictors = vec![0i32; scan.components.len()];
+        let mut next_restart: u8 = 0;
+
+        for (mcu, unit) in self.scan_units(scan).iter().enumerate() {
+            if scan.restart_interval != 0 && mcu != 0 && mcu % scan.restart_interval as usize == 0 {
+                writer.flush();
+                writer.output.extend_from_slice(&[0xFF, RST0 + next_restart]);
+                next_restart = (next_restart + 1) % 8;
+                predictors.iter_mut().for_each(|predictor| *predictor = 0);
+            }
+
+            for (scan_component, block) in unit {
+                let tables = &scan.components[*scan_component];
+                encode_block(
+                    &mut writer,
+                    &self.frame.components[tables.component].coefficients[*block],
+                    &mut predictors[*scan_component],
+                    &tables.dc_table,
+                    &tables.ac_table,
+                )?;
+            }
+        }
+
+        writer.flush();
+        Ok(writer.output)
+    }
+
+    fn parse_frame(&mut self, marker: u8, data: &[u8]) -> io::Result<()> {
+        if data.len() < 6 {
+            return Err(invalid_data("Truncated frame header"));
+        }
+
+        let count = data[5] as usize;
+        if count == 0 || count > 4 || data.len() != 6 + count * 3 {
+            return Err(invalid_data("Bad frame header component count"));
+        }
+
+        let mut frame = JpegFrame {
+            marker,
+            precision: data[0],
+            height: u16::from_be_bytes([data[1], data[2]]),
+            width: u16::from_be_bytes([data[3], data[4]]),
+            components: Vec::with_capacity(count),
+            max_h_sampling: 1,
+            max_v_sampling: 1,
+        };
+
+        if frame.precision != 8 {
+            return Err(invalid_data("Only 8 bit samples are supported"));
+        }
+        if frame.height == 0 || frame.width == 0 {
+            return Err(invalid_data("Frames sized by a DNL marker are not supported"));
+        }
+
+        for entry in data[6..].chunks(3) {
+            let component = JpegComponent {
+                id: entry[0],
+                h_sampling: entry[1] >> 4,
+                v_sampling: entry[1] & 15,
+                quant_table: entry[2],
+                ..Default::default()
+            };
+
+            if !(1..=4).contains(&component.h_sampling) || !(1..=4).contains(&component.v_sampling) || component.quant_table > 3 {
+                return Err(invalid_data("Bad component sampling factors"));
+            }
+
+            frame.max_h_sampling = frame.max_h_sampling.max(component.h_sampling);
+            frame.max_v_sampling = frame.max_v_sampling.max(component.v_sampling);
+            frame.components.push(component);
+        }
+
+        let mcus_wide = frame.mcus_wide();
+        let mcus_high = frame.mcus_high();
+        let mut total_blocks: usize = 0;
+        for component in frame.components.iter_mut() {
+            component.blocks_wide = mcus_wide * component.h_sampling as usize;
+            component.blocks_high = mcus_high * component.v_sampling as usize;
+            total_blocks = component
+                .blocks_wide
+                .checked_mul(component.blocks_high)
+                .and_then(|blocks| total_blocks.checked_add(blocks))
+                .ok_or_else(|| invalid_data("Frame is too large"))?;
+        }
+
+        // The header alone decides how much this is, so check it before any of it is allocated
+        if total_blocks > MAX_COEFFICIENT_BLOCKS {
+            return Err(invalid_data(&format!("Frame of {}x{} is too large to decode", frame.width, frame.height)));
+        }
+
+        for component in frame.components.iter_mut() {
+            component.coefficients = vec![[0i16; 64]; component.blocks_wide * component.blocks_high];
+        }
+
+        self.frame = frame;
+        Ok(())
+    }
+
+    fn parse_quantization_tables(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut position = 0;
+
+        while position < data.len() {
+            let precision = data[position] >> 4;
+            let id = (data[position] & 15) as usize;
+            let size = if precision == 0 { 64 } else { 128 };
+
+            if id > 3 || precision > 1 || position + 1 + size > data.len() {
+                return Err(invalid_data("Bad quantization table"));
+            }
+
+            let mut values = [0u16; 64];
+            for (k, value) in values.iter_mut().enumerate() {
+                *value = if precision == 0 {
+                    data[position + 1 + k] as u16
+                } else {
+                    u16::from_be_bytes([data[position + 1 + k * 2], data[position + 2 + k * 2]])
+                };
+            }
+
+            self.quantization_tables[id] = Some(QuantizationTable { precision, values });
+            position += 1 + size;
+        }
+
+        Ok(())
+    }
+
+    fn parse_scan(
+        &self,
+        data: &[u8],
+        dc_tables: &[Option<HuffmanTable>; 4],
+        ac_tables: &[Option<HuffmanTable>; 4],
+        restart_interval: u16,
+    ) -> io::Result<JpegScan> {
+        if self.frame.components.is_empty() {
+            return Err(invalid_data("Scan before frame header"));
+        }
+
+        let count = *data.first().unwrap_or(&0) as usize;
+        if count == 0 || count > 4 || data.len() != 4 + count * 2 {
+            return Err(invalid_data("Bad scan header component count"));
+        }
+
+        let (start, end, approximation) = (data[1 + count * 2], data[2 + count * 2], data[3 + count * 2]);
+        if start != 0 || end != 63 || approximation != 0 {
+            return Err(invalid_data("Spectral selection / successive approximation is not sequential"));
+        }
+
+        let mut components = Vec::with_capacity(count);
+        let mut blocks_per_mcu = 0;
+
+        for entry in data[1..1 + count * 2].chunks(2) {
+            let component = match self.frame.components.iter().position(|component| component.id == entry[0]) {
+                Some(component) => component,
+                None => return Err(invalid_data("Scan refers to an unknown component")),
+            };
+
+            let dc_table = dc_tables[(entry[1] >> 4) as usize & 3].clone();
+            let ac_table = ac_tables[(entry[1] & 15) as usize & 3].clone();
+
+            match (dc_table, ac_table) {
+                (Some(dc_table), Some(ac_table)) => components.push(ScanComponent {
+                    component,
+                    dc_table,
+                    ac_table,
+                }),
+                _ => return Err(invalid_data("Scan uses an undefined Huffman table")),
+            }
+
+            let component = &self.frame.components[component];
+            blocks_per_mcu += component.h_sampling as usize * component.v_sampling as usize;
+        }
+
+        if count > 1 && blocks_per_mcu > 10 {
+            return Err(invalid_data("Too many blocks per MCU"));
+        }
+
+        Ok(JpegScan {
+            header: data.to_vec(),
+            components,
+            restart_interval,
+        })
+    }
+
+    fn parse_segments(&mut self) -> io::Result<()> {
+        if self.file_data.len() < 4 || self.file_data[0] != 0xFF || self.file_data[1] != SOI {
+            return Err(invalid_data("Missing SOI marker"));
+        }
+
+        let mut dc_tables: [Option<HuffmanTable>; 4] = Default::default();
+        let mut ac_tables: [Option<HuffmanTable>; 4] = Default::default();
+        let mut restart_interval: u16 = 0;
+        let mut position = 2;
+
+        loop {
+            if position >= self.file_data.len() || self.file_data[position] != 0xFF {
+                return Err(invalid_data("Expected a marker"));
+            }
+
+            // Any number of 0xFF fill bytes can come before a marker
+            while position < self.file_data.len() && self.file_data[position] == 0xFF {
+                position += 1;
+            }
+            if position >= self.file_data.len() {
+                return Err(invalid_data("Missing EOI marker"));
+            }
+
+            let marker = self.file_data[position];
+            position += 1;
+
+            match marker {
+                EOI => {
+                    self.trailer = self.file_data[position..].to_vec();
+                    break;
+                }
+                TEM | RST0..=RST7 | SOI => continue,
+                _ => {}
+            }
+
+            if position + 2 > self.file_data.len() {
+                return Err(invalid_data("Truncated segment"));
+            }
+            let length = u16::from_be_bytes([self.file_data[position], self.file_data[position + 1]]) as usize;
+            if length < 2 || position + length > self.file_data.len() {
+                return Err(invalid_data("Segment length out of range"));
+            }
+            let data = self.file_data[position + 2..position + length].to_vec();
+            position += length;
+
+            match marker {
+                SOF0 | SOF1 => {
+                    if !self.frame.components.is_empty() {
+                        return Err(invalid_data("More than one frame header"));
+                    }
+                    self.parse_frame(marker, &data)?;
+                }
+                SOF2..=SOF15 if marker != DHT && marker != JPG && marker != DAC => {
+                    return Err(invalid_data("Only baseline and extended sequential Huffman JPEGs are supported"));
+                }
+                DAC => return Err(invalid_data("Arithmetic coding is not supported")),
+                DNL => return Err(invalid_data("DNL markers are not supported")),
+                DHT => {
+                    let mut offset = 0;
+                    while offset < data.len() {
+                        if offset + 17 > data.len() {
+                            return Err(invalid_data("Truncated Huffman table"));
+                        }
+                        let class = data[offset] >> 4;
+                        let id = (data[offset] & 15) as usize;
+                        let mut counts = [0u8; 16];
+                        counts.copy_from_slice(&data[offset + 1..offset + 17]);
+                        let total: usize = counts.iter().map(|count| *count as usize).sum();
+
+                        if class > 1 || id > 3 || offset + 17 + total > data.len() {
+                            return Err(invalid_data("Bad Huffman table"));
+                        }
+
+                        let table = HuffmanTable::new(counts, data[offset + 17..offset + 17 + total].to_vec())?;
+                        if class == 0 {
+                            dc_tables[id] = Some(table);
+                        } else {
+                            ac_tables[id] = Some(table);
+                        }
+                        offset += 17 + total;
+                    }
+                }
+                DQT => self.parse_quantization_tables(&data)?,
+                DRI => {
+                    if data.len() != 2 {
+                        return Err(invalid_data("Bad restart interval"));
+                    }
+                    restart_interval = u16::from_be_bytes([data[0], data[1]]);
+                }
+                SOS => {
+                    let scan = self.parse_scan(&data, &dc_tables, &ac_tables, restart_interval)?;
+                    self.scans.push(scan);
+                    self.segments.push(JpegSegment::Scan(self.scans.len() - 1));
+                    position = self.decode_scan(self.scans.len() - 1, position)?;
+                    continue;
+                }
+                _ => {}
+            }
+
+            self.segments.push(JpegSegment::Marker { marker, data });
+        }
+
+        if self.scans.is_empty() {
+            return Err(invalid_data("No scans found"));
+        }
+
+        Ok(())
+    }
+
+    /*
+        Which stored blocks are actually coded by some scan, a scan of a single component leaves out the blocks
+        that only exist to pad out the last MCU and anything written there would be lost
+     */
+    fn coded_blocks(&self) -> Vec<Vec<bool>> {
+        let mut coded: Vec<Vec<bool>> = self
+            .frame
+            .components
+            .iter()
+            .map(|component| vec![false; component.coefficients.len()])
+            .collect();
+
+        for scan in self.scans.iter() {
+            for unit in self.scan_units(scan) {
+                for (scan_component, block) in unit {
+                    coded[scan.components[scan_component].component][block] = true;
+                }
+            }
+        }
+
+        coded
+    }
+
+    /*
+        The AC coefficients of every coded block as one flat run, component by component in block order. This is
+        what the coefficient domain encodings work on, DC coefficients are never handed out.
+     */
+    pub fn ac_coefficients(&self) -> Vec<i16> {
+        let coded = self.coded_blocks();
+        let mut coefficients = Vec::new();
+
+        for (component, coded) in self.frame.components.iter().zip(coded.iter()) {
+            for (block, _) in component.coefficients.iter().zip(coded.iter()).filter(|(_, coded)| **coded) {
+                coefficients.extend_from_slice(&block[1..]);
+            }
+        }
+
+        coefficients
+    }
+
+    pub fn store_ac_coefficients(&mut self, coefficients: &[i16]) {
+        let coded = self.coded_blocks();
+        let mut runs = coefficients.chunks(63);
+
+        for (component, coded) in self.frame.components.iter_mut().zip(coded.iter()) {
+            for (block, _) in component.coefficients.iter_mut().zip(coded.iter()).filter(|(_, coded)| **coded) {
+                if let Some(run) = runs.next() {
+                    block[1..].copy_from_slice(run);
+                }
+            }
+        }
+    }
+
+    /*
+        Replace every Huffman table with one built from the symbols the current coefficients need. Embedding can
+        call for a (run, size) pair an optimized table never included, in that case this is the only way to write
+        the file back out. All DHT segments are dropped and a single one goes in before the first scan.
+     */
+    pub fn rebuild_huffman_tables(&mut self) {
+        let mut dc_frequencies = [[0u32; 256]; 4];
+        let mut ac_frequencies = [[0u32; 256]; 4];
+        let mut used = [[false; 4]; 2];
+
+        for scan in self.scans.iter() {
+            let selectors = &scan.header[1..1 + scan.components.len() * 2];
+            let mut predictors = vec![0i32; scan.components.len()];
+
+            for (mcu, unit) in self.scan_units(scan).iter().enumerate() {
+                if scan.restart_interval != 0 && mcu != 0 && mcu % scan.restart_interval as usize == 0 {
+                    predictors.iter_mut().for_each(|predictor| *predictor = 0);
+                }
+
+                for (scan_component, block) in unit {
+                    let dc = (selectors[scan_component * 2 + 1] >> 4) as usize & 3;
+                    let ac = (selectors[scan_component * 2 + 1] & 15) as usize & 3;
+                    used[0][dc] = true;
+                    used[1][ac] = true;
+
+                    count_block_symbols(
+                        &self.frame.components[scan.components[*scan_component].component].coefficients[*block],
+                        &mut predictors[*scan_component],
+                        &mut dc_frequencies[dc],
+                        &mut ac_frequencies[ac],
+                    );
+                }
+            }
+        }
+
+        let dc_tables: Vec<HuffmanTable> = dc_frequencies.iter().map(HuffmanTable::optimal).collect();
+        let ac_tables: Vec<HuffmanTable> = ac_frequencies.iter().map(HuffmanTable::optimal).collect();
+
+        let mut dht = Vec::new();
+        for (class, tables) in [(0u8, &dc_tables), (1u8, &ac_tables)] {
+            for (id, table) in tables.iter().enumerate() {
+                if used[class as usize][id] {
+                    dht.push((class << 4) | id as u8);
+                    dht.extend_from_slice(&table.counts);
+                    dht.extend_from_slice(&table.symbols);
+                }
+            }
+        }
+
+        for scan in self.scans.iter_mut() {
+            let selectors = scan.header[1..1 + scan.components.len() * 2].to_vec();
+            for (index, component) in scan.components.iter_mut().enumerate() {
+                component.dc_table = dc_tables[(selectors[index * 2 + 1] >> 4) as usize & 3].clone();
+                component.ac_table = ac_tables[(selectors[index * 2 + 1] & 15) as usize & 3].clone();
+            }
+        }
+
+        self.segments.retain(|segment| !matches!(segment, JpegSegment::Marker { marker: DHT, .. }));
+        let first_scan = self
+            .segments
+            .iter()
+            .position(|segment| matches!(segment, JpegSegment::Scan(_)))
+            .unwrap_or(self.segments.len());
+        self.segments.insert(first_scan, JpegSegment::Marker { marker: DHT, data: dht });
+    }
+
+    fn embed_bits(
+        &mut self,
+        data: &[u8],
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Result<(), MayaError> {
+        let mut coefficients = self.ac_coefficients();
+
+        match encoding {
+            FileEncoding::JSteg => embed_jsteg_data(data, &mut coefficients, encoding_method, file_encoding_function_derivation)?,
+            FileEncoding::F5 => embed_f5_data(data, &mut coefficients, encoding_method, file_encoding_function_derivation)?,
+            _ => unreachable!(),
+        }
+
+        self.store_ac_coefficients(&coefficients);
+        Ok(())
+    }
+
+    fn extract_bits(
+        &self,
+        embedded_bits: u64,
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Result<Vec<u8>, MayaError> {
+        let coefficients = self.ac_coefficients();
+
+        match encoding {
+            FileEncoding::JSteg => Ok(extract_jsteg_data(&coefficients, embedded_bits, encoding_method, file_encoding_function_derivation)),
+            FileEncoding::F5 => Ok(extract_f5_data(&coefficients, embedded_bits, encoding_method, file_encoding_function_derivation)),
+            _ => unreachable!(),
+        }
+    }
+
+    /*
+        Rebuild the whole file from the segments and coefficients
+     */
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        let mut output: Vec<u8> = Vec::with_capacity(self.file_data.len());
+        output.extend_from_slice(&[0xFF, SOI]);
+
+        for segment in self.segments.iter() {
+            let (marker, data) = match segment {
+                JpegSegment::Marker { marker, data } => (*marker, data),
+                JpegSegment::Scan(index) => (SOS, &self.scans[*index].header),
+            };
+
+            output.extend_from_slice(&[0xFF, marker]);
+            output.extend_from_slice(&((data.len() + 2) as u16).to_be_bytes());
+            output.extend_from_slice(data);
+
+            if let JpegSegment::Scan(index) = segment {
+                output.extend_from_slice(&self.encode_scan(&self.scans[*index])?);
+            }
+        }
+
+        output.extend_from_slice(&[0xFF, EOI]);
+        output.extend_from_slice(&self.trailer);
+        Ok(output)
+    }
+}
+
+impl FileEncodingSupport for JpegImageParser {
+    fn new(filename: &str) -> Self {
+        JpegImageParser {
+            frame: JpegFrame::default(),
+            quantization_tables: [None; 4],
+            segments: Vec::new(),
+            scans: Vec::new(),
+            trailer: Vec::new(),
+            filename: filename.to_string(),
+            file_data: Vec::new(),
+            ready: false,
+        }
+    }
+
+    fn file_type(&self) -> FileType {
+        FileType::Jpeg
+    }
+
+    fn parse_file(&mut self) -> Result<(), MayaError> {
+        let file_data = std::fs::read(&self.filename)?;
+        self.parse_bytes(file_data)
+    }
+
+    fn parse_bytes(&mut self, file_data: Vec<u8>) -> Result<(), MayaError> {
+        self.file_data = file_data;
+
+        if let Err(e) = self.parse_segments() {
+            return Err(MayaError::CorruptHeader(format!("JPEG: {e}")));
+        }
+
+        self.ready = true;
+        Ok(())
+    }
+
+    fn carrier_capacity_bits(&mut self, encoding: FileEncoding) -> Result<u64, MayaError> {
+        if !self.ready {
+            return Err(MayaError::NotParsed);
+        }
+
+        /*
+            Pixel encodings would be wiped out by the next lossy save, only the coefficient ones make sense here
+         */
+        match encoding {
+            FileEncoding::JSteg => Ok(jsteg_capacity_bits(&self.ac_coefficients())),
+            FileEncoding::F5 => Ok(f5_capacity_bits(&self.ac_coefficients())),
+            _ => Err(MayaError::UnsupportedEncoding { encoding, file_type: FileType::Jpeg }),
+        }
+    }
+
+    fn embed_data_with_flags(
+        &mut self,
+        data: &mut Vec<u8>,
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+        flags: u8,
+    ) -> Result<(), MayaError> {
+        let capacity_bits = self.carrier_capacity_bits(encoding)?;
+        let payload = build_payload(data, encoding, encoding_method, flags);
+
+        if payload.len() as u64 * 8 > capacity_bits {
+            return Err(MayaError::CapacityExceeded {
+                needed_bits: payload.len() as u64 * 8,
+                capacity_bits,
+            });
+        }
+
+        self.embed_bits(&payload, encoding, encoding_method, file_encoding_function_derivation)
+    }
+
+    fn retrieve_data_with_flags(
+        &mut self,
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Result<(Vec<u8>, u8), MayaError> {
+        let capacity_bits = self.carrier_capacity_bits(encoding)?;
+
+        /*
+            F5 shrinkage means the embedder's capacity estimate can't be recomputed from the output, so extraction
+            checks the header against every non zero coefficient instead
+         */
+        let capacity_bits = match encoding {
+            FileEncoding::F5 => f5_max_bits(&self.ac_coefficients()),
+            _ => capacity_bits,
+        };
+
+        read_payload(encoding, encoding_method, capacity_bits, |bits| {
+            self.extract_bits(bits, encoding, encoding_method, file_encoding_function_derivation)
+        })
+    }
+
+    fn to_bytes(&mut self) -> Result<Vec<u8>, MayaError> {
+        if !self.ready {
+            return Err(MayaError::NotParsed);
+        }
+
+        /*
+            The original tables may not have codes for everything the embedded coefficients need, fall back to
+            tables built for them
+         */
+        let output = self.encode().or_else(|_| {
+            self.rebuild_huffman_tables();
+            self.encode()
+        })?;
+
+        Ok(output)
+    }
+}
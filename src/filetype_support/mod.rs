@@ -19,5 +19,5 @@ pub mod filetype_support;
 pub mod svg;
 pub mod bmp;
 mod test;
-mod png;
-mod jpg;
\ No newline at end of file
+pub mod png;
+pub mod jpg;
\ No newline at end of file
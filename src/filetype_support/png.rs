@@ -16,19 +16,32 @@
  * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
  */
 
+#![allow(non_upper_case_globals)]
+
 use crate::file_encoding_support::file_encoding_support::{
-    FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod, FileEncodingSupport,
+    EncodingParameters, FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod, FileEncodingSupport,
+};
+use crate::file_encoding_support::palette::{
+    embed_palette_data, extract_palette_data, palette_capacity_bits, Palette, PaletteEmbedding,
+    PaletteEntry,
+};
+use crate::file_encoding_support::payload::{build_payload, read_payload};
+use crate::file_encoding_support::pixel::{
+    embed_data_with_method, extract_data_with_method, hamming_payload_flags, pack_samples, pixel_map_capacity_bits,
+    unpack_samples, Pixel,
 };
-use crate::file_encoding_support::pixel::{embed_lsb_data_left_right, extract_lsb_data_left_right, Pixel};
-use std::fs::File;
-use std::io::{Read, Write};
-use std::mem;
-use std::process::exit;
+use crate::analysis::analysis::PixelChannels;
+use crate::error::error::MayaError;
+use crate::filetype_support::filetype_support::FileType;
+use crate::mathematics_support::mathematics_support::crc32_update;
+use fdeflate::BoundedDecompressionError;
+use std::io;
+use std::io::Read;
 
 const PNG_MAGIC : [u8;8] = [0x89,0x50,0x4E,0x47,0x0D,0x0A,0x1A,0x0A];
 
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct ChunkType(pub [u8; 4]);
 
 // -- Critical chunks --
@@ -76,6 +89,8 @@ pub const zTXt: ChunkType = ChunkType(*b"zTXt");
 pub const iTXt: ChunkType = ChunkType(*b"iTXt");
 // Significant bits
 pub const sBIT: ChunkType = ChunkType(*b"sBIT");
+/// Palette histogram
+pub const hIST: ChunkType = ChunkType(*b"hIST");
 
 // -- Extension chunks --
 
@@ -109,7 +124,7 @@ pub fn safe_to_copy(ChunkType(type_): ChunkType) -> bool {
     type_[3] & 32 != 0
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct IHDRData {
     pub width: u32,
     pub height: u32,
@@ -131,13 +146,93 @@ fn parse_ihdr(data: &[u8]) -> IHDRData {
         interlace_method: data[12],
     }
 }
+fn ihdr_to_bytes(ihdr: &IHDRData) -> Vec<u8> {
+    let mut data = Vec::with_capacity(13);
+    data.extend_from_slice(&ihdr.width.to_be_bytes());
+    data.extend_from_slice(&ihdr.height.to_be_bytes());
+    data.push(ihdr.bit_depth);
+    data.push(ihdr.color_type);
+    data.push(ihdr.compression_method);
+    data.push(ihdr.filter_method);
+    data.push(ihdr.interlace_method);
+    data
+}
+
+/*
+    Color types as defined in the IHDR chunk
+ */
+pub const COLOR_TYPE_GRAYSCALE: u8 = 0;
+pub const COLOR_TYPE_RGB: u8 = 2;
+pub const COLOR_TYPE_PALETTE: u8 = 3;
+pub const COLOR_TYPE_GRAYSCALE_ALPHA: u8 = 4;
+pub const COLOR_TYPE_RGBA: u8 = 6;
+
+/*
+    Filter types, every scanline is prefixed by one of these
+ */
+pub const FILTER_NONE: u8 = 0;
+pub const FILTER_SUB: u8 = 1;
+pub const FILTER_UP: u8 = 2;
+pub const FILTER_AVERAGE: u8 = 3;
+pub const FILTER_PAETH: u8 = 4;
+
+/*
+    Interlace methods
+ */
+pub const INTERLACE_NONE: u8 = 0;
+pub const INTERLACE_ADAM7: u8 = 1;
+
+/*
+    libpng splits its IDAT output the same way, there is no real reason for the size other than convention
+ */
+const IDAT_CHUNK_SIZE: usize = 8192;
+
+pub fn channels_for_color_type(color_type: u8) -> Option<u8> {
+    match color_type {
+        COLOR_TYPE_GRAYSCALE => Some(1),
+        COLOR_TYPE_RGB => Some(3),
+        COLOR_TYPE_PALETTE => Some(1),
+        COLOR_TYPE_GRAYSCALE_ALPHA => Some(2),
+        COLOR_TYPE_RGBA => Some(4),
+        _ => None,
+    }
+}
+
 /*
+    PLTE holds RGB triples, tRNS (if present) holds one alpha value per entry and may be shorter than the palette
+ */
+pub fn parse_palette(plte: &[u8], trns: Option<&[u8]>) -> Palette {
+    let alpha = trns.unwrap_or(&[]);
+    Palette {
+        entries: plte
+            .chunks(3)
+            .enumerate()
+            .map(|(index, rgb)| PaletteEntry {
+                red: rgb[0],
+                green: rgb[1],
+                blue: rgb[2],
+                alpha: alpha.get(index).copied().unwrap_or(255),
+            })
+            .collect(),
+        tolerance: None,
+    }
+}
+
+pub struct PngChunk {
+    pub chunk_type: ChunkType,
+    pub data: Vec<u8>,
+}
+
 fn read_chunk<R: Read>(reader: &mut R) -> io::Result<(ChunkType, Vec<u8>)> {
     // Read chunk length (4 bytes)
     let mut length_bytes = [0u8; 4];
     reader.read_exact(&mut length_bytes)?;
     let length = u32::from_be_bytes(length_bytes) as usize;
 
+    if length > i32::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Chunk length out of range"));
+    }
+
     // Read chunk type (4 bytes)
     let mut type_bytes = [0u8; 4];
     reader.read_exact(&mut type_bytes)?;
@@ -151,8 +246,8 @@ fn read_chunk<R: Read>(reader: &mut R) -> io::Result<(ChunkType, Vec<u8>)> {
     let mut crc_bytes = [0u8; 4];
     reader.read_exact(&mut crc_bytes)?;
 
-    // Validate CRC32 (checksum of the chunk type)
-    let crc = crc32::checksum_ieee(&chunk_type.0);
+    // Validate CRC32 (checksum of the chunk type followed by the chunk data)
+    let crc = crc32_update(crc32_update(0xFFFF_FFFF, &chunk_type.0), &data) ^ 0xFFFF_FFFF;
     let expected_crc = u32::from_be_bytes(crc_bytes);
     if crc != expected_crc {
         return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid CRC"));
@@ -161,5 +256,1290 @@ fn read_chunk<R: Read>(reader: &mut R) -> io::Result<(ChunkType, Vec<u8>)> {
     Ok((chunk_type, data))
 }
 
+fn write_chunk(output: &mut Vec<u8>, chunk_type: ChunkType, data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    output.extend_from_slice(&chunk_type.0);
+    output.extend_from_slice(data);
+    let crc = crc32_update(crc32_update(0xFFFF_FFFF, &chunk_type.0), data) ^ 0xFFFF_FFFF;
+    output.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn paeth_predictor(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/*
+    Undo the per scanline filtering. filter_bpp is the number of bytes per complete pixel rounded up to 1,
+    which is what the filters use to find the "left" byte. Returns the raw scanlines without their filter
+    bytes along with the filter type each row used so we can reapply the same filters on write.
+ */
+pub fn unfilter_scanlines(
+    filtered: &[u8],
+    rows: usize,
+    row_bytes: usize,
+    filter_bpp: usize,
+) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    // The raw image is smaller than the filtered one, so once that is known to be there allocating for it is fine
+    match row_bytes.checked_add(1).and_then(|line| line.checked_mul(rows)) {
+        Some(size) if size <= filtered.len() => {}
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "Image data is truncated")),
+    }
+
+    let mut raw = vec![0u8; rows * row_bytes];
+    let mut filter_types = Vec::with_capacity(rows);
+
+    for row in 0..rows {
+        let filter_type = filtered[row * (row_bytes + 1)];
+        let line = &filtered[row * (row_bytes + 1) + 1..(row + 1) * (row_bytes + 1)];
+        let (previous, current) = raw.split_at_mut(row * row_bytes);
+        let prior = if row == 0 { None } else { Some(&previous[(row - 1) * row_bytes..]) };
+        let current = &mut current[..row_bytes];
+
+        for i in 0..row_bytes {
+            let a = if i >= filter_bpp { current[i - filter_bpp] } else { 0 };
+            let b = prior.map_or(0, |p| p[i]);
+            let c = if i >= filter_bpp { prior.map_or(0, |p| p[i - filter_bpp]) } else { 0 };
+
+            current[i] = match filter_type {
+                FILTER_NONE => line[i],
+                FILTER_SUB => line[i].wrapping_add(a),
+                FILTER_UP => line[i].wrapping_add(b),
+                FILTER_AVERAGE => line[i].wrapping_add(((a as u16 + b as u16) / 2) as u8),
+                FILTER_PAETH => line[i].wrapping_add(paeth_predictor(a, b, c)),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid filter type {filter_type}"),
+                    ))
+                }
+            };
+        }
+
+        filter_types.push(filter_type);
+    }
+
+    Ok((raw, filter_types))
+}
+
+/*
+    Reverse of unfilter_scanlines, each row is filtered with the filter type it was originally stored with
+ */
+pub fn filter_scanlines(
+    raw: &[u8],
+    rows: usize,
+    row_bytes: usize,
+    filter_bpp: usize,
+    filter_types: &[u8],
+) -> Vec<u8> {
+    let mut filtered = Vec::with_capacity(rows * (row_bytes + 1));
+
+    for row in 0..rows {
+        let filter_type = filter_types.get(row).copied().unwrap_or(FILTER_NONE);
+        let current = &raw[row * row_bytes..(row + 1) * row_bytes];
+        let prior = if row == 0 { None } else { Some(&raw[(row - 1) * row_bytes..row * row_bytes]) };
+
+        filtered.push(filter_type);
+
+        for i in 0..row_bytes {
+            let a = if i >= filter_bpp { current[i - filter_bpp] } else { 0 };
+            let b = prior.map_or(0, |p| p[i]);
+            let c = if i >= filter_bpp { prior.map_or(0, |p| p[i - filter_bpp]) } else { 0 };
+
+            filtered.push(match filter_type {
+                FILTER_SUB => current[i].wrapping_sub(a),
+                FILTER_UP => current[i].wrapping_sub(b),
+                FILTER_AVERAGE => current[i].wrapping_sub(((a as u16 + b as u16) / 2) as u8),
+                FILTER_PAETH => current[i].wrapping_sub(paeth_predictor(a, b, c)),
+                _ => current[i],
+            });
+        }
+    }
+
+    filtered
+}
+
+/*
+    Adam7 passes as (x start, y start, x step, y step)
+ */
+pub const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/*
+    Width and height in pixels of the reduced image for an Adam7 pass, either can be 0 for tiny images in which
+    case the pass is not stored at all
+ */
+pub fn adam7_pass_size(width: usize, height: usize, pass: usize) -> (usize, usize) {
+    let (x_start, y_start, x_step, y_step) = ADAM7_PASSES[pass];
+    let pass_width = if width > x_start { (width - x_start).div_ceil(x_step) } else { 0 };
+    let pass_height = if height > y_start { (height - y_start).div_ceil(y_step) } else { 0 };
+    (pass_width, pass_height)
+}
+
+/*
+    Most image data we'll inflate or allocate for, IHDR can claim up to 2^31 pixels each way from a file of a few bytes
+ */
+pub const MAX_PNG_PIXEL_DATA_SIZE: usize = 1 << 30;
+
+/*
+    Bytes of filtered data all seven passes take together, None if that doesn't even fit in a usize
+ */
+pub fn adam7_filtered_size(width: usize, height: usize, bits_per_pixel: usize) -> Option<usize> {
+    (0..ADAM7_PASSES.len()).try_fold(0usize, |total, pass| {
+        let (pass_width, pass_height) = adam7_pass_size(width, height, pass);
+        if pass_width == 0 || pass_height == 0 {
+            return Some(total);
+        }
+
+        let pass_row_bytes = pass_width.checked_mul(bits_per_pixel)?.div_ceil(8);
+        total.checked_add(pass_row_bytes.checked_add(1)?.checked_mul(pass_height)?)
+    })
+}
+
+/*
+    Bytes of filtered data IHDR says the IDAT stream inflates to, None when that's more than we'll decode
+ */
+pub fn filtered_image_size(width: usize, height: usize, bits_per_pixel: usize, interlaced: bool) -> Option<usize> {
+    let size = if interlaced {
+        adam7_filtered_size(width, height, bits_per_pixel)?
+    } else {
+        width.checked_mul(bits_per_pixel)?.div_ceil(8).checked_add(1)?.checked_mul(height)?
+    };
+
+    Some(size).filter(|size| *size <= MAX_PNG_PIXEL_DATA_SIZE)
+}
+
+/*
+    Copy one pixel worth of bits between two packed scanlines, pixels under 8 bits are packed most significant bit first
+ */
+fn copy_pixel_bits(
+    source: &[u8],
+    source_index: usize,
+    destination: &mut [u8],
+    destination_index: usize,
+    bits_per_pixel: usize,
+) {
+    if bits_per_pixel >= 8 {
+        let bytes = bits_per_pixel / 8;
+        destination[destination_index * bytes..(destination_index + 1) * bytes]
+            .copy_from_slice(&source[source_index * bytes..(source_index + 1) * bytes]);
+        return;
+    }
+
+    let mask = ((1u16 << bits_per_pixel) - 1) as u8;
+    let source_bit = source_index * bits_per_pixel;
+    let destination_bit = destination_index * bits_per_pixel;
+    let source_shift = 8 - bits_per_pixel - (source_bit % 8);
+    let destination_shift = 8 - bits_per_pixel - (destination_bit % 8);
+
+    let value = (source[source_bit / 8] >> source_shift) & mask;
+    destination[destination_bit / 8] &= !(mask << destination_shift);
+    destination[destination_bit / 8] |= value << destination_shift;
+}
+
+/*
+    Unfilter each of the seven passes and scatter them back into a single flat image so that embedding sees the same
+    pixel grid an interlaced and non interlaced copy of the image would have. The filter types of every pass are
+    returned back to back in pass order.
+ */
+pub fn deinterlace_adam7(
+    filtered: &[u8],
+    width: usize,
+    height: usize,
+    bits_per_pixel: usize,
+    filter_bpp: usize,
+) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    // Everything is sized from IHDR, so check it adds up and is actually there before allocating anything
+    let too_large = || io::Error::new(io::ErrorKind::InvalidData, format!("Interlaced image of {width}x{height} is too large to decode"));
+    let row_bytes = width.checked_mul(bits_per_pixel).ok_or_else(too_large)?.div_ceil(8);
+    let raw_size = row_bytes.checked_mul(height).filter(|size| *size <= MAX_PNG_PIXEL_DATA_SIZE).ok_or_else(too_large)?;
+
+    if adam7_filtered_size(width, height, bits_per_pixel).is_none_or(|size| size > filtered.len()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Interlaced image data is truncated"));
+    }
+
+    let mut raw = vec![0u8; raw_size];
+    let mut filter_types = Vec::new();
+    let mut offset = 0;
+
+    for (pass, &(x_start, y_start, x_step, y_step)) in ADAM7_PASSES.iter().enumerate() {
+        let (pass_width, pass_height) = adam7_pass_size(width, height, pass);
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+
+        let pass_row_bytes = (pass_width * bits_per_pixel).div_ceil(8);
+        let pass_length = pass_height * (pass_row_bytes + 1);
+
+        if offset + pass_length > filtered.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Interlaced image data is truncated"));
+        }
+
+        let (pass_raw, pass_filter_types) = unfilter_scanlines(
+            &filtered[offset..offset + pass_length],
+            pass_height,
+            pass_row_bytes,
+            filter_bpp,
+        )?;
+        offset += pass_length;
+        filter_types.extend_from_slice(&pass_filter_types);
+
+        for pass_y in 0..pass_height {
+            let y = y_start + pass_y * y_step;
+            let source = &pass_raw[pass_y * pass_row_bytes..(pass_y + 1) * pass_row_bytes];
+            let destination = &mut raw[y * row_bytes..(y + 1) * row_bytes];
+            for pass_x in 0..pass_width {
+                copy_pixel_bits(source, pass_x, destination, x_start + pass_x * x_step, bits_per_pixel);
+            }
+        }
+    }
+
+    Ok((raw, filter_types))
+}
+
+/*
+    Reverse of deinterlace_adam7, gathers the passes back out of the flat image and filters each one with the
+    filter types it was originally stored with
+ */
+pub fn interlace_adam7(
+    raw: &[u8],
+    width: usize,
+    height: usize,
+    bits_per_pixel: usize,
+    filter_bpp: usize,
+    filter_types: &[u8],
+) -> Vec<u8> {
+    let row_bytes = (width * bits_per_pixel).div_ceil(8);
+    let mut filtered = Vec::new();
+    let mut filter_offset = 0;
+
+    for (pass, &(x_start, y_start, x_step, y_step)) in ADAM7_PASSES.iter().enumerate() {
+        let (pass_width, pass_height) = adam7_pass_size(width, height, pass);
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+
+        let pass_row_bytes = (pass_width * bits_per_pixel).div_ceil(8);
+        let mut pass_raw = vec![0u8; pass_row_bytes * pass_height];
+
+        for pass_y in 0..pass_height {
+            let y = y_start + pass_y * y_step;
+            let source = &raw[y * row_bytes..(y + 1) * row_bytes];
+            let destination = &mut pass_raw[pass_y * pass_row_bytes..(pass_y + 1) * pass_row_bytes];
+            for pass_x in 0..pass_width {
+                copy_pixel_bits(source, x_start + pass_x * x_step, destination, pass_x, bits_per_pixel);
+            }
+        }
+
+        let pass_filter_types = filter_types.get(filter_offset..).unwrap_or(&[]);
+        filtered.extend_from_slice(&filter_scanlines(
+            &pass_raw,
+            pass_height,
+            pass_row_bytes,
+            filter_bpp,
+            pass_filter_types,
+        ));
+        filter_offset += pass_height;
+    }
+
+    filtered
+}
+
+// PNG stores truecolor samples in R, G, B order
+#[repr(C, packed)]
+#[derive(Debug, Default, Clone)]
+pub struct PngRgbPixel {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+#[repr(C, packed)]
+#[derive(Debug, Default, Clone)]
+pub struct PngRgbaPixel {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+/*
+    Grayscale at 8 bits, or at 1, 2 and 4 bits once the samples have been unpacked to one per byte, BITS is the
+    real sample width so embedding never sets a bit the sample can't hold
+ */
+#[repr(C, packed)]
+#[derive(Debug, Default, Clone)]
+pub struct PngGrayPixel<const BITS: u32> {
+    pub gray: u8,
+}
+
+#[repr(C, packed)]
+#[derive(Debug, Default, Clone)]
+pub struct PngGrayAlphaPixel {
+    pub gray: u8,
+    pub alpha: u8,
+}
+
+// 16 bit samples are big endian on disk and we leave them that way in the pixel map
+#[repr(C, packed)]
+#[derive(Debug, Default, Clone)]
+pub struct PngGray16Pixel {
+    pub gray: [u8; 2],
+}
+
+#[repr(C, packed)]
+#[derive(Debug, Default, Clone)]
+pub struct PngGrayAlpha16Pixel {
+    pub gray: [u8; 2],
+    pub alpha: [u8; 2],
+}
+
+#[repr(C, packed)]
+#[derive(Debug, Default, Clone)]
+pub struct PngRgb16Pixel {
+    pub red: [u8; 2],
+    pub green: [u8; 2],
+    pub blue: [u8; 2],
+}
+
+#[repr(C, packed)]
+#[derive(Debug, Default, Clone)]
+pub struct PngRgba16Pixel {
+    pub red: [u8; 2],
+    pub green: [u8; 2],
+    pub blue: [u8; 2],
+    pub alpha: [u8; 2],
+}
+
+fn bad_channel(pixel: &str, index: usize) -> ! {
+    unreachable!("png.rs {pixel} channel {index} does not exist! This is a bug!")
+}
+
+impl Pixel for PngRgbPixel {
+    fn channel_count(&self) -> usize {
+        3
+    }
+
+    fn channel(&self, index: usize) -> u16 {
+        match index {
+            0 => self.red as u16,
+            1 => self.green as u16,
+            2 => self.blue as u16,
+            _ => bad_channel("PngRgbPixel", index),
+        }
+    }
+
+    fn set_channel(&mut self, index: usize, value: u16) {
+        match index {
+            0 => self.red = value as u8,
+            1 => self.green = value as u8,
+            2 => self.blue = value as u8,
+            _ => bad_channel("PngRgbPixel", index),
+        }
+    }
+
+    fn red(&self) -> u16 {
+        self.red as u16
+    }
+    fn green(&self) -> u16 {
+        self.green as u16
+    }
+    fn blue(&self) -> u16 {
+        self.blue as u16
+    }
+    fn alpha(&self) -> u16 {
+        self.max_sample()
+    }
+
+    fn pixel_size(&self) -> usize {
+        3
+    }
+}
+
+impl Pixel for PngRgbaPixel {
+    fn channel_count(&self) -> usize {
+        4
+    }
+
+    fn channel(&self, index: usize) -> u16 {
+        match index {
+            0 => self.red as u16,
+            1 => self.green as u16,
+            2 => self.blue as u16,
+            3 => self.alpha as u16,
+            _ => bad_channel("PngRgbaPixel", index),
+        }
+    }
+
+    fn set_channel(&mut self, index: usize, value: u16) {
+        match index {
+            0 => self.red = value as u8,
+            1 => self.green = value as u8,
+            2 => self.blue = value as u8,
+            3 => self.alpha = value as u8,
+            _ => bad_channel("PngRgbaPixel", index),
+        }
+    }
+
+    fn red(&self) -> u16 {
+        self.red as u16
+    }
+    fn green(&self) -> u16 {
+        self.green as u16
+    }
+    fn blue(&self) -> u16 {
+        self.blue as u16
+    }
+    fn alpha(&self) -> u16 {
+        self.alpha as u16
+    }
+
+    fn pixel_size(&self) -> usize {
+        4
+    }
+}
+
+impl<const BITS: u32> Pixel for PngGrayPixel<BITS> {
+    fn channel_count(&self) -> usize {
+        1
+    }
+
+    fn sample_bits(&self) -> u32 {
+        BITS
+    }
+
+    fn channel(&self, index: usize) -> u16 {
+        match index {
+            0 => self.gray as u16,
+            _ => bad_channel("PngGrayPixel", index),
+        }
+    }
+
+    fn set_channel(&mut self, index: usize, value: u16) {
+        match index {
+            0 => self.gray = value as u8 & self.max_sample() as u8,
+            _ => bad_channel("PngGrayPixel", index),
+        }
+    }
+
+    fn red(&self) -> u16 {
+        self.gray as u16
+    }
+    fn green(&self) -> u16 {
+        self.gray as u16
+    }
+    fn blue(&self) -> u16 {
+        self.gray as u16
+    }
+    fn alpha(&self) -> u16 {
+        self.max_sample()
+    }
+
+    fn pixel_size(&self) -> usize {
+        1
+    }
+}
 
- */
\ No newline at end of file
+impl Pixel for PngGrayAlphaPixel {
+    fn channel_count(&self) -> usize {
+        2
+    }
+
+    fn channel(&self, index: usize) -> u16 {
+        match index {
+            0 => self.gray as u16,
+            1 => self.alpha as u16,
+            _ => bad_channel("PngGrayAlphaPixel", index),
+        }
+    }
+
+    fn set_channel(&mut self, index: usize, value: u16) {
+        match index {
+            0 => self.gray = value as u8,
+            1 => self.alpha = value as u8,
+            _ => bad_channel("PngGrayAlphaPixel", index),
+        }
+    }
+
+    fn red(&self) -> u16 {
+        self.gray as u16
+    }
+    fn green(&self) -> u16 {
+        self.gray as u16
+    }
+    fn blue(&self) -> u16 {
+        self.gray as u16
+    }
+    fn alpha(&self) -> u16 {
+        self.alpha as u16
+    }
+
+    fn pixel_size(&self) -> usize {
+        2
+    }
+}
+
+impl Pixel for PngGray16Pixel {
+    fn channel_count(&self) -> usize {
+        1
+    }
+
+    fn sample_bits(&self) -> u32 {
+        16
+    }
+
+    fn channel(&self, index: usize) -> u16 {
+        match index {
+            0 => u16::from_be_bytes(self.gray),
+            _ => bad_channel("PngGray16Pixel", index),
+        }
+    }
+
+    fn set_channel(&mut self, index: usize, value: u16) {
+        match index {
+            0 => self.gray = value.to_be_bytes(),
+            _ => bad_channel("PngGray16Pixel", index),
+        }
+    }
+
+    fn red(&self) -> u16 {
+        u16::from_be_bytes(self.gray)
+    }
+    fn green(&self) -> u16 {
+        u16::from_be_bytes(self.gray)
+    }
+    fn blue(&self) -> u16 {
+        u16::from_be_bytes(self.gray)
+    }
+    fn alpha(&self) -> u16 {
+        self.max_sample()
+    }
+
+    fn pixel_size(&self) -> usize {
+        2
+    }
+}
+
+impl Pixel for PngGrayAlpha16Pixel {
+    fn channel_count(&self) -> usize {
+        2
+    }
+
+    fn sample_bits(&self) -> u32 {
+        16
+    }
+
+    fn channel(&self, index: usize) -> u16 {
+        match index {
+            0 => u16::from_be_bytes(self.gray),
+            1 => u16::from_be_bytes(self.alpha),
+            _ => bad_channel("PngGrayAlpha16Pixel", index),
+        }
+    }
+
+    fn set_channel(&mut self, index: usize, value: u16) {
+        match index {
+            0 => self.gray = value.to_be_bytes(),
+            1 => self.alpha = value.to_be_bytes(),
+            _ => bad_channel("PngGrayAlpha16Pixel", index),
+        }
+    }
+
+    fn red(&self) -> u16 {
+        u16::from_be_bytes(self.gray)
+    }
+    fn green(&self) -> u16 {
+        u16::from_be_bytes(self.gray)
+    }
+    fn blue(&self) -> u16 {
+        u16::from_be_bytes(self.gray)
+    }
+    fn alpha(&self) -> u16 {
+        u16::from_be_bytes(self.alpha)
+    }
+
+    fn pixel_size(&self) -> usize {
+        4
+    }
+}
+
+impl Pixel for PngRgb16Pixel {
+    fn channel_count(&self) -> usize {
+        3
+    }
+
+    fn sample_bits(&self) -> u32 {
+        16
+    }
+
+    fn channel(&self, index: usize) -> u16 {
+        match index {
+            0 => u16::from_be_bytes(self.red),
+            1 => u16::from_be_bytes(self.green),
+            2 => u16::from_be_bytes(self.blue),
+            _ => bad_channel("PngRgb16Pixel", index),
+        }
+    }
+
+    fn set_channel(&mut self, index: usize, value: u16) {
+        match index {
+            0 => self.red = value.to_be_bytes(),
+            1 => self.green = value.to_be_bytes(),
+            2 => self.blue = value.to_be_bytes(),
+            _ => bad_channel("PngRgb16Pixel", index),
+        }
+    }
+
+    fn red(&self) -> u16 {
+        u16::from_be_bytes(self.red)
+    }
+    fn green(&self) -> u16 {
+        u16::from_be_bytes(self.green)
+    }
+    fn blue(&self) -> u16 {
+        u16::from_be_bytes(self.blue)
+    }
+    fn alpha(&self) -> u16 {
+        self.max_sample()
+    }
+
+    fn pixel_size(&self) -> usize {
+        6
+    }
+}
+
+impl Pixel for PngRgba16Pixel {
+    fn channel_count(&self) -> usize {
+        4
+    }
+
+    fn sample_bits(&self) -> u32 {
+        16
+    }
+
+    fn channel(&self, index: usize) -> u16 {
+        match index {
+            0 => u16::from_be_bytes(self.red),
+            1 => u16::from_be_bytes(self.green),
+            2 => u16::from_be_bytes(self.blue),
+            3 => u16::from_be_bytes(self.alpha),
+            _ => bad_channel("PngRgba16Pixel", index),
+        }
+    }
+
+    fn set_channel(&mut self, index: usize, value: u16) {
+        match index {
+            0 => self.red = value.to_be_bytes(),
+            1 => self.green = value.to_be_bytes(),
+            2 => self.blue = value.to_be_bytes(),
+            3 => self.alpha = value.to_be_bytes(),
+            _ => bad_channel("PngRgba16Pixel", index),
+        }
+    }
+
+    fn red(&self) -> u16 {
+        u16::from_be_bytes(self.red)
+    }
+    fn green(&self) -> u16 {
+        u16::from_be_bytes(self.green)
+    }
+    fn blue(&self) -> u16 {
+        u16::from_be_bytes(self.blue)
+    }
+    fn alpha(&self) -> u16 {
+        u16::from_be_bytes(self.alpha)
+    }
+
+    fn pixel_size(&self) -> usize {
+        8
+    }
+}
+
+pub struct PngImageParser {
+    pub ihdr: IHDRData,
+    /*
+        Every chunk other than IHDR, IDAT and IEND in the order they appeared, idat_position is the index
+        the image data gets written back at so ancillary chunks keep their place relative to it
+     */
+    pub chunks: Vec<PngChunk>,
+    pub idat_position: usize,
+    pub filter_types: Vec<u8>, // For Adam7 images this holds the filter type of every pass row in pass order
+    pub pixel_data: Vec<u8>, // Unfiltered scanlines with the filter bytes stripped, always deinterlaced
+    pub pixel_size: u8, // Channels per pixel, the byte size also depends on the bit depth
+    pub row_bytes: usize,
+    pub palette: Option<Palette>, // Only present for indexed (color type 3) images
+    pub encoding_parameters: EncodingParameters,
+    pub filename: String,
+    pub file_data: Vec<u8>,
+    ready: bool,
+}
+
+impl PngImageParser {
+    /*
+        Samples unpacked to one byte each. Only used for images under 8 bits per pixel, those are always a single
+        channel (palette indices or grayscale) so there is one sample per pixel
+     */
+    fn unpacked_samples(&self) -> Vec<u8> {
+        unpack_samples(
+            &self.pixel_data,
+            self.ihdr.height as usize,
+            self.row_bytes,
+            self.ihdr.width as usize,
+            self.ihdr.bit_depth as usize,
+        )
+    }
+
+    fn store_unpacked_samples(&mut self, indices: &[u8]) {
+        pack_samples(
+            indices,
+            &mut self.pixel_data,
+            self.ihdr.height as usize,
+            self.row_bytes,
+            self.ihdr.width as usize,
+            self.ihdr.bit_depth as usize,
+        );
+    }
+
+    /*
+        Put the palette into luminance order and remap everything that refers to a palette index, that is the
+        pixels themselves along with the tRNS, bKGD and hIST chunks
+     */
+    fn reorder_palette(&mut self) {
+        let palette = match self.palette.as_mut() {
+            Some(palette) => palette,
+            None => return,
+        };
+
+        let previous = palette.entries.clone();
+        let remap = palette.sort_by_luminance();
+        let entries = palette.entries.clone();
+
+        for chunk in self.chunks.iter_mut() {
+            match chunk.chunk_type {
+                PLTE => {
+                    chunk.data = entries
+                        .iter()
+                        .flat_map(|entry| [entry.red, entry.green, entry.blue])
+                        .collect();
+                }
+                tRNS => {
+                    let mut alpha: Vec<u8> = entries.iter().map(|entry| entry.alpha).collect();
+                    while alpha.len() > 1 && alpha[alpha.len() - 1] == 255 {
+                        alpha.pop();
+                    }
+                    chunk.data = alpha;
+                }
+                bKGD if chunk.data.len() == 1 => {
+                    chunk.data[0] = remap[chunk.data[0] as usize];
+                }
+                hIST if chunk.data.len() == previous.len() * 2 => {
+                    let mut histogram = vec![0u8; chunk.data.len()];
+                    for (old_index, new_index) in remap.iter().take(previous.len()).enumerate() {
+                        let new_index = *new_index as usize;
+                        histogram[new_index * 2..new_index * 2 + 2]
+                            .copy_from_slice(&chunk.data[old_index * 2..old_index * 2 + 2]);
+                    }
+                    chunk.data = histogram;
+                }
+                _ => {}
+            }
+        }
+
+        let indices: Vec<u8> = self
+            .unpacked_samples()
+            .iter()
+            .map(|index| remap[*index as usize])
+            .collect();
+        self.store_unpacked_samples(&indices);
+    }
+
+    /*
+        Filters work on whole bytes, anything under 8 bits per pixel just uses the previous byte
+     */
+    fn filter_bpp(&self) -> usize {
+        let channels = channels_for_color_type(self.ihdr.color_type).unwrap_or(1) as usize;
+        ((channels * self.ihdr.bit_depth as usize) / 8).max(1)
+    }
+
+    fn bytes_per_pixel(&self) -> u64 {
+        self.pixel_size as u64 * self.ihdr.bit_depth as u64 / 8
+    }
+
+    /*
+        The PLTE palette with the palette tolerance applied, this is what embedding and extraction actually work against
+     */
+    fn embedding_palette(&self) -> Option<Palette> {
+        self.palette.as_ref().map(|palette| Palette {
+            entries: palette.entries.clone(),
+            tolerance: self.encoding_parameters.palette_tolerance,
+        })
+    }
+
+    fn capacity_bits(&mut self, encoding: FileEncoding) -> Result<u64, MayaError> {
+        if let Some(palette) = &self.embedding_palette() {
+            return Ok(palette_capacity_bits(&self.unpacked_samples(), palette));
+        }
+
+        match (self.ihdr.color_type, self.ihdr.bit_depth) {
+            (COLOR_TYPE_GRAYSCALE, 1) => self.capacity_pixels::<PngGrayPixel<1>>(encoding),
+            (COLOR_TYPE_GRAYSCALE, 2) => self.capacity_pixels::<PngGrayPixel<2>>(encoding),
+            (COLOR_TYPE_GRAYSCALE, 4) => self.capacity_pixels::<PngGrayPixel<4>>(encoding),
+            (COLOR_TYPE_GRAYSCALE, 8) => self.capacity_pixels::<PngGrayPixel<8>>(encoding),
+            (COLOR_TYPE_GRAYSCALE, _) => self.capacity_pixels::<PngGray16Pixel>(encoding),
+            (COLOR_TYPE_GRAYSCALE_ALPHA, 8) => self.capacity_pixels::<PngGrayAlphaPixel>(encoding),
+            (COLOR_TYPE_GRAYSCALE_ALPHA, _) => self.capacity_pixels::<PngGrayAlpha16Pixel>(encoding),
+            (COLOR_TYPE_RGB, 8) => self.capacity_pixels::<PngRgbPixel>(encoding),
+            (COLOR_TYPE_RGB, _) => self.capacity_pixels::<PngRgb16Pixel>(encoding),
+            (COLOR_TYPE_RGBA, 8) => self.capacity_pixels::<PngRgbaPixel>(encoding),
+            _ => self.capacity_pixels::<PngRgba16Pixel>(encoding),
+        }
+    }
+
+    fn capacity_pixels<P: Pixel + Default>(&mut self, encoding: FileEncoding) -> Result<u64, MayaError> {
+        let width = self.ihdr.width as u64;
+        let height = self.ihdr.height as u64;
+
+        if self.ihdr.bit_depth < 8 {
+            let mut samples = self.unpacked_samples();
+            pixel_map_capacity_bits::<P>(&mut samples, width, height, 0, 1, encoding, &self.encoding_parameters)
+        } else {
+            let pixel_size = self.bytes_per_pixel();
+            pixel_map_capacity_bits::<P>(&mut self.pixel_data, width, height, 0, pixel_size, encoding, &self.encoding_parameters)
+        }
+    }
+
+    fn channels_pixels<P: Pixel + Default>(&mut self) -> PixelChannels {
+        let width = self.ihdr.width as u64;
+        let height = self.ihdr.height as u64;
+
+        if self.ihdr.bit_depth < 8 {
+            let mut samples = self.unpacked_samples();
+            PixelChannels::from_pixel_map::<P>(&mut samples, width, height, 0, 1)
+        } else {
+            let pixel_size = self.bytes_per_pixel();
+            PixelChannels::from_pixel_map::<P>(&mut self.pixel_data, width, height, 0, pixel_size)
+        }
+    }
+
+    /*
+        Whole byte pixels are embedded in place, anything narrower is unpacked first and packed back afterwards
+     */
+    fn embed_pixels<P: Pixel + Default>(
+        &mut self,
+        data: &[u8],
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Result<(), MayaError> {
+        let width = self.ihdr.width as u64;
+        let height = self.ihdr.height as u64;
+
+        if self.ihdr.bit_depth < 8 {
+            let mut samples = self.unpacked_samples();
+            embed_data_with_method::<P>(data, &mut samples, width, height, 0, 1, encoding, encoding_method, file_encoding_function_derivation, &self.encoding_parameters)?;
+            self.store_unpacked_samples(&samples);
+            Ok(())
+        } else {
+            let pixel_size = self.bytes_per_pixel();
+            embed_data_with_method::<P>(data, &mut self.pixel_data, width, height, 0, pixel_size, encoding, encoding_method, file_encoding_function_derivation, &self.encoding_parameters)
+        }
+    }
+
+    fn extract_pixels<P: Pixel + Default>(
+        &mut self,
+        embedded_bits: u64,
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Result<Vec<u8>, MayaError> {
+        let width = self.ihdr.width as u64;
+        let height = self.ihdr.height as u64;
+
+        if self.ihdr.bit_depth < 8 {
+            let mut samples = self.unpacked_samples();
+            extract_data_with_method::<P>(&mut samples, width, height, 0, 1, embedded_bits, encoding, encoding_method, file_encoding_function_derivation, &self.encoding_parameters)
+        } else {
+            let pixel_size = self.bytes_per_pixel();
+            extract_data_with_method::<P>(&mut self.pixel_data, width, height, 0, pixel_size, embedded_bits, encoding, encoding_method, file_encoding_function_derivation, &self.encoding_parameters)
+        }
+    }
+
+    fn embed_bits(
+        &mut self,
+        data: &[u8],
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Result<(), MayaError> {
+        if self.palette.is_some() {
+            if self.encoding_parameters.palette_embedding == PaletteEmbedding::ReorderPalette {
+                self.reorder_palette();
+            }
+
+            let mut indices = self.unpacked_samples();
+            if let Some(palette) = &self.embedding_palette() {
+                embed_palette_data(data, &mut indices, self.ihdr.width as usize, self.ihdr.height as usize, palette, encoding_method, file_encoding_function_derivation)?;
+            }
+            self.store_unpacked_samples(&indices);
+            return Ok(());
+        }
+
+        match (self.ihdr.color_type, self.ihdr.bit_depth) {
+            (COLOR_TYPE_GRAYSCALE, 1) => self.embed_pixels::<PngGrayPixel<1>>(data, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_GRAYSCALE, 2) => self.embed_pixels::<PngGrayPixel<2>>(data, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_GRAYSCALE, 4) => self.embed_pixels::<PngGrayPixel<4>>(data, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_GRAYSCALE, 8) => self.embed_pixels::<PngGrayPixel<8>>(data, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_GRAYSCALE, _) => self.embed_pixels::<PngGray16Pixel>(data, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_GRAYSCALE_ALPHA, 8) => self.embed_pixels::<PngGrayAlphaPixel>(data, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_GRAYSCALE_ALPHA, _) => self.embed_pixels::<PngGrayAlpha16Pixel>(data, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_RGB, 8) => self.embed_pixels::<PngRgbPixel>(data, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_RGB, _) => self.embed_pixels::<PngRgb16Pixel>(data, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_RGBA, 8) => self.embed_pixels::<PngRgbaPixel>(data, encoding, encoding_method, file_encoding_function_derivation),
+            _ => self.embed_pixels::<PngRgba16Pixel>(data, encoding, encoding_method, file_encoding_function_derivation),
+        }
+    }
+
+    fn extract_bits(
+        &mut self,
+        embedded_bits: u64,
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Result<Vec<u8>, MayaError> {
+        if let Some(palette) = &self.embedding_palette() {
+            return Ok(extract_palette_data(&self.unpacked_samples(), self.ihdr.width as usize, self.ihdr.height as usize, palette, embedded_bits, encoding_method, file_encoding_function_derivation));
+        }
+
+        match (self.ihdr.color_type, self.ihdr.bit_depth) {
+            (COLOR_TYPE_GRAYSCALE, 1) => self.extract_pixels::<PngGrayPixel<1>>(embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_GRAYSCALE, 2) => self.extract_pixels::<PngGrayPixel<2>>(embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_GRAYSCALE, 4) => self.extract_pixels::<PngGrayPixel<4>>(embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_GRAYSCALE, 8) => self.extract_pixels::<PngGrayPixel<8>>(embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_GRAYSCALE, _) => self.extract_pixels::<PngGray16Pixel>(embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_GRAYSCALE_ALPHA, 8) => self.extract_pixels::<PngGrayAlphaPixel>(embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_GRAYSCALE_ALPHA, _) => self.extract_pixels::<PngGrayAlpha16Pixel>(embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_RGB, 8) => self.extract_pixels::<PngRgbPixel>(embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_RGB, _) => self.extract_pixels::<PngRgb16Pixel>(embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+            (COLOR_TYPE_RGBA, 8) => self.extract_pixels::<PngRgbaPixel>(embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+            _ => self.extract_pixels::<PngRgba16Pixel>(embedded_bits, encoding, encoding_method, file_encoding_function_derivation),
+        }
+    }
+}
+
+impl FileEncodingSupport for PngImageParser {
+    fn new(filename: &str) -> Self {
+        PngImageParser {
+            ihdr: IHDRData::default(),
+            chunks: Vec::new(),
+            idat_position: 0,
+            filter_types: Vec::new(),
+            pixel_data: Vec::new(),
+            pixel_size: 0,
+            row_bytes: 0,
+            palette: None,
+            encoding_parameters: EncodingParameters::default(),
+            filename: filename.to_string(),
+            file_data: Vec::new(),
+            ready: false,
+        }
+    }
+
+    fn file_type(&self) -> FileType {
+        FileType::Png
+    }
+
+    fn set_encoding_parameters(&mut self, parameters: EncodingParameters) {
+        self.encoding_parameters = parameters;
+    }
+
+    fn parse_file(&mut self) -> Result<(), MayaError> {
+        let file_data = std::fs::read(&self.filename)?;
+        self.parse_bytes(file_data)
+    }
+
+    fn parse_bytes(&mut self, file_data: Vec<u8>) -> Result<(), MayaError> {
+        self.file_data = file_data;
+
+        if self.file_data.len() < PNG_MAGIC.len() || self.file_data[0..8] != PNG_MAGIC {
+            return Err(MayaError::UnsupportedFormat("invalid PNG signature".to_string()));
+        }
+
+        let mut reader = &self.file_data[8..];
+        let mut idat: Vec<u8> = Vec::new();
+        let mut seen_ihdr = false;
+        let mut seen_idat = false;
+
+        loop {
+            let (chunk_type, data) = match read_chunk(&mut reader) {
+                Ok(chunk) => chunk,
+                Err(e) => return Err(MayaError::CorruptHeader(format!("PNG chunk: {e}"))),
+            };
+
+            if !seen_ihdr {
+                if chunk_type != IHDR || data.len() != 13 {
+                    return Err(MayaError::CorruptHeader("first PNG chunk is not a valid IHDR".to_string()));
+                }
+                self.ihdr = parse_ihdr(&data);
+                seen_ihdr = true;
+                continue;
+            }
+
+            match chunk_type {
+                IEND => break,
+                IDAT => {
+                    if seen_idat && self.idat_position != self.chunks.len() {
+                        return Err(MayaError::CorruptHeader("PNG IDAT chunks are not consecutive".to_string()));
+                    }
+                    if !seen_idat {
+                        self.idat_position = self.chunks.len();
+                        seen_idat = true;
+                    }
+                    idat.extend_from_slice(&data);
+                }
+                IHDR => {
+                    return Err(MayaError::CorruptHeader("duplicate PNG IHDR chunk".to_string()));
+                }
+                _ => {
+                    if is_critical(chunk_type) && chunk_type != PLTE {
+                        return Err(MayaError::UnsupportedFormat(format!(
+                            "unknown critical PNG chunk {}",
+                            String::from_utf8_lossy(&chunk_type.0)
+                        )));
+                    }
+                    self.chunks.push(PngChunk { chunk_type, data });
+                }
+            }
+        }
+
+        if !seen_idat {
+            return Err(MayaError::CorruptHeader("no PNG IDAT chunk found".to_string()));
+        }
+
+        if self.ihdr.compression_method != 0 || self.ihdr.filter_method != 0 {
+            return Err(MayaError::UnsupportedFormat("unknown PNG compression or filter method".to_string()));
+        }
+
+        if self.ihdr.interlace_method != INTERLACE_NONE && self.ihdr.interlace_method != INTERLACE_ADAM7 {
+            return Err(MayaError::UnsupportedFormat(format!(
+                "unknown PNG interlace method {}",
+                self.ihdr.interlace_method
+            )));
+        }
+
+        /*
+            Every combination the PNG spec allows. Palette images are embedded in the index domain, everything else
+            goes through the Pixel trait with grayscale under 8 bits unpacked to a sample per byte first.
+         */
+        let supported = match self.ihdr.color_type {
+            COLOR_TYPE_GRAYSCALE => matches!(self.ihdr.bit_depth, 1 | 2 | 4 | 8 | 16),
+            COLOR_TYPE_RGB | COLOR_TYPE_GRAYSCALE_ALPHA | COLOR_TYPE_RGBA => matches!(self.ihdr.bit_depth, 8 | 16),
+            COLOR_TYPE_PALETTE => matches!(self.ihdr.bit_depth, 1 | 2 | 4 | 8),
+            _ => false,
+        };
+
+        if !supported {
+            return Err(MayaError::UnsupportedFormat(format!(
+                "PNG bit depth {} / color type {}",
+                self.ihdr.bit_depth, self.ihdr.color_type
+            )));
+        }
+
+        if self.ihdr.color_type == COLOR_TYPE_PALETTE {
+            let plte = self.chunks.iter().find(|chunk| chunk.chunk_type == PLTE);
+            let trns = self.chunks.iter().find(|chunk| chunk.chunk_type == tRNS);
+
+            match plte {
+                Some(plte) if !plte.data.is_empty() && plte.data.len() % 3 == 0 && plte.data.len() <= 256 * 3 => {
+                    self.palette = Some(parse_palette(&plte.data, trns.map(|chunk| chunk.data.as_slice())));
+                }
+                _ => {
+                    return Err(MayaError::CorruptHeader("PNG palette image without a valid PLTE chunk".to_string()));
+                }
+            }
+        }
+
+        self.pixel_size = channels_for_color_type(self.ihdr.color_type).unwrap();
+        let bits_per_pixel = self.pixel_size as usize * self.ihdr.bit_depth as usize;
+        self.row_bytes = (self.ihdr.width as usize * bits_per_pixel).div_ceil(8);
+
+        let interlaced = self.ihdr.interlace_method == INTERLACE_ADAM7;
+        let (width, height) = (self.ihdr.width as usize, self.ihdr.height as usize);
+        let filtered_size = match filtered_image_size(width, height, bits_per_pixel, interlaced) {
+            Some(filtered_size) => filtered_size,
+            None => return Err(MayaError::CorruptHeader(format!("PNG of {width}x{height} is too large to decode"))),
+        };
+
+        // Never inflate past what IHDR asked for, anything after the last row is dropped like other decoders do
+        let filtered = match fdeflate::decompress_to_vec_bounded(&idat, filtered_size) {
+            Ok(filtered) => filtered,
+            Err(BoundedDecompressionError::OutputTooLarge { partial_output }) => partial_output,
+            Err(BoundedDecompressionError::DecompressionError { inner }) => {
+                return Err(MayaError::CorruptHeader(format!("PNG image data doesn't inflate: {inner:?}")))
+            }
+        };
+
+        let unfiltered = if interlaced {
+            deinterlace_adam7(
+                &filtered,
+                self.ihdr.width as usize,
+                self.ihdr.height as usize,
+                bits_per_pixel,
+                self.filter_bpp(),
+            )
+        } else {
+            unfilter_scanlines(&filtered, self.ihdr.height as usize, self.row_bytes, self.filter_bpp())
+        };
+
+        match unfiltered {
+            Ok((pixel_data, filter_types)) => {
+                self.pixel_data = pixel_data;
+                self.filter_types = filter_types;
+            }
+            Err(e) => return Err(MayaError::CorruptHeader(format!("PNG image data doesn't unfilter: {e}"))),
+        }
+
+        self.ready = true;
+        Ok(())
+    }
+
+    fn carrier_capacity_bits(&mut self, encoding: FileEncoding) -> Result<u64, MayaError> {
+        if !self.ready {
+            return Err(MayaError::NotParsed);
+        }
+
+        // Indexed images only have the palette embedding, see palette.rs
+        if encoding.works_on_coefficients() || (self.palette.is_some() && encoding != FileEncoding::Lsb) {
+            return Err(MayaError::UnsupportedEncoding { encoding, file_type: FileType::Png });
+        }
+
+        self.capacity_bits(encoding)
+    }
+
+    fn embed_data_with_flags(
+        &mut self,
+        data: &mut Vec<u8>,
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+        flags: u8,
+    ) -> Result<(), MayaError> {
+        let capacity_bits = self.carrier_capacity_bits(encoding)?;
+        let flags = match encoding {
+            FileEncoding::HammingMatrix => hamming_payload_flags(flags, self.encoding_parameters.hamming_k, data.len(), capacity_bits)?,
+            _ => flags,
+        };
+        let payload = build_payload(data, encoding, encoding_method, flags);
+
+        if payload.len() as u64 * 8 > capacity_bits {
+            return Err(MayaError::CapacityExceeded {
+                needed_bits: payload.len() as u64 * 8,
+                capacity_bits,
+            });
+        }
+
+        self.embed_bits(&payload, encoding, encoding_method, file_encoding_function_derivation)
+    }
+
+    fn retrieve_data_with_flags(
+        &mut self,
+        encoding: FileEncoding,
+        encoding_method: FileEncodingMethod,
+        file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    ) -> Result<(Vec<u8>, u8), MayaError> {
+        let capacity_bits = self.carrier_capacity_bits(encoding)?;
+
+        read_payload(encoding, encoding_method, capacity_bits, |bits| {
+            self.extract_bits(bits, encoding, encoding_method, file_encoding_function_derivation)
+        })
+    }
+
+    fn pixel_channels(&mut self) -> Result<PixelChannels, MayaError> {
+        if !self.ready {
+            return Err(MayaError::NotParsed);
+        }
+
+        if let Some(palette) = &self.palette {
+            return Ok(PixelChannels::from_palette_indices(&self.unpacked_samples(), palette, self.ihdr.width as usize, self.ihdr.height as usize));
+        }
+
+        Ok(match (self.ihdr.color_type, self.ihdr.bit_depth) {
+            (COLOR_TYPE_GRAYSCALE, 1) => self.channels_pixels::<PngGrayPixel<1>>(),
+            (COLOR_TYPE_GRAYSCALE, 2) => self.channels_pixels::<PngGrayPixel<2>>(),
+            (COLOR_TYPE_GRAYSCALE, 4) => self.channels_pixels::<PngGrayPixel<4>>(),
+            (COLOR_TYPE_GRAYSCALE, 8) => self.channels_pixels::<PngGrayPixel<8>>(),
+            (COLOR_TYPE_GRAYSCALE, _) => self.channels_pixels::<PngGray16Pixel>(),
+            (COLOR_TYPE_GRAYSCALE_ALPHA, 8) => self.channels_pixels::<PngGrayAlphaPixel>(),
+            (COLOR_TYPE_GRAYSCALE_ALPHA, _) => self.channels_pixels::<PngGrayAlpha16Pixel>(),
+            (COLOR_TYPE_RGB, 8) => self.channels_pixels::<PngRgbPixel>(),
+            (COLOR_TYPE_RGB, _) => self.channels_pixels::<PngRgb16Pixel>(),
+            (COLOR_TYPE_RGBA, 8) => self.channels_pixels::<PngRgbaPixel>(),
+            _ => self.channels_pixels::<PngRgba16Pixel>(),
+        })
+    }
+
+    fn to_bytes(&mut self) -> Result<Vec<u8>, MayaError> {
+        if !self.ready {
+            return Err(MayaError::NotParsed);
+        }
+
+        let filtered = if self.ihdr.interlace_method == INTERLACE_ADAM7 {
+            interlace_adam7(
+                &self.pixel_data,
+                self.ihdr.width as usize,
+                self.ihdr.height as usize,
+                self.pixel_size as usize * self.ihdr.bit_depth as usize,
+                self.filter_bpp(),
+                &self.filter_types,
+            )
+        } else {
+            filter_scanlines(
+                &self.pixel_data,
+                self.ihdr.height as usize,
+                self.row_bytes,
+                self.filter_bpp(),
+                &self.filter_types,
+            )
+        };
+        let compressed = fdeflate::compress_to_vec(&filtered);
+
+        let mut output: Vec<u8> = Vec::with_capacity(compressed.len() + 1024);
+        output.extend_from_slice(&PNG_MAGIC);
+        write_chunk(&mut output, IHDR, &ihdr_to_bytes(&self.ihdr));
+
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            if index == self.idat_position {
+                for idat in compressed.chunks(IDAT_CHUNK_SIZE) {
+                    write_chunk(&mut output, IDAT, idat);
+                }
+            }
+            write_chunk(&mut output, chunk.chunk_type, &chunk.data);
+        }
+
+        if self.idat_position >= self.chunks.len() {
+            for idat in compressed.chunks(IDAT_CHUNK_SIZE) {
+                write_chunk(&mut output, IDAT, idat);
+            }
+        }
+
+        write_chunk(&mut output, IEND, &[]);
+
+        Ok(output)
+    }
+}
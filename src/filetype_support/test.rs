@@ -15,19 +15,933 @@
  * along with this program; if not, write to the Free Software
  * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
  */
-use crate::file_encoding_support::pixel::embed_lsb_data_right_left;
+/*
+    Helpers shared by every module below
+ */
+#[cfg(test)]
+mod fixtures {
+    /*
+        Where a test writes the image it is about to read back, kept out of the assets folder so a run leaves the tree clean
+     */
+    pub fn scratch(file: &str) -> String {
+        std::env::temp_dir().join(format!("veritasobscura-{file}")).to_string_lossy().into_owned()
+    }
+}
 
 #[cfg(test)]
 mod svg_tests{
 
 }
 
+#[cfg(test)]
+mod jpeg_tests{
+    use crate::file_encoding_support::file_encoding_support::{FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod, FileEncodingSupport};
+    use crate::error::error::MayaError;
+    use crate::filetype_support::bmp::BmpImageParser;
+    use crate::filetype_support::jpg::{JpegImageParser, JpegSegment, APP0, COM, DHT, SOF0};
+    use crate::filetype_support::test::fixtures::scratch;
+
+    #[test]
+    fn test_jpeg_parsing(){
+        let mut jpeg_image_parser = JpegImageParser::new("src/filetype_support/assets/sample-320x240.jpg");
+        jpeg_image_parser.parse_file().unwrap();
+
+        let frame = &jpeg_image_parser.frame;
+        assert_eq!(frame.marker, SOF0);
+        assert_eq!((frame.width, frame.height), (320, 240));
+        assert_eq!(frame.components.len(), 3);
+        assert_eq!((frame.components[0].h_sampling, frame.components[0].v_sampling), (2, 2));
+        assert_eq!((frame.components[1].h_sampling, frame.components[1].v_sampling), (1, 1));
+        assert_eq!((frame.components[0].blocks_wide, frame.components[0].blocks_high), (40, 30));
+        assert_eq!((frame.components[2].blocks_wide, frame.components[2].blocks_high), (20, 15));
+
+        assert!(jpeg_image_parser.quantization_tables[0].is_some());
+        assert!(jpeg_image_parser.quantization_tables[1].is_some());
+        assert_eq!(jpeg_image_parser.scans.len(), 1);
+        assert_eq!(jpeg_image_parser.scans[0].restart_interval, 4);
+
+        let markers: Vec<u8> = jpeg_image_parser.segments.iter().filter_map(|segment| match segment {
+            JpegSegment::Marker { marker, .. } => Some(*marker),
+            JpegSegment::Scan(_) => None,
+        }).collect();
+        assert_eq!(markers[0], APP0);
+        assert_eq!(markers[1], COM);
+    }
+
+    /*
+        The DC coefficient is 8 times the mean of the block minus 128 once dequantized, check the luma blocks
+        against the BMP the asset was cropped from at (256, 256)
+     */
+    #[test]
+    fn test_jpeg_dc_matches_bmp(){
+        let mut jpeg_image_parser = JpegImageParser::new("src/filetype_support/assets/sample-320x240.jpg");
+        jpeg_image_parser.parse_file().unwrap();
+
+        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
+        bmp_image_parser.parse_file().unwrap();
+        let start = bmp_image_parser.pixel_map.pixel_map_start as usize;
+
+        let quant = jpeg_image_parser.quantization_tables[0].unwrap().values[0] as f64;
+        let luma = &jpeg_image_parser.frame.components[0];
+
+        for (block_x, block_y) in [(0, 0), (7, 3), (39, 29), (20, 15)] {
+            let mut sum = 0.0;
+            for y in 0..8 {
+                for x in 0..8 {
+                    let row = 1023 - (256 + block_y * 8 + y);
+                    let offset = start + (row * 1024 + 256 + block_x * 8 + x) * 3;
+                    let (blue, green, red) = (bmp_image_parser.file_data[offset] as f64, bmp_image_parser.file_data[offset + 1] as f64, bmp_image_parser.file_data[offset + 2] as f64);
+                    sum += 0.299 * red + 0.587 * green + 0.114 * blue;
+                }
+            }
+
+            let dc = luma.coefficients[block_y * luma.blocks_wide + block_x][0] as f64;
+            assert!((dc * quant / 8.0 + 128.0 - sum / 64.0).abs() < 1.0, "block {block_x},{block_y}");
+        }
+    }
+
+    #[test]
+    fn test_jpeg_rewrite_is_lossless(){
+        for name in ["sample-320x240", "sample-200x150-multiscan"] {
+            let mut jpeg_image_parser = JpegImageParser::new(&format!("src/filetype_support/assets/{name}.jpg"));
+            jpeg_image_parser.parse_file().unwrap();
+            jpeg_image_parser.write_file(&scratch(&format!("{name}-TEST_REWRITE.jpg"))).unwrap();
+
+            let original = std::fs::read(format!("src/filetype_support/assets/{name}.jpg")).unwrap();
+            let rewritten = std::fs::read(scratch(&format!("{name}-TEST_REWRITE.jpg"))).unwrap();
+            assert!(original == rewritten, "{name} was not written back byte for byte");
+        }
+    }
+
+    /*
+        A frame header can ask for far more coefficients than the file could ever hold, that has to be an error and
+        not an allocation
+     */
+    #[test]
+    fn test_jpeg_huge_frame_is_refused(){
+        let mut jpeg = std::fs::read("src/filetype_support/assets/sample-320x240.jpg").unwrap();
+        let sof = jpeg.windows(2).position(|marker| marker == [0xFF, SOF0]).unwrap();
+        jpeg[sof + 5..sof + 9].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        for component in 0..jpeg[sof + 9] as usize {
+            jpeg[sof + 11 + component * 3] = 0x11;
+        }
+
+        assert!(matches!(JpegImageParser::from_bytes(jpeg), Err(MayaError::CorruptHeader(_))));
+    }
+
+    #[test]
+    fn test_jpeg_non_interleaved_scans(){
+        let mut jpeg_image_parser = JpegImageParser::new("src/filetype_support/assets/sample-200x150-multiscan.jpg");
+        jpeg_image_parser.parse_file().unwrap();
+
+        assert_eq!(jpeg_image_parser.scans.len(), 3);
+        for (index, scan) in jpeg_image_parser.scans.iter().enumerate() {
+            assert_eq!(scan.components.len(), 1);
+            assert_eq!(scan.components[0].component, index);
+            assert_eq!(scan.restart_interval, 0);
+        }
+
+        /*
+            Luma is stored padded to 13x10 MCUs (26x20 blocks) but a scan of luma alone only codes the 25x19
+            blocks the image covers, the rest stay zero
+         */
+        let luma = &jpeg_image_parser.frame.components[0];
+        assert_eq!((luma.blocks_wide, luma.blocks_high), (26, 20));
+        assert!(luma.coefficients[25].iter().all(|coefficient| *coefficient == 0));
+        assert!(luma.coefficients[19 * 26].iter().all(|coefficient| *coefficient == 0));
+        assert!(luma.coefficients[24].iter().any(|coefficient| *coefficient != 0));
+    }
+
+    #[test]
+    fn test_jpeg_coefficient_changes_survive(){
+        let mut jpeg_image_parser = JpegImageParser::new("src/filetype_support/assets/sample-320x240.jpg");
+        jpeg_image_parser.parse_file().unwrap();
+
+        let original = jpeg_image_parser.frame.components.clone();
+        jpeg_image_parser.frame.components[0].coefficients[100][5] += 3;
+        jpeg_image_parser.frame.components[1].coefficients[7][63] = -1;
+        jpeg_image_parser.frame.components[2].coefficients[299][0] -= 1;
+        jpeg_image_parser.write_file(&scratch("sample-320x240-TEST_COEFFICIENTS.jpg")).unwrap();
+
+        let mut jpeg_image_parser = JpegImageParser::new(&scratch("sample-320x240-TEST_COEFFICIENTS.jpg"));
+        jpeg_image_parser.parse_file().unwrap();
+
+        for (component, (before, after)) in original.iter().zip(jpeg_image_parser.frame.components.iter()).enumerate() {
+            for (block, (a, b)) in before.coefficients.iter().zip(after.coefficients.iter()).enumerate() {
+                match (component, block) {
+                    (0, 100) => assert_eq!(b[5], a[5] + 3),
+                    (1, 7) => assert_eq!(b[63], -1),
+                    (2, 299) => assert_eq!(b[0], a[0] - 1),
+                    _ => assert_eq!(a, b),
+                }
+            }
+        }
+    }
+
+    fn embed_and_retrieve(name: &str, encoding: FileEncoding, encoding_method: FileEncodingMethod, data: &[u8]) -> JpegImageParser {
+        let mut jpeg_image_parser = JpegImageParser::new(&format!("src/filetype_support/assets/{name}.jpg"));
+        jpeg_image_parser.parse_file().unwrap();
+        jpeg_image_parser.embed_data(&mut data.to_vec(), encoding, encoding_method, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        jpeg_image_parser.write_file(&scratch(&format!("{name}-TEST_{encoding:?}.jpg"))).unwrap();
+
+        let mut jpeg_image_parser = JpegImageParser::new(&scratch(&format!("{name}-TEST_{encoding:?}.jpg")));
+        jpeg_image_parser.parse_file().unwrap();
+        let retrieved = jpeg_image_parser.retrieve_data(encoding, encoding_method, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data);
+
+        jpeg_image_parser
+    }
+
+    #[test]
+    fn test_jpeg_jsteg_round_trip(){
+        let data = b"JSteg hides in the LSBs of the quantized AC coefficients".to_vec();
+        for name in ["sample-320x240", "sample-200x150-multiscan"] {
+            for encoding_method in [FileEncodingMethod::LeftToRight, FileEncodingMethod::PolynomialFunction] {
+                embed_and_retrieve(name, FileEncoding::JSteg, encoding_method, &data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_jpeg_f5_round_trip(){
+        let data: Vec<u8> = (0..40u32).map(|i| (i * 91 + 7) as u8).collect();
+        for name in ["sample-320x240", "sample-200x150-multiscan"] {
+            for encoding_method in [FileEncodingMethod::LeftToRight, FileEncodingMethod::RightToLeft] {
+                embed_and_retrieve(name, FileEncoding::F5, encoding_method, &data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_jpeg_keyed_round_trip(){
+        let key = FileEncodingFunctionDerivation::from_passphrase("jpeg passphrase");
+        let data = b"keyed coefficients".to_vec();
+
+        for encoding in [FileEncoding::JSteg, FileEncoding::F5] {
+            let mut jpeg_image_parser = JpegImageParser::new("src/filetype_support/assets/sample-320x240.jpg");
+            jpeg_image_parser.parse_file().unwrap();
+            jpeg_image_parser.embed_data(&mut data.clone(), encoding, FileEncodingMethod::LeftToRight, key).unwrap();
+            jpeg_image_parser.write_file(&scratch("sample-320x240-TEST_KEYED.jpg")).unwrap();
+
+            let mut jpeg_image_parser = JpegImageParser::new(&scratch("sample-320x240-TEST_KEYED.jpg"));
+            jpeg_image_parser.parse_file().unwrap();
+            assert_eq!(jpeg_image_parser.retrieve_data(encoding, FileEncodingMethod::LeftToRight, key).unwrap(), data, "{encoding:?}");
+        }
+    }
+
+    /*
+        Neither method is allowed near the DC coefficients, and F5 only ever moves a coefficient towards zero
+     */
+    #[test]
+    fn test_jpeg_coefficient_embedding_changes(){
+        let mut original = JpegImageParser::new("src/filetype_support/assets/sample-320x240.jpg");
+        original.parse_file().unwrap();
+
+        let data = b"a short message".to_vec();
+        for encoding in [FileEncoding::JSteg, FileEncoding::F5] {
+            let embedded = embed_and_retrieve("sample-320x240", encoding, FileEncodingMethod::LeftToRight, &data);
+
+            for (before, after) in original.frame.components.iter().zip(embedded.frame.components.iter()) {
+                for (a, b) in before.coefficients.iter().zip(after.coefficients.iter()) {
+                    assert_eq!(a[0], b[0]);
+                }
+            }
+
+            if encoding == FileEncoding::F5 {
+                for (a, b) in original.ac_coefficients().iter().zip(embedded.ac_coefficients().iter()) {
+                    assert!(b.abs() <= a.abs());
+                }
+            }
+        }
+    }
+
+    /*
+        Rebuilt tables have to cover every symbol in use, a file written with them parses back to the same coefficients
+     */
+    #[test]
+    fn test_jpeg_rebuilt_huffman_tables(){
+        let mut jpeg_image_parser = JpegImageParser::new("src/filetype_support/assets/sample-200x150-multiscan.jpg");
+        jpeg_image_parser.parse_file().unwrap();
+
+        let original = jpeg_image_parser.frame.components.clone();
+        jpeg_image_parser.rebuild_huffman_tables();
+        assert_eq!(jpeg_image_parser.segments.iter().filter(|segment| matches!(segment, JpegSegment::Marker { marker: DHT, .. })).count(), 1);
+        jpeg_image_parser.write_file(&scratch("sample-200x150-multiscan-TEST_HUFFMAN.jpg")).unwrap();
+
+        let mut jpeg_image_parser = JpegImageParser::new(&scratch("sample-200x150-multiscan-TEST_HUFFMAN.jpg"));
+        jpeg_image_parser.parse_file().unwrap();
+        for (before, after) in original.iter().zip(jpeg_image_parser.frame.components.iter()) {
+            assert_eq!(before.coefficients, after.coefficients);
+        }
+    }
+}
+
+#[cfg(test)]
+mod png_tests{
+    use crate::file_encoding_support::encryption::{decrypt_payload, encrypt_payload_with_params, is_encrypted, EncryptionError, KdfParams};
+    use crate::file_encoding_support::file_encoding_support::{FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod, FileEncodingSupport};
+    use crate::filetype_support::bmp::BmpImageParser;
+    use crate::file_encoding_support::palette::PaletteEmbedding;
+    use crate::file_encoding_support::pixel::{capacity_bits, embed_color_data_left_right, extract_color_data_left_right};
+    use crate::error::error::MayaError;
+    use crate::mathematics_support::mathematics_support::crc32;
+    use crate::filetype_support::png::{adam7_pass_size, PngImageParser, PngGrayPixel, PngRgbPixel, PngRgb16Pixel, PngRgba16Pixel, bKGD, gAMA, pHYs, tEXt, sRGB, INTERLACE_ADAM7};
+    use crate::filetype_support::test::fixtures::scratch;
+
+    #[test]
+    fn test_png_image_parsing(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-256x256.png");
+        png_image_parser.parse_file().unwrap();
+
+        assert_eq!(png_image_parser.ihdr.width, 256);
+        assert_eq!(png_image_parser.ihdr.height, 256);
+        assert_eq!(png_image_parser.ihdr.bit_depth, 8);
+        assert_eq!(png_image_parser.pixel_size, 3);
+        assert_eq!(png_image_parser.pixel_data.len(), 256 * 256 * 3);
+
+        // The sample cycles through every filter type so all of them get exercised
+        for filter_type in 0..5u8 {
+            assert!(png_image_parser.filter_types.contains(&filter_type));
+        }
+
+        assert_eq!(png_image_parser.chunks.len(), 3);
+        assert!(png_image_parser.chunks[0].chunk_type == pHYs);
+        assert!(png_image_parser.chunks[1].chunk_type == gAMA);
+        assert!(png_image_parser.chunks[2].chunk_type == tEXt);
+        assert_eq!(png_image_parser.idat_position, 2);
+    }
+
+    /*
+        The png sample is a 256x256 crop out of the middle of the bmp sample so the decoded pixels must match it exactly
+     */
+    #[test]
+    fn test_png_pixels_match_bmp(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-256x256.png");
+        png_image_parser.parse_file().unwrap();
+
+        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
+        bmp_image_parser.parse_file().unwrap();
+
+        let bmp_start = bmp_image_parser.pixel_map.pixel_map_start as usize;
+
+        for y in 0..256usize {
+            // bmp rows are stored bottom up and in BGR order
+            let bmp_row = 1023 - (384 + y);
+            for x in 0..256usize {
+                let bmp_offset = bmp_start + (bmp_row * 1024 + 384 + x) * 3;
+                let png_offset = (y * 256 + x) * 3;
+
+                assert_eq!(png_image_parser.pixel_data[png_offset], bmp_image_parser.file_data[bmp_offset + 2]);
+                assert_eq!(png_image_parser.pixel_data[png_offset + 1], bmp_image_parser.file_data[bmp_offset + 1]);
+                assert_eq!(png_image_parser.pixel_data[png_offset + 2], bmp_image_parser.file_data[bmp_offset]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_png_write_preserves_image(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-256x256.png");
+        png_image_parser.parse_file().unwrap();
+        png_image_parser.write_file(&scratch("sample-256x256-TEST_REWRITE.png")).unwrap();
+
+        let mut rewritten = PngImageParser::new(&scratch("sample-256x256-TEST_REWRITE.png"));
+        rewritten.parse_file().unwrap();
+
+        assert_eq!(rewritten.pixel_data, png_image_parser.pixel_data);
+        assert_eq!(rewritten.filter_types, png_image_parser.filter_types);
+        assert_eq!(rewritten.chunks.len(), png_image_parser.chunks.len());
+        for (a, b) in rewritten.chunks.iter().zip(png_image_parser.chunks.iter()) {
+            assert!(a.chunk_type == b.chunk_type);
+            assert_eq!(a.data, b.data);
+        }
+        assert_eq!(rewritten.idat_position, png_image_parser.idat_position);
+    }
+
+    #[test]
+    fn test_png_lsb_round_trip(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-256x256.png");
+        png_image_parser.parse_file().unwrap();
+
+        let mut data_vec : Vec<u8> = "Hidden in the low bits of a deflated PNG".as_bytes().to_vec();
+        png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        png_image_parser.write_file(&scratch("sample-256x256-TEST_EMBED.png")).unwrap();
+
+        let mut png_image_parser = PngImageParser::new(&scratch("sample-256x256-TEST_EMBED.png"));
+        png_image_parser.parse_file().unwrap();
+
+        let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    #[test]
+    fn test_png_rgba_lsb_round_trip(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-256x256-rgba.png");
+        png_image_parser.parse_file().unwrap();
+
+        assert_eq!(png_image_parser.pixel_size, 4);
+        assert!(png_image_parser.chunks[0].chunk_type == sRGB);
+
+        let mut data_vec : Vec<u8> = "Alpha channels carry bits too".repeat(64).as_bytes().to_vec();
+        png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        png_image_parser.write_file(&scratch("sample-256x256-rgba-TEST_EMBED.png")).unwrap();
+
+        let mut png_image_parser = PngImageParser::new(&scratch("sample-256x256-rgba-TEST_EMBED.png"));
+        png_image_parser.parse_file().unwrap();
+
+        let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    /*
+        Walk the chunks of a png on disk and inflate its IDAT stream, used to compare the filtered data byte for byte
+     */
+    fn inflated_idat(path: &str) -> Vec<u8> {
+        let file_data = std::fs::read(path).unwrap();
+        let mut offset = 8;
+        let mut idat = Vec::new();
+
+        while offset < file_data.len() {
+            let length = u32::from_be_bytes(file_data[offset..offset + 4].try_into().unwrap()) as usize;
+            if &file_data[offset + 4..offset + 8] == b"IDAT" {
+                idat.extend_from_slice(&file_data[offset + 8..offset + 8 + length]);
+            }
+            offset += length + 12;
+        }
+
+        fdeflate::decompress_to_vec(&idat).unwrap()
+    }
+
+    #[test]
+    fn test_png_adam7_pass_sizes(){
+        // 8x8 is one full Adam7 block, every pass gets a share of the 64 pixels
+        let sizes: Vec<(usize, usize)> = (0..7).map(|pass| adam7_pass_size(8, 8, pass)).collect();
+        assert_eq!(sizes, vec![(1, 1), (1, 1), (2, 1), (2, 2), (4, 2), (4, 4), (8, 4)]);
+
+        // Passes that fall entirely outside of a tiny image are empty
+        assert_eq!(adam7_pass_size(1, 1, 0), (1, 1));
+        assert_eq!(adam7_pass_size(1, 1, 1), (0, 1));
+        assert_eq!(adam7_pass_size(1, 1, 6), (1, 0));
+    }
+
+    /*
+        A real PNG with its IHDR rewritten to claim some other size, the image data is left as it was
+     */
+    fn with_ihdr_size(name: &str, width: u32, height: u32, interlace: u8) -> Vec<u8> {
+        let mut png = std::fs::read(format!("src/filetype_support/assets/{name}")).unwrap();
+        png[16..20].copy_from_slice(&width.to_be_bytes());
+        png[20..24].copy_from_slice(&height.to_be_bytes());
+        png[28] = interlace;
+        let crc = crc32(&png[12..29]);
+        png[29..33].copy_from_slice(&crc.to_be_bytes());
+        png
+    }
+
+    #[test]
+    fn test_png_adam7_huge_ihdr_is_refused(){
+        for (name, width, height) in [("sample-256x256.png", 1u32 << 31, 1 << 31), ("sample-128x128-rgba16.png", u32::MAX, u32::MAX), ("sample-256x256.png", 40000, 40000)] {
+            let png = with_ihdr_size(name, width, height, INTERLACE_ADAM7);
+            assert!(matches!(PngImageParser::from_bytes(png), Err(MayaError::CorruptHeader(_))), "{name} {width}x{height}");
+        }
+    }
+
+    /*
+        IDAT is only inflated as far as IHDR says the image goes, a stream that keeps going is cut off there
+     */
+    #[test]
+    fn test_png_inflate_stops_at_ihdr_size(){
+        for (name, width, height) in [("sample-256x256.png", 1u32 << 31, 1 << 31), ("sample-128x128-rgba16.png", u32::MAX, 3)] {
+            let png = with_ihdr_size(name, width, height, 0);
+            assert!(matches!(PngImageParser::from_bytes(png), Err(MayaError::CorruptHeader(_))), "{name} {width}x{height}");
+        }
+
+        let full = PngImageParser::from_bytes(std::fs::read("src/filetype_support/assets/sample-256x256.png").unwrap()).unwrap();
+        let cut = PngImageParser::from_bytes(with_ihdr_size("sample-256x256.png", 256, 100, 0)).unwrap();
+        assert_eq!(cut.pixel_data, full.pixel_data[..256 * 3 * 100]);
+    }
+
+    #[test]
+    fn test_png_adam7_pixels_match_bmp(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-253x251-adam7.png");
+        png_image_parser.parse_file().unwrap();
+
+        assert_eq!(png_image_parser.ihdr.interlace_method, INTERLACE_ADAM7);
+        assert_eq!(png_image_parser.pixel_data.len(), 253 * 251 * 3);
+
+        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
+        bmp_image_parser.parse_file().unwrap();
+
+        let bmp_start = bmp_image_parser.pixel_map.pixel_map_start as usize;
+
+        for y in 0..251usize {
+            let bmp_row = 1023 - (384 + y);
+            for x in 0..253usize {
+                let bmp_offset = bmp_start + (bmp_row * 1024 + 384 + x) * 3;
+                let png_offset = (y * 253 + x) * 3;
+
+                assert_eq!(png_image_parser.pixel_data[png_offset], bmp_image_parser.file_data[bmp_offset + 2]);
+                assert_eq!(png_image_parser.pixel_data[png_offset + 1], bmp_image_parser.file_data[bmp_offset + 1]);
+                assert_eq!(png_image_parser.pixel_data[png_offset + 2], bmp_image_parser.file_data[bmp_offset]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_png_adam7_write_keeps_interlacing(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-253x251-adam7.png");
+        png_image_parser.parse_file().unwrap();
+        png_image_parser.write_file(&scratch("sample-253x251-adam7-TEST_REWRITE.png")).unwrap();
+
+        // Same passes, same filters, so the inflated stream must be identical to the original
+        assert_eq!(
+            inflated_idat(&scratch("sample-253x251-adam7-TEST_REWRITE.png")),
+            inflated_idat("src/filetype_support/assets/sample-253x251-adam7.png")
+        );
+
+        let mut rewritten = PngImageParser::new(&scratch("sample-253x251-adam7-TEST_REWRITE.png"));
+        rewritten.parse_file().unwrap();
+
+        assert_eq!(rewritten.ihdr.interlace_method, INTERLACE_ADAM7);
+        assert_eq!(rewritten.pixel_data, png_image_parser.pixel_data);
+    }
+
+    #[test]
+    fn test_png_adam7_lsb_round_trip(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-253x251-adam7.png");
+        png_image_parser.parse_file().unwrap();
+
+        let mut data_vec : Vec<u8> = "Seven passes, one message".repeat(100).as_bytes().to_vec();
+        png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        png_image_parser.write_file(&scratch("sample-253x251-adam7-TEST_EMBED.png")).unwrap();
+
+        let mut png_image_parser = PngImageParser::new(&scratch("sample-253x251-adam7-TEST_EMBED.png"));
+        png_image_parser.parse_file().unwrap();
+
+        assert_eq!(png_image_parser.ihdr.interlace_method, INTERLACE_ADAM7);
+
+        let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    #[test]
+    fn test_png_palette_round_trip(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-250x200-palette.png");
+        png_image_parser.parse_file().unwrap();
+
+        let original_palette = png_image_parser.palette.clone().unwrap();
+        assert_eq!(original_palette.entries.len(), 252);
+        assert_eq!(original_palette.entries[40].alpha, 128);
+        assert_eq!(original_palette.entries[100].alpha, 255);
+        let original_indices = png_image_parser.pixel_data.clone();
+
+        let mut data_vec : Vec<u8> = "Index parity in luminance order".as_bytes().to_vec();
+        png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        png_image_parser.write_file(&scratch("sample-250x200-palette-TEST_EMBED.png")).unwrap();
+
+        let mut png_image_parser = PngImageParser::new(&scratch("sample-250x200-palette-TEST_EMBED.png"));
+        png_image_parser.parse_file().unwrap();
+
+        // The palette itself is left alone and every changed pixel only moved to its luminance neighbour
+        assert_eq!(png_image_parser.palette.clone().unwrap(), original_palette);
+        let ranks = original_palette.ranks();
+        for (before, after) in original_indices.iter().zip(png_image_parser.pixel_data.iter()) {
+            let before = ranks[*before as usize].unwrap();
+            let after = ranks[*after as usize].unwrap();
+            assert!(before == after || before ^ 1 == after);
+        }
+
+        let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    #[test]
+    fn test_png_palette_reorder_round_trip(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-250x200-palette.png");
+        png_image_parser.parse_file().unwrap();
+
+        let original_palette = png_image_parser.palette.clone().unwrap();
+        let background = original_palette.entries[7];
+
+        png_image_parser.encoding_parameters.palette_embedding = PaletteEmbedding::ReorderPalette;
+        let mut data_vec : Vec<u8> = "The palette is rewritten so index parity carries the bits".as_bytes().to_vec();
+        png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        png_image_parser.write_file(&scratch("sample-250x200-palette-TEST_REORDER.png")).unwrap();
+
+        let mut png_image_parser = PngImageParser::new(&scratch("sample-250x200-palette-TEST_REORDER.png"));
+        png_image_parser.parse_file().unwrap();
+
+        // Same colors, now stored in luminance order with the transparent entries first
+        let palette = png_image_parser.palette.clone().unwrap();
+        assert_eq!(palette.luminance_order(), (0..palette.entries.len()).collect::<Vec<usize>>());
+        assert_eq!(palette.entries[0].alpha, 64);
+        assert_eq!(palette.entries[1].alpha, 128);
+
+        // bKGD still points at the same color
+        let bkgd = png_image_parser.chunks.iter().find(|chunk| chunk.chunk_type == bKGD).unwrap();
+        assert_eq!(palette.entries[bkgd.data[0] as usize], background);
+
+        let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    #[test]
+    fn test_png_4bit_palette_round_trip(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-250x200-palette4.png");
+        png_image_parser.parse_file().unwrap();
+
+        assert_eq!(png_image_parser.ihdr.bit_depth, 4);
+        assert_eq!(png_image_parser.row_bytes, 125);
+        assert_eq!(png_image_parser.palette.as_ref().unwrap().entries.len(), 16);
+
+        let mut data_vec : Vec<u8> = "Two indices per byte".repeat(20).as_bytes().to_vec();
+        png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        png_image_parser.write_file(&scratch("sample-250x200-palette4-TEST_EMBED.png")).unwrap();
+
+        let mut png_image_parser = PngImageParser::new(&scratch("sample-250x200-palette4-TEST_EMBED.png"));
+        png_image_parser.parse_file().unwrap();
+
+        let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    #[test]
+    fn test_png_grayscale_round_trip(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-256x256-gray.png");
+        png_image_parser.parse_file().unwrap();
+
+        assert_eq!(png_image_parser.pixel_size, 1);
+        assert_eq!(png_image_parser.row_bytes, 256);
+        let original = png_image_parser.pixel_data.clone();
+
+        let mut data_vec : Vec<u8> = "One sample per pixel".repeat(40).as_bytes().to_vec();
+        png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert!(original.iter().zip(png_image_parser.pixel_data.iter()).all(|(a, b)| a & !1 == b & !1));
+        png_image_parser.write_file(&scratch("sample-256x256-gray-TEST_EMBED.png")).unwrap();
+
+        let mut png_image_parser = PngImageParser::new(&scratch("sample-256x256-gray-TEST_EMBED.png"));
+        png_image_parser.parse_file().unwrap();
+
+        let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    #[test]
+    fn test_png_gray_alpha_round_trip(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-256x256-gray-alpha.png");
+        png_image_parser.parse_file().unwrap();
+
+        assert_eq!(png_image_parser.pixel_size, 2);
+
+        let mut data_vec : Vec<u8> = "Gray and alpha".repeat(64).as_bytes().to_vec();
+        png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        png_image_parser.write_file(&scratch("sample-256x256-gray-alpha-TEST_EMBED.png")).unwrap();
+
+        let mut png_image_parser = PngImageParser::new(&scratch("sample-256x256-gray-alpha-TEST_EMBED.png"));
+        png_image_parser.parse_file().unwrap();
+
+        let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    #[test]
+    fn test_png_4bit_grayscale_round_trip(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-250x200-gray4.png");
+        png_image_parser.parse_file().unwrap();
+
+        assert_eq!(png_image_parser.ihdr.bit_depth, 4);
+        assert_eq!(png_image_parser.row_bytes, 125);
+        let original = png_image_parser.pixel_data.clone();
+
+        let mut data_vec : Vec<u8> = "Two samples per byte".repeat(20).as_bytes().to_vec();
+        png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+
+        // Only the low bit of each nibble may move
+        assert!(original.iter().zip(png_image_parser.pixel_data.iter()).all(|(a, b)| (a ^ b) & 0xEE == 0));
+        png_image_parser.write_file(&scratch("sample-250x200-gray4-TEST_EMBED.png")).unwrap();
+
+        let mut png_image_parser = PngImageParser::new(&scratch("sample-250x200-gray4-TEST_EMBED.png"));
+        png_image_parser.parse_file().unwrap();
+
+        let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    #[test]
+    fn test_png_16bit_capacity(){
+        assert_eq!(capacity_bits::<PngRgbPixel>(128, 128, FileEncoding::Lsb), 128 * 128 * 3);
+        assert_eq!(capacity_bits::<PngRgb16Pixel>(128, 128, FileEncoding::Lsb), 128 * 128 * 3 * 4);
+        assert_eq!(capacity_bits::<PngRgba16Pixel>(128, 128, FileEncoding::Lsb), 128 * 128 * 4 * 4);
+        assert_eq!(capacity_bits::<PngGrayPixel<2>>(128, 128, FileEncoding::Lsb), 128 * 128);
+    }
+
+    #[test]
+    fn test_png_pvd_round_trip(){
+        for name in ["sample-256x256", "sample-256x256-gray", "sample-128x128-rgba16"] {
+            let mut png_image_parser = PngImageParser::new(&format!("src/filetype_support/assets/{name}.png"));
+            png_image_parser.parse_file().unwrap();
+
+            let mut data_vec : Vec<u8> = "Wu and Tsai".repeat(100).as_bytes().to_vec();
+            png_image_parser.embed_data(&mut data_vec, FileEncoding::PixelValueDifferencing, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+            png_image_parser.write_file(&scratch(&format!("{name}-TEST_PVD.png"))).unwrap();
+
+            let mut png_image_parser = PngImageParser::new(&scratch(&format!("{name}-TEST_PVD.png")));
+            png_image_parser.parse_file().unwrap();
+
+            let retrieved = png_image_parser.retrieve_data(FileEncoding::PixelValueDifferencing, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+            assert_eq!(retrieved, data_vec, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_png_hamming_round_trip(){
+        for name in ["sample-256x256-gray", "sample-128x128-rgb16"] {
+            let mut png_image_parser = PngImageParser::new(&format!("src/filetype_support/assets/{name}.png"));
+            png_image_parser.parse_file().unwrap();
+
+            let mut data_vec : Vec<u8> = "Matrix embedding".repeat(40).as_bytes().to_vec();
+            png_image_parser.embed_data(&mut data_vec, FileEncoding::HammingMatrix, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+            png_image_parser.write_file(&scratch(&format!("{name}-TEST_HAMMING.png"))).unwrap();
+
+            let mut png_image_parser = PngImageParser::new(&scratch(&format!("{name}-TEST_HAMMING.png")));
+            png_image_parser.parse_file().unwrap();
+
+            let retrieved = png_image_parser.retrieve_data(FileEncoding::HammingMatrix, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+            assert_eq!(retrieved, data_vec, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_png_keyed_round_trip(){
+        let key = FileEncodingFunctionDerivation::from_passphrase("png passphrase");
+
+        for name in ["sample-256x256", "sample-250x200-gray4", "sample-128x128-rgba16"] {
+            let mut png_image_parser = PngImageParser::new(&format!("src/filetype_support/assets/{name}.png"));
+            png_image_parser.parse_file().unwrap();
+
+            let mut data_vec : Vec<u8> = "Keyed PNG".repeat(20).as_bytes().to_vec();
+            png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::RightToLeft, key).unwrap();
+            png_image_parser.write_file(&scratch(&format!("{name}-TEST_KEYED.png"))).unwrap();
+
+            let mut png_image_parser = PngImageParser::new(&scratch(&format!("{name}-TEST_KEYED.png")));
+            png_image_parser.parse_file().unwrap();
+
+            let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::RightToLeft, key).unwrap();
+            assert_eq!(retrieved, data_vec, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_png_lsb_matching_round_trip(){
+        let key = FileEncodingFunctionDerivation::from_passphrase("png matching");
+
+        for name in ["sample-256x256", "sample-256x256-gray", "sample-250x200-gray4", "sample-128x128-rgb16"] {
+            for derivation in [FileEncodingFunctionDerivation::MethodBased, key] {
+                let mut png_image_parser = PngImageParser::new(&format!("src/filetype_support/assets/{name}.png"));
+                png_image_parser.parse_file().unwrap();
+
+                let mut data_vec : Vec<u8> = "Plus or minus one".repeat(20).as_bytes().to_vec();
+                png_image_parser.embed_data(&mut data_vec, FileEncoding::LsbMatching, FileEncodingMethod::LeftToRight, derivation).unwrap();
+                png_image_parser.write_file(&scratch(&format!("{name}-TEST_MATCHING.png"))).unwrap();
+
+                let mut png_image_parser = PngImageParser::new(&scratch(&format!("{name}-TEST_MATCHING.png")));
+                png_image_parser.parse_file().unwrap();
+
+                let retrieved = png_image_parser.retrieve_data(FileEncoding::LsbMatching, FileEncodingMethod::LeftToRight, derivation).unwrap();
+                assert_eq!(retrieved, data_vec, "{name} {derivation:?}");
+            }
+        }
+
+        // Palette indices aren't values, stepping them by one picks an unrelated color
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-250x200-palette.png");
+        png_image_parser.parse_file().unwrap();
+        assert!(png_image_parser.embed_data(&mut b"no".to_vec(), FileEncoding::LsbMatching, FileEncodingMethod::LeftToRight, key).is_err());
+    }
+
+    #[test]
+    fn test_png_curve_round_trip(){
+        let curves = [FileEncodingMethod::TopToBottom, FileEncodingMethod::SinWave, FileEncodingMethod::CosWave, FileEncodingMethod::PolynomialFunction, FileEncodingMethod::FractalFunction];
+        let key = FileEncodingFunctionDerivation::from_passphrase("curves");
+
+        for (name, encoding) in [("sample-256x256", FileEncoding::Lsb), ("sample-128x128-rgba16", FileEncoding::HammingMatrix), ("sample-256x256", FileEncoding::PixelValueDifferencing)] {
+            for method in curves {
+                for derivation in [FileEncodingFunctionDerivation::MethodBased, key] {
+                    let mut png_image_parser = PngImageParser::new(&format!("src/filetype_support/assets/{name}.png"));
+                    png_image_parser.parse_file().unwrap();
+
+                    let mut data_vec : Vec<u8> = "Along the curve ".repeat(8).as_bytes().to_vec();
+                    png_image_parser.embed_data(&mut data_vec, encoding, method, derivation).unwrap();
+                    png_image_parser.write_file(&scratch(&format!("{name}-TEST_CURVE.png"))).unwrap();
+
+                    let mut png_image_parser = PngImageParser::new(&scratch(&format!("{name}-TEST_CURVE.png")));
+                    png_image_parser.parse_file().unwrap();
+
+                    let retrieved = png_image_parser.retrieve_data(encoding, method, derivation).unwrap();
+                    assert_eq!(retrieved, data_vec, "{name} {encoding:?} {method:?} {derivation:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_png_encrypted_round_trip(){
+        let params = KdfParams { memory_kib: 256, iterations: 1, parallelism: 1 };
+        let message = b"encrypted before it ever touches a pixel".to_vec();
+
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-256x256.png");
+        png_image_parser.parse_file().unwrap();
+
+        let mut data_vec = encrypt_payload_with_params(&message, "png passphrase", params).unwrap();
+        png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        png_image_parser.write_file(&scratch("sample-256x256-TEST_ENCRYPTED.png")).unwrap();
+
+        let mut png_image_parser = PngImageParser::new(&scratch("sample-256x256-TEST_ENCRYPTED.png"));
+        png_image_parser.parse_file().unwrap();
+
+        let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert!(is_encrypted(&retrieved));
+        assert_eq!(decrypt_payload(&retrieved, "png passphrase"), Ok(message));
+        assert_eq!(decrypt_payload(&retrieved, "not the passphrase"), Err(EncryptionError::WrongKeyOrNoPayload));
+    }
+
+    #[test]
+    fn test_png_48bit_lsb_round_trip(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-128x128-rgb16.png");
+        png_image_parser.parse_file().unwrap();
+
+        assert_eq!(png_image_parser.ihdr.bit_depth, 16);
+        assert_eq!(png_image_parser.row_bytes, 128 * 6);
+        let original = png_image_parser.pixel_data.clone();
+
+        // Far more than the 3 bits per pixel an 8 bit image could take
+        let mut data_vec : Vec<u8> = "Sixteen bit samples hide four bits apiece".repeat(500).as_bytes().to_vec();
+        assert!(data_vec.len() * 8 > 128 * 128 * 3);
+
+        png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+
+        // Samples are big endian, the high byte of each one must be untouched and the low byte only in its low nibble
+        for (index, (a, b)) in original.iter().zip(png_image_parser.pixel_data.iter()).enumerate() {
+            if index % 2 == 0 {
+                assert_eq!(a, b);
+            } else {
+                assert_eq!(a & 0xF0, b & 0xF0);
+            }
+        }
+        png_image_parser.write_file(&scratch("sample-128x128-rgb16-TEST_EMBED.png")).unwrap();
+
+        let mut png_image_parser = PngImageParser::new(&scratch("sample-128x128-rgb16-TEST_EMBED.png"));
+        png_image_parser.parse_file().unwrap();
+
+        let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    #[test]
+    fn test_png_64bit_lsb_round_trip(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-128x128-rgba16.png");
+        png_image_parser.parse_file().unwrap();
+
+        assert_eq!(png_image_parser.pixel_size, 4);
+        assert_eq!(png_image_parser.row_bytes, 128 * 8);
+
+        let mut data_vec : Vec<u8> = "Alpha at sixteen bits".repeat(300).as_bytes().to_vec();
+        png_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        png_image_parser.write_file(&scratch("sample-128x128-rgba16-TEST_EMBED.png")).unwrap();
+
+        let mut png_image_parser = PngImageParser::new(&scratch("sample-128x128-rgba16-TEST_EMBED.png"));
+        png_image_parser.parse_file().unwrap();
+
+        let retrieved = png_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    #[test]
+    fn test_png_16bit_color_round_trip(){
+        let mut png_image_parser = PngImageParser::new("src/filetype_support/assets/sample-128x128-rgb16.png");
+        png_image_parser.parse_file().unwrap();
+
+        let data_vec : Vec<u8> = "Parity over 48 bits".repeat(20).as_bytes().to_vec();
+        embed_color_data_left_right::<PngRgb16Pixel>(&data_vec, &mut png_image_parser.pixel_data, 128, 128, 0, 6).unwrap();
+
+        let retrieved = extract_color_data_left_right::<PngRgb16Pixel>(&mut png_image_parser.pixel_data, 128, 128, 0, 6, (data_vec.len() * 8) as u64);
+        assert_eq!(retrieved[0..data_vec.len()], data_vec);
+    }
+}
+
 #[cfg(test)]
 mod bmp_tests{
     use std::process::exit;
-    use crate::file_encoding_support::file_encoding_support::FileEncodingSupport;
+    use crate::compression::compression::{compress, decompress, CompressionCodec};
+    use crate::file_encoding_support::file_encoding_support::{FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod, FileEncodingSupport};
     use crate::file_encoding_support::pixel::{embed_color_data_left_right, embed_color_data_right_left, embed_lsb_data_left_right, embed_lsb_data_right_left, extract_color_data_left_right, extract_color_data_right_left, extract_lsb_data_left_right, extract_lsb_data_right_left};
-    use crate::filetype_support::bmp::{BmpImageParser, RgbPixel, RgbaPixel};
+    use crate::file_encoding_support::palette::PaletteEmbedding;
+    use crate::error::error::MayaError;
+    use crate::filetype_support::bmp::{decode_rle, encode_rle, BmpImageParser, RgbPixel, RgbaPixel, RleOutput, BI_ALPHABITFIELDS, BI_BITFIELDS, BI_RGB, BI_RLE4, BI_RLE8};
+    use crate::{embed, embed_with_report, extract, EmbedOptions};
+    use crate::filetype_support::test::fixtures::scratch;
+
+    #[test]
+    fn test_bmp_curve_round_trip(){
+        for method in [FileEncodingMethod::SinWave, FileEncodingMethod::FractalFunction] {
+            for name in ["sample-1024x1024", "sample-250x200-8bit"] {
+                let mut bmp_image_parser = BmpImageParser::new(&format!("src/filetype_support/assets/{name}.bmp"));
+                bmp_image_parser.parse_file().unwrap();
+
+                let mut data_vec : Vec<u8> = "Wavy BMP".repeat(10).as_bytes().to_vec();
+                bmp_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, method, FileEncodingFunctionDerivation::MethodBased).unwrap();
+                bmp_image_parser.write_file(&scratch(&format!("{name}-TEST_CURVE.bmp"))).unwrap();
+
+                let mut bmp_image_parser = BmpImageParser::new(&scratch(&format!("{name}-TEST_CURVE.bmp")));
+                bmp_image_parser.parse_file().unwrap();
+
+                let retrieved = bmp_image_parser.retrieve_data(FileEncoding::Lsb, method, FileEncodingFunctionDerivation::MethodBased).unwrap();
+                assert_eq!(retrieved, data_vec, "{name} {method:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_bmp_clustered_round_trip(){
+        let methods = [FileEncodingMethod::HilbertCurve, FileEncodingMethod::MortonOrder, FileEncodingMethod::SpiralInward, FileEncodingMethod::SpiralOutward, FileEncodingMethod::BlockRaster];
+        let key = FileEncodingFunctionDerivation::from_passphrase("clusters");
+
+        for (name, encoding) in [("sample-1024x1024", FileEncoding::Lsb), ("sample-1024x1024", FileEncoding::HammingMatrix), ("sample-1024x1024", FileEncoding::PixelValueDifferencing), ("sample-250x200-8bit", FileEncoding::Lsb)] {
+            for method in methods {
+                for derivation in [FileEncodingFunctionDerivation::MethodBased, key] {
+                    let mut bmp_image_parser = BmpImageParser::new(&format!("src/filetype_support/assets/{name}.bmp"));
+                    bmp_image_parser.parse_file().unwrap();
+
+                    let mut data_vec : Vec<u8> = "Clustered BMP ".repeat(10).as_bytes().to_vec();
+                    bmp_image_parser.embed_data(&mut data_vec, encoding, method, derivation).unwrap();
+                    bmp_image_parser.write_file(&scratch(&format!("{name}-TEST_CLUSTERED.bmp"))).unwrap();
+
+                    let mut bmp_image_parser = BmpImageParser::new(&scratch(&format!("{name}-TEST_CLUSTERED.bmp")));
+                    bmp_image_parser.parse_file().unwrap();
+
+                    let retrieved = bmp_image_parser.retrieve_data(encoding, method, derivation).unwrap();
+                    assert_eq!(retrieved, data_vec, "{name} {encoding:?} {method:?} {derivation:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bmp_compressed_round_trip(){
+        let message = "the quick brown fox jumps over the lazy dog, then does it again, and again, and again".as_bytes();
+        let (codec, mut packed) = compress(message, CompressionCodec::ShortText);
+        assert_eq!(codec, CompressionCodec::ShortText);
+
+        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
+        bmp_image_parser.parse_file().unwrap();
+        bmp_image_parser.embed_data_with_flags(&mut packed, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased, codec.id()).unwrap();
+        bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_COMPRESSED.bmp")).unwrap();
+
+        let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_COMPRESSED.bmp"));
+        bmp_image_parser.parse_file().unwrap();
+        let (retrieved, flags) = bmp_image_parser.retrieve_data_with_flags(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+
+        let codec = CompressionCodec::from_flags(flags).unwrap();
+        assert_eq!(codec, CompressionCodec::ShortText);
+        assert_eq!(decompress(&retrieved, codec).unwrap(), message);
+    }
 
     #[test]
     fn test_bmp_object_creation(){
@@ -88,7 +1002,7 @@ mod bmp_tests{
     fn test_bmp_image_parsing(){
         let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
 
-        bmp_image_parser.parse_file();
+        bmp_image_parser.parse_file().unwrap();
        assert_ne!(bmp_image_parser.file_data.len(), 0);
 
         assert_ne!(bmp_image_parser.pixel_size, 0);
@@ -107,6 +1021,8 @@ mod bmp_tests{
         // Check the bmp_header fields via local variables
         assert_ne!(bf_type, 0);
         assert_ne!(bf_size, 0);
+        assert_eq!(bf_reserved1, 0);
+        assert_eq!(bf_reserved2, 0);
         assert_eq!(bf_off_bits, 54);
 
         let bi_size = bmp_image_parser.bmp_dib_header.bi_size;
@@ -126,9 +1042,12 @@ mod bmp_tests{
         assert_eq!(bi_height, 1024);
         assert_ne!(bi_planes, 0);
         assert_eq!(bi_bit_count, 24);
+        assert_eq!(bi_compression, BI_RGB);
         assert_ne!(bi_size_image, 0);
         assert_ne!(bi_x_pels_per_meter, 0);
         assert_ne!(bi_y_pels_per_meter, 0);
+        assert_eq!(bi_clr_used, 0);
+        assert_eq!(bi_clr_important, 0);
 
         assert_eq!(bmp_image_parser.padding_size, 0);
     }
@@ -139,7 +1058,7 @@ mod bmp_tests{
     #[test]
     fn test_bmp_lsb_embed_left_right(){
         let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
-        bmp_image_parser.parse_file();
+        bmp_image_parser.parse_file().unwrap();
 
         let data_vec : Vec<u8> = "We are unwilling to admit that we are weak, incapable, cowardly, shameless, or morally dead, yet when you raise the banner of Christian doctrine but, through your actions or inaction, declare to the world, “I would rather choose comfort than truth and justice,” what does that make you? Christ is not an external savior; He is the pure essence of human goodness. And you, using the name of a Christian, have betrayed that sacred name. Christ should not merely be followed—He must be embodied, enacted. You must become Christ—this is the destiny of those who listen as Christ knocks on the door of their soul, who examine themselves and pursue truth.
 
@@ -148,11 +1067,11 @@ Ask yourself: do you dare face Babylon? Do you dare stand against Goliath? Or wi
 What Babylon fears most is for the people to stand hand in hand and, with the purest essence of faith, declare to the machine: I see you. I am Christ, and Christ is me. I dispel your illusions and deceptions. I am the pioneer of my own destiny. Though I am human, God may act through me. Today I choose the way of Christ, for I am, by nature, the Son of Man—only now have I come to realize it.".as_bytes().to_vec();
         match bmp_image_parser.pixel_size {
             3 => {
-                embed_lsb_data_left_right::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+                embed_lsb_data_left_right::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
             }
 
             4 => {
-                embed_lsb_data_left_right::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+                embed_lsb_data_left_right::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
             }
             _ => {
                 println!("bmp test.rs Got bad value for pixel size, exiting ...");
@@ -160,14 +1079,14 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
             }
         }
 
-        bmp_image_parser.write_file("src/filetype_support/assets/sample-1024x1024-TEST_EMBED.bmp");
+        bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_EMBED.bmp")).unwrap();
 
     }
 
     #[test]
     fn test_bmp_lsb_retrieve_left_right(){
-        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024-TEST_EMBED.bmp");
-        bmp_image_parser.parse_file();
+        let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_EMBED.bmp"));
+        bmp_image_parser.parse_file().unwrap();
 
         let mut data_vec: Vec<u8> = vec![0];
         match bmp_image_parser.pixel_size {
@@ -187,7 +1106,7 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
         /*
             the data vec will have an extra byte. the slice is just to remove that extra byte so that the test will pass
          */
-            assert_eq!(data_vec[0..(1387)], "We are unwilling to admit that we are weak, incapable, cowardly, shameless, or morally dead, yet when you raise the banner of Christian doctrine but, through your actions or inaction, declare to the world, “I would rather choose comfort than truth and justice,” what does that make you? Christ is not an external savior; He is the pure essence of human goodness. And you, using the name of a Christian, have betrayed that sacred name. Christ should not merely be followed—He must be embodied, enacted. You must become Christ—this is the destiny of those who listen as Christ knocks on the door of their soul, who examine themselves and pursue truth.
+            assert_eq!(data_vec[0..1387], "We are unwilling to admit that we are weak, incapable, cowardly, shameless, or morally dead, yet when you raise the banner of Christian doctrine but, through your actions or inaction, declare to the world, “I would rather choose comfort than truth and justice,” what does that make you? Christ is not an external savior; He is the pure essence of human goodness. And you, using the name of a Christian, have betrayed that sacred name. Christ should not merely be followed—He must be embodied, enacted. You must become Christ—this is the destiny of those who listen as Christ knocks on the door of their soul, who examine themselves and pursue truth.
 
 Ask yourself: do you dare face Babylon? Do you dare stand against Goliath? Or will you rationalize this imbalance by saying, “That’s just the way the world is”? You were not born to kneel; you are a supremely sacred individual and must rediscover the Christ within you—not only to “follow” Christ, but to become Christ.
 
@@ -198,16 +1117,16 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
     #[test]
     fn test_bmp_lsb_embed_large_message_left_right(){
         let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
-        bmp_image_parser.parse_file();
+        bmp_image_parser.parse_file().unwrap();
 
         let data_vec : Vec<u8> = "This is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposes".as_bytes().to_vec();
         match bmp_image_parser.pixel_size {
             3 => {
-                embed_lsb_data_left_right::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+                embed_lsb_data_left_right::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
             }
 
             4 => {
-                embed_lsb_data_left_right::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+                embed_lsb_data_left_right::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
             }
             _ => {
                 println!("bmp test.rs Got bad value for pixel size, exiting ...");
@@ -215,7 +1134,7 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
             }
         }
 
-        bmp_image_parser.write_file("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_LARGE.bmp");
+        bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_EMBED_LARGE.bmp")).unwrap();
 
         //ensure we shoved more than a full pixel row of bits in there so that we can test multi row embedding
         assert!(data_vec.len() > 384);
@@ -224,8 +1143,8 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
 
     #[test]
     fn test_bmp_lsb_retrieve_large_message_left_right(){
-        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_LARGE.bmp");
-        bmp_image_parser.parse_file();
+        let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_EMBED_LARGE.bmp"));
+        bmp_image_parser.parse_file().unwrap();
         let message_vec : Vec<u8> = "This is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposes".as_bytes().to_vec();
 
         let mut data_vec: Vec<u8> = vec![0];
@@ -259,7 +1178,7 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
 #[test]
 fn test_bmp_lsb_embed_right_left(){
     let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
-    bmp_image_parser.parse_file();
+    bmp_image_parser.parse_file().unwrap();
 
     let data_vec : Vec<u8> = "We are unwilling to admit that we are weak, incapable, cowardly, shameless, or morally dead, yet when you raise the banner of Christian doctrine but, through your actions or inaction, declare to the world, “I would rather choose comfort than truth and justice,” what does that make you? Christ is not an external savior; He is the pure essence of human goodness. And you, using the name of a Christian, have betrayed that sacred name. Christ should not merely be followed—He must be embodied, enacted. You must become Christ—this is the destiny of those who listen as Christ knocks on the door of their soul, who examine themselves and pursue truth.
 
@@ -268,11 +1187,11 @@ Ask yourself: do you dare face Babylon? Do you dare stand against Goliath? Or wi
 What Babylon fears most is for the people to stand hand in hand and, with the purest essence of faith, declare to the machine: I see you. I am Christ, and Christ is me. I dispel your illusions and deceptions. I am the pioneer of my own destiny. Though I am human, God may act through me. Today I choose the way of Christ, for I am, by nature, the Son of Man—only now have I come to realize it.".as_bytes().to_vec();
     match bmp_image_parser.pixel_size {
         3 => {
-            embed_lsb_data_right_left::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+            embed_lsb_data_right_left::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
         }
 
         4 => {
-            embed_lsb_data_right_left::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+            embed_lsb_data_right_left::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
         }
         _ => {
             println!("bmp test.rs Got bad value for pixel size, exiting ...");
@@ -280,14 +1199,14 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
         }
     }
 
-    bmp_image_parser.write_file("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_RIGHT_LEFT.bmp");
+    bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_EMBED_RIGHT_LEFT.bmp")).unwrap();
 
 }
 
 #[test]
 fn test_bmp_lsb_retrieve_right_left(){
-    let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_RIGHT_LEFT.bmp");
-    bmp_image_parser.parse_file();
+    let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_EMBED_RIGHT_LEFT.bmp"));
+    bmp_image_parser.parse_file().unwrap();
 
     let mut data_vec: Vec<u8> = vec![0];
     match bmp_image_parser.pixel_size {
@@ -307,7 +1226,7 @@ fn test_bmp_lsb_retrieve_right_left(){
     /*
         the data vec will have an extra byte. the slice is just to remove that extra byte so that the test will pass
      */
-    assert_eq!(data_vec[0..(1387)], "We are unwilling to admit that we are weak, incapable, cowardly, shameless, or morally dead, yet when you raise the banner of Christian doctrine but, through your actions or inaction, declare to the world, “I would rather choose comfort than truth and justice,” what does that make you? Christ is not an external savior; He is the pure essence of human goodness. And you, using the name of a Christian, have betrayed that sacred name. Christ should not merely be followed—He must be embodied, enacted. You must become Christ—this is the destiny of those who listen as Christ knocks on the door of their soul, who examine themselves and pursue truth.
+    assert_eq!(data_vec[0..1387], "We are unwilling to admit that we are weak, incapable, cowardly, shameless, or morally dead, yet when you raise the banner of Christian doctrine but, through your actions or inaction, declare to the world, “I would rather choose comfort than truth and justice,” what does that make you? Christ is not an external savior; He is the pure essence of human goodness. And you, using the name of a Christian, have betrayed that sacred name. Christ should not merely be followed—He must be embodied, enacted. You must become Christ—this is the destiny of those who listen as Christ knocks on the door of their soul, who examine themselves and pursue truth.
 
 Ask yourself: do you dare face Babylon? Do you dare stand against Goliath? Or will you rationalize this imbalance by saying, “That’s just the way the world is”? You were not born to kneel; you are a supremely sacred individual and must rediscover the Christ within you—not only to “follow” Christ, but to become Christ.
 
@@ -318,16 +1237,16 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
 #[test]
 fn test_bmp_lsb_embed_large_message_right_left(){
     let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
-    bmp_image_parser.parse_file();
+    bmp_image_parser.parse_file().unwrap();
 
     let data_vec : Vec<u8> = "This is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposes".as_bytes().to_vec();
     match bmp_image_parser.pixel_size {
         3 => {
-            embed_lsb_data_right_left::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+            embed_lsb_data_right_left::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
         }
 
         4 => {
-            embed_lsb_data_right_left::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+            embed_lsb_data_right_left::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
         }
         _ => {
             println!("bmp test.rs Got bad value for pixel size, exiting ...");
@@ -335,7 +1254,7 @@ fn test_bmp_lsb_embed_large_message_right_left(){
         }
     }
 
-    bmp_image_parser.write_file("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_LARGE_RIGHT_LEFT.bmp");
+    bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_EMBED_LARGE_RIGHT_LEFT.bmp")).unwrap();
 
     //ensure we shoved more than a full pixel row of bits in there so that we can test multi row embedding
     assert!(data_vec.len() > 384);
@@ -344,8 +1263,8 @@ fn test_bmp_lsb_embed_large_message_right_left(){
 
 #[test]
 fn test_bmp_lsb_retrieve_large_message_right_left(){
-    let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_LARGE_RIGHT_LEFT.bmp");
-    bmp_image_parser.parse_file();
+    let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_EMBED_LARGE_RIGHT_LEFT.bmp"));
+    bmp_image_parser.parse_file().unwrap();
     let message_vec : Vec<u8> = "This is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposes".as_bytes().to_vec();
 
     let mut data_vec: Vec<u8> = vec![0];
@@ -373,7 +1292,7 @@ fn test_bmp_lsb_retrieve_large_message_right_left(){
     #[test]
     fn test_bmp_color_embed_left_right(){
         let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
-        bmp_image_parser.parse_file();
+        bmp_image_parser.parse_file().unwrap();
 
         let data_vec : Vec<u8> = "We are unwilling to admit that we are weak, incapable, cowardly, shameless, or morally dead, yet when you raise the banner of Christian doctrine but, through your actions or inaction, declare to the world, “I would rather choose comfort than truth and justice,” what does that make you? Christ is not an external savior; He is the pure essence of human goodness. And you, using the name of a Christian, have betrayed that sacred name. Christ should not merely be followed—He must be embodied, enacted. You must become Christ—this is the destiny of those who listen as Christ knocks on the door of their soul, who examine themselves and pursue truth.
 
@@ -382,11 +1301,11 @@ Ask yourself: do you dare face Babylon? Do you dare stand against Goliath? Or wi
 What Babylon fears most is for the people to stand hand in hand and, with the purest essence of faith, declare to the machine: I see you. I am Christ, and Christ is me. I dispel your illusions and deceptions. I am the pioneer of my own destiny. Though I am human, God may act through me. Today I choose the way of Christ, for I am, by nature, the Son of Man—only now have I come to realize it.".as_bytes().to_vec();
         match bmp_image_parser.pixel_size {
             3 => {
-                embed_color_data_left_right::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+                embed_color_data_left_right::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
             }
 
             4 => {
-                embed_color_data_left_right::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+                embed_color_data_left_right::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
             }
             _ => {
                 println!("bmp test.rs Got bad value for pixel size, exiting ...");
@@ -394,14 +1313,14 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
             }
         }
 
-        bmp_image_parser.write_file("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_COLOR_LEFT_RIGHT.bmp");
+        bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_EMBED_COLOR_LEFT_RIGHT.bmp")).unwrap();
 
     }
 
     #[test]
     fn test_bmp_color_retrieve_left_right(){
-        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_COLOR_LEFT_RIGHT.bmp");
-        bmp_image_parser.parse_file();
+        let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_EMBED_COLOR_LEFT_RIGHT.bmp"));
+        bmp_image_parser.parse_file().unwrap();
 
         let mut data_vec: Vec<u8> = vec![0];
         match bmp_image_parser.pixel_size {
@@ -421,7 +1340,7 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
         /*
             the data vec will have an extra byte. the slice is just to remove that extra byte so that the test will pass
          */
-        assert_eq!(data_vec[0..(1387)], "We are unwilling to admit that we are weak, incapable, cowardly, shameless, or morally dead, yet when you raise the banner of Christian doctrine but, through your actions or inaction, declare to the world, “I would rather choose comfort than truth and justice,” what does that make you? Christ is not an external savior; He is the pure essence of human goodness. And you, using the name of a Christian, have betrayed that sacred name. Christ should not merely be followed—He must be embodied, enacted. You must become Christ—this is the destiny of those who listen as Christ knocks on the door of their soul, who examine themselves and pursue truth.
+        assert_eq!(data_vec[0..1387], "We are unwilling to admit that we are weak, incapable, cowardly, shameless, or morally dead, yet when you raise the banner of Christian doctrine but, through your actions or inaction, declare to the world, “I would rather choose comfort than truth and justice,” what does that make you? Christ is not an external savior; He is the pure essence of human goodness. And you, using the name of a Christian, have betrayed that sacred name. Christ should not merely be followed—He must be embodied, enacted. You must become Christ—this is the destiny of those who listen as Christ knocks on the door of their soul, who examine themselves and pursue truth.
 
 Ask yourself: do you dare face Babylon? Do you dare stand against Goliath? Or will you rationalize this imbalance by saying, “That’s just the way the world is”? You were not born to kneel; you are a supremely sacred individual and must rediscover the Christ within you—not only to “follow” Christ, but to become Christ.
 
@@ -434,16 +1353,16 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
     #[test]
     fn test_bmp_color_embed_large_message_left_right(){
         let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
-        bmp_image_parser.parse_file();
+        bmp_image_parser.parse_file().unwrap();
 
         let data_vec : Vec<u8> = "This is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposes".as_bytes().to_vec();
         match bmp_image_parser.pixel_size {
             3 => {
-                embed_color_data_left_right::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+                embed_color_data_left_right::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
             }
 
             4 => {
-                embed_color_data_left_right::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+                embed_color_data_left_right::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
             }
             _ => {
                 println!("bmp test.rs Got bad value for pixel size, exiting ...");
@@ -451,7 +1370,7 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
             }
         }
 
-        bmp_image_parser.write_file("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_COLOR_LEFT_RIGHT_LARGE.bmp");
+        bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_EMBED_COLOR_LEFT_RIGHT_LARGE.bmp")).unwrap();
 
         //ensure we shoved more than a full pixel row of bits in there so that we can test multi row embedding
         assert!(data_vec.len() > 384);
@@ -460,8 +1379,8 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
 
     #[test]
     fn test_bmp_color_retrieve_large_message_left_right(){
-        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_COLOR_LEFT_RIGHT_LARGE.bmp");
-        bmp_image_parser.parse_file();
+        let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_EMBED_COLOR_LEFT_RIGHT_LARGE.bmp"));
+        bmp_image_parser.parse_file().unwrap();
         let message_vec : Vec<u8> = "This is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposes".as_bytes().to_vec();
 
         let mut data_vec: Vec<u8> = vec![0];
@@ -495,7 +1414,7 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
     #[test]
     fn test_bmp_color_embed_right_left(){
         let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
-        bmp_image_parser.parse_file();
+        bmp_image_parser.parse_file().unwrap();
 
         let data_vec : Vec<u8> = "We are unwilling to admit that we are weak, incapable, cowardly, shameless, or morally dead, yet when you raise the banner of Christian doctrine but, through your actions or inaction, declare to the world, “I would rather choose comfort than truth and justice,” what does that make you? Christ is not an external savior; He is the pure essence of human goodness. And you, using the name of a Christian, have betrayed that sacred name. Christ should not merely be followed—He must be embodied, enacted. You must become Christ—this is the destiny of those who listen as Christ knocks on the door of their soul, who examine themselves and pursue truth.
 
@@ -504,11 +1423,11 @@ Ask yourself: do you dare face Babylon? Do you dare stand against Goliath? Or wi
 What Babylon fears most is for the people to stand hand in hand and, with the purest essence of faith, declare to the machine: I see you. I am Christ, and Christ is me. I dispel your illusions and deceptions. I am the pioneer of my own destiny. Though I am human, God may act through me. Today I choose the way of Christ, for I am, by nature, the Son of Man—only now have I come to realize it.".as_bytes().to_vec();
         match bmp_image_parser.pixel_size {
             3 => {
-                embed_color_data_right_left::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+                embed_color_data_right_left::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
             }
 
             4 => {
-                embed_color_data_right_left::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+                embed_color_data_right_left::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
             }
             _ => {
                 println!("bmp test.rs Got bad value for pixel size, exiting ...");
@@ -516,14 +1435,14 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
             }
         }
 
-        bmp_image_parser.write_file("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_COLOR_RIGHT_LEFT.bmp");
+        bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_EMBED_COLOR_RIGHT_LEFT.bmp")).unwrap();
 
     }
 
     #[test]
     fn test_bmp_color_retrieve_right_left(){
-        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_COLOR_RIGHT_LEFT.bmp");
-        bmp_image_parser.parse_file();
+        let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_EMBED_COLOR_RIGHT_LEFT.bmp"));
+        bmp_image_parser.parse_file().unwrap();
 
         let mut data_vec: Vec<u8> = vec![0];
         match bmp_image_parser.pixel_size {
@@ -554,16 +1473,16 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
     #[test]
     fn test_bmp_color_embed_large_message_right_left(){
         let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
-        bmp_image_parser.parse_file();
+        bmp_image_parser.parse_file().unwrap();
 
         let data_vec : Vec<u8> = "This is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposes".as_bytes().to_vec();
         match bmp_image_parser.pixel_size {
             3 => {
-                embed_color_data_right_left::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+                embed_color_data_right_left::<RgbPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
             }
 
             4 => {
-                embed_color_data_right_left::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64);
+                embed_color_data_right_left::<RgbaPixel>(&data_vec, &mut bmp_image_parser.file_data[bmp_image_parser.pixel_map.pixel_map_start as usize..], bmp_image_parser.pixel_map.width as u64, bmp_image_parser.pixel_map.height as u64, bmp_image_parser.padding_size as u64, bmp_image_parser.pixel_size as u64).unwrap();
             }
             _ => {
                 println!("bmp test.rs Got bad value for pixel size, exiting ...");
@@ -571,7 +1490,7 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
             }
         }
 
-        bmp_image_parser.write_file("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_COLOR_LARGE_RIGHT_LEFT.bmp");
+        bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_EMBED_COLOR_LARGE_RIGHT_LEFT.bmp")).unwrap();
 
         //ensure we shoved more than a full pixel row of bits in there so that we can test multi row embedding
         assert!(data_vec.len() > 384);
@@ -580,8 +1499,8 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
 
     #[test]
     fn test_bmp_color_retrieve_large_message_right_left(){
-        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024-TEST_EMBED_COLOR_LARGE_RIGHT_LEFT.bmp");
-        bmp_image_parser.parse_file();
+        let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_EMBED_COLOR_LARGE_RIGHT_LEFT.bmp"));
+        bmp_image_parser.parse_file().unwrap();
         let message_vec : Vec<u8> = "This is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposesThis is a test embedding for testing purposes".as_bytes().to_vec();
 
         let mut data_vec: Vec<u8> = vec![0];
@@ -612,7 +1531,587 @@ What Babylon fears most is for the people to stand hand in hand and, with the pu
 
 
 
-}
+    /*
+        Full embed_data / retrieve_data round trip, the payload header is embedded alongside the message so nothing is read from the bmp headers
+     */
+    #[test]
+    fn test_bmp_payload_round_trip(){
+        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
+        bmp_image_parser.parse_file().unwrap();
+
+        let mut data_vec : Vec<u8> = "The payload header travels with the message through the pixels".as_bytes().to_vec();
+        bmp_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_EMBED_PAYLOAD.bmp")).unwrap();
+
+        let bf_reserved1 = bmp_image_parser.bmp_header.bf_reserved1;
+        assert_eq!(bf_reserved1, 0);
+
+        let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_EMBED_PAYLOAD.bmp"));
+        bmp_image_parser.parse_file().unwrap();
+
+        let retrieved = bmp_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+        assert_eq!(bmp_image_parser.pixel_map.num_embedded_bits, Some(data_vec.len() * 8));
+    }
+
+    #[test]
+    fn test_bmp_8bit_palette_round_trip(){
+        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-250x200-8bit.bmp");
+        bmp_image_parser.parse_file().unwrap();
+
+        // 250 one byte indices need 2 bytes of padding to get to a multiple of 4
+        assert_eq!(bmp_image_parser.pixel_size, 1);
+        assert_eq!(bmp_image_parser.padding_size, 2);
+        let original_palette = bmp_image_parser.palette.clone().unwrap();
+        assert_eq!(original_palette.entries.len(), 252);
+
+        let mut data_vec : Vec<u8> = "Hidden in an 8 bit color table image".as_bytes().to_vec();
+        bmp_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        bmp_image_parser.write_file(&scratch("sample-250x200-8bit-TEST_EMBED.bmp")).unwrap();
+
+        let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-250x200-8bit-TEST_EMBED.bmp"));
+        bmp_image_parser.parse_file().unwrap();
+        assert_eq!(bmp_image_parser.palette.clone().unwrap(), original_palette);
+
+        let retrieved = bmp_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    #[test]
+    fn test_bmp_8bit_palette_reorder_round_trip(){
+        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-250x200-8bit.bmp");
+        bmp_image_parser.parse_file().unwrap();
+        bmp_image_parser.encoding_parameters.palette_embedding = PaletteEmbedding::ReorderPalette;
+
+        let mut data_vec : Vec<u8> = "Sorted color table".as_bytes().to_vec();
+        bmp_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        bmp_image_parser.write_file(&scratch("sample-250x200-8bit-TEST_REORDER.bmp")).unwrap();
+
+        let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-250x200-8bit-TEST_REORDER.bmp"));
+        bmp_image_parser.parse_file().unwrap();
+
+        let palette = bmp_image_parser.palette.clone().unwrap();
+        assert_eq!(palette.luminance_order(), (0..palette.entries.len()).collect::<Vec<usize>>());
+
+        let retrieved = bmp_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+
+    #[test]
+    fn test_bmp_pvd_round_trip(){
+        for encoding_method in [FileEncodingMethod::LeftToRight, FileEncodingMethod::RightToLeft] {
+            let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
+            bmp_image_parser.parse_file().unwrap();
+            let original = bmp_image_parser.file_data.clone();
 
+            let mut data_vec : Vec<u8> = "Pixel value differencing hides more in busy regions".repeat(200).as_bytes().to_vec();
+            bmp_image_parser.embed_data(&mut data_vec, FileEncoding::PixelValueDifferencing, encoding_method, FileEncodingFunctionDerivation::MethodBased).unwrap();
 
+            // The larger ranges move samples further than LSB would but never past the top of their range
+            let start = bmp_image_parser.pixel_map.pixel_map_start as usize;
+            assert!(original[start..].iter().zip(bmp_image_parser.file_data[start..].iter()).any(|(a, b)| a.abs_diff(*b) > 1));
+            bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_PVD.bmp")).unwrap();
 
+            let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_PVD.bmp"));
+            bmp_image_parser.parse_file().unwrap();
+
+            let retrieved = bmp_image_parser.retrieve_data(FileEncoding::PixelValueDifferencing, encoding_method, FileEncodingFunctionDerivation::MethodBased).unwrap();
+            assert_eq!(retrieved, data_vec);
+        }
+    }
+
+
+    #[test]
+    fn test_bmp_hamming_round_trip(){
+        for encoding_method in [FileEncodingMethod::LeftToRight, FileEncodingMethod::RightToLeft] {
+            let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
+            bmp_image_parser.parse_file().unwrap();
+            let original = bmp_image_parser.file_data.clone();
+
+            let mut data_vec : Vec<u8> = "Hamming codes flip at most one bit a group".repeat(20).as_bytes().to_vec();
+            bmp_image_parser.embed_data(&mut data_vec, FileEncoding::HammingMatrix, encoding_method, FileEncodingFunctionDerivation::MethodBased).unwrap();
+
+            // Nothing but bit 0 moves, and far fewer samples than bits embedded
+            let start = bmp_image_parser.pixel_map.pixel_map_start as usize;
+            let changed: Vec<(u8, u8)> = original[start..].iter().zip(bmp_image_parser.file_data[start..].iter()).filter(|(a, b)| a != b).map(|(a, b)| (*a, *b)).collect();
+            assert!(changed.iter().all(|(a, b)| a ^ b == 1));
+            assert!(changed.len() < data_vec.len() * 8 / 4);
+            bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_HAMMING.bmp")).unwrap();
+
+            let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_HAMMING.bmp"));
+            bmp_image_parser.parse_file().unwrap();
+
+            let retrieved = bmp_image_parser.retrieve_data(FileEncoding::HammingMatrix, encoding_method, FileEncodingFunctionDerivation::MethodBased).unwrap();
+            assert_eq!(retrieved, data_vec);
+        }
+    }
+
+
+    #[test]
+    fn test_bmp_keyed_round_trip(){
+        let key = FileEncodingFunctionDerivation::from_passphrase("bmp passphrase");
+
+        for encoding in [FileEncoding::Lsb, FileEncoding::PixelValueDifferencing, FileEncoding::HammingMatrix] {
+            let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
+            bmp_image_parser.parse_file().unwrap();
+
+            let mut data_vec : Vec<u8> = "Scattered all over the image".repeat(10).as_bytes().to_vec();
+            bmp_image_parser.embed_data(&mut data_vec, encoding, FileEncodingMethod::LeftToRight, key).unwrap();
+            bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_KEYED.bmp")).unwrap();
+
+            let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_KEYED.bmp"));
+            bmp_image_parser.parse_file().unwrap();
+
+            let retrieved = bmp_image_parser.retrieve_data(encoding, FileEncodingMethod::LeftToRight, key).unwrap();
+            assert_eq!(retrieved, data_vec, "{encoding:?}");
+        }
+    }
+
+    #[test]
+    fn test_bmp_lsb_matching_round_trip(){
+        let key = FileEncodingFunctionDerivation::from_passphrase("bmp matching");
+
+        for derivation in [FileEncodingFunctionDerivation::MethodBased, key] {
+            let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-1024x1024.bmp");
+            bmp_image_parser.parse_file().unwrap();
+            let original = bmp_image_parser.file_data.clone();
+
+            let mut data_vec : Vec<u8> = "Plus or minus one".repeat(50).as_bytes().to_vec();
+            bmp_image_parser.embed_data(&mut data_vec, FileEncoding::LsbMatching, FileEncodingMethod::TopToBottom, derivation).unwrap();
+            assert!(original.iter().zip(bmp_image_parser.file_data.iter()).all(|(a, b)| a.abs_diff(*b) <= 1));
+            bmp_image_parser.write_file(&scratch("sample-1024x1024-TEST_MATCHING.bmp")).unwrap();
+
+            let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-1024x1024-TEST_MATCHING.bmp"));
+            bmp_image_parser.parse_file().unwrap();
+
+            let retrieved = bmp_image_parser.retrieve_data(FileEncoding::LsbMatching, FileEncodingMethod::TopToBottom, derivation).unwrap();
+            assert_eq!(retrieved, data_vec, "{derivation:?}");
+        }
+
+        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-250x200-8bit.bmp");
+        bmp_image_parser.parse_file().unwrap();
+        assert!(bmp_image_parser.embed_data(&mut b"no".to_vec(), FileEncoding::LsbMatching, FileEncodingMethod::LeftToRight, key).is_err());
+    }
+
+    #[test]
+    fn test_bmp_8bit_palette_keyed_round_trip(){
+        let key = FileEncodingFunctionDerivation::from_passphrase("palette passphrase");
+
+        let mut bmp_image_parser = BmpImageParser::new("src/filetype_support/assets/sample-250x200-8bit.bmp");
+        bmp_image_parser.parse_file().unwrap();
+
+        let mut data_vec : Vec<u8> = "Keyed color table".as_bytes().to_vec();
+        bmp_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::RightToLeft, key).unwrap();
+        bmp_image_parser.write_file(&scratch("sample-250x200-8bit-TEST_KEYED.bmp")).unwrap();
+
+        let mut bmp_image_parser = BmpImageParser::new(&scratch("sample-250x200-8bit-TEST_KEYED.bmp"));
+        bmp_image_parser.parse_file().unwrap();
+
+        let retrieved = bmp_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::RightToLeft, key).unwrap();
+        assert_eq!(retrieved, data_vec);
+    }
+
+    /*
+        A BMP put together in memory so every header version can be tried without an asset for each. V2 and up get
+        their masks in the header, a BITMAPINFOHEADER gets them tacked on after it, the rest of a V4 / V5 header is
+        filled with junk standing in for the colour space fields and a V5 points at an ICC profile after the pixels
+     */
+    fn synthetic_bmp(header_size: u32, bit_count: u16, compression: u32, masks: &[u32], width: u32, height: u32) -> Vec<u8> {
+        let mut dib: Vec<u8> = Vec::new();
+        if header_size == 12 {
+            for field in [width as u16, height as u16, 1, bit_count] {
+                dib.extend_from_slice(&field.to_le_bytes());
+            }
+        } else {
+            dib.extend_from_slice(&(width as i32).to_le_bytes());
+            dib.extend_from_slice(&(height as i32).to_le_bytes());
+            dib.extend_from_slice(&1u16.to_le_bytes());
+            dib.extend_from_slice(&bit_count.to_le_bytes());
+            dib.extend_from_slice(&compression.to_le_bytes());
+            for field in [0u32, 2835, 2835, 0, 0] {
+                dib.extend_from_slice(&field.to_le_bytes());
+            }
+            for mask in masks {
+                dib.extend_from_slice(&mask.to_le_bytes());
+            }
+            while dib.len() + 4 < header_size as usize {
+                dib.push(dib.len() as u8 ^ 0x5A);
+            }
+        }
+        dib.splice(0..0, header_size.to_le_bytes());
+
+        // Evenly spaced grays, 0 and 255 for 1 bit up to every level for 8 bit
+        let color_table: Vec<u8> = match bit_count {
+            1 | 4 | 8 => (0..1usize << bit_count)
+                .map(|index| (index * 255 / ((1 << bit_count) - 1)) as u8)
+                .flat_map(|gray| if header_size == 12 { vec![gray, gray, gray] } else { vec![gray, gray, gray, 0] })
+                .collect(),
+            _ => vec![],
+        };
+
+        let pixel_bytes = bit_count as usize / 8;
+        let row_bytes = (width as usize * bit_count as usize).div_ceil(8);
+        let stride = row_bytes.div_ceil(4) * 4;
+        let mut pixels = vec![0u8; stride * height as usize];
+        for y in 0..height as usize {
+            if pixel_bytes == 0 {
+                for x in 0..row_bytes {
+                    pixels[y * stride + x] = (((x * 7 + y * 13) as u32).wrapping_mul(2654435761) >> 24) as u8;
+                }
+                continue;
+            }
+            for x in 0..width as usize {
+                let value = ((x * 7 + y * 13) as u32).wrapping_mul(2654435761) ^ (x as u32 * y as u32);
+                pixels[y * stride + x * pixel_bytes..y * stride + (x + 1) * pixel_bytes].copy_from_slice(&value.to_le_bytes()[..pixel_bytes]);
+            }
+        }
+
+        let off_bits = 14 + dib.len() + color_table.len();
+        let profile = b"not really an ICC profile but it has to come out the other side untouched";
+        if header_size == 124 {
+            let profile_offset = (dib.len() + color_table.len() + pixels.len()) as u32;
+            dib[112..116].copy_from_slice(&profile_offset.to_le_bytes());
+            dib[116..120].copy_from_slice(&(profile.len() as u32).to_le_bytes());
+        }
+
+        let mut bmp = b"BM".to_vec();
+        bmp.extend_from_slice(&((off_bits + pixels.len() + profile.len()) as u32).to_le_bytes());
+        bmp.extend_from_slice(&[0; 4]);
+        bmp.extend_from_slice(&(off_bits as u32).to_le_bytes());
+        bmp.extend_from_slice(&dib);
+        bmp.extend_from_slice(&color_table);
+        bmp.extend_from_slice(&pixels);
+        bmp.extend_from_slice(profile);
+        bmp
+    }
+
+    fn embed_and_retrieve_bytes(bmp: &[u8], encoding: FileEncoding, data: &[u8]) -> Vec<u8> {
+        embed_and_retrieve_with_method(bmp, encoding, FileEncodingMethod::HilbertCurve, data)
+    }
+
+    fn embed_and_retrieve_with_method(bmp: &[u8], encoding: FileEncoding, encoding_method: FileEncodingMethod, data: &[u8]) -> Vec<u8> {
+        let mut bmp_image_parser = BmpImageParser::from_bytes(bmp.to_vec()).unwrap();
+        let mut data_vec = data.to_vec();
+        bmp_image_parser.embed_data(&mut data_vec, encoding, encoding_method, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        let written = bmp_image_parser.to_bytes().unwrap();
+
+        let mut bmp_image_parser = BmpImageParser::from_bytes(written.clone()).unwrap();
+        let retrieved = bmp_image_parser.retrieve_data(encoding, encoding_method, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(retrieved, data);
+        written
+    }
+
+    /*
+        Same picture with the rows stored the other way round and the height sign flipped to match,
+        doing it twice gets you back where you started
+     */
+    fn flip_row_order(bmp: &[u8]) -> Vec<u8> {
+        let field = |offset: usize| u32::from_le_bytes(bmp[offset..offset + 4].try_into().unwrap());
+        let off_bits = field(10) as usize;
+        let width = field(18) as usize;
+        let height = field(22) as i32;
+        let bit_count = u16::from_le_bytes([bmp[28], bmp[29]]) as usize;
+        let stride = (width * bit_count / 8).div_ceil(4) * 4;
+        let rows = height.unsigned_abs() as usize;
+
+        let mut flipped = bmp.to_vec();
+        flipped[22..26].copy_from_slice(&(-height).to_le_bytes());
+        for row in 0..rows {
+            let from = off_bits + (rows - 1 - row) * stride;
+            flipped[off_bits + row * stride..off_bits + (row + 1) * stride].copy_from_slice(&bmp[from..from + stride]);
+        }
+        flipped
+    }
+
+    #[test]
+    fn test_bmp_header_versions(){
+        for (header_size, bit_count) in [(12, 24), (12, 8), (40, 24), (52, 24), (56, 32), (108, 24), (124, 24), (124, 8)] {
+            let bmp = synthetic_bmp(header_size, bit_count, BI_RGB, &[], 61, 37);
+            let bmp_image_parser = BmpImageParser::from_bytes(bmp.clone()).unwrap();
+            assert_eq!((bmp_image_parser.pixel_map.width, bmp_image_parser.pixel_map.height), (61, 37));
+            assert_eq!(bmp_image_parser.palette.is_some(), bit_count == 8);
+
+            let written = embed_and_retrieve_bytes(&bmp, FileEncoding::Lsb, b"any header will do");
+
+            // Headers, colour space fields and the profile after the pixels all come back byte for byte
+            let start = bmp_image_parser.pixel_map.pixel_map_start as usize;
+            let end = bmp.len() - 73;
+            assert_eq!(written.len(), bmp.len());
+            assert_eq!(written[..start], bmp[..start], "{header_size} {bit_count}");
+            assert_eq!(written[end..], bmp[end..], "{header_size} {bit_count}");
+            assert_ne!(written[start..end], bmp[start..end]);
+        }
+    }
+
+    #[test]
+    fn test_bmp_bitfields_round_trip(){
+        let cases: [(u32, u16, u32, &[u32]); 7] = [
+            (40, 16, BI_RGB, &[]),                                                      // 555 by default
+            (40, 16, BI_BITFIELDS, &[0xF800, 0x07E0, 0x001F]),                          // 565 masks after the header
+            (56, 16, BI_BITFIELDS, &[0x7C00, 0x03E0, 0x001F, 0x8000]),                  // 1555, alpha left alone
+            (108, 16, BI_BITFIELDS, &[0x0F00, 0x00F0, 0x000F, 0xF000]),                 // 4444
+            (124, 32, BI_BITFIELDS, &[0x00FF0000, 0x0000FF00, 0x000000FF, 0xFF000000]), // BGRA
+            (40, 32, BI_ALPHABITFIELDS, &[0x000000FF, 0x0000FF00, 0x00FF0000, 0xFF000000]), // RGBA
+            (52, 32, BI_BITFIELDS, &[0x00FF0000, 0x0000FF00, 0x000000FF]),              // BGRX
+        ];
+
+        for (header_size, bit_count, compression, masks) in cases {
+            let bmp = synthetic_bmp(header_size, bit_count, compression, masks, 64, 48);
+            let bmp_image_parser = BmpImageParser::from_bytes(bmp.clone()).unwrap();
+            let bitfields = bmp_image_parser.bitfields.unwrap();
+            let start = bmp_image_parser.pixel_map.pixel_map_start as usize;
+
+            // Only the top sample_bits of each embedded channel are ever written to
+            let bits = bitfields.sample_bits();
+            let writable = bitfields.channels().iter().fold(0u32, |writable, mask| writable | (mask & !((1 << (mask.trailing_zeros() + mask.count_ones() - bits)) - 1)));
+
+            for encoding in [FileEncoding::Lsb, FileEncoding::HammingMatrix, FileEncoding::PixelValueDifferencing] {
+                let written = embed_and_retrieve_bytes(&bmp, encoding, b"bitfields keep their shape");
+                let pixel_bytes = bit_count as usize / 8;
+
+                let changed = written[start..start + 64 * 48 * pixel_bytes].chunks(pixel_bytes)
+                    .zip(bmp[start..start + 64 * 48 * pixel_bytes].chunks(pixel_bytes))
+                    .map(|(a, b)| a.iter().zip(b.iter()).rev().fold(0u32, |raw, (a, b)| (raw << 8) | (a ^ b) as u32))
+                    .fold(0, |changed, difference| changed | difference);
+                assert_ne!(changed, 0, "{header_size} {masks:x?} {encoding:?}");
+                assert_eq!(changed & !writable, 0, "{header_size} {masks:x?} {encoding:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_bmp_unsupported_variants(){
+        let mut os2_v2 = synthetic_bmp(64, 24, BI_RGB, &[], 8, 8);
+        os2_v2[14..18].copy_from_slice(&64u32.to_le_bytes());
+        assert!(matches!(BmpImageParser::from_bytes(os2_v2), Err(MayaError::UnsupportedFormat(_))));
+
+        let rle = synthetic_bmp(40, 24, BI_RLE8, &[], 8, 8);
+        assert!(matches!(BmpImageParser::from_bytes(rle), Err(MayaError::UnsupportedFormat(_))));
+
+        let top_down_rle = flip_row_order(&rle_bmp(40, 8, 8, 8));
+        assert!(matches!(BmpImageParser::from_bytes(top_down_rle), Err(MayaError::CorruptHeader(_))));
+
+        let overlapping = synthetic_bmp(40, 16, BI_BITFIELDS, &[0xF800, 0x0FE0, 0x001F], 8, 8);
+        assert!(matches!(BmpImageParser::from_bytes(overlapping), Err(MayaError::CorruptHeader(_))));
+
+        let wide = synthetic_bmp(56, 32, BI_BITFIELDS, &[0x3FF00000, 0x000FFC00, 0x000003FF, 0xC0000000], 8, 8);
+        assert!(matches!(BmpImageParser::from_bytes(wide), Err(MayaError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    fn test_bmp_top_down_rows(){
+        let cases: [(u32, u16, u32, &[u32]); 4] = [
+            (40, 24, BI_RGB, &[]),
+            (108, 32, BI_RGB, &[]),
+            (40, 16, BI_BITFIELDS, &[0xF800, 0x07E0, 0x001F]),
+            (124, 8, BI_RGB, &[]),
+        ];
+
+        for (header_size, bit_count, compression, masks) in cases {
+            let bottom_up = synthetic_bmp(header_size, bit_count, compression, masks, 45, 29);
+            let top_down = flip_row_order(&bottom_up);
+
+            let bmp_image_parser = BmpImageParser::from_bytes(top_down.clone()).unwrap();
+            assert_eq!((bmp_image_parser.pixel_map.width, bmp_image_parser.pixel_map.height), (45, 29));
+            assert!(bmp_image_parser.pixel_map.top_down);
+            assert!(!BmpImageParser::from_bytes(bottom_up.clone()).unwrap().pixel_map.top_down);
+
+            // Traversal works on what you see, so the stego'd pictures have to match pixel for pixel
+            for encoding_method in [FileEncodingMethod::LeftToRight, FileEncodingMethod::TopToBottom, FileEncodingMethod::HilbertCurve, FileEncodingMethod::SpiralInward] {
+                let written_bottom_up = embed_and_retrieve_with_method(&bottom_up, FileEncoding::Lsb, encoding_method, b"upside down or not");
+                let written_top_down = embed_and_retrieve_with_method(&top_down, FileEncoding::Lsb, encoding_method, b"upside down or not");
+                assert_eq!(flip_row_order(&written_top_down), written_bottom_up, "{header_size} {bit_count} {encoding_method:?}");
+            }
+        }
+
+        // Left to right starts in the top left corner, which is the last row of a bottom up file
+        let bottom_up = synthetic_bmp(40, 24, BI_RGB, &[], 45, 29);
+        let written = embed_and_retrieve_with_method(&bottom_up, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, b"corner");
+        let start = BmpImageParser::from_bytes(bottom_up.clone()).unwrap().pixel_map.pixel_map_start as usize;
+        let stride = (45 * 3usize).div_ceil(4) * 4;
+        let top_row = start + 28 * stride;
+        assert_eq!(written[start..start + 20 * stride], bottom_up[start..start + 20 * stride]);
+        assert_ne!(written[top_row..top_row + stride], bottom_up[top_row..top_row + stride]);
+
+        let mut negative_width = synthetic_bmp(40, 24, BI_RGB, &[], 8, 8);
+        negative_width[18..22].copy_from_slice(&(-8i32).to_le_bytes());
+        assert!(matches!(BmpImageParser::from_bytes(negative_width), Err(MayaError::CorruptHeader(_))));
+
+        // A huge top down height has to fail the bounds check, not wrap around
+        let mut too_tall = synthetic_bmp(40, 24, BI_RGB, &[], 8, 8);
+        too_tall[22..26].copy_from_slice(&i32::MIN.to_le_bytes());
+        assert!(matches!(BmpImageParser::from_bytes(too_tall), Err(MayaError::CorruptHeader(_))));
+    }
+
+    #[test]
+    fn test_bmp_sub_byte_palette_round_trip(){
+        // Odd widths so the last byte of a row is only partly pixels and the rows need padding
+        for (header_size, bit_count, width) in [(40, 1, 61), (40, 4, 37), (12, 4, 45), (124, 1, 200), (40, 8, 53)] {
+            let bmp = synthetic_bmp(header_size, bit_count, BI_RGB, &[], width, 33);
+            let bmp_image_parser = BmpImageParser::from_bytes(bmp.clone()).unwrap();
+            assert_eq!(bmp_image_parser.palette.as_ref().unwrap().entries.len(), 1 << bit_count);
+
+            let start = bmp_image_parser.pixel_map.pixel_map_start as usize;
+            let row_bytes = (width as usize * bit_count as usize).div_ceil(8);
+            let stride = row_bytes.div_ceil(4) * 4;
+            let unused_bits = (row_bytes * 8 - width as usize * bit_count as usize) as u32;
+
+            // The core header has no way of saying top down
+            let row_orders = if header_size == 12 { vec![bmp.clone()] } else { vec![bmp.clone(), flip_row_order(&bmp)] };
+            for bmp in row_orders {
+                let written = embed_and_retrieve_with_method(&bmp, FileEncoding::Lsb, FileEncodingMethod::MortonOrder, b"packed indices");
+                assert_ne!(written[start..start + stride * 33], bmp[start..start + stride * 33]);
+
+                // Padding bytes and the spare bits at the end of each row are never touched
+                for row in 0..33 {
+                    let line = start + row * stride;
+                    assert_eq!(written[line + row_bytes..line + stride], bmp[line + row_bytes..line + stride]);
+                    let spare = (1u16 << unused_bits) as u8 - 1;
+                    assert_eq!(written[line + row_bytes - 1] & spare, bmp[line + row_bytes - 1] & spare);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bmp_palette_tolerance(){
+        // Black and white have nothing close enough to swap with
+        let bmp = synthetic_bmp(40, 1, BI_RGB, &[], 64, 64);
+        let mut bmp_image_parser = BmpImageParser::from_bytes(bmp.clone()).unwrap();
+        bmp_image_parser.encoding_parameters.palette_tolerance = Some(64);
+        assert_eq!(bmp_image_parser.carrier_capacity_bits(FileEncoding::Lsb).unwrap(), 0);
+        let mut data_vec = b"nowhere to go".to_vec();
+        assert!(matches!(
+            bmp_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased),
+            Err(MayaError::CapacityExceeded { .. })
+        ));
+
+        // 16 grays are 17 apart, so every swap stays within one step
+        let bmp = synthetic_bmp(40, 4, BI_RGB, &[], 64, 64);
+        let mut bmp_image_parser = BmpImageParser::from_bytes(bmp.clone()).unwrap();
+        bmp_image_parser.encoding_parameters.palette_tolerance = Some(17);
+        let mut data_vec = b"one step at a time".to_vec();
+        bmp_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::HilbertCurve, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        let written = bmp_image_parser.to_bytes().unwrap();
+
+        let start = bmp_image_parser.pixel_map.pixel_map_start as usize;
+        let moved = written[start..].iter().zip(bmp[start..].iter())
+            .flat_map(|(a, b)| [(a >> 4, b >> 4), (a & 15, b & 15)])
+            .map(|(a, b)| a.abs_diff(b))
+            .max()
+            .unwrap();
+        assert_eq!(moved, 1);
+
+        let mut bmp_image_parser = BmpImageParser::from_bytes(written).unwrap();
+        bmp_image_parser.encoding_parameters.palette_tolerance = Some(17);
+        assert_eq!(bmp_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::HilbertCurve, FileEncodingFunctionDerivation::MethodBased).unwrap(), b"one step at a time");
+    }
+
+    /*
+        synthetic_bmp with the pixels swapped for an RLE stream of blocky indices so there are runs worth encoding
+     */
+    fn rle_bmp(header_size: u32, bit_count: u16, width: u32, height: u32) -> Vec<u8> {
+        let bmp = synthetic_bmp(header_size, bit_count, BI_RGB, &[], width, height);
+        let (width, height, bits) = (width as usize, height as usize, bit_count as usize);
+        let indices: Vec<u8> = (0..width * height)
+            .map(|pixel| (((pixel % width) / 5 + (pixel / width) / 3) * 7 % (1 << bits)) as u8)
+            .collect();
+        let stream = encode_rle(&indices, width, height, bits);
+
+        let off_bits = u32::from_le_bytes(bmp[10..14].try_into().unwrap()) as usize;
+        let pixels_size = (width * bits).div_ceil(32) * 4 * height;
+        let mut rle = bmp[..off_bits].to_vec();
+        rle.extend_from_slice(&stream);
+        rle.extend_from_slice(&bmp[off_bits + pixels_size..]);
+
+        let file_size = rle.len() as u32;
+        rle[2..6].copy_from_slice(&file_size.to_le_bytes());
+        rle[30..34].copy_from_slice(&(if bits == 4 { BI_RLE4 } else { BI_RLE8 }).to_le_bytes());
+        rle[34..38].copy_from_slice(&(stream.len() as u32).to_le_bytes());
+        if header_size == 124 {
+            let profile_offset = u32::from_le_bytes(rle[126..130].try_into().unwrap()) as usize;
+            rle[126..130].copy_from_slice(&((profile_offset + stream.len() - pixels_size) as u32).to_le_bytes());
+        }
+        rle
+    }
+
+    #[test]
+    fn test_bmp_rle_decode_escapes(){
+        // Run, literal (padded), end of line, delta over a row, run, end of bitmap
+        let stream = [3, 7, 0, 3, 1, 2, 3, 0, 0, 0, 0, 2, 2, 1, 2, 9, 0, 1];
+        let (indices, used) = decode_rle(&stream, 6, 3, 8).unwrap();
+        assert_eq!(indices, vec![7, 7, 7, 1, 2, 3, 0, 0, 0, 0, 0, 0, 0, 0, 9, 9, 0, 0]);
+        assert_eq!(used, stream.len());
+
+        // RLE4 runs alternate nibbles, literals are packed two to a byte
+        let stream = [5, 0x12, 0, 3, 0x34, 0x50, 0, 1];
+        let (indices, _) = decode_rle(&stream, 8, 1, 4).unwrap();
+        assert_eq!(indices, vec![1, 2, 1, 2, 1, 3, 4, 5]);
+
+        assert!(matches!(decode_rle(&[0, 5, 1, 2], 8, 1, 8), Err(MayaError::CorruptHeader(_))));
+
+        for (bits, width) in [(8, 37), (4, 37), (4, 300), (8, 1)] {
+            let indices: Vec<u8> = (0..width * 11)
+                .map(|pixel: usize| if pixel % 13 < 6 { 3 } else { ((pixel.wrapping_mul(2654435761) >> 7) % (1 << bits)) as u8 })
+                .collect();
+            let stream = encode_rle(&indices, width, 11, bits);
+            assert_eq!(decode_rle(&stream, width, 11, bits).unwrap(), (indices, stream.len()), "{bits} {width}");
+        }
+    }
+
+    #[test]
+    fn test_bmp_rle_round_trip(){
+        for (header_size, bit_count) in [(40, 8), (40, 4), (124, 8), (108, 4)] {
+            let bmp = rle_bmp(header_size, bit_count, 83, 41);
+            let bmp_image_parser = BmpImageParser::from_bytes(bmp.clone()).unwrap();
+            let original_pixels = bmp_image_parser.rle_pixels.clone().unwrap();
+
+            let written = embed_and_retrieve_with_method(&bmp, FileEncoding::Lsb, FileEncodingMethod::BlockRaster, b"run length");
+            assert_eq!(u32::from_le_bytes(written[2..6].try_into().unwrap()) as usize, written.len());
+            assert_eq!(written[30..34], bmp[30..34], "still RLE");
+
+            let rewritten = BmpImageParser::from_bytes(written.clone()).unwrap();
+            assert_ne!(rewritten.rle_pixels.unwrap(), original_pixels);
+
+            // The profile moved along with the end of the pixel map
+            if header_size == 124 {
+                let profile_offset = u32::from_le_bytes(written[126..130].try_into().unwrap()) as usize;
+                assert!(written[14 + profile_offset..].starts_with(b"not really an ICC profile"));
+            }
+
+            // Or write it out uncompressed
+            let mut bmp_image_parser = BmpImageParser::from_bytes(bmp.clone()).unwrap();
+            bmp_image_parser.rle_output = RleOutput::Uncompressed;
+            let mut data_vec = b"flat out".to_vec();
+            bmp_image_parser.embed_data(&mut data_vec, FileEncoding::Lsb, FileEncodingMethod::BlockRaster, FileEncodingFunctionDerivation::MethodBased).unwrap();
+            let uncompressed = bmp_image_parser.to_bytes().unwrap();
+            assert_eq!(u32::from_le_bytes(uncompressed[30..34].try_into().unwrap()), BI_RGB);
+            assert_eq!(uncompressed.len(), bmp.len() - bmp_image_parser.rle_stream_size + original_pixels.len());
+
+            let mut bmp_image_parser = BmpImageParser::from_bytes(uncompressed).unwrap();
+            assert!(bmp_image_parser.rle_pixels.is_none());
+            assert_eq!(bmp_image_parser.retrieve_data(FileEncoding::Lsb, FileEncodingMethod::BlockRaster, FileEncodingFunctionDerivation::MethodBased).unwrap(), b"flat out");
+        }
+    }
+
+    #[test]
+    fn test_bmp_rle_output_option(){
+        let bmp = rle_bmp(40, 8, 83, 41);
+        let recompressed = embed(&bmp, b"through the library", &EmbedOptions::default()).unwrap();
+        assert_eq!(recompressed[30..34], bmp[30..34], "still RLE");
+
+        let options = EmbedOptions { rle_output: RleOutput::Uncompressed, ..EmbedOptions::default() };
+        let uncompressed = embed(&bmp, b"through the library", &options).unwrap();
+        assert_eq!(u32::from_le_bytes(uncompressed[30..34].try_into().unwrap()), BI_RGB);
+        assert!(uncompressed.len() > recompressed.len());
+        assert_eq!(extract(&uncompressed, &EmbedOptions::default()).unwrap(), b"through the library");
+
+        // Growing the file is reported back instead of printed
+        let report = embed_with_report(&bmp, b"through the library", &options).unwrap();
+        assert_eq!(report.output, uncompressed);
+        let (compressed, expanded) = report.rle_expansion.unwrap();
+        assert_eq!(uncompressed.len() - expanded, bmp.len() - compressed);
+        assert_eq!(embed_with_report(&bmp, b"through the library", &EmbedOptions::default()).unwrap().rle_expansion, None);
+    }
+}
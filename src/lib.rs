@@ -0,0 +1,273 @@
+/*
+    Copyright (C) 2025 Dustyn Gibb
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+
+ */
+// Every module keeps its code in a file of the same name inside its own directory
+#![allow(clippy::module_inception)]
+
+use crate::analysis::analysis::{LengthEstimate, PixelChannels};
+use crate::analysis::chi_square::{chi_square_attack, ChannelChiSquare};
+use crate::analysis::rs::rs_estimate;
+use crate::analysis::sample_pair::sample_pair_estimate;
+use crate::compression::compression::{compress, decompress, CompressionCodec};
+use crate::error::error::MayaError;
+use crate::file_encoding_support::capacity::CapacityReport;
+use crate::file_encoding_support::encryption::{decrypt_payload, encrypt_payload, is_encrypted};
+use crate::file_encoding_support::file_encoding_support::{
+    EncodingParameters, FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod, FileEncodingSupport,
+};
+use crate::file_encoding_support::palette::PaletteEmbedding;
+use crate::filetype_support::bmp::{BmpImageParser, RleOutput};
+use crate::filetype_support::filetype_support::FileType;
+use crate::filetype_support::jpg::JpegImageParser;
+use crate::filetype_support::png::PngImageParser;
+
+pub mod analysis;
+pub mod filetype_support;
+pub mod file_encoding_support;
+pub mod mathematics_support;
+pub mod compression;
+pub mod error;
+#[cfg(test)]
+mod tests;
+
+/*
+    Everything that has to match between embedding and extracting, plus how the message is packed on the way in
+ */
+#[derive(Clone, Debug)]
+pub struct EmbedOptions {
+    pub encoding: FileEncoding,
+    pub encoding_method: FileEncodingMethod,
+    pub file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    pub passphrase: Option<String>, // Encrypts the message before embedding when set, see encryption.rs
+    pub compression: CompressionCodec, // Only matters when embedding, extraction reads the codec from the payload flags
+    pub pvd_ranges: Option<Vec<u32>>, // PVD range widths instead of Wu and Tsai's, see PvdRangeTable
+    pub hamming_k: Option<u32>, // HammingMatrix group size, picked from the message size when None
+    pub palette_tolerance: Option<u8>, // Indexed BMP and PNG only swap palette neighbours at most this far apart
+    pub palette_embedding: PaletteEmbedding, // ReorderPalette rewrites the palette in luminance order when embedding
+    pub rle_output: RleOutput, // How an RLE compressed BMP is written back out, only matters when embedding
+}
+
+impl Default for EmbedOptions {
+    fn default() -> Self {
+        EmbedOptions {
+            encoding: FileEncoding::Lsb,
+            encoding_method: FileEncodingMethod::LeftToRight,
+            file_encoding_function_derivation: FileEncodingFunctionDerivation::MethodBased,
+            passphrase: None,
+            compression: CompressionCodec::Deflate,
+            pvd_ranges: None,
+            hamming_k: None,
+            palette_tolerance: None,
+            palette_embedding: PaletteEmbedding::LuminanceOrder,
+            rle_output: RleOutput::Recompress,
+        }
+    }
+}
+
+impl EmbedOptions {
+    /*
+        The parts of the options the parsers hold on to, see EncodingParameters
+     */
+    pub fn encoding_parameters(&self) -> EncodingParameters {
+        EncodingParameters {
+            hamming_k: self.hamming_k,
+            pvd_ranges: self.pvd_ranges.clone(),
+            palette_tolerance: self.palette_tolerance,
+            palette_embedding: self.palette_embedding,
+        }
+    }
+}
+
+/*
+    The new file from embed_with_report and anything about it the caller might want to pass on
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EmbedReport {
+    pub output: Vec<u8>,
+    pub rle_expansion: Option<(usize, usize)>, // Bytes before / after when an RLE BMP was written uncompressed
+}
+
+/*
+    A payload find_payloads turned up, the message itself stays encrypted / compressed
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FoundPayload {
+    pub encoding: FileEncoding,
+    pub encoding_method: FileEncodingMethod,
+    pub length: usize,
+    pub flags: u8,
+    pub encrypted: bool,
+}
+
+/*
+    Picks the parser off the magic bytes and parses the carrier with it
+ */
+pub fn open_carrier(carrier: &[u8]) -> Result<Box<dyn FileEncodingSupport>, MayaError> {
+    match FileType::detect(carrier) {
+        Some(FileType::Bmp) => Ok(Box::new(BmpImageParser::from_bytes(carrier.to_vec())?)),
+        Some(FileType::Png) => Ok(Box::new(PngImageParser::from_bytes(carrier.to_vec())?)),
+        Some(FileType::Jpeg) => Ok(Box::new(JpegImageParser::from_bytes(carrier.to_vec())?)),
+        _ => Err(MayaError::UnsupportedFormat("not a BMP, PNG or JPEG file".to_string())),
+    }
+}
+
+/*
+    open_carrier with the encoding parameters from the options already set on the parser
+ */
+fn open_carrier_with_options(carrier: &[u8], options: &EmbedOptions) -> Result<Box<dyn FileEncodingSupport>, MayaError> {
+    let mut parser = open_carrier(carrier)?;
+    parser.set_encoding_parameters(options.encoding_parameters());
+    Ok(parser)
+}
+
+/*
+    Hides message in carrier and hands back the whole new file. Compression happens first since ciphertext
+    doesn't compress.
+ */
+pub fn embed(carrier: &[u8], message: &[u8], options: &EmbedOptions) -> Result<Vec<u8>, MayaError> {
+    Ok(embed_with_report(carrier, message, options)?.output)
+}
+
+/*
+    Same as embed, with what happened to the file along the way
+ */
+pub fn embed_with_report(carrier: &[u8], message: &[u8], options: &EmbedOptions) -> Result<EmbedReport, MayaError> {
+    let mut parser = open_carrier_with_options(carrier, options)?;
+    parser.set_rle_output(options.rle_output);
+
+    let (codec, mut data) = compress(message, options.compression);
+    if let Some(passphrase) = &options.passphrase {
+        data = encrypt_payload(&data, passphrase)?;
+    }
+
+    parser.embed_data_with_flags(
+        &mut data,
+        options.encoding,
+        options.encoding_method,
+        options.file_encoding_function_derivation,
+        codec.id(),
+    )?;
+    Ok(EmbedReport {
+        output: parser.to_bytes()?,
+        rle_expansion: parser.rle_expansion(),
+    })
+}
+
+/*
+    Undoes embed, the options need the same encoding, method, key and passphrase that were used to embed
+ */
+pub fn extract(carrier: &[u8], options: &EmbedOptions) -> Result<Vec<u8>, MayaError> {
+    let mut parser = open_carrier_with_options(carrier, options)?;
+
+    let (mut data, flags) = parser.retrieve_data_with_flags(
+        options.encoding,
+        options.encoding_method,
+        options.file_encoding_function_derivation,
+    )?;
+
+    match &options.passphrase {
+        Some(passphrase) => data = decrypt_payload(&data, passphrase)?,
+        None if is_encrypted(&data) => return Err(MayaError::PassphraseRequired),
+        None => {}
+    }
+
+    Ok(decompress(&data, CompressionCodec::from_flags(flags)?)?)
+}
+
+/*
+    How big a message carrier can take with these options, see capacity.rs for what goes into the numbers. Usable
+    bytes are before compression, so compressible messages can go over it.
+ */
+pub fn capacity(carrier: &[u8], options: &EmbedOptions) -> Result<CapacityReport, MayaError> {
+    let mut parser = open_carrier_with_options(carrier, options)?;
+    let raw_bits = parser.carrier_capacity_bits(options.encoding)?;
+
+    Ok(CapacityReport::new(
+        parser.file_type(),
+        options.encoding,
+        options.encoding_method,
+        raw_bits,
+        options.passphrase.is_some(),
+    ))
+}
+
+/*
+    Tries every encoding and method the carrier supports with the given derivation and keeps whatever comes back
+    with a valid header and checksum. A keyed payload only shows up when the same key is passed in.
+ */
+pub fn find_payloads(
+    carrier: &[u8],
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+) -> Result<Vec<FoundPayload>, MayaError> {
+    let mut parser = open_carrier(carrier)?;
+    let mut found = Vec::new();
+
+    for encoding in (0..=u8::MAX).map_while(FileEncoding::from_id) {
+        for encoding_method in (0..=u8::MAX).map_while(FileEncodingMethod::from_id) {
+            match parser.retrieve_data_with_flags(encoding, encoding_method, file_encoding_function_derivation) {
+                Ok((data, flags)) => found.push(FoundPayload {
+                    encoding,
+                    encoding_method,
+                    length: data.len(),
+                    flags,
+                    encrypted: is_encrypted(&data),
+                }),
+                Err(MayaError::UnsupportedEncoding { .. }) => break,
+                Err(_) => {}
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/*
+    Every channel of a BMP or PNG carrier as plain samples, see analysis/
+ */
+pub fn pixel_channels(carrier: &[u8]) -> Result<PixelChannels, MayaError> {
+    open_carrier(carrier)?.pixel_channels()
+}
+
+/*
+    Chi-square attack on every channel of the carrier, the curve follows the pixel order the method and derivation
+    would embed in. window is the number of pixels per point on the curve, None picks one for you.
+ */
+pub fn chi_square_analysis(
+    carrier: &[u8],
+    encoding_method: FileEncodingMethod,
+    file_encoding_function_derivation: FileEncodingFunctionDerivation,
+    window: Option<usize>,
+) -> Result<Vec<ChannelChiSquare>, MayaError> {
+    let pixels = pixel_channels(carrier)?;
+    let order = pixels.visit_order(encoding_method, file_encoding_function_derivation);
+    Ok(chi_square_attack(&pixels, &order, window))
+}
+
+/*
+    RS analysis on every channel, how much of each channel looks like it was LSB replaced
+ */
+pub fn rs_analysis(carrier: &[u8]) -> Result<Vec<LengthEstimate>, MayaError> {
+    Ok(rs_estimate(&pixel_channels(carrier)?))
+}
+
+/*
+    Sample pair analysis on every channel, same idea as rs_analysis by a different route
+ */
+pub fn sample_pair_analysis(carrier: &[u8]) -> Result<Vec<LengthEstimate>, MayaError> {
+    Ok(sample_pair_estimate(&pixel_channels(carrier)?))
+}
@@ -16,25 +16,152 @@ along with this program; if not, write to the Free Software
 Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
 
  */
+// Same layout as the library, every module's code sits in a file of the same name
+#![allow(clippy::module_inception)]
+
 use std::env;
-use std::process::CommandArgs;
-use crate::arg_handling::arg_handling::arg_handling::parse_arguments;
-use crate::file_encoding_support::file_encoding_support::{ImageSupport, Operation};
+use std::fs;
+use std::io::{Read, Write};
+use crate::arg_handling::arg_handling::arg_handling::{parse_arguments, ImageSupport, STDIO};
+use veritasobscura::error::error::MayaError;
+use veritasobscura::file_encoding_support::file_encoding_support::{FileEncoding, Operation};
+use veritasobscura::filetype_support::filetype_support::FileType;
+use veritasobscura::{capacity, chi_square_analysis, embed_with_report, extract, find_payloads, open_carrier, rs_analysis, sample_pair_analysis, EmbedOptions};
+use std::process::exit;
 
-mod filetype_support;
-mod file_encoding_support;
 mod arg_handling;
-mod mathematics_support;
 
 fn main()  {
     let args : Vec<String> = env::args().collect();
 
-    let mut image_support: ImageSupport = parse_arguments(args);
-    
-    match image_support.operation {
-        Operation::Embed  => { image_support.encoding_support.embed_data(&mut image_support.data,image_support.encoding,image_support.encoding_method,image_support.file_encoding_function_derivation) },
-        Operation::Extract=> { image_support.encoding_support.retrieve_data(image_support.data, image_support.encoding, image_support.encoding_method, image_support.file_encoding_function_derivation) }
+    let image_support: ImageSupport = parse_arguments(args);
+
+    if let Err(e) = run(image_support) {
+        eprintln!("main.rs: {e}");
+        exit(1);
+    }
+}
+
+fn read_input(path: &str) -> Result<Vec<u8>, MayaError> {
+    if path == STDIO {
+        let mut data = Vec::new();
+        std::io::stdin().read_to_end(&mut data)?;
+        return Ok(data);
     }
 
+    Ok(fs::read(path)?)
+}
+
+fn write_output(path: &str, data: &[u8]) -> Result<(), MayaError> {
+    if path == STDIO {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(data)?;
+        stdout.flush()?;
+        return Ok(());
+    }
+
+    Ok(fs::write(path, data)?)
+}
+
+/*
+    All the real work is in the library, this just moves bytes between it and files / stdin / stdout
+ */
+fn run(image_support: ImageSupport) -> Result<(), MayaError> {
+    let carrier = read_input(&image_support.input)?;
+    let options = EmbedOptions {
+        encoding: image_support.encoding,
+        encoding_method: image_support.encoding_method,
+        file_encoding_function_derivation: image_support.file_encoding_function_derivation,
+        passphrase: image_support.passphrase,
+        compression: image_support.compression,
+        pvd_ranges: image_support.pvd_ranges,
+        hamming_k: image_support.hamming_k,
+        palette_tolerance: image_support.palette_tolerance,
+        palette_embedding: image_support.palette_embedding,
+        rle_output: image_support.rle_output,
+    };
+
+    match image_support.operation {
+        Operation::Embed => {
+            let message = match &image_support.payload_file {
+                Some(payload_file) => read_input(payload_file)?,
+                None => image_support.data,
+            };
+            let report = embed_with_report(&carrier, &message, &options)?;
+            write_output(image_support.output.as_deref().unwrap_or(STDIO), &report.output)?;
+
+            if let Some((compressed, uncompressed)) = report.rle_expansion {
+                eprintln!("main.rs: warning: the RLE compressed pixel map was written uncompressed, it grew from {compressed} to {uncompressed} bytes");
+            }
+        }
+        Operation::Extract => {
+            let data = extract(&carrier, &options)?;
+            write_output(image_support.output.as_deref().unwrap_or(STDIO), &data)?;
+        }
+        Operation::Capacity => {
+            println!("{}", capacity(&carrier, &options)?);
+        }
+        Operation::Info => {
+            let file_type = match FileType::detect(&carrier) {
+                Some(file_type) => file_type,
+                None => return Err(MayaError::UnsupportedFormat("not a BMP, PNG or JPEG file".to_string())),
+            };
+            println!("{:?}, {} bytes", file_type, carrier.len());
+
+            let mut parser = open_carrier(&carrier)?;
+            parser.set_encoding_parameters(options.encoding_parameters());
+            for encoding in (0..=u8::MAX).map_while(FileEncoding::from_id) {
+                match parser.carrier_capacity_bits(encoding) {
+                    Ok(bits) => println!("  {:?}: {} bits raw", encoding, bits),
+                    Err(MayaError::UnsupportedEncoding { .. }) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Operation::Analyze if image_support.chi_square => {
+            let results = chi_square_analysis(&carrier, options.encoding_method, options.file_encoding_function_derivation, image_support.window)?;
+            if let Some(first) = results.first() {
+                println!("Chi-square attack along {:?}, {} pixels per window", options.encoding_method, first.window);
+            }
+
+            for result in results {
+                println!(
+                    "  channel {}: statistic {:.2} with {} degrees of freedom, embedding probability {:.4}",
+                    result.channel, result.overall.statistic, result.overall.degrees_of_freedom, result.overall.probability
+                );
+                let curve: Vec<String> = result.curve.iter().map(|probability| format!("{:.0}", probability * 100.0)).collect();
+                println!("    curve (%): {}", curve.join(" "));
+            }
+        }
+        Operation::Analyze if image_support.estimate_length => {
+            for (name, estimates) in [("RS analysis", rs_analysis(&carrier)?), ("Sample pair analysis", sample_pair_analysis(&carrier)?)] {
+                println!("{name}");
+                for estimate in estimates {
+                    println!(
+                        "  channel {}: embedding rate {:.4}, about {} message bits",
+                        estimate.channel, estimate.embedding_rate, estimate.message_bits
+                    );
+                }
+            }
+        }
+        Operation::Analyze => {
+            let found = find_payloads(&carrier, options.file_encoding_function_derivation)?;
+            if found.is_empty() {
+                println!("No payload found (a keyed payload needs the same --key to show up)");
+            }
+
+            for payload in found {
+                println!(
+                    "{:?} / {:?}: {} byte payload, flags {:#04x}{}",
+                    payload.encoding,
+                    payload.encoding_method,
+                    payload.length,
+                    payload.flags,
+                    if payload.encrypted { ", encrypted" } else { "" }
+                );
+            }
+        }
+    }
 
+    Ok(())
 }
@@ -0,0 +1,191 @@
+/*
+ * Copyright (C) 2025 Dustyn Gibb
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
+ */
+use std::io;
+
+/*
+    Canonical Huffman tables limited to 16 bit codes the way JPEG builds them. The JPEG parser reads and writes its
+    DHT segments with these and ShortText compression codes with one built from English letter frequencies
+ */
+#[derive(Debug, Clone)]
+pub struct HuffmanTable {
+    pub counts: [u8; 16], // Number of codes of each length 1..=16
+    pub symbols: Vec<u8>,
+    max_code: [i32; 17], // Largest code of each length, -1 if there are none
+    value_offset: [i32; 17], // Added to a code of that length to get its index into symbols
+    codes: [(u16, u8); 256], // Code and length for every symbol, length 0 if the symbol has no code
+}
+
+impl HuffmanTable {
+    /*
+        Canonical Huffman codes as laid out in section C of the JPEG spec, codes of each length are consecutive and
+        the first code of a length is one past the last code of the previous length shifted left
+     */
+    pub fn new(counts: [u8; 16], symbols: Vec<u8>) -> io::Result<HuffmanTable> {
+        let total: usize = counts.iter().map(|count| *count as usize).sum();
+        if total != symbols.len() || total > 256 {
+            return Err(invalid_data("Huffman table symbol count mismatch"));
+        }
+
+        let mut table = HuffmanTable {
+            counts,
+            symbols,
+            max_code: [-1; 17],
+            value_offset: [0; 17],
+            codes: [(0, 0); 256],
+        };
+
+        let mut code: u32 = 0;
+        let mut index: usize = 0;
+
+        for length in 1..=16 {
+            let count = counts[length - 1] as usize;
+
+            if count > 0 {
+                table.value_offset[length] = index as i32 - code as i32;
+                for _ in 0..count {
+                    table.codes[table.symbols[index] as usize] = (code as u16, length as u8);
+                    code += 1;
+                    index += 1;
+                }
+                table.max_code[length] = code as i32 - 1;
+            }
+
+            if code > (1 << length) {
+                return Err(invalid_data("Huffman table has too many codes"));
+            }
+            code <<= 1;
+        }
+
+        Ok(table)
+    }
+
+    /*
+        Optimal code lengths for the given symbol frequencies limited to 16 bits, section K.2 of the spec. A reserved
+        extra symbol with a count of 1 makes sure no real symbol ends up with the all ones code.
+     */
+    pub fn optimal(frequencies: &[u32; 256]) -> HuffmanTable {
+        let mut frequency = [0u64; 257];
+        for (symbol, count) in frequencies.iter().enumerate() {
+            frequency[symbol] = *count as u64;
+        }
+        frequency[256] = 1;
+
+        let mut code_size = [0usize; 257];
+        let mut others = [-1i32; 257];
+
+        loop {
+            // Least frequent symbol, ties go to the larger symbol, then the next least frequent after it
+            let mut v1: Option<usize> = None;
+            for symbol in 0..257 {
+                if frequency[symbol] > 0 && v1.is_none_or(|v1| frequency[symbol] <= frequency[v1]) {
+                    v1 = Some(symbol);
+                }
+            }
+            let mut v2: Option<usize> = None;
+            for symbol in 0..257 {
+                if Some(symbol) != v1 && frequency[symbol] > 0 && v2.is_none_or(|v2| frequency[symbol] <= frequency[v2]) {
+                    v2 = Some(symbol);
+                }
+            }
+
+            let (mut v1, mut v2) = match (v1, v2) {
+                (Some(v1), Some(v2)) => (v1, v2),
+                _ => break,
+            };
+
+            frequency[v1] += frequency[v2];
+            frequency[v2] = 0;
+
+            code_size[v1] += 1;
+            while others[v1] >= 0 {
+                v1 = others[v1] as usize;
+                code_size[v1] += 1;
+            }
+            others[v1] = v2 as i32;
+
+            code_size[v2] += 1;
+            while others[v2] >= 0 {
+                v2 = others[v2] as usize;
+                code_size[v2] += 1;
+            }
+        }
+
+        let mut lengths = [0u32; 33];
+        for size in code_size.iter().filter(|size| **size > 0) {
+            lengths[*size] += 1;
+        }
+
+        // Move anything longer than 16 bits up the tree, section K.2 figure K.3
+        for length in (17..=32).rev() {
+            while lengths[length] > 0 {
+                let mut shorter = length - 2;
+                while lengths[shorter] == 0 {
+                    shorter -= 1;
+                }
+                lengths[length] -= 2;
+                lengths[length - 1] += 1;
+                lengths[shorter + 1] += 2;
+                lengths[shorter] -= 1;
+            }
+        }
+
+        // Drop the reserved symbol, it always has one of the longest codes. A table nothing uses has no codes at all
+        if let Some(longest) = (1..=16).rev().find(|length| lengths[*length] > 0) {
+            lengths[longest] -= 1;
+        }
+
+        let mut counts = [0u8; 16];
+        for length in 1..=16 {
+            counts[length - 1] = lengths[length] as u8;
+        }
+
+        let mut symbols = Vec::new();
+        for size in 1..=32 {
+            for (symbol, _) in code_size[..256].iter().enumerate().filter(|(_, code_size)| **code_size == size) {
+                symbols.push(symbol as u8);
+            }
+        }
+
+        HuffmanTable::new(counts, symbols).expect("section K.2 always yields a valid table")
+    }
+
+    /*
+        Code and length for the symbol, None if the table has no code for it
+     */
+    pub fn code(&self, symbol: u8) -> Option<(u16, u8)> {
+        match self.codes[symbol as usize] {
+            (_, 0) => None,
+            code => Some(code),
+        }
+    }
+
+    /*
+        The symbol for the first length bits read so far, None means keep reading. Shorter prefixes have to be
+        looked up first, that is what makes the canonical ordering unambiguous
+     */
+    pub fn lookup(&self, code: i32, length: usize) -> Option<u8> {
+        if code <= self.max_code[length] {
+            return Some(self.symbols[(code + self.value_offset[length]) as usize]);
+        }
+        None
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
@@ -16,43 +16,220 @@
  * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
  */
 
-fn sin_wave(input: u64) -> f64 {
-    (input as f64).sin()
+/*
+    Scaled and rounded to the nearest integer, negative values stay negative. These go through portable_sin rather
+    than f64::sin so they come out the same on every platform
+ */
+pub fn sin_scaled(input: f64, scale: f64) -> i64 {
+    (portable_sin(input) * scale).round() as i64
 }
 
-fn sin_scaled(input: u64, scale: f64) -> u64 {
-    ((input as f64).sin() * scale) as u64
+pub fn cos_scaled(input: f64, scale: f64) -> i64 {
+    (portable_sin(input + std::f64::consts::FRAC_PI_2) * scale).round() as i64
+}
+
+/*
+    sqrt is correctly rounded by IEEE 754 so unlike sin this one is already the same everywhere
+ */
+pub fn sqrt_scaled(input: u64, scale: f64) -> u64 {
+    ((input as f64).sqrt() * scale) as u64
 }
 
-fn cos_wave(input: u64) -> f64 {
-    (input as f64).cos()
+/*
+    Sine built out of nothing but + - * and round on f64, all of which IEEE 754 pins down exactly. f64::sin goes to
+    the platform's libm which is free to differ in the last bit, and a traversal that rounds a wave to a pixel row
+    on one machine has to land on the same row on every other one or the message can't be found again.
+ */
+pub fn portable_sin(input: f64) -> f64 {
+    use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+    // Down to [-pi, pi] and then to [-pi/2, pi/2] with sin(pi - x) = sin(x)
+    let mut x = input - (input / TAU).round() * TAU;
+    if x > FRAC_PI_2 {
+        x = PI - x;
+    } else if x < -FRAC_PI_2 {
+        x = -PI - x;
+    }
+
+    // Taylor series out to x^17, the first term left off is under 1e-13 at pi/2
+    let x2 = x * x;
+    let mut sum = 0.0;
+    let mut term = x;
+    for n in 1..=9 {
+        sum += term;
+        term *= -x2 / ((2 * n) * (2 * n + 1)) as f64;
+    }
+
+    sum
 }
 
-fn cos_scaled(input: u64, scale: f64) -> u64 {
-    ((input as f64).cos() * scale) as u64
+/*
+    Standard CRC-32 (IEEE 802.3, reflected, polynomial 0xEDB88320). This is the same CRC that PNG and zlib use
+    so it can be shared by the payload container and any file format parser that needs it
+ */
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+const fn make_crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            if c & 1 != 0 {
+                c = CRC32_POLYNOMIAL ^ (c >> 1);
+            } else {
+                c >>= 1;
+            }
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
 }
 
-fn tan_wave(input: u64) -> f64 {
-    (input as f64).tan()
+static CRC32_TABLE: [u32; 256] = make_crc32_table();
+
+/*
+    Feed more bytes into a running crc, start with 0xFFFFFFFF and xor the final value with 0xFFFFFFFF.
+    Use crc32 if you just have a single buffer
+ */
+pub fn crc32_update(mut crc: u32, bytes: &[u8]) -> u32 {
+    for byte in bytes {
+        crc = CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
 }
 
-fn exp_wave(input: u64) -> f64 {
-    (input as f64).exp()
+pub fn crc32(bytes: &[u8]) -> u32 {
+    crc32_update(0xFFFF_FFFF, bytes) ^ 0xFFFF_FFFF
 }
 
-fn log_wave(input: u64) -> f64 {
-    (input as f64).ln()
+/*
+    The checksum that closes a zlib stream, RFC 1950 section 8
+ */
+pub fn adler32(bytes: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+
+    // 5552 is the most bytes that can be summed before b could overflow a u32
+    for chunk in bytes.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= MODULUS;
+        b %= MODULUS;
+    }
+
+    (b << 16) | a
 }
 
-fn sqrt_wave(input: u64) -> f64 {
-    (input as f64).sqrt()
+/*
+    Syndrome of a group of cover bits under the (1, 2^k - 1, k) Hamming code, the XOR of the 1 based positions of
+    every set bit. Matrix embedding makes this equal to k message bits by changing at most one cover bit, the one
+    at position syndrome ^ message.
+ */
+pub fn hamming_syndrome<I: Iterator<Item = bool>>(bits: I) -> u32 {
+    bits.enumerate()
+        .filter(|(_, bit)| *bit)
+        .fold(0, |syndrome, (index, _)| syndrome ^ (index as u32 + 1))
 }
 
-fn sqrt_scaled(input: u64, scale: f64) -> u64 {
-    ((input as f64).sqrt() * scale) as u64
+/*
+    Largest k up to max_k where groups of 2^k - 1 cover bits still fit the payload, a bigger k means fewer changes
+    per embedded bit
+ */
+pub fn hamming_choose_k(payload_bits: u64, cover_bits: u64, max_k: u32) -> u32 {
+    (1..=max_k)
+        .rev()
+        .find(|k| (cover_bits / ((1 << k) - 1)) * *k as u64 >= payload_bits)
+        .unwrap_or(1)
 }
 
-fn mod_wave(input: u64, modulus: u64) -> u64 {
-    input % modulus
+/*
+    ln(Gamma(x)) for x > 0, Lanczos approximation (g = 7, 9 terms) which is good to about 15 digits
+ */
+pub fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        // Reflection, the series below only holds for x >= 0.5
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (index, coefficient)| sum + coefficient / (x + index as f64 + 1.0));
+
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
 }
 
+/*
+    Upper regularized incomplete gamma function Q(a, x) = Gamma(a, x) / Gamma(a). The chi-square survival function
+    with k degrees of freedom is Q(k / 2, statistic / 2). Series below a + 1, continued fraction above it, same
+    split as Numerical Recipes uses.
+ */
+pub fn regularized_gamma_q(a: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-15;
+    const TINY: f64 = 1e-300;
+    const MAX_ITERATIONS: usize = 1000;
+
+    if x <= 0.0 {
+        return 1.0;
+    }
+
+    let prefix = (-x + a * x.ln() - ln_gamma(a)).exp();
+
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..MAX_ITERATIONS {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+        return (1.0 - sum * prefix).clamp(0.0, 1.0);
+    }
+
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / TINY;
+    let mut d = 1.0 / b;
+    let mut fraction = d;
+    for i in 1..MAX_ITERATIONS {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = b + an / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        fraction *= delta;
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+
+    (fraction * prefix).clamp(0.0, 1.0)
+}
@@ -16,4 +16,5 @@
  * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
  */
 
+pub mod huffman;
 pub mod mathematics_support;
\ No newline at end of file
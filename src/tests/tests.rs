@@ -14,4 +14,1342 @@ GNU General Public License for more details.
 You should have received a copy of the GNU General Public License
 along with this program; if not, write to the Free Software
 Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA
- */
\ No newline at end of file
+ */
+/*
+    Helpers shared by every module below
+ */
+#[cfg(test)]
+mod fixtures {
+    /*
+        One of the sample images in filetype_support/assets, read into memory the way the library API takes it
+     */
+    pub fn carrier(name: &str) -> Vec<u8> {
+        std::fs::read(format!("src/filetype_support/assets/{name}")).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod payload_tests {
+    use crate::file_encoding_support::file_encoding_support::{FileEncoding, FileEncodingMethod};
+    use crate::file_encoding_support::payload::{build_payload, read_payload, PayloadError, PayloadHeader, PAYLOAD_HEADER_BITS, PAYLOAD_HEADER_SIZE};
+    use crate::mathematics_support::mathematics_support::crc32;
+
+    #[test]
+    fn test_crc32_known_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_payload_header_round_trip() {
+        let data = b"header round trip".to_vec();
+        let header = PayloadHeader::new(&data, FileEncoding::Lsb, FileEncodingMethod::RightToLeft, 0);
+        let parsed = PayloadHeader::from_bytes(&header.to_bytes()).unwrap();
+
+        assert_eq!(parsed, header);
+        assert_eq!(parsed.length, data.len() as u64);
+        assert_eq!(parsed.total_bits(), PAYLOAD_HEADER_BITS + data.len() as u64 * 8);
+        assert!(parsed.verify(&data).is_ok());
+    }
+
+    #[test]
+    fn test_payload_header_rejects_garbage() {
+        assert_eq!(PayloadHeader::from_bytes(&[0u8; PAYLOAD_HEADER_SIZE]), Err(PayloadError::BadMagic));
+        assert_eq!(PayloadHeader::from_bytes(b"MAYA"), Err(PayloadError::BadMagic));
+
+        let mut bytes = PayloadHeader::new(b"x", FileEncoding::Lsb, FileEncodingMethod::LeftToRight, 0).to_bytes();
+        bytes[4] = 0xFF;
+        assert_eq!(PayloadHeader::from_bytes(&bytes), Err(PayloadError::UnsupportedVersion(0xFF)));
+    }
+
+    #[test]
+    fn test_read_payload_detects_corruption() {
+        let mut payload = build_payload(b"some secret", FileEncoding::Lsb, FileEncodingMethod::LeftToRight, 0b10);
+        let capacity = payload.len() as u64 * 8;
+
+        let data = read_payload(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, capacity, |_| Ok::<_, PayloadError>(payload.clone()));
+        assert_eq!(data.unwrap(), (b"some secret".to_vec(), 0b10));
+
+        let mismatch = read_payload(FileEncoding::Lsb, FileEncodingMethod::RightToLeft, capacity, |_| Ok::<_, PayloadError>(payload.clone()));
+        assert_eq!(mismatch, Err(PayloadError::EncodingMismatch));
+
+        let too_small = read_payload(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, capacity - 8, |_| Ok::<_, PayloadError>(payload.clone()));
+        assert!(matches!(too_small, Err(PayloadError::LengthExceedsCapacity { .. })));
+
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+        let corrupted = read_payload(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, capacity, |_| Ok::<_, PayloadError>(payload.clone()));
+        assert!(matches!(corrupted, Err(PayloadError::CrcMismatch { .. })));
+    }
+}
+
+#[cfg(test)]
+mod palette_tests {
+    use crate::file_encoding_support::file_encoding_support::{FileEncodingFunctionDerivation, FileEncodingMethod};
+    use crate::file_encoding_support::palette::{
+        embed_palette_data, extract_palette_data, palette_capacity_bits, Palette, PaletteEntry,
+    };
+
+    fn gray_palette(levels: &[u8]) -> Palette {
+        Palette {
+            entries: levels
+                .iter()
+                .map(|level| PaletteEntry { red: *level, green: *level, blue: *level, alpha: 255 })
+                .collect(),
+            tolerance: None,
+        }
+    }
+
+    #[test]
+    fn test_luminance_order_and_sort() {
+        let mut palette = gray_palette(&[200, 10, 100, 50]);
+        assert_eq!(palette.luminance_order(), vec![1, 3, 2, 0]);
+
+        let remap = palette.sort_by_luminance();
+        assert_eq!(&remap[0..4], &[3, 0, 2, 1]);
+        assert_eq!(palette.entries, gray_palette(&[10, 50, 100, 200]).entries);
+
+        // Once sorted the rank of every entry is its index
+        assert_eq!(palette.luminance_order(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_transparent_entries_are_grouped() {
+        let mut palette = gray_palette(&[10, 20, 30]);
+        palette.entries[2].alpha = 0;
+        assert_eq!(palette.luminance_order(), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn test_palette_round_trip_only_moves_to_neighbours() {
+        let palette = gray_palette(&[250, 0, 128, 64, 192, 32, 96, 160, 224]);
+        let original: Vec<u8> = (0..400u32).map(|i| ((i * 7 + i / 3) % 9) as u8).collect();
+        let mut indices = original.clone();
+
+        // 9 entries, the brightest has no partner so those pixels can't carry anything
+        let unpaired = original.iter().filter(|index| **index == 0).count() as u64;
+        assert_eq!(palette_capacity_bits(&indices, &palette), 400 - unpaired);
+
+        let data = b"EzStego".to_vec();
+        embed_palette_data(&data, &mut indices, 20, 20, &palette, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+
+        let ranks = palette.ranks();
+        for (before, after) in original.iter().zip(indices.iter()) {
+            let before = ranks[*before as usize].unwrap();
+            let after = ranks[*after as usize].unwrap();
+            assert!(before == after || before ^ 1 == after);
+        }
+
+        let extracted = extract_palette_data(&indices, 20, 20, &palette, data.len() as u64 * 8, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased);
+        assert_eq!(extracted[0..data.len()], data[..]);
+    }
+
+    #[test]
+    fn test_palette_tolerance() {
+        let mut palette = gray_palette(&[0, 10, 100, 200, 205, 250]);
+        assert_eq!(palette.entries[0].distance(&palette.entries[1]), 10);
+
+        let indices: Vec<u8> = (0..600).map(|index| (index % 6) as u8).collect();
+        assert_eq!(palette_capacity_bits(&indices, &palette), 600);
+
+        // 100 / 200 are too far apart, 205 / 250 too, only 0 / 10 are left
+        palette.tolerance = Some(20);
+        assert_eq!(palette_capacity_bits(&indices, &palette), 200);
+
+        let data = b"close enough".to_vec();
+        let mut embedded = indices.clone();
+        embed_palette_data(&data, &mut embedded, 30, 20, &palette, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        for (before, after) in indices.iter().zip(embedded.iter()) {
+            assert!(palette.entries[*before as usize].distance(&palette.entries[*after as usize]) <= 20);
+        }
+
+        let extracted = extract_palette_data(&embedded, 30, 20, &palette, data.len() as u64 * 8, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased);
+        assert_eq!(&extracted[..data.len()], data.as_slice());
+    }
+
+    /*
+        The curves walk the image, not the run of indices. Every pixel here carries and every bit flips its pixel,
+        so the pixels that changed are exactly the first ones on the Hilbert curve over the real 40 x 10 grid
+     */
+    #[test]
+    fn test_palette_follows_image_grid() {
+        let palette = gray_palette(&[0, 10]);
+        let mut indices = vec![0u8; 400];
+        let data = vec![0xFFu8; 4];
+
+        embed_palette_data(&data, &mut indices, 40, 10, &palette, FileEncodingMethod::HilbertCurve, FileEncodingFunctionDerivation::MethodBased).unwrap();
+
+        let changed: Vec<usize> = (0..indices.len()).filter(|position| indices[*position] != 0).collect();
+        let mut expected: Vec<usize> = FileEncodingFunctionDerivation::MethodBased.grid_visit_order(FileEncodingMethod::HilbertCurve, 40, 10, 1).take(32).collect();
+        expected.sort();
+        assert_eq!(changed, expected);
+
+        let extracted = extract_palette_data(&indices, 40, 10, &palette, 32, FileEncodingMethod::HilbertCurve, FileEncodingFunctionDerivation::MethodBased);
+        assert_eq!(extracted[..4], data[..]);
+    }
+}
+
+#[cfg(test)]
+mod coefficient_tests {
+    use crate::file_encoding_support::coefficient::{
+        embed_f5_data, embed_jsteg_data, extract_f5_data, extract_jsteg_data, f5_capacity_bits, jsteg_capacity_bits,
+        F5_MAX_K,
+    };
+    use crate::file_encoding_support::file_encoding_support::{FileEncodingFunctionDerivation, FileEncodingMethod};
+    use crate::mathematics_support::mathematics_support::hamming_choose_k;
+
+    /*
+        Something shaped like real AC coefficients, mostly zeros and small magnitudes with the odd large one
+     */
+    fn sample_coefficients(len: usize) -> Vec<i16> {
+        (0..len as u32)
+            .map(|i| {
+                let value = ((i.wrapping_mul(2654435761) >> 7) % 23) as i16 - 11;
+                if i % 3 == 0 { 0 } else { value / 2 }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_jsteg_never_touches_zero_or_one() {
+        let original = sample_coefficients(4000);
+        let mut coefficients = original.clone();
+
+        let data = b"JSteg leaves 0 and 1 alone".to_vec();
+        assert!(data.len() as u64 * 8 <= jsteg_capacity_bits(&coefficients));
+        embed_jsteg_data(&data, &mut coefficients, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+
+        for (before, after) in original.iter().zip(coefficients.iter()) {
+            if *before == 0 || *before == 1 {
+                assert_eq!(before, after);
+            } else {
+                assert!(*after != 0 && *after != 1);
+                assert!((before - after).abs() <= 1);
+            }
+        }
+
+        assert_eq!(jsteg_capacity_bits(&original), jsteg_capacity_bits(&coefficients));
+        let extracted = extract_jsteg_data(&coefficients, data.len() as u64 * 8, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased);
+        assert_eq!(extracted[0..data.len()], data[..]);
+    }
+
+    #[test]
+    fn test_f5_round_trip_with_shrinkage() {
+        let original = sample_coefficients(6000);
+        let data: Vec<u8> = (0..120u32).map(|i| (i * 37 + 11) as u8).collect();
+
+        for encoding_method in [FileEncodingMethod::LeftToRight, FileEncodingMethod::RightToLeft] {
+            let mut coefficients = original.clone();
+            embed_f5_data(&data, &mut coefficients, encoding_method, FileEncodingFunctionDerivation::MethodBased).unwrap();
+
+            // Plenty of +-1s in the sample, some of them have to have shrunk
+            let shrunk = original.iter().zip(coefficients.iter()).filter(|(before, after)| **before != 0 && **after == 0).count();
+            assert!(shrunk > 0);
+
+            let extracted = extract_f5_data(&coefficients, data.len() as u64 * 8, encoding_method, FileEncodingFunctionDerivation::MethodBased);
+            assert_eq!(extracted[0..data.len()], data[..]);
+        }
+    }
+
+    #[test]
+    fn test_f5_only_moves_towards_zero() {
+        let original = sample_coefficients(3000);
+        let mut coefficients = original.clone();
+        let data = b"F5 never adds energy".to_vec();
+        embed_f5_data(&data, &mut coefficients, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+
+        for (before, after) in original.iter().zip(coefficients.iter()) {
+            assert!(after.abs() <= before.abs());
+            assert!(*after == 0 || after.signum() == before.signum());
+        }
+    }
+
+    #[test]
+    fn test_f5_matrix_encoding_changes_less() {
+        let original = sample_coefficients(20000);
+        let capacity = f5_capacity_bits(&original);
+        let data = b"short".to_vec();
+        assert!(hamming_choose_k(data.len() as u64 * 8, capacity, F5_MAX_K) > 1);
+
+        let mut f5 = original.clone();
+        embed_f5_data(&data, &mut f5, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        let mut jsteg = original.clone();
+        embed_jsteg_data(&data, &mut jsteg, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased).unwrap();
+
+        let changed = |coefficients: &[i16]| original.iter().zip(coefficients.iter()).filter(|(a, b)| a != b).count();
+        assert!(changed(&f5) < changed(&jsteg));
+    }
+}
+
+#[cfg(test)]
+mod pvd_tests {
+    use crate::file_encoding_support::file_encoding_support::{FileEncodingFunctionDerivation, FileEncodingMethod};
+    use crate::file_encoding_support::pixel::{embed_pvd_data, extract_pvd_data, pvd_capacity_bits, PvdRangeTable};
+    use crate::filetype_support::png::PngGrayPixel;
+
+    #[test]
+    fn test_range_table_validation() {
+        assert!(PvdRangeTable::new(vec![8, 8, 16, 32, 64, 128], 255).is_some());
+        assert_eq!(PvdRangeTable::new(vec![8, 8, 16, 32, 64, 128], 255), Some(PvdRangeTable::wu_tsai(8)));
+        assert!(PvdRangeTable::new(vec![8, 8, 16, 32, 64, 128], 65535).is_none());
+        assert!(PvdRangeTable::new(vec![8, 8, 16, 32, 64, 127, 1], 255).is_none());
+        assert!(PvdRangeTable::new(vec![8, 8, 16, 32, 64], 255).is_none());
+        assert!(PvdRangeTable::new(vec![2; 8], 15).is_some());
+
+        assert_eq!(PvdRangeTable::scaled(&[8, 8, 16, 32, 64, 128], 16), Some(PvdRangeTable::wu_tsai(16)));
+        assert_eq!(PvdRangeTable::scaled(&[4, 4, 8, 16, 32, 64, 128], 4), Some(PvdRangeTable::wu_tsai(4)));
+        assert!(PvdRangeTable::scaled(&[8, 8, 16, 32, 64], 16).is_none());
+    }
+
+    /*
+        A smooth gradient and noise of the same size, the noise has to take a lot more
+     */
+    #[test]
+    fn test_busy_regions_carry_more() {
+        let table = PvdRangeTable::wu_tsai(8);
+        let mut smooth: Vec<u8> = (0..64 * 64u32).map(|i| (i % 64 + i / 64) as u8).collect();
+        let mut busy: Vec<u8> = (0..64 * 64u32).map(|i| 96 + (i.wrapping_mul(2654435761) >> 26) as u8).collect();
+
+        let smooth_bits = pvd_capacity_bits::<PngGrayPixel<8>>(&mut smooth, 64, 64, 0, 1, &table);
+        let busy_bits = pvd_capacity_bits::<PngGrayPixel<8>>(&mut busy, 64, 64, 0, 1, &table);
+
+        // 3 bits a pair at most, a few pairs near 0 fall off
+        assert!(smooth_bits <= 32 * 64 * 3);
+        assert!(busy_bits > smooth_bits * 5 / 4);
+    }
+
+    /*
+        Pairs sitting at the ends of the sample range must either be skipped or stay in bounds, and the extractor
+        has to agree on which were skipped
+     */
+    #[test]
+    fn test_fall_off_pairs_round_trip() {
+        let table = PvdRangeTable::new(vec![4, 4, 8, 16, 32, 64, 128], 255).unwrap();
+        let original: Vec<u8> = (0..40 * 40u32)
+            .map(|i| match i % 7 {
+                0 => 0,
+                1 => 255,
+                2 => 254,
+                3 => 2,
+                _ => (i.wrapping_mul(40503) >> 5) as u8,
+            })
+            .collect();
+
+        let mut pixels = original.clone();
+        let capacity = pvd_capacity_bits::<PngGrayPixel<8>>(&mut pixels, 40, 40, 0, 1, &table);
+        let data: Vec<u8> = (0..(capacity / 8) as u32).map(|i| (i * 13 + 5) as u8).collect();
+
+        embed_pvd_data::<PngGrayPixel<8>>(&data, &mut pixels, 40, 40, 0, 1, &table, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased).unwrap();
+        assert_eq!(pvd_capacity_bits::<PngGrayPixel<8>>(&mut pixels, 40, 40, 0, 1, &table), capacity);
+
+        // The integer average of every pair is left where it was
+        for (before, after) in original.chunks(2).zip(pixels.chunks(2)) {
+            assert_eq!((before[0] as u32 + before[1] as u32) / 2, (after[0] as u32 + after[1] as u32) / 2);
+        }
+
+        let extracted = extract_pvd_data::<PngGrayPixel<8>>(&mut pixels, 40, 40, 0, 1, data.len() as u64 * 8, &table, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased);
+        assert_eq!(extracted[0..data.len()], data[..]);
+    }
+}
+
+#[cfg(test)]
+mod hamming_tests {
+    use crate::file_encoding_support::pixel::{hamming_embed_bits, hamming_extract_bits};
+    use crate::mathematics_support::mathematics_support::{hamming_choose_k, hamming_syndrome};
+
+    fn cover(len: usize) -> Vec<bool> {
+        (0..len as u32).map(|i| (i.wrapping_mul(2654435761) >> 31) == 1).collect()
+    }
+
+    #[test]
+    fn test_hamming_syndrome() {
+        assert_eq!(hamming_syndrome([false, false, false].into_iter()), 0);
+        assert_eq!(hamming_syndrome([true, false, false].into_iter()), 1);
+        assert_eq!(hamming_syndrome([true, true, false].into_iter()), 3);
+        assert_eq!(hamming_syndrome([true, true, true].into_iter()), 0);
+    }
+
+    #[test]
+    fn test_hamming_choose_k() {
+        assert_eq!(hamming_choose_k(1000, 1000, 7), 1);
+        assert_eq!(hamming_choose_k(600, 1000, 7), 2);
+        assert_eq!(hamming_choose_k(10, 100_000, 7), 7);
+    }
+
+    #[test]
+    fn test_hamming_round_trip_every_k() {
+        let original = cover(20_000);
+        let data = b"matrix embedding".to_vec();
+
+        for k in 1..=7 {
+            let mut cover = original.clone();
+            hamming_embed_bits(&data, &mut cover, k).unwrap();
+
+            let extracted = hamming_extract_bits(&cover, data.len() as u64 * 8, k);
+            assert_eq!(extracted[0..data.len()], data[..], "k = {k}");
+        }
+    }
+
+    /*
+        Each group of 2^k - 1 cover bits takes k message bits for at most one flip, so a small payload in a big
+        cover should need well under the one flip per two bits plain LSB averages
+     */
+    #[test]
+    fn test_hamming_changes_less_than_lsb() {
+        let original = cover(50_000);
+        let data: Vec<u8> = (0..200u32).map(|i| (i * 73 + 19) as u8).collect();
+
+        let k = hamming_choose_k(data.len() as u64 * 8, original.len() as u64, 7);
+        let mut embedded = original.clone();
+        hamming_embed_bits(&data, &mut embedded, k).unwrap();
+
+        let changes = original.iter().zip(embedded.iter()).filter(|(a, b)| a != b).count();
+        let groups = data.len() * 8;
+        assert!(changes < groups / 4, "{changes} changes for {groups} bits");
+
+        let extracted = hamming_extract_bits(&embedded, data.len() as u64 * 8, k);
+        assert_eq!(extracted[0..data.len()], data[..]);
+    }
+}
+
+#[cfg(test)]
+mod lsb_matching_tests {
+    use crate::file_encoding_support::file_encoding_support::{
+        EncodingParameters, FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod,
+    };
+    use crate::file_encoding_support::pixel::{capacity_bits, embed_data_with_method, extract_data_with_method};
+    use crate::filetype_support::bmp::RgbPixel;
+    use crate::filetype_support::png::PngRgb16Pixel;
+
+    /*
+        64x64 RGB with a stripe of black and a stripe of white so both ends of the range get tried
+     */
+    fn cover() -> Vec<u8> {
+        (0..64 * 64 * 3u32)
+            .map(|i| match i / (64 * 3) {
+                0..=7 => 0,
+                8..=15 => 255,
+                _ => (i.wrapping_mul(2654435761) >> 24) as u8,
+            })
+            .collect()
+    }
+
+    fn message() -> Vec<u8> {
+        (0..1200u32).map(|i| (i * 73 + 19) as u8).collect()
+    }
+
+    #[test]
+    fn test_lsb_matching_steps_by_one() {
+        let original = cover();
+        let data = message();
+        let key = FileEncodingFunctionDerivation::from_passphrase("plus or minus");
+
+        for derivation in [FileEncodingFunctionDerivation::MethodBased, key] {
+            let mut pixels = original.clone();
+            embed_data_with_method::<RgbPixel>(&data, &mut pixels, 64, 64, 0, 3, FileEncoding::LsbMatching, FileEncodingMethod::LeftToRight, derivation, &EncodingParameters::default()).unwrap();
+
+            let steps: Vec<i32> = original.iter().zip(pixels.iter()).map(|(before, after)| *after as i32 - *before as i32).collect();
+            assert!(steps.iter().all(|step| step.abs() <= 1), "{derivation:?}");
+            assert!(steps.contains(&1) && steps.contains(&-1), "{derivation:?}");
+
+            // Extraction is plain LSB
+            let bits = data.len() as u64 * 8;
+            let extracted = extract_data_with_method::<RgbPixel>(&mut pixels, 64, 64, 0, 3, bits, FileEncoding::LsbMatching, FileEncodingMethod::LeftToRight, derivation, &EncodingParameters::default()).unwrap();
+            assert_eq!(extracted[..data.len()], data[..], "{derivation:?}");
+            let extracted = extract_data_with_method::<RgbPixel>(&mut pixels, 64, 64, 0, 3, bits, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, derivation, &EncodingParameters::default()).unwrap();
+            assert_eq!(extracted[..data.len()], data[..], "{derivation:?}");
+        }
+    }
+
+    #[test]
+    fn test_lsb_matching_signs_follow_key() {
+        let data = message();
+        let embed = |derivation: FileEncodingFunctionDerivation| {
+            let mut pixels = cover();
+            embed_data_with_method::<RgbPixel>(&data, &mut pixels, 64, 64, 0, 3, FileEncoding::LsbMatching, FileEncodingMethod::TopToBottom, derivation, &EncodingParameters::default()).unwrap();
+            pixels
+        };
+
+        let first = FileEncodingFunctionDerivation::from_passphrase("first");
+        let second = FileEncodingFunctionDerivation::from_passphrase("second");
+        assert_eq!(embed(first), embed(first));
+        assert_ne!(embed(first), embed(second));
+        assert_eq!(embed(FileEncodingFunctionDerivation::MethodBased), embed(FileEncodingFunctionDerivation::MethodBased));
+    }
+
+    /*
+        16 bit samples only give up their lowest plane, the rest of the sample moves by one at most
+     */
+    #[test]
+    fn test_lsb_matching_16bit_samples() {
+        assert_eq!(capacity_bits::<PngRgb16Pixel>(64, 64, FileEncoding::LsbMatching), 64 * 64 * 3);
+
+        let original: Vec<u8> = (0..64 * 64 * 6u32).map(|i| (i.wrapping_mul(2246822519) >> 24) as u8).collect();
+        let data = message();
+        let mut pixels = original.clone();
+        embed_data_with_method::<PngRgb16Pixel>(&data, &mut pixels, 64, 64, 0, 6, FileEncoding::LsbMatching, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased, &EncodingParameters::default()).unwrap();
+
+        let sample = |bytes: &[u8], i: usize| u16::from_be_bytes([bytes[i * 2], bytes[i * 2 + 1]]) as i32;
+        assert!((0..original.len() / 2).all(|i| (sample(&pixels, i) - sample(&original, i)).abs() <= 1));
+
+        let extracted = extract_data_with_method::<PngRgb16Pixel>(&mut pixels, 64, 64, 0, 6, data.len() as u64 * 8, FileEncoding::LsbMatching, FileEncodingMethod::RightToLeft, FileEncodingFunctionDerivation::MethodBased, &EncodingParameters::default()).unwrap();
+        assert_eq!(extracted[..data.len()], data[..]);
+    }
+}
+
+#[cfg(test)]
+mod key_tests {
+    use crate::file_encoding_support::file_encoding_support::{
+        EncodingParameters, FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod,
+    };
+    use crate::file_encoding_support::key::TraversalKey;
+    use crate::file_encoding_support::payload::{build_payload, read_payload, PayloadError};
+    use crate::file_encoding_support::pixel::{capacity_bits, embed_data_with_method, extract_data_with_method};
+    use crate::filetype_support::png::PngRgbPixel;
+
+    fn permutation(passphrase: &str, stream: u64, len: usize) -> Vec<usize> {
+        TraversalKey::from_passphrase(passphrase).permutation(stream, len).collect()
+    }
+
+    #[test]
+    fn test_permutation_is_a_permutation() {
+        let mut order = permutation("correct horse battery staple", 0, 10_000);
+        assert_ne!(order, (0..10_000).collect::<Vec<usize>>());
+
+        order.sort_unstable();
+        assert_eq!(order, (0..10_000).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn test_permutation_depends_on_key_and_stream() {
+        assert_eq!(permutation("passphrase", 1, 500), permutation("passphrase", 1, 500));
+        assert_ne!(permutation("passphrase", 1, 500), permutation("passphrase", 0, 500));
+        assert_ne!(permutation("passphrase", 1, 500), permutation("passphrasf", 1, 500));
+
+        assert_eq!(permutation("passphrase", 0, 1), vec![0]);
+        assert!(permutation("passphrase", 0, 0).is_empty());
+    }
+
+    /*
+        The lazy shuffle only ever touches what has been asked for, taking the start of a huge permutation is cheap
+     */
+    #[test]
+    fn test_permutation_is_lazy() {
+        let key = TraversalKey::from_passphrase("lazy");
+        let start: Vec<usize> = key.permutation(0, usize::MAX / 2).take(1000).collect();
+        assert!(start.iter().all(|position| *position < usize::MAX / 2));
+        assert_eq!(start, key.permutation(0, usize::MAX / 2).take(1000).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn test_key_stays_out_of_debug_output() {
+        let derivation = FileEncodingFunctionDerivation::from_passphrase("hunter2");
+        assert_eq!(format!("{derivation:?}"), "KeyBased(TraversalKey(..))");
+    }
+
+    /*
+        Keyed LSB scatters every bit, reading back with a different key or no key can't even find the header
+     */
+    #[test]
+    fn test_keyed_lsb_needs_the_key() {
+        let original: Vec<u8> = (0..64 * 64 * 3u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect();
+        let mut pixels = original.clone();
+        let key = FileEncodingFunctionDerivation::from_passphrase("open sesame");
+        let payload = build_payload(b"only with the key", FileEncoding::Lsb, FileEncodingMethod::LeftToRight, 0);
+
+        embed_data_with_method::<PngRgbPixel>(&payload, &mut pixels, 64, 64, 0, 3, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, key, &EncodingParameters::default()).unwrap();
+
+        // The changes are spread over the whole image instead of packed into the first rows
+        let last_changed = original.iter().zip(pixels.iter()).rposition(|(a, b)| a != b).unwrap();
+        assert!(last_changed > original.len() * 3 / 4);
+
+        let capacity = capacity_bits::<PngRgbPixel>(64, 64, FileEncoding::Lsb);
+        let mut read_with = |derivation: FileEncodingFunctionDerivation| {
+            read_payload(FileEncoding::Lsb, FileEncodingMethod::LeftToRight, capacity, |bits| {
+                Ok(extract_data_with_method::<PngRgbPixel>(&mut pixels, 64, 64, 0, 3, bits, FileEncoding::Lsb, FileEncodingMethod::LeftToRight, derivation, &EncodingParameters::default()).unwrap())
+            })
+        };
+
+        assert_eq!(read_with(key), Ok((b"only with the key".to_vec(), 0)));
+        assert_eq!(read_with(FileEncodingFunctionDerivation::from_passphrase("open sesane")), Err(PayloadError::BadMagic));
+        assert_eq!(read_with(FileEncodingFunctionDerivation::MethodBased), Err(PayloadError::BadMagic));
+    }
+}
+
+#[cfg(test)]
+mod encryption_tests {
+    use crate::file_encoding_support::encryption::{
+        decrypt_payload, encrypt_payload_with_params, is_encrypted, EncryptionError, KdfParams, ENCRYPTION_HEADER_SIZE,
+        ENCRYPTION_OVERHEAD,
+    };
+
+    // The real cost makes every test take seconds in a debug build
+    const CHEAP: KdfParams = KdfParams { memory_kib: 256, iterations: 1, parallelism: 1 };
+
+    #[test]
+    fn test_encryption_round_trip() {
+        let data = b"attack at dawn".to_vec();
+        let encrypted = encrypt_payload_with_params(&data, "passphrase", CHEAP).unwrap();
+
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(encrypted.len(), data.len() + ENCRYPTION_OVERHEAD);
+        assert!(!encrypted.windows(data.len()).any(|window| window == data));
+        assert_eq!(decrypt_payload(&encrypted, "passphrase"), Ok(data));
+    }
+
+    #[test]
+    fn test_salt_and_nonce_are_fresh() {
+        let first = encrypt_payload_with_params(b"same message", "same passphrase", CHEAP).unwrap();
+        let second = encrypt_payload_with_params(b"same message", "same passphrase", CHEAP).unwrap();
+
+        assert_eq!(first[..14], second[..14]);
+        assert_ne!(first[14..ENCRYPTION_HEADER_SIZE], second[14..ENCRYPTION_HEADER_SIZE]);
+        assert_ne!(first[ENCRYPTION_HEADER_SIZE..], second[ENCRYPTION_HEADER_SIZE..]);
+    }
+
+    #[test]
+    fn test_wrong_key_or_tampering_is_reported() {
+        let encrypted = encrypt_payload_with_params(b"secret", "right", CHEAP).unwrap();
+        assert_eq!(decrypt_payload(&encrypted, "wrong"), Err(EncryptionError::WrongKeyOrNoPayload));
+
+        // Salt, nonce and ciphertext are all authenticated, not just the ciphertext
+        for index in [14, 20, 35, ENCRYPTION_HEADER_SIZE, encrypted.len() - 1] {
+            let mut tampered = encrypted.clone();
+            tampered[index] ^= 0x01;
+            assert_eq!(decrypt_payload(&tampered, "right"), Err(EncryptionError::WrongKeyOrNoPayload), "byte {index}");
+        }
+    }
+
+    #[test]
+    fn test_unencrypted_and_hostile_payloads() {
+        assert_eq!(decrypt_payload(b"plain old message, nothing to see here at all", "key"), Err(EncryptionError::NotEncrypted));
+        assert_eq!(decrypt_payload(b"MAYE", "key"), Err(EncryptionError::NotEncrypted));
+
+        let mut encrypted = encrypt_payload_with_params(b"secret", "key", CHEAP).unwrap();
+        encrypted[4] = 9;
+        assert_eq!(decrypt_payload(&encrypted, "key"), Err(EncryptionError::UnsupportedVersion(9)));
+
+        // A header asking for terabytes of memory is turned away before Argon2 ever runs
+        let mut encrypted = encrypt_payload_with_params(b"secret", "key", CHEAP).unwrap();
+        encrypted[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decrypt_payload(&encrypted, "key"), Err(EncryptionError::UnsupportedKdfParams));
+    }
+
+    #[test]
+    fn test_bad_kdf_params_are_refused() {
+        let greedy = KdfParams { memory_kib: u32::MAX, ..CHEAP };
+        assert_eq!(encrypt_payload_with_params(b"secret", "key", greedy), Err(EncryptionError::UnsupportedKdfParams));
+
+        let slow = KdfParams { iterations: u32::MAX, ..CHEAP };
+        assert_eq!(encrypt_payload_with_params(b"secret", "key", slow), Err(EncryptionError::UnsupportedKdfParams));
+
+        let no_lanes = KdfParams { parallelism: 0, ..CHEAP };
+        assert_eq!(encrypt_payload_with_params(b"secret", "key", no_lanes), Err(EncryptionError::UnsupportedKdfParams));
+    }
+}
+
+#[cfg(test)]
+mod compression_tests {
+    use crate::compression::compression::{compress, decompress, CompressionCodec, CompressionError};
+    use crate::file_encoding_support::payload::PAYLOAD_FLAG_COMPRESSION_MASK;
+    use crate::mathematics_support::mathematics_support::adler32;
+
+    const PROSE: &[u8] = b"It was the best of times, it was the worst of times, it was the age of wisdom, it was the age of \
+        foolishness, it was the epoch of belief, it was the epoch of incredulity, it was the season of Light, it was the \
+        season of Darkness, it was the spring of hope, it was the winter of despair.";
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(&[0xFF; 100_000]), 0x149A_302C);
+    }
+
+    #[test]
+    fn test_codecs_round_trip() {
+        let binary: Vec<u8> = (0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect();
+
+        for codec in [CompressionCodec::None, CompressionCodec::Deflate, CompressionCodec::ShortText] {
+            for data in [&b""[..], b"a", PROSE, &binary] {
+                let (used, packed) = compress(data, codec);
+                assert_eq!(decompress(&packed, used).unwrap(), data, "{codec:?} {}", data.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_compression_is_skipped_when_it_would_grow() {
+        let mut state: u32 = 0x9E37_79B9;
+        let noise: Vec<u8> = (0..512)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state >> 24) as u8
+            })
+            .collect();
+
+        assert_eq!(compress(&noise, CompressionCodec::Deflate), (CompressionCodec::None, noise.clone()));
+        assert_eq!(compress(&noise, CompressionCodec::ShortText), (CompressionCodec::None, noise.clone()));
+        assert_eq!(compress(b"hi", CompressionCodec::Deflate), (CompressionCodec::None, b"hi".to_vec()));
+
+        let (codec, packed) = compress(PROSE, CompressionCodec::Deflate);
+        assert_eq!(codec, CompressionCodec::Deflate);
+        assert!(packed.len() < PROSE.len());
+    }
+
+    #[test]
+    fn test_short_text_beats_deflate_on_short_messages() {
+        let message = b"meet me at the old bridge at nine";
+
+        let (deflate_codec, _) = compress(message, CompressionCodec::Deflate);
+        let (text_codec, text) = compress(message, CompressionCodec::ShortText);
+
+        assert_eq!(deflate_codec, CompressionCodec::None);
+        assert_eq!(text_codec, CompressionCodec::ShortText);
+        assert!(text.len() * 3 < message.len() * 2, "{} bytes", text.len());
+    }
+
+    #[test]
+    fn test_codec_flags_and_corruption() {
+        for codec in [CompressionCodec::None, CompressionCodec::Deflate, CompressionCodec::ShortText] {
+            assert_eq!(CompressionCodec::from_flags(codec.id() | !PAYLOAD_FLAG_COMPRESSION_MASK), Ok(codec));
+        }
+        assert_eq!(CompressionCodec::from_flags(3), Err(CompressionError::UnknownCodec(3)));
+
+        assert_eq!(decompress(b"not zlib", CompressionCodec::Deflate), Err(CompressionError::Corrupt));
+        assert_eq!(decompress(&[], CompressionCodec::ShortText), Err(CompressionError::Corrupt));
+
+        // A length the data can't possibly hold is turned away before anything is allocated for it
+        assert_eq!(decompress(&[0xFF, 0xFF, 0x7F, 0x00], CompressionCodec::ShortText), Err(CompressionError::Corrupt));
+        assert_eq!(decompress(&[0xFF, 0xFF, 0xFF, 0xFF, 0x7F], CompressionCodec::ShortText), Err(CompressionError::TooLarge));
+
+        let (_, mut packed) = compress(PROSE, CompressionCodec::ShortText);
+        packed.truncate(packed.len() / 2);
+        assert_eq!(decompress(&packed, CompressionCodec::ShortText), Err(CompressionError::Corrupt));
+    }
+}
+
+#[cfg(test)]
+mod traversal_tests {
+    use crate::file_encoding_support::file_encoding_support::{EncodingParameters, FileEncodingFunctionDerivation, FileEncodingMethod};
+    use crate::file_encoding_support::file_encoding_support::FileEncoding;
+    use crate::file_encoding_support::payload::{build_payload, hamming_k_flags};
+    use crate::file_encoding_support::pixel::{
+        embed_color_data_in_order, embed_data_with_method, extract_color_data_in_order, extract_data_with_method,
+    };
+    use crate::file_encoding_support::traversal::{TraversalParameters, WaveFunction};
+    use crate::filetype_support::bmp::RgbPixel;
+    use crate::mathematics_support::mathematics_support::{cos_scaled, portable_sin, sin_scaled};
+
+    const CURVES: [FileEncodingMethod; 4] = [
+        FileEncodingMethod::SinWave,
+        FileEncodingMethod::CosWave,
+        FileEncodingMethod::PolynomialFunction,
+        FileEncodingMethod::FractalFunction,
+    ];
+
+    const SHAPES: [FileEncodingMethod; 6] = [
+        FileEncodingMethod::TopToBottom,
+        FileEncodingMethod::HilbertCurve,
+        FileEncodingMethod::MortonOrder,
+        FileEncodingMethod::SpiralInward,
+        FileEncodingMethod::SpiralOutward,
+        FileEncodingMethod::BlockRaster,
+    ];
+
+    fn assert_permutation(order: impl Iterator<Item = usize>, len: usize, context: &str) {
+        let mut seen = vec![false; len];
+        let mut count = 0;
+        for index in order {
+            assert!(index < len, "{context}: {index} out of range");
+            assert!(!seen[index], "{context}: {index} visited twice");
+            seen[index] = true;
+            count += 1;
+        }
+        assert_eq!(count, len, "{context}: not every slot visited");
+    }
+
+    #[test]
+    fn test_portable_sin() {
+        for i in -2000..2000 {
+            let x = i as f64 * 0.0173;
+            assert!((portable_sin(x) - x.sin()).abs() < 1e-12, "{x}");
+        }
+
+        assert_eq!(sin_scaled(std::f64::consts::FRAC_PI_2, 10.0), 10);
+        assert_eq!(sin_scaled(-std::f64::consts::FRAC_PI_2, 10.0), -10);
+        assert_eq!(cos_scaled(std::f64::consts::PI, 7.0), -7);
+    }
+
+    #[test]
+    fn test_curves_visit_every_cell_once() {
+        let key = FileEncodingFunctionDerivation::from_passphrase("curvy");
+
+        for (cols, rows) in [(1, 1), (1, 9), (9, 1), (16, 16), (37, 23), (250, 200)] {
+            for method in CURVES.iter().chain(SHAPES.iter()).copied() {
+                for derivation in [FileEncodingFunctionDerivation::MethodBased, key] {
+                    let context = format!("{method:?} {cols}x{rows} {derivation:?}");
+                    assert_permutation(derivation.grid_visit_order(method, cols, rows, 3), cols * rows * 3, &context);
+                }
+            }
+        }
+
+        for len in [0, 1, 2, 10, 17, 100, 1001] {
+            for method in CURVES.iter().chain(SHAPES.iter()).copied() {
+                assert_permutation(method.visit_order(len), len, &format!("{method:?} flat {len}"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_curves_follow_their_shape() {
+        let (cols, rows) = (200, 100);
+        let parameters = TraversalParameters::default();
+
+        for method in CURVES {
+            let first_band: Vec<usize> = method.grid_visit_order(&parameters, cols, rows).take(cols).collect();
+            let band_rows: Vec<usize> = first_band.iter().map(|cell| cell / cols).collect();
+
+            // One cell in every column, left to right, and not just the first row
+            assert_eq!(first_band.iter().map(|cell| cell % cols).collect::<Vec<_>>(), (0..cols).collect::<Vec<_>>());
+            assert!(band_rows.iter().any(|row| *row != band_rows[0]), "{method:?} is flat");
+        }
+
+        // The waves are smooth, neighbouring columns never land far apart (allowing for the wrap around)
+        for method in [FileEncodingMethod::SinWave, FileEncodingMethod::CosWave] {
+            let band_rows: Vec<usize> = method.grid_visit_order(&parameters, cols, rows).take(cols).map(|cell| cell / cols).collect();
+            for pair in band_rows.windows(2) {
+                let step = pair[0].abs_diff(pair[1]);
+                assert!(step.min(rows - step) <= 2, "{method:?} jumps {step}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_key_picks_the_curve() {
+        let first = FileEncodingFunctionDerivation::from_passphrase("first");
+        let second = FileEncodingFunctionDerivation::from_passphrase("second");
+
+        for method in CURVES {
+            let order = |derivation: FileEncodingFunctionDerivation| derivation.grid_visit_order(method, 64, 64, 1).take(256).collect::<Vec<_>>();
+            assert_eq!(order(first), order(first), "{method:?}");
+            assert_ne!(order(first), order(second), "{method:?}");
+            assert_ne!(order(first), order(FileEncodingFunctionDerivation::MethodBased), "{method:?}");
+        }
+
+        // Everything that isn't a curve keeps the full keyed shuffle it always had
+        let shuffled: Vec<usize> = first.grid_visit_order(FileEncodingMethod::LeftToRight, 8, 8, 2).collect();
+        assert_eq!(shuffled, first.visit_order(FileEncodingMethod::LeftToRight, 128).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_wave_functions_visit_every_cell_once() {
+        let waves = [
+            WaveFunction::Horizontal,
+            WaveFunction::Vertical,
+            WaveFunction::DiagonalRight,
+            WaveFunction::DiagonalLeft,
+            WaveFunction::ZigZagHorizontal,
+            WaveFunction::ZigZagVertical,
+            WaveFunction::Sinusoidal,
+            WaveFunction::Hilbert,
+            WaveFunction::Morton,
+            WaveFunction::SpiralInward,
+            WaveFunction::SpiralOutward,
+            WaveFunction::BlockRaster,
+        ];
+
+        for (rows, cols) in [(0, 5), (5, 0), (1, 1), (1, 9), (9, 1), (2, 7), (3, 3), (16, 16), (23, 37), (200, 120), (17, 300)] {
+            for wave in waves {
+                let context = format!("{wave:?} {rows}x{cols}");
+                assert!(wave.traverse(rows, cols).all(|(row, col)| row < rows && col < cols), "{context}");
+                assert_permutation(wave.cells(rows, cols), rows * cols, &context);
+            }
+        }
+
+        // rows then cols, a wide grid walked vertically goes down the first column before moving right
+        assert_eq!(WaveFunction::Vertical.traverse(2, 3).collect::<Vec<_>>(), vec![(0, 0), (1, 0), (0, 1), (1, 1), (0, 2), (1, 2)]);
+        assert_eq!(WaveFunction::DiagonalLeft.traverse(2, 2).collect::<Vec<_>>(), vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+        assert_eq!(WaveFunction::DiagonalRight.traverse(2, 2).collect::<Vec<_>>(), vec![(1, 0), (0, 0), (1, 1), (0, 1)]);
+        assert_eq!(WaveFunction::ZigZagHorizontal.cells(2, 3).collect::<Vec<_>>(), vec![0, 1, 2, 5, 4, 3]);
+    }
+
+    #[test]
+    fn test_clustered_orders() {
+        let steps = |wave: WaveFunction, rows: usize, cols: usize| {
+            let cells: Vec<(usize, usize)> = wave.traverse(rows, cols).collect();
+            cells.windows(2).map(|pair| pair[0].0.abs_diff(pair[1].0) + pair[0].1.abs_diff(pair[1].1)).collect::<Vec<_>>()
+        };
+
+        // Hilbert only ever moves to a neighbour, on odd sizes allowing the odd diagonal
+        assert!(steps(WaveFunction::Hilbert, 64, 64).iter().all(|step| *step == 1));
+        assert!(steps(WaveFunction::Hilbert, 40, 96).iter().all(|step| *step == 1));
+        for (rows, cols) in [(23, 37), (200, 120), (1, 9), (17, 300)] {
+            let steps = steps(WaveFunction::Hilbert, rows, cols);
+            assert!(steps.iter().all(|step| *step <= 2), "{rows}x{cols}");
+            assert!(steps.iter().filter(|step| **step == 2).count() * 50 < steps.len(), "{rows}x{cols}");
+        }
+        assert!(steps(WaveFunction::SpiralInward, 9, 14).iter().all(|step| *step == 1));
+
+        assert_eq!(WaveFunction::Morton.cells(4, 4).collect::<Vec<_>>(), vec![0, 1, 4, 5, 2, 3, 6, 7, 8, 9, 12, 13, 10, 11, 14, 15]);
+        assert_eq!(WaveFunction::SpiralInward.cells(3, 3).collect::<Vec<_>>(), vec![0, 1, 2, 5, 8, 7, 6, 3, 4]);
+        assert_eq!(WaveFunction::SpiralOutward.cells(3, 3).collect::<Vec<_>>(), vec![4, 3, 6, 7, 8, 5, 2, 1, 0]);
+
+        // Every 8x8 block is finished before the next one starts, partial blocks on the edges included
+        let blocks: Vec<(usize, usize)> = WaveFunction::BlockRaster.traverse(20, 12).map(|(row, col)| (row / 8, col / 8)).collect();
+        let mut finished = Vec::new();
+        for pair in blocks.windows(2).filter(|pair| pair[0] != pair[1]) {
+            finished.push(pair[0]);
+            assert!(!finished.contains(&pair[1]), "{:?} revisited", pair[1]);
+        }
+        assert_eq!(WaveFunction::BlockRaster.cells(20, 12).take(9).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4, 5, 6, 7, 12]);
+    }
+
+    /*
+        A 24 bit BMP 5 pixels wide pads every row with a single byte, nothing may ever land in it. Hamming reads
+        its k from a payload header so the message goes in as a whole payload
+     */
+    #[test]
+    fn test_padded_rows_round_trip() {
+        let (width, length, padding) = (5u64, 20u64, 1u64);
+        let stride = (width * 3 + padding) as usize;
+        let original: Vec<u8> = (0..stride * length as usize).map(|i| (i * 37 % 251) as u8).collect();
+        let key = FileEncodingFunctionDerivation::from_passphrase("rows");
+
+        let padding_untouched = |pixels: &[u8]| (0..length as usize).all(|row| pixels[row * stride + stride - 1] == original[row * stride + stride - 1]);
+
+        for method in [FileEncodingMethod::LeftToRight, FileEncodingMethod::RightToLeft, FileEncodingMethod::TopToBottom, FileEncodingMethod::SinWave] {
+            for derivation in [FileEncodingFunctionDerivation::MethodBased, key] {
+                for encoding in [FileEncoding::Lsb, FileEncoding::HammingMatrix] {
+                    let data = build_payload(b"pad", encoding, method, hamming_k_flags(1));
+                    let mut pixels = original.clone();
+                    embed_data_with_method::<RgbPixel>(&data, &mut pixels, width, length, padding, 3, encoding, method, derivation, &EncodingParameters::default()).unwrap();
+                    assert!(padding_untouched(&pixels), "{encoding:?} {method:?} {derivation:?}");
+
+                    let extracted = extract_data_with_method::<RgbPixel>(&mut pixels, width, length, padding, 3, data.len() as u64 * 8, encoding, method, derivation, &EncodingParameters::default()).unwrap();
+                    assert_eq!(&extracted[..data.len()], &data[..], "{encoding:?} {method:?} {derivation:?}");
+                }
+
+                let mut pixels = original.clone();
+                embed_color_data_in_order::<RgbPixel>(b"ok", &mut pixels, width, length, padding, 3, method, derivation).unwrap();
+                assert!(padding_untouched(&pixels), "color {method:?} {derivation:?}");
+                let extracted = extract_color_data_in_order::<RgbPixel>(&mut pixels, width, length, padding, 3, 16, method, derivation);
+                assert_eq!(&extracted[..2], b"ok", "color {method:?} {derivation:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod library_tests {
+    use crate::compression::compression::CompressionCodec;
+    use crate::error::error::MayaError;
+    use crate::file_encoding_support::file_encoding_support::{FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod};
+    use crate::file_encoding_support::encryption::ENCRYPTION_OVERHEAD;
+    use crate::file_encoding_support::payload::{hamming_k_flags, hamming_k_from_flags, PAYLOAD_HEADER_SIZE};
+    use crate::file_encoding_support::file_encoding_support::FileEncodingSupport;
+    use crate::file_encoding_support::palette::PaletteEmbedding;
+    use crate::filetype_support::bmp::BmpImageParser;
+    use crate::filetype_support::filetype_support::FileType;
+    use crate::filetype_support::png::PngImageParser;
+    use crate::tests::tests::fixtures::carrier;
+    use crate::{capacity, embed, extract, find_payloads, EmbedOptions, FoundPayload};
+
+    #[test]
+    fn test_round_trip_in_memory() {
+        let options = EmbedOptions {
+            encoding_method: FileEncodingMethod::HilbertCurve,
+            file_encoding_function_derivation: FileEncodingFunctionDerivation::from_passphrase("library"),
+            ..EmbedOptions::default()
+        };
+
+        for name in ["sample-1024x1024.bmp", "sample-256x256.png", "sample-250x200-palette.png"] {
+            let stego = embed(&carrier(name), b"nothing touches the disk", &options).unwrap();
+            assert_eq!(extract(&stego, &options).unwrap(), b"nothing touches the disk");
+        }
+
+        let options = EmbedOptions { encoding: FileEncoding::F5, ..EmbedOptions::default() };
+        let stego = embed(&carrier("sample-320x240.jpg"), b"coefficients too", &options).unwrap();
+        assert_eq!(FileType::detect(&stego), Some(FileType::Jpeg));
+        assert_eq!(extract(&stego, &options).unwrap(), b"coefficients too");
+    }
+
+    #[test]
+    fn test_passphrase_errors() {
+        let options = EmbedOptions { passphrase: Some("right".to_string()), ..EmbedOptions::default() };
+        let stego = embed(&carrier("sample-256x256.png"), b"sealed", &options).unwrap();
+
+        let wrong = EmbedOptions { passphrase: Some("wrong".to_string()), ..EmbedOptions::default() };
+        assert!(matches!(extract(&stego, &wrong), Err(MayaError::WrongKey)));
+        assert!(matches!(extract(&stego, &EmbedOptions::default()), Err(MayaError::PassphraseRequired)));
+    }
+
+    #[test]
+    fn test_errors_instead_of_exiting() {
+        let options = EmbedOptions::default();
+        let bmp = carrier("sample-250x200-8bit.bmp");
+
+        assert!(matches!(embed(b"definitely not an image", b"hi", &options), Err(MayaError::UnsupportedFormat(_))));
+        assert!(matches!(extract(&bmp[..20], &options), Err(MayaError::CorruptHeader(_))));
+        assert!(matches!(extract(&bmp, &options), Err(MayaError::Payload(_))));
+
+        let jsteg = EmbedOptions { encoding: FileEncoding::JSteg, ..EmbedOptions::default() };
+        assert!(matches!(
+            embed(&bmp, b"hi", &jsteg),
+            Err(MayaError::UnsupportedEncoding { encoding: FileEncoding::JSteg, file_type: FileType::Bmp })
+        ));
+
+        let matching = EmbedOptions { encoding: FileEncoding::LsbMatching, ..EmbedOptions::default() };
+        assert!(matches!(
+            embed(&carrier("sample-320x240.jpg"), b"hi", &matching),
+            Err(MayaError::UnsupportedEncoding { encoding: FileEncoding::LsbMatching, file_type: FileType::Jpeg })
+        ));
+    }
+
+    /*
+        capacity is exactly the largest message that still goes in, uncompressed
+     */
+    #[test]
+    fn test_capacity_matches_embed() {
+        let png = carrier("sample-250x200-palette.png");
+        let options = EmbedOptions { compression: CompressionCodec::None, ..EmbedOptions::default() };
+
+        let usable = capacity(&png, &options).unwrap().usable_bytes as usize;
+        assert!(usable > PAYLOAD_HEADER_SIZE);
+
+        let message: Vec<u8> = (0..usable + 1).map(|i| (i * 7) as u8).collect();
+        let stego = embed(&png, &message[..usable], &options).unwrap();
+        assert_eq!(extract(&stego, &options).unwrap(), &message[..usable]);
+
+        match embed(&png, &message, &options) {
+            Err(MayaError::CapacityExceeded { needed_bits, capacity_bits }) => assert!(needed_bits > capacity_bits),
+            other => panic!("expected CapacityExceeded, got {:?}", other.map(|output| output.len())),
+        }
+    }
+
+    #[test]
+    fn test_capacity_report() {
+        let bmp = carrier("sample-1024x1024.bmp");
+        let report = capacity(&bmp, &EmbedOptions::default()).unwrap();
+
+        assert_eq!(report.file_type, FileType::Bmp);
+        assert_eq!(report.raw_bits, 1024 * 1024 * 3);
+        assert_eq!(report.usable_bytes, 1024 * 1024 * 3 / 8 - PAYLOAD_HEADER_SIZE as u64);
+        assert!(report.recommended_bytes < report.usable_bytes / 10);
+
+        // Every method visits every slot, only the overhead moves with the options
+        for encoding_method in (0..=u8::MAX).map_while(FileEncodingMethod::from_id) {
+            let options = EmbedOptions {
+                encoding_method,
+                passphrase: Some("sealed".to_string()),
+                ..EmbedOptions::default()
+            };
+            let keyed = capacity(&bmp, &options).unwrap();
+
+            assert_eq!(keyed.raw_bits, report.raw_bits);
+            assert_eq!(keyed.usable_bytes + ENCRYPTION_OVERHEAD as u64, report.usable_bytes);
+        }
+
+        let f5 = EmbedOptions { encoding: FileEncoding::F5, ..EmbedOptions::default() };
+        let report = capacity(&carrier("sample-320x240.jpg"), &f5).unwrap();
+        assert!(report.recommended_bytes > 0 && report.recommended_bytes < report.usable_bytes);
+
+        // LsbMatching says it has to be extracted as itself, and plain Lsb really can't read it
+        let matching = EmbedOptions { encoding: FileEncoding::LsbMatching, ..EmbedOptions::default() };
+        assert!(capacity(&bmp, &matching).unwrap().to_string().contains("extract with LsbMatching"));
+        assert!(!report.to_string().contains("extract with"));
+
+        let stego = embed(&bmp, b"hi", &matching).unwrap();
+        assert!(extract(&stego, &EmbedOptions::default()).is_err());
+        assert_eq!(extract(&stego, &matching).unwrap(), b"hi");
+    }
+
+    #[test]
+    fn test_find_payloads() {
+        let options = EmbedOptions {
+            encoding: FileEncoding::HammingMatrix,
+            encoding_method: FileEncodingMethod::SpiralInward,
+            compression: CompressionCodec::None,
+            ..EmbedOptions::default()
+        };
+        let png = carrier("sample-256x256-gray.png");
+        let stego = embed(&png, b"spot me", &options).unwrap();
+
+        assert_eq!(find_payloads(&png, FileEncodingFunctionDerivation::MethodBased).unwrap(), vec![]);
+        assert_eq!(
+            find_payloads(&stego, FileEncodingFunctionDerivation::MethodBased).unwrap(),
+            vec![FoundPayload {
+                encoding: FileEncoding::HammingMatrix,
+                encoding_method: FileEncodingMethod::SpiralInward,
+                length: 7,
+                flags: hamming_k_flags(7),
+                encrypted: false,
+            }]
+        );
+    }
+
+    /*
+        A custom range table has to come back through the options on both sides, Wu and Tsai's can't read it
+     */
+    #[test]
+    fn test_custom_pvd_ranges() {
+        let options = EmbedOptions {
+            encoding: FileEncoding::PixelValueDifferencing,
+            compression: CompressionCodec::None,
+            pvd_ranges: Some(vec![4, 4, 8, 16, 32, 64, 128]),
+            ..EmbedOptions::default()
+        };
+        let default_ranges = EmbedOptions { pvd_ranges: None, ..options.clone() };
+
+        for name in ["sample-256x256.png", "sample-1024x1024.bmp"] {
+            let cover = carrier(name);
+            assert_ne!(capacity(&cover, &options).unwrap().raw_bits, capacity(&cover, &default_ranges).unwrap().raw_bits);
+
+            let stego = embed(&cover, b"narrow ranges first", &options).unwrap();
+            assert_eq!(extract(&stego, &options).unwrap(), b"narrow ranges first");
+            assert!(extract(&stego, &default_ranges).is_err());
+        }
+
+        let broken = EmbedOptions { pvd_ranges: Some(vec![8, 8, 16, 32, 64]), ..options };
+        assert!(matches!(embed(&carrier("sample-256x256.png"), b"nope", &broken), Err(MayaError::InvalidParameter(_))));
+    }
+
+    /*
+        A chosen k goes into the header flags, and extraction still only needs what is in the image
+     */
+    #[test]
+    fn test_hamming_k_option() {
+        let cover = carrier("sample-256x256.png");
+
+        for k in [1, 3, 7] {
+            let options = EmbedOptions {
+                encoding: FileEncoding::HammingMatrix,
+                compression: CompressionCodec::None,
+                hamming_k: Some(k),
+                ..EmbedOptions::default()
+            };
+            let stego = embed(&cover, b"fixed group size", &options).unwrap();
+
+            let found = find_payloads(&stego, FileEncodingFunctionDerivation::MethodBased).unwrap();
+            assert_eq!(found.len(), 1);
+            assert_eq!(hamming_k_from_flags(found[0].flags), k);
+            assert_eq!(extract(&stego, &EmbedOptions { hamming_k: None, ..options }).unwrap(), b"fixed group size");
+        }
+
+        let options = EmbedOptions { encoding: FileEncoding::HammingMatrix, hamming_k: Some(9), ..EmbedOptions::default() };
+        assert!(matches!(embed(&cover, b"too big", &options), Err(MayaError::InvalidParameter(_))));
+    }
+
+    /*
+        Both indexed formats only swap colors within the tolerance, and extraction has to be told the same one
+     */
+    #[test]
+    fn test_palette_tolerance_option() {
+        let options = EmbedOptions {
+            compression: CompressionCodec::None,
+            palette_tolerance: Some(64),
+            ..EmbedOptions::default()
+        };
+        let no_tolerance = EmbedOptions { palette_tolerance: None, ..options.clone() };
+
+        for name in ["sample-250x200-palette.png", "sample-250x200-8bit.bmp"] {
+            let cover = carrier(name);
+            let tolerant = capacity(&cover, &options).unwrap().raw_bits;
+            assert!(tolerant > 0 && tolerant < capacity(&cover, &no_tolerance).unwrap().raw_bits, "{name}");
+
+            let stego = embed(&cover, b"close colors only", &options).unwrap();
+            assert_eq!(extract(&stego, &options).unwrap(), b"close colors only");
+            assert!(extract(&stego, &no_tolerance).is_err());
+
+            assert!(matches!(
+                embed(&cover, &[0x55; 200], &options),
+                Err(MayaError::CapacityExceeded { .. })
+            ));
+        }
+    }
+
+    /*
+        Reordering the palette is an embedding choice only, the stego file comes back sorted and the plain options
+        still read it
+     */
+    #[test]
+    fn test_palette_reorder_option() {
+        let options = EmbedOptions { palette_embedding: PaletteEmbedding::ReorderPalette, ..EmbedOptions::default() };
+
+        for name in ["sample-250x200-palette.png", "sample-250x200-8bit.bmp"] {
+            let cover = carrier(name);
+            let stego = embed(&cover, b"sorted by brightness", &options).unwrap();
+            assert_ne!(stego, embed(&cover, b"sorted by brightness", &EmbedOptions::default()).unwrap());
+            assert_eq!(extract(&stego, &EmbedOptions::default()).unwrap(), b"sorted by brightness");
+
+            let palette = match FileType::detect(&stego) {
+                Some(FileType::Png) => PngImageParser::from_bytes(stego).unwrap().palette,
+                _ => BmpImageParser::from_bytes(stego).unwrap().palette,
+            }
+            .unwrap();
+            assert_eq!(palette.luminance_order(), (0..palette.entries.len()).collect::<Vec<usize>>(), "{name}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod analysis_tests {
+    use crate::analysis::analysis::PixelChannels;
+    use crate::analysis::chi_square::{chi_square, chi_square_attack};
+    use crate::compression::compression::CompressionCodec;
+    use crate::error::error::MayaError;
+    use crate::file_encoding_support::file_encoding_support::{FileEncoding, FileEncodingFunctionDerivation, FileEncodingMethod};
+    use crate::mathematics_support::mathematics_support::regularized_gamma_q;
+    use crate::file_encoding_support::payload::PAYLOAD_HEADER_SIZE;
+    use crate::tests::tests::fixtures::carrier;
+    use crate::{chi_square_analysis, embed, pixel_channels, rs_analysis, sample_pair_analysis, EmbedOptions};
+
+    // Cheap deterministic noise, xorshift
+    fn noise(length: usize, mut state: u64) -> Vec<u8> {
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 32) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_regularized_gamma_q() {
+        for x in [0.1, 1.0, 3.5, 20.0] {
+            assert!((regularized_gamma_q(1.0, x) - (-x).exp()).abs() < 1e-12);
+        }
+        // erfc(1)
+        assert!((regularized_gamma_q(0.5, 1.0) - 0.157_299_207_050_285_1).abs() < 1e-12);
+        // 95th percentile of chi-square with 10 degrees of freedom
+        assert!((regularized_gamma_q(5.0, 18.307 / 2.0) - 0.05).abs() < 1e-4);
+        assert_eq!(regularized_gamma_q(3.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn test_chi_square_pairs() {
+        // Mostly even values, so every pair is lopsided
+        let cover: Vec<u16> = noise(20_000, 7).iter().map(|value| (*value & !1) as u16 | (*value % 7 == 0) as u16).collect();
+        let result = chi_square(cover.iter().copied(), 8);
+        assert!(result.probability < 1e-6);
+        assert!(result.degrees_of_freedom > 100);
+
+        // Replacing every LSB with message bits evens the pairs out
+        let bits = noise(20_000, 99);
+        let stego: Vec<u16> = cover.iter().zip(bits.iter()).map(|(value, bit)| (value & !1) | (*bit & 1) as u16).collect();
+        assert!(chi_square(stego.iter().copied(), 8).probability > 0.5);
+
+        // Too little to go on
+        assert_eq!(chi_square([4, 5, 5, 4], 8).probability, 0.0);
+
+        let pixels = PixelChannels { width: 100, height: 100, sample_bits: 8, channels: vec![stego[..10_000].to_vec(), cover[..10_000].to_vec()] };
+        let order: Vec<usize> = (0..pixels.pixel_count()).collect();
+        let results = chi_square_attack(&pixels, &order, Some(2_500));
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].curve.len(), 4);
+        assert!(results[0].curve.iter().all(|probability| *probability > 0.1));
+        assert!(results[1].curve.iter().all(|probability| *probability < 1e-3));
+    }
+
+    #[test]
+    fn test_chi_square_finds_sequential_lsb() {
+        let cover = carrier("sample-1024x1024.bmp");
+        let options = EmbedOptions { compression: CompressionCodec::None, ..EmbedOptions::default() };
+        let stego = embed(&cover, &noise(100_000, 1), &options).unwrap();
+
+        let clean = chi_square_analysis(&cover, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased, Some(1024 * 1024 / 10)).unwrap();
+        let attacked = chi_square_analysis(&stego, FileEncodingMethod::LeftToRight, FileEncodingFunctionDerivation::MethodBased, Some(1024 * 1024 / 10)).unwrap();
+        assert_eq!(attacked.len(), 3);
+
+        // The message covers the first quarter or so of the pixels, the rest of the curve looks like the cover
+        for (clean, attacked) in clean.iter().zip(attacked.iter()) {
+            assert!(clean.curve.iter().all(|probability| *probability < 0.01), "{:?}", clean.curve);
+            assert!(attacked.curve[0] > 0.5, "{:?}", attacked.curve);
+            assert!(attacked.curve[4..].iter().all(|probability| *probability < 0.01), "{:?}", attacked.curve);
+        }
+    }
+
+    #[test]
+    fn test_pixel_channels() {
+        let rgb = pixel_channels(&carrier("sample-256x256.png")).unwrap();
+        assert_eq!((rgb.width, rgb.height, rgb.sample_bits, rgb.channels.len()), (256, 256, 8, 3));
+        assert!(rgb.channels.iter().all(|channel| channel.len() == 256 * 256));
+
+        let indexed = pixel_channels(&carrier("sample-250x200-8bit.bmp")).unwrap();
+        assert_eq!((indexed.width, indexed.height, indexed.channels.len()), (250, 200, 1));
+
+        let wide = pixel_channels(&carrier("sample-128x128-rgba16.png")).unwrap();
+        assert_eq!((wide.sample_bits, wide.channels.len()), (16, 4));
+
+        assert!(matches!(pixel_channels(&carrier("sample-320x240.jpg")), Err(MayaError::UnsupportedFormat(_))));
+    }
+
+    /*
+        Keyed so the message is spread over the whole image, which is what RS and SPA assume
+     */
+    fn embed_ratio(cover: &[u8], samples: usize, ratio: f64) -> (Vec<u8>, usize) {
+        let bits = (ratio * samples as f64) as usize;
+        if bits == 0 {
+            return (cover.to_vec(), 0);
+        }
+
+        let options = EmbedOptions {
+            compression: CompressionCodec::None,
+            file_encoding_function_derivation: FileEncodingFunctionDerivation::from_passphrase("spread it out"),
+            ..EmbedOptions::default()
+        };
+        let message = noise(bits / 8 - PAYLOAD_HEADER_SIZE, 3);
+        (embed(cover, &message, &options).unwrap(), bits / 8 * 8)
+    }
+
+    #[test]
+    fn test_length_estimates() {
+        let cases: [(&str, usize, &[f64]); 3] = [
+            ("sample-256x256.png", 256 * 256 * 3, &[0.0, 0.1, 0.25, 0.5, 0.75]),
+            ("sample-256x256-gray.png", 256 * 256, &[0.0, 0.1, 0.25, 0.5, 0.75]),
+            ("sample-1024x1024.bmp", 1024 * 1024 * 3, &[0.0, 0.5]),
+        ];
+
+        for (name, samples, ratios) in cases {
+            let cover = carrier(name);
+            for ratio in ratios {
+                let (stego, embedded_bits) = embed_ratio(&cover, samples, *ratio);
+
+                for (method, estimates) in [("RS", rs_analysis(&stego).unwrap()), ("SPA", sample_pair_analysis(&stego).unwrap())] {
+                    for estimate in estimates.iter() {
+                        assert!((estimate.embedding_rate - ratio).abs() < 0.05, "{name} {ratio} {method} {estimate:?}");
+                    }
+
+                    let estimated_bits: u64 = estimates.iter().map(|estimate| estimate.message_bits).sum();
+                    assert!((estimated_bits as f64 - embedded_bits as f64).abs() < 0.05 * samples as f64, "{name} {ratio} {method} {estimated_bits}");
+                }
+            }
+        }
+    }
+
+    /*
+        Half the samples carry message bits either way, only replacement leaves the pairs RS and SPA go looking for
+     */
+    #[test]
+    fn test_lsb_matching_hides_length() {
+        let cover = carrier("sample-256x256.png");
+
+        for (encoding, detected) in [(FileEncoding::Lsb, true), (FileEncoding::LsbMatching, false)] {
+            let options = EmbedOptions {
+                encoding,
+                compression: CompressionCodec::None,
+                file_encoding_function_derivation: FileEncodingFunctionDerivation::from_passphrase("spread it out"),
+                ..EmbedOptions::default()
+            };
+            let stego = embed(&cover, &noise(256 * 256 * 3 / 16 - PAYLOAD_HEADER_SIZE, 5), &options).unwrap();
+
+            for estimate in rs_analysis(&stego).unwrap().iter().chain(sample_pair_analysis(&stego).unwrap().iter()) {
+                assert_eq!(estimate.embedding_rate > 0.4, detected, "{encoding:?} {estimate:?}");
+                assert_eq!(estimate.embedding_rate < 0.1, !detected, "{encoding:?} {estimate:?}");
+            }
+        }
+    }
+}